@@ -0,0 +1,159 @@
+//! Compatibility with Unikey. Every word in `data/unikey.tsv` is typed into
+//! the input processor with settings matching Unikey's defaults, and what
+//! ends up on screen is compared with what Unikey shows for the same keys.
+//! The report groups the differences by rule, most differences first, so the
+//! rules worth fixing first are at the top. It is printed and written to
+//! `target/compat-report.md`; differences do not fail the run.
+
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/core/mod.rs"]
+mod core;
+#[path = "../src/error/mod.rs"]
+mod error;
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use crate::core::types::AdvancedSettings;
+use crate::core::{InputType, VietnameseInputProcessor};
+
+const REFERENCE: &str = include_str!("data/unikey.tsv");
+
+/// A word typed with Unikey
+struct Case {
+    input_type: InputType,
+    rule: &'static str,
+    keys: &'static str,
+    expected: &'static str,
+}
+
+/// A word VKey types differently
+struct Difference<'a> {
+    case: &'a Case,
+    actual: String,
+}
+
+fn parse_input_type(name: &str) -> Option<InputType> {
+    match name {
+        "telex" => Some(InputType::Telex),
+        "vni" => Some(InputType::VNI),
+        _ => None,
+    }
+}
+
+fn reference_cases() -> Vec<Case> {
+    REFERENCE
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            let [input_type, rule, keys, expected] = columns[..] else {
+                panic!("unikey.tsv:{}: expected 4 columns, found {}", index + 1, columns.len());
+            };
+            let input_type = parse_input_type(input_type)
+                .unwrap_or_else(|| panic!("unikey.tsv:{}: unknown input method '{}'", index + 1, input_type));
+            Case {
+                input_type,
+                rule,
+                keys,
+                expected,
+            }
+        })
+        .collect()
+}
+
+/// A processor set up the way Unikey is out of the box
+fn unikey_processor(input_type: InputType) -> VietnameseInputProcessor {
+    let mut processor = VietnameseInputProcessor::new(input_type);
+    processor.apply_settings(&AdvancedSettings {
+        replace_oa_uy: false,
+        spell_check: true,
+        auto_restart_typos: true,
+        macro_enabled: false,
+        ..AdvancedSettings::default()
+    });
+    processor
+}
+
+/// What is on screen after typing `keys` and a space
+fn type_word(input_type: InputType, keys: &str) -> String {
+    let mut processor = unikey_processor(input_type);
+    let mut screen = String::new();
+    for key in keys.chars() {
+        processor.process_key(key).apply_to(&mut screen);
+    }
+    processor.handle_space().apply_to(&mut screen);
+    screen.trim_end_matches(' ').to_string()
+}
+
+fn report(cases: &[Case], differences: &[Difference]) -> String {
+    let mut by_rule: BTreeMap<&str, (usize, Vec<&Difference>)> = BTreeMap::new();
+    for case in cases {
+        by_rule.entry(case.rule).or_default().0 += 1;
+    }
+    for difference in differences {
+        by_rule.entry(difference.case.rule).or_default().1.push(difference);
+    }
+    let mut rules: Vec<_> = by_rule.into_iter().collect();
+    rules.sort_by_key(|(_, (_, found))| Reverse(found.len()));
+
+    let mut out = String::new();
+    let matching = cases.len() - differences.len();
+    let _ = writeln!(out, "# Unikey compatibility\n");
+    let _ = writeln!(
+        out,
+        "{} of {} words typed as Unikey does ({:.1}%).\n",
+        matching,
+        cases.len(),
+        matching as f64 * 100.0 / cases.len().max(1) as f64
+    );
+    let _ = writeln!(out, "| Rule | Words | Different |");
+    let _ = writeln!(out, "|---|---|---|");
+    for (rule, (total, found)) in &rules {
+        let _ = writeln!(out, "| {} | {} | {} |", rule, total, found.len());
+    }
+    for (rule, (_, found)) in rules.iter().filter(|(_, (_, found))| !found.is_empty()) {
+        let _ = writeln!(out, "\n## {}\n", rule);
+        let _ = writeln!(out, "| Input | Keys | Unikey | VKey |");
+        let _ = writeln!(out, "|---|---|---|---|");
+        for difference in found {
+            let case = difference.case;
+            let _ = writeln!(
+                out,
+                "| {} | `{}` | {} | {} |",
+                case.input_type, case.keys, case.expected, difference.actual
+            );
+        }
+    }
+    out
+}
+
+fn report_path() -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"));
+    target.join("compat-report.md")
+}
+
+fn main() {
+    let cases = reference_cases();
+    let differences: Vec<Difference> = cases
+        .iter()
+        .filter_map(|case| {
+            let actual = type_word(case.input_type, case.keys);
+            (actual != case.expected).then_some(Difference { case, actual })
+        })
+        .collect();
+
+    let report = report(&cases, &differences);
+    print!("{}", report);
+    let path = report_path();
+    match std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| std::fs::write(&path, &report)) {
+        Ok(()) => println!("\nReport written to {}", path.display()),
+        Err(e) => eprintln!("Failed to write '{}': {}", path.display(), e),
+    }
+}
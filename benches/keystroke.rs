@@ -0,0 +1,154 @@
+//! Per-keystroke latency of the input processor over Vietnamese text typed in
+//! Telex and VNI, and over the longest word the processor keeps composing.
+//! `cargo bench` fails when a key takes longer on average than `KEY_BUDGET`,
+//! the budget documented under "Performance" in the README.
+
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/core/mod.rs"]
+mod core;
+#[path = "../src/error/mod.rs"]
+mod error;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, BatchSize, Criterion, Throughput};
+
+use crate::core::config::DEFAULT_MAX_WORD_LENGTH;
+use crate::core::types::AdvancedSettings;
+use crate::core::{InputType, VietnameseInputProcessor};
+
+/// Longest a key may take on average
+const KEY_BUDGET: Duration = Duration::from_micros(100);
+
+/// Rounds of each case timed for the budget check
+const BUDGET_ROUNDS: u32 = 200;
+
+const CORPUS: &str = include_str!("data/corpus.txt");
+
+/// The longest syllable with its tone changed again and again, which keeps
+/// composing up to the word length limit
+const LONG_WORD: &str = "nghieengsfrxjsfrxjsfrxj";
+
+fn processor(input_type: InputType) -> VietnameseInputProcessor {
+    let mut processor = VietnameseInputProcessor::new(input_type);
+    processor.apply_settings(&AdvancedSettings {
+        spell_check: true,
+        ..AdvancedSettings::default()
+    });
+    processor
+}
+
+/// The keys that type the corpus with `input_type`
+fn corpus_keys(input_type: InputType) -> String {
+    processor(input_type).reverse_transform(CORPUS)
+}
+
+fn type_keys(processor: &mut VietnameseInputProcessor, keys: &str) {
+    for key in keys.chars() {
+        black_box(match key {
+            ' ' => processor.handle_space(),
+            _ => processor.process_key(key),
+        });
+    }
+}
+
+/// A processor part way through the longest word it composes
+fn long_buffer() -> VietnameseInputProcessor {
+    let mut processor = processor(InputType::Telex);
+    let keys: String = LONG_WORD.chars().take(DEFAULT_MAX_WORD_LENGTH - 1).collect();
+    type_keys(&mut processor, &keys);
+    processor
+}
+
+fn bench_process_key(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_key");
+    for input_type in [InputType::Telex, InputType::VNI] {
+        let keys = corpus_keys(input_type);
+        group.throughput(Throughput::Elements(keys.chars().count() as u64));
+        group.bench_function(format!("{} corpus", input_type), |b| {
+            b.iter_batched_ref(|| processor(input_type), |processor| type_keys(processor, &keys), BatchSize::SmallInput)
+        });
+    }
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("long buffer", |b| {
+        b.iter_batched_ref(long_buffer, |processor| black_box(processor.process_key('s')), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+fn bench_handle_backspace(c: &mut Criterion) {
+    let mut group = c.benchmark_group("handle_backspace");
+    group.bench_function("word", |b| {
+        b.iter_batched_ref(
+            || {
+                let mut processor = processor(InputType::Telex);
+                type_keys(&mut processor, "nghieengs");
+                processor
+            },
+            |processor| black_box(processor.handle_backspace()),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("long buffer", |b| {
+        b.iter_batched_ref(long_buffer, |processor| black_box(processor.handle_backspace()), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+/// Average time per key of `run`, which types `keys` keys after `setup`
+fn time_per_key<S, T, R>(setup: S, run: R, keys: usize) -> Duration
+where
+    S: Fn() -> T,
+    R: Fn(&mut T),
+{
+    let mut total = Duration::ZERO;
+    for _ in 0..BUDGET_ROUNDS {
+        let mut state = setup();
+        let start = Instant::now();
+        run(&mut state);
+        total += start.elapsed();
+    }
+    total / (BUDGET_ROUNDS * keys as u32)
+}
+
+/// Fail the run when any case goes over the budget
+fn check_budget() {
+    let mut over = Vec::new();
+    let mut check = |name: &str, per_key: Duration| {
+        println!("{:<32} {:>10.2?} per key", name, per_key);
+        if per_key > KEY_BUDGET {
+            over.push(format!("{} takes {:?} per key", name, per_key));
+        }
+    };
+    for input_type in [InputType::Telex, InputType::VNI] {
+        let keys = corpus_keys(input_type);
+        let count = keys.chars().count();
+        check(
+            &format!("process_key/{} corpus", input_type),
+            time_per_key(|| processor(input_type), |processor| type_keys(processor, &keys), count),
+        );
+    }
+    check(
+        "process_key/long buffer",
+        time_per_key(long_buffer, |processor| {
+            black_box(processor.process_key('s'));
+        }, 1),
+    );
+    check(
+        "handle_backspace/long buffer",
+        time_per_key(long_buffer, |processor| {
+            black_box(processor.handle_backspace());
+        }, 1),
+    );
+    assert!(over.is_empty(), "over the {:?} budget: {}", KEY_BUDGET, over.join(", "));
+}
+
+criterion_group!(benches, bench_process_key, bench_handle_backspace);
+
+fn main() {
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+    check_budget();
+}
@@ -0,0 +1,139 @@
+/* C interface to the VKey input processor */
+
+#ifndef VKEY_H
+#define VKEY_H
+
+/* Generated by cbindgen from src/lib.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Input types, in the order of `InputType`
+ */
+#define VKEY_TELEX 0
+
+#define VKEY_VNI 1
+
+#define VKEY_VIQR 2
+
+#define VKEY_HYBRID 3
+
+#define VKEY_TELEX_SIMPLE 4
+
+/**
+ * Key codes for the keys that do not type a character
+ */
+#define VKEY_BACKSPACE 8
+
+#define VKEY_ESCAPE 27
+
+/**
+ * A processor and the strings it last handed out, which stay valid until
+ * the next call on the same handle
+ */
+typedef struct VKeyProcessor VKeyProcessor;
+
+/**
+ * What a key did to the text before the cursor
+ */
+typedef struct VKeyEdit {
+  /**
+   * Characters to delete before the cursor
+   */
+  size_t backspaces;
+  /**
+   * UTF-8 text to type after deleting them, owned by the processor
+   */
+  const char *text;
+  /**
+   * The key itself must not be typed
+   */
+  bool blocked;
+} VKeyEdit;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a processor with the default settings, or NULL for an unknown
+ * input type. Release it with `vkey_processor_free`.
+ */
+VKeyProcessor *vkey_processor_new(int input_type_code);
+
+/**
+ * Release a processor. NULL is ignored.
+ *
+ * # Safety
+ * `processor` must come from `vkey_processor_new` and not be used afterwards.
+ */
+void vkey_processor_free(VKeyProcessor *processor);
+
+/**
+ * Use the `advanced` settings of a config file, given as UTF-8 JSON.
+ * Returns false, keeping the current settings, if they cannot be read.
+ *
+ * # Safety
+ * `processor` must be a live handle and `settings_json` a NUL-terminated string.
+ */
+bool vkey_processor_apply_settings(VKeyProcessor *processor, const char *settings_json);
+
+/**
+ * Type a key, given as a Unicode scalar value: `VKEY_BACKSPACE` and
+ * `VKEY_ESCAPE` stand for those keys. `text` is valid until the next call
+ * on this processor.
+ *
+ * # Safety
+ * `processor` must be a live handle.
+ */
+VKeyEdit vkey_processor_process_key(VKeyProcessor *processor, uint32_t key);
+
+/**
+ * Keys of the word being composed, valid until the next call on this
+ * processor
+ *
+ * # Safety
+ * `processor` must be a live handle.
+ */
+const char *vkey_processor_keys(VKeyProcessor *processor);
+
+/**
+ * Forget the word being composed, as after moving the cursor
+ *
+ * # Safety
+ * `processor` must be a live handle.
+ */
+void vkey_processor_reset(VKeyProcessor *processor);
+
+/**
+ * Transform a whole UTF-8 text with an input method and the default
+ * settings. Returns NULL for an unknown input type or invalid text; release
+ * the result with `vkey_string_free`.
+ *
+ * # Safety
+ * `text` must be a NUL-terminated string.
+ */
+char *vkey_transform_text(int input_type_code, const char *text);
+
+/**
+ * Release a string returned by `vkey_transform_text`. NULL is ignored.
+ *
+ * # Safety
+ * `text` must come from `vkey_transform_text` and not be used afterwards.
+ */
+void vkey_string_free(char *text);
+
+/**
+ * Version of the library, as a static string
+ */
+const char *vkey_version(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VKEY_H */
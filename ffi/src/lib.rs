@@ -0,0 +1,268 @@
+// C interface to the input processor
+// The application's `core` and `error` modules are compiled here unchanged,
+// behind `extern "C"` functions and an opaque processor handle, so editor
+// plugins and other input methods compose words exactly the way VKey does.
+// `include/vkey.h` is generated from this file with cbindgen.
+
+#[path = "../../src/core/mod.rs"]
+pub mod core;
+#[path = "../../src/error/mod.rs"]
+pub mod error;
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+use crate::core::types::AdvancedSettings;
+use crate::core::{InputType, ProcessingResult, VietnameseInputProcessor};
+
+/// Input types, in the order of `InputType`
+pub const VKEY_TELEX: c_int = 0;
+pub const VKEY_VNI: c_int = 1;
+pub const VKEY_VIQR: c_int = 2;
+pub const VKEY_HYBRID: c_int = 3;
+pub const VKEY_TELEX_SIMPLE: c_int = 4;
+
+/// Key codes for the keys that do not type a character
+pub const VKEY_BACKSPACE: u32 = 0x08;
+pub const VKEY_ESCAPE: u32 = 0x1B;
+
+fn input_type(code: c_int) -> Option<InputType> {
+    match code {
+        VKEY_TELEX => Some(InputType::Telex),
+        VKEY_VNI => Some(InputType::VNI),
+        VKEY_VIQR => Some(InputType::VIQR),
+        VKEY_HYBRID => Some(InputType::Hybrid),
+        VKEY_TELEX_SIMPLE => Some(InputType::TelexSimple),
+        _ => None,
+    }
+}
+
+fn new_processor(input_type: InputType) -> VietnameseInputProcessor {
+    let mut processor = VietnameseInputProcessor::new(input_type);
+    processor.apply_settings(&AdvancedSettings::default());
+    processor
+}
+
+/// A C string for `text`; NUL cannot be passed through and is dropped
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+/// A processor and the strings it last handed out, which stay valid until
+/// the next call on the same handle
+pub struct VKeyProcessor {
+    processor: VietnameseInputProcessor,
+    text: CString,
+    keys: CString,
+}
+
+/// What a key did to the text before the cursor
+#[repr(C)]
+pub struct VKeyEdit {
+    /// Characters to delete before the cursor
+    pub backspaces: usize,
+    /// UTF-8 text to type after deleting them, owned by the processor
+    pub text: *const c_char,
+    /// The key itself must not be typed
+    pub blocked: bool,
+}
+
+/// Create a processor with the default settings, or NULL for an unknown
+/// input type. Release it with `vkey_processor_free`.
+#[no_mangle]
+pub extern "C" fn vkey_processor_new(input_type_code: c_int) -> *mut VKeyProcessor {
+    match input_type(input_type_code) {
+        Some(input_type) => Box::into_raw(Box::new(VKeyProcessor {
+            processor: new_processor(input_type),
+            text: CString::default(),
+            keys: CString::default(),
+        })),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Release a processor. NULL is ignored.
+///
+/// # Safety
+/// `processor` must come from `vkey_processor_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vkey_processor_free(processor: *mut VKeyProcessor) {
+    if !processor.is_null() {
+        drop(Box::from_raw(processor));
+    }
+}
+
+/// Use the `advanced` settings of a config file, given as UTF-8 JSON.
+/// Returns false, keeping the current settings, if they cannot be read.
+///
+/// # Safety
+/// `processor` must be a live handle and `settings_json` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vkey_processor_apply_settings(
+    processor: *mut VKeyProcessor,
+    settings_json: *const c_char,
+) -> bool {
+    let Some(handle) = processor.as_mut() else {
+        return false;
+    };
+    if settings_json.is_null() {
+        return false;
+    }
+    let Ok(json) = CStr::from_ptr(settings_json).to_str() else {
+        return false;
+    };
+    match serde_json::from_str::<AdvancedSettings>(json) {
+        Ok(settings) => {
+            handle.processor.apply_settings(&settings);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Type a key, given as a Unicode scalar value: `VKEY_BACKSPACE` and
+/// `VKEY_ESCAPE` stand for those keys. `text` is valid until the next call
+/// on this processor.
+///
+/// # Safety
+/// `processor` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn vkey_processor_process_key(processor: *mut VKeyProcessor, key: u32) -> VKeyEdit {
+    let unchanged = VKeyEdit {
+        backspaces: 0,
+        text: c"".as_ptr(),
+        blocked: false,
+    };
+    let (Some(handle), Some(key)) = (processor.as_mut(), char::from_u32(key)) else {
+        return unchanged;
+    };
+    let result = match key {
+        '\u{8}' => handle.processor.handle_backspace(),
+        ' ' => handle.processor.handle_space(),
+        _ => handle.processor.process_key(key),
+    };
+    let (backspaces, text, blocked) = match &result {
+        ProcessingResult::ProcessedText { text, buffer_length }
+        | ProcessingResult::RestoreText { text, buffer_length }
+        | ProcessingResult::AcceptSuggestion { text, buffer_length } => (*buffer_length, text.clone(), true),
+        ProcessingResult::PassThrough(_) | ProcessingResult::ClearAndPassBackspace => (0, String::new(), false),
+        ProcessingResult::DismissSuggestions => (0, String::new(), true),
+    };
+    handle.text = c_string(&text);
+    VKeyEdit {
+        backspaces,
+        text: handle.text.as_ptr(),
+        blocked,
+    }
+}
+
+/// Keys of the word being composed, valid until the next call on this
+/// processor
+///
+/// # Safety
+/// `processor` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn vkey_processor_keys(processor: *mut VKeyProcessor) -> *const c_char {
+    let Some(handle) = processor.as_mut() else {
+        return ptr::null();
+    };
+    handle.keys = c_string(handle.processor.get_current_buffer());
+    handle.keys.as_ptr()
+}
+
+/// Forget the word being composed, as after moving the cursor
+///
+/// # Safety
+/// `processor` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn vkey_processor_reset(processor: *mut VKeyProcessor) {
+    if let Some(handle) = processor.as_mut() {
+        handle.processor.reset();
+    }
+}
+
+/// Transform a whole UTF-8 text with an input method and the default
+/// settings. Returns NULL for an unknown input type or invalid text; release
+/// the result with `vkey_string_free`.
+///
+/// # Safety
+/// `text` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vkey_transform_text(input_type_code: c_int, text: *const c_char) -> *mut c_char {
+    let Some(input_type) = input_type(input_type_code) else {
+        return ptr::null_mut();
+    };
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(text) = CStr::from_ptr(text).to_str() else {
+        return ptr::null_mut();
+    };
+    c_string(&new_processor(input_type).transform_text(text)).into_raw()
+}
+
+/// Release a string returned by `vkey_transform_text`. NULL is ignored.
+///
+/// # Safety
+/// `text` must come from `vkey_transform_text` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vkey_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+/// Version of the library, as a static string
+#[no_mangle]
+pub extern "C" fn vkey_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Type `keys` through the C interface, mirroring the edits on a screen
+    unsafe fn type_keys(processor: *mut VKeyProcessor, keys: &str) -> String {
+        let mut screen: Vec<char> = Vec::new();
+        for key in keys.chars() {
+            let edit = vkey_processor_process_key(processor, key as u32);
+            if key == '\u{8}' && !edit.blocked {
+                screen.pop();
+                continue;
+            }
+            screen.truncate(screen.len().saturating_sub(edit.backspaces));
+            screen.extend(CStr::from_ptr(edit.text).to_str().unwrap().chars());
+            if !edit.blocked {
+                screen.push(key);
+            }
+        }
+        screen.into_iter().collect()
+    }
+
+    #[test]
+    fn keys_round_trip_through_the_c_interface() {
+        unsafe {
+            let processor = vkey_processor_new(VKEY_TELEX);
+            assert!(!processor.is_null());
+            assert_eq!(type_keys(processor, "tieengs Vieetj"), "tiếng Việt");
+            assert_eq!(CStr::from_ptr(vkey_processor_keys(processor)).to_str().unwrap(), "Vieetj");
+            vkey_processor_reset(processor);
+            assert_eq!(CStr::from_ptr(vkey_processor_keys(processor)).to_str().unwrap(), "");
+
+            let settings = c_string("{\"telex_bracket_shortcuts\": true}");
+            assert!(vkey_processor_apply_settings(processor, settings.as_ptr()));
+            let invalid = c_string("{");
+            assert!(!vkey_processor_apply_settings(processor, invalid.as_ptr()));
+            vkey_processor_free(processor);
+
+            let text = c_string("xin chaof");
+            let transformed = vkey_transform_text(VKEY_TELEX, text.as_ptr());
+            assert_eq!(CStr::from_ptr(transformed).to_str().unwrap(), "xin chào");
+            vkey_string_free(transformed);
+
+            assert!(vkey_processor_new(99).is_null());
+            assert!(vkey_transform_text(99, text.as_ptr()).is_null());
+        }
+    }
+}
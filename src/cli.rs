@@ -0,0 +1,193 @@
+// Command line interface
+// `vkey` with no arguments starts the normal tray application, while
+// subcommands run a single action and exit.
+
+use crate::core::{AppConfig, InputType, VietnameseInputProcessor};
+
+pub const USAGE: &str = "Usage:
+  vkey                                  Start the VKey application
+  vkey daemon                           Run the input method without a window or menu bar icon
+  vkey status                           Print the state of the running input method as JSON
+  vkey type [--telex|--vni|--viqr|--hybrid|--telex-simple] [--delay <ms>] <text>
+                                        Transform <text> and type it into the focused app
+  vkey help                             Show this message";
+
+/// Default wait before typing, giving the user time to focus the target app
+const DEFAULT_TYPE_DELAY_MS: u64 = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliCommand {
+    /// Start the GUI application
+    Run,
+    /// Run only the event tap and engine, until stopped by a signal
+    Daemon,
+    /// Print usage and exit
+    Help,
+    /// Ask the running input method for its state and print it
+    Status,
+    /// Transform text and inject it into the focused application
+    Type {
+        input_type: Option<InputType>,
+        delay_ms: u64,
+        text: String,
+    },
+}
+
+/// Parse command line arguments (without the program name)
+pub fn parse_args<I>(args: I) -> Result<CliCommand, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(CliCommand::Run),
+    };
+
+    match command.as_str() {
+        "help" | "--help" | "-h" => Ok(CliCommand::Help),
+        "daemon" => Ok(CliCommand::Daemon),
+        "status" => Ok(CliCommand::Status),
+        "type" => parse_type_args(args),
+        other => Err(format!("Unknown command '{}'", other)),
+    }
+}
+
+fn parse_type_args<I>(mut args: I) -> Result<CliCommand, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut input_type = None;
+    let mut delay_ms = DEFAULT_TYPE_DELAY_MS;
+    let mut words = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--telex" => input_type = Some(InputType::Telex),
+            "--vni" => input_type = Some(InputType::VNI),
+            "--hybrid" => input_type = Some(InputType::Hybrid),
+            "--telex-simple" => input_type = Some(InputType::TelexSimple),
+            "--viqr" => input_type = Some(InputType::VIQR),
+            "--delay" => {
+                let value = args.next().ok_or("--delay requires a value in milliseconds")?;
+                delay_ms = value
+                    .parse()
+                    .map_err(|_| format!("Invalid --delay value '{}'", value))?;
+            }
+            option if option.starts_with("--") => return Err(format!("Unknown option '{}'", option)),
+            _ => words.push(arg),
+        }
+    }
+
+    if words.is_empty() {
+        return Err("Missing text to type".to_string());
+    }
+
+    Ok(CliCommand::Type {
+        input_type,
+        delay_ms,
+        text: words.join(" "),
+    })
+}
+
+/// Compose `text` the way the configured engine types it, optionally with
+/// another input method
+pub fn compose_text(text: &str, input_type: Option<InputType>, config: &AppConfig) -> String {
+    let mut processor = VietnameseInputProcessor::new(input_type.unwrap_or(config.input_type));
+    processor.set_encoding(config.encoding);
+    processor.apply_settings(&config.advanced);
+    processor.set_word_limit(config.max_word_length, config.word_overflow);
+    processor.set_restore_patterns(config.active_restore_patterns());
+    crate::core::normalize(&processor.transform_text(text), config.effective_normalization())
+}
+
+/// Run the `type` command: compose the text with the configured engine and inject it
+pub fn run_type_command(input_type: Option<InputType>, delay_ms: u64, text: &str) -> Result<(), String> {
+    let config = AppConfig::load_default().unwrap_or_default();
+    let output = compose_text(text, input_type, &config);
+    eprintln!("Typing '{}' in {}ms", output, delay_ms);
+
+    std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+
+    #[cfg(target_os = "macos")]
+    {
+        post_to_focused_app(&output, &config)
+            .map_err(|e| format!("Failed to post text to the focused application: {}", e))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Err("Typing automation is only supported on macOS".to_string())
+    }
+}
+
+/// Type `text` into the frontmost application through the injection queue,
+/// set up the way the input method sets it up for that application: its
+/// injection method, the wait between erasing and typing, and typing per
+/// letter in terminals. There is no event tap, so events go to the system
+/// event stream.
+#[cfg(target_os = "macos")]
+fn post_to_focused_app(text: &str, config: &AppConfig) -> Result<(), crate::platform::InjectionError> {
+    use crate::core::EditOp;
+    use crate::platform::{self, InjectionQueue};
+
+    let app = platform::get_active_app_name();
+    let compat = crate::COMPAT_CATALOG.entry_for(&app, config);
+    let mut queue = InjectionQueue::new();
+    queue.set_method(crate::injection_method(config, &app, compat));
+    queue.set_batch_delay(crate::injection_delay(config, compat));
+    queue.set_terminal_safe(platform::is_terminal_app(&app));
+    queue.push_edits(&EditOp::replacement(0, text));
+    queue.flush(platform::NO_TAP)
+}
+
+/// Run the `status` command: print what the control socket of the running
+/// input method answers
+pub fn run_status_command() -> Result<(), String> {
+    let reply = crate::control::request("status")
+        .map_err(|e| format!("VKey is not running or its control socket is unavailable: {}", e))?;
+    println!("{}", reply);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliCommand, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn type_options_are_parsed() {
+        assert_eq!(parse(&[]), Ok(CliCommand::Run));
+        assert_eq!(
+            parse(&["type", "--vni", "--delay", "0", "Vie65t", "Nam"]),
+            Ok(CliCommand::Type { input_type: Some(InputType::VNI), delay_ms: 0, text: "Vie65t Nam".to_string() })
+        );
+        assert_eq!(
+            parse(&["type", "--telex", "tieengs"]),
+            Ok(CliCommand::Type { input_type: Some(InputType::Telex), delay_ms: DEFAULT_TYPE_DELAY_MS, text: "tieengs".to_string() })
+        );
+        assert_eq!(
+            parse(&["type", "--viqr", "Vie^.t"]),
+            Ok(CliCommand::Type { input_type: Some(InputType::VIQR), delay_ms: DEFAULT_TYPE_DELAY_MS, text: "Vie^.t".to_string() })
+        );
+    }
+
+    #[test]
+    fn bad_type_arguments_are_errors() {
+        assert_eq!(parse(&["type", "--telex"]), Err("Missing text to type".to_string()));
+        assert_eq!(parse(&["type", "xin", "--delay"]), Err("--delay requires a value in milliseconds".to_string()));
+        assert_eq!(parse(&["type", "--delay", "soon", "xin"]), Err("Invalid --delay value 'soon'".to_string()));
+        assert_eq!(parse(&["type", "--vnii", "xin"]), Err("Unknown option '--vnii'".to_string()));
+        assert_eq!(parse(&["typo"]), Err("Unknown command 'typo'".to_string()));
+    }
+
+    #[test]
+    fn text_is_composed_with_the_chosen_input_method() {
+        let config = AppConfig::default();
+        assert_eq!(compose_text("tieengs Vieetj", Some(InputType::Telex), &config), "tiếng Việt");
+        assert_eq!(compose_text("tie61ng Vie65t", Some(InputType::VNI), &config), "tiếng Việt");
+    }
+}
@@ -0,0 +1,150 @@
+// Control socket
+// The process that types listens on a Unix socket in the config directory, so
+// scripts and menu bar tools (SwiftBar, xbar) can ask what the engine is
+// doing. Each line received is a command and is answered with one line of
+// JSON. Only read-only commands are accepted: `status` describes the engine.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::core::{AppConfig, InputType};
+use crate::error::{Result, VKeyError};
+
+/// File name of the socket in the config directory
+const SOCKET_NAME: &str = "vkey.sock";
+
+/// How long a client may take to send its command or read the answer
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether the event tap is reading keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TapHealth {
+    /// Installed and enabled
+    Running,
+    /// Installed, but macOS switched it off
+    Disabled,
+    /// Not installed
+    Stopped,
+}
+
+/// What VKey does in the frontmost application
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AppPolicy {
+    /// Bundle path of the application
+    pub app: String,
+    /// Listed in the excluded apps, so every key passes through untouched
+    pub excluded: bool,
+    /// Words are typed only once committed
+    pub commit_only: bool,
+    /// The word being typed is shown as marked text
+    pub marked_text: bool,
+    /// Letters are typed with their marks in one event, for terminals
+    pub terminal_safe: bool,
+    /// Words are replaced by typing over them through accessibility
+    pub accessibility_replacement: bool,
+    /// Replacement text is pasted from the clipboard
+    pub pasted: bool,
+    /// Compatibility catalog entry applied, if any
+    pub compat: Option<String>,
+    /// Each decision above with what caused it, such as
+    /// "commit only: compatibility entry Excel"
+    pub reasons: Vec<String>,
+}
+
+/// Answer to the `status` command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Status {
+    /// Vietnamese typing is on
+    pub enabled: bool,
+    pub input_type: InputType,
+    /// Language pack composing words instead of Vietnamese, if any
+    pub transform_scheme: Option<String>,
+    pub app_policy: AppPolicy,
+    /// No word is being composed
+    pub buffer_empty: bool,
+    pub tap: TapHealth,
+    pub version: &'static str,
+}
+
+/// Path of the control socket
+pub fn socket_path() -> Result<PathBuf> {
+    let mut path = AppConfig::get_config_dir()?;
+    path.push(SOCKET_NAME);
+    Ok(path)
+}
+
+/// Listen on the control socket on its own thread, answering `status` with
+/// what `status` returns. A socket left behind by a previous run is replaced.
+pub fn start<F>(status: F) -> Result<()>
+where
+    F: Fn() -> Status + Send + 'static,
+{
+    let path = socket_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| VKeyError::SystemError(format!("Failed to create '{}': {}", dir.display(), e)))?;
+    }
+    if path.exists() {
+        let _ = std::fs::remove_file(&path);
+    }
+    let listener = UnixListener::bind(&path)
+        .map_err(|e| VKeyError::SystemError(format!("Failed to listen on '{}': {}", path.display(), e)))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve(stream, &status) {
+                        eprintln!("Control socket client failed: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("Control socket accept failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answer every command a client sends until it hangs up
+fn serve<F>(stream: UnixStream, status: &F) -> io::Result<()>
+where
+    F: Fn() -> Status,
+{
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let reply = answer(line?.trim(), status);
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// The JSON reply to one command
+fn answer<F>(command: &str, status: &F) -> String
+where
+    F: Fn() -> Status,
+{
+    match command {
+        "status" => serde_json::to_string(&status())
+            .unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }).to_string()),
+        other => serde_json::json!({ "error": format!("unknown command '{}'", other) }).to_string(),
+    }
+}
+
+/// Send `command` to a running VKey and return its answer
+pub fn request(command: &str) -> io::Result<String> {
+    let path = socket_path().map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?;
+    let mut stream = UnixStream::connect(&path)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    writeln!(stream, "{}", command)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
@@ -0,0 +1,87 @@
+use crate::core::charset;
+
+/// Give each letter of `composed` the case of the key that typed it, so
+/// "VIEETJ" gives "VIỆT" and "ViEETJ" gives "ViỆT" whatever case the engine
+/// composed in. Keys are matched in order by base letter; a letter with no
+/// such key, like the ư of a Telex w or the ơ of a bracket, comes from the
+/// next key and takes its case when that key is a letter.
+pub fn apply_case(keys: &str, composed: &str) -> String {
+    let keys: Vec<char> = keys.chars().collect();
+    let mut next = 0;
+
+    composed
+        .chars()
+        .map(|ch| {
+            if !ch.is_alphabetic() || next >= keys.len() {
+                return ch;
+            }
+            let base = charset::base_letter(ch).to_ascii_lowercase();
+            let index = keys[next..]
+                .iter()
+                .position(|key| key.to_ascii_lowercase() == base)
+                .map_or(next, |offset| next + offset);
+            next = index + 1;
+
+            let key = keys[index];
+            if !key.is_ascii_alphabetic() {
+                ch
+            } else if key.is_ascii_uppercase() {
+                ch.to_uppercase().next().unwrap_or(ch)
+            } else {
+                ch.to_lowercase().next().unwrap_or(ch)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::InputType;
+    use crate::core::vietnamese_input::VietnameseInputProcessor;
+
+    fn type_keys(processor: &mut VietnameseInputProcessor, keys: &str) {
+        for key in keys.chars() {
+            processor.process_key(key);
+        }
+    }
+
+    #[test]
+    fn caps_lock_words_stay_upper_case() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        type_keys(&mut processor, "VIEETJ");
+        assert_eq!(processor.get_display_buffer(), "VIỆT");
+
+        // Backspace re-transforms the remaining keys in the same case
+        processor.handle_backspace();
+        assert_eq!(processor.get_display_buffer(), "VIÊT");
+
+        processor.clear_buffer();
+        type_keys(&mut processor, "DDAAUF");
+        assert_eq!(processor.get_display_buffer(), "ĐẦU");
+
+        let mut processor = VietnameseInputProcessor::new(InputType::VNI);
+        type_keys(&mut processor, "VIE65T");
+        assert_eq!(processor.get_display_buffer(), "VIỆT");
+    }
+
+    #[test]
+    fn shift_capitalizes_single_letters() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        type_keys(&mut processor, "Vieetj");
+        assert_eq!(processor.get_display_buffer(), "Việt");
+
+        processor.clear_buffer();
+        type_keys(&mut processor, "Wa");
+        assert_eq!(processor.get_display_buffer(), "Ưa");
+    }
+
+    #[test]
+    fn mixed_case_follows_each_key() {
+        assert_eq!(apply_case("NGuyeenx", "nguyễn"), "NGuyễn");
+        assert_eq!(apply_case("ViEETJ", "việt"), "ViỆT");
+        // The tone key's case does not matter
+        assert_eq!(apply_case("VIEETj", "việt"), "VIỆT");
+        assert_eq!(apply_case("t{", "tƠ"), "tƠ");
+    }
+}
@@ -0,0 +1,362 @@
+use crate::core::types::Normalization;
+
+/// Tone marks that can be placed on a Vietnamese vowel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ToneMark {
+    /// No tone (thanh ngang)
+    None,
+    /// Sắc (á)
+    Acute,
+    /// Huyền (à)
+    Grave,
+    /// Hỏi (ả)
+    HookAbove,
+    /// Ngã (ã)
+    Tilde,
+    /// Nặng (ạ)
+    DotBelow,
+}
+
+impl ToneMark {
+    /// All tones in table column order
+    pub const ALL: [ToneMark; 6] = [
+        ToneMark::None,
+        ToneMark::Acute,
+        ToneMark::Grave,
+        ToneMark::HookAbove,
+        ToneMark::Tilde,
+        ToneMark::DotBelow,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            ToneMark::None => 0,
+            ToneMark::Acute => 1,
+            ToneMark::Grave => 2,
+            ToneMark::HookAbove => 3,
+            ToneMark::Tilde => 4,
+            ToneMark::DotBelow => 5,
+        }
+    }
+}
+
+/// Lowercase Vietnamese vowels, one row per base vowel, columns in `ToneMark::ALL` order
+pub const VOWEL_TABLE: [[char; 6]; 12] = [
+    ['a', 'á', 'à', 'ả', 'ã', 'ạ'],
+    ['ă', 'ắ', 'ằ', 'ẳ', 'ẵ', 'ặ'],
+    ['â', 'ấ', 'ầ', 'ẩ', 'ẫ', 'ậ'],
+    ['e', 'é', 'è', 'ẻ', 'ẽ', 'ẹ'],
+    ['ê', 'ế', 'ề', 'ể', 'ễ', 'ệ'],
+    ['i', 'í', 'ì', 'ỉ', 'ĩ', 'ị'],
+    ['o', 'ó', 'ò', 'ỏ', 'õ', 'ọ'],
+    ['ô', 'ố', 'ồ', 'ổ', 'ỗ', 'ộ'],
+    ['ơ', 'ớ', 'ờ', 'ở', 'ỡ', 'ợ'],
+    ['u', 'ú', 'ù', 'ủ', 'ũ', 'ụ'],
+    ['ư', 'ứ', 'ừ', 'ử', 'ữ', 'ự'],
+    ['y', 'ý', 'ỳ', 'ỷ', 'ỹ', 'ỵ'],
+];
+
+fn to_lower(ch: char) -> char {
+    ch.to_lowercase().next().unwrap_or(ch)
+}
+
+fn to_upper(ch: char) -> char {
+    ch.to_uppercase().next().unwrap_or(ch)
+}
+
+fn find_vowel(ch: char) -> Option<(usize, usize)> {
+    let lower = to_lower(ch);
+    VOWEL_TABLE.iter().enumerate().find_map(|(row, forms)| {
+        forms.iter().position(|&c| c == lower).map(|col| (row, col))
+    })
+}
+
+/// Check if a character is a Vietnamese vowel (with or without marks)
+pub fn is_vowel(ch: char) -> bool {
+    find_vowel(ch).is_some()
+}
+
+/// Split a character into its toneless base and its tone mark.
+/// Letter modifications (â, ă, ơ, ...) and case are kept on the base.
+pub fn split_tone(ch: char) -> (char, ToneMark) {
+    match find_vowel(ch) {
+        Some((row, col)) => {
+            let base = VOWEL_TABLE[row][0];
+            let base = if ch.is_uppercase() { to_upper(base) } else { base };
+            (base, ToneMark::ALL[col])
+        }
+        None => (ch, ToneMark::None),
+    }
+}
+
+/// Put a tone mark on a vowel, replacing any tone it already carries.
+/// Non-vowels are returned unchanged.
+pub fn with_tone(ch: char, tone: ToneMark) -> char {
+    match find_vowel(ch) {
+        Some((row, _)) => {
+            let toned = VOWEL_TABLE[row][tone.index()];
+            if ch.is_uppercase() { to_upper(toned) } else { toned }
+        }
+        None => ch,
+    }
+}
+
+/// The plain letter under a Vietnamese letter ('ệ' -> 'e', 'Đ' -> 'D').
+/// Other characters are returned unchanged.
+pub fn base_letter(ch: char) -> char {
+    let (base, _) = split_tone(ch);
+    match base {
+        'ă' | 'â' => 'a',
+        'Ă' | 'Â' => 'A',
+        'ê' => 'e',
+        'Ê' => 'E',
+        'ô' | 'ơ' => 'o',
+        'Ô' | 'Ơ' => 'O',
+        'ư' => 'u',
+        'Ư' => 'U',
+        'đ' => 'd',
+        'Đ' => 'D',
+        other => other,
+    }
+}
+
+/// Fold Vietnamese text to plain ASCII letters ("Tiếng Việt" -> "Tieng Viet").
+/// Tones and letter marks are dropped, also when written as combining marks;
+/// other characters are kept.
+pub fn remove_diacritics(text: &str) -> String {
+    text.chars()
+        .filter(|c| !('\u{0300}'..='\u{036F}').contains(c))
+        .map(base_letter)
+        .collect()
+}
+
+/// Zero width joiner, which joins the characters on either side into one
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Whether `ch` belongs to the character before it: combining marks and
+/// variation selectors
+fn extends_cluster(ch: char) -> bool {
+    matches!(ch,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0100}'..='\u{E01EF}'
+        | ZERO_WIDTH_JOINER)
+}
+
+/// Split text into the characters a reader sees: a base character with the
+/// combining marks, variation selectors and zero-width-joined characters
+/// after it. Covers Vietnamese in either normalization form and common emoji;
+/// it is not a full UAX #29 segmentation.
+pub fn grapheme_clusters(text: &str) -> Vec<&str> {
+    let mut clusters = Vec::new();
+    let mut start = 0;
+    let mut joined = false;
+    for (index, ch) in text.char_indices() {
+        if index > start && !joined && !extends_cluster(ch) {
+            clusters.push(&text[start..index]);
+            start = index;
+        }
+        joined = ch == ZERO_WIDTH_JOINER;
+    }
+    if start < text.len() {
+        clusters.push(&text[start..]);
+    }
+    clusters
+}
+
+// Combining marks used by Vietnamese letters in decomposed (NFD) form
+const COMBINING_GRAVE: char = '\u{0300}';
+const COMBINING_ACUTE: char = '\u{0301}';
+const COMBINING_CIRCUMFLEX: char = '\u{0302}';
+const COMBINING_TILDE: char = '\u{0303}';
+const COMBINING_BREVE: char = '\u{0306}';
+const COMBINING_HOOK_ABOVE: char = '\u{0309}';
+const COMBINING_HORN: char = '\u{031B}';
+const COMBINING_DOT_BELOW: char = '\u{0323}';
+
+/// Lowercase letters carrying a letter mark, with their base letter and the mark
+const LETTER_MARKS: [(char, char, char); 6] = [
+    ('ă', 'a', COMBINING_BREVE),
+    ('â', 'a', COMBINING_CIRCUMFLEX),
+    ('ê', 'e', COMBINING_CIRCUMFLEX),
+    ('ô', 'o', COMBINING_CIRCUMFLEX),
+    ('ơ', 'o', COMBINING_HORN),
+    ('ư', 'u', COMBINING_HORN),
+];
+
+fn tone_combining_mark(tone: ToneMark) -> Option<char> {
+    match tone {
+        ToneMark::None => None,
+        ToneMark::Acute => Some(COMBINING_ACUTE),
+        ToneMark::Grave => Some(COMBINING_GRAVE),
+        ToneMark::HookAbove => Some(COMBINING_HOOK_ABOVE),
+        ToneMark::Tilde => Some(COMBINING_TILDE),
+        ToneMark::DotBelow => Some(COMBINING_DOT_BELOW),
+    }
+}
+
+fn combining_tone(mark: char) -> Option<ToneMark> {
+    ToneMark::ALL[1..]
+        .iter()
+        .copied()
+        .find(|&tone| tone_combining_mark(tone) == Some(mark))
+}
+
+/// Canonical combining class, which fixes the order of marks in NFD
+fn combining_class(mark: char) -> u8 {
+    match mark {
+        COMBINING_HORN => 216,
+        COMBINING_DOT_BELOW => 220,
+        _ => 230,
+    }
+}
+
+fn with_case(ch: char, uppercase: bool) -> char {
+    if uppercase { to_upper(ch) } else { ch }
+}
+
+/// Write a Vietnamese letter as its base letter followed by combining marks
+fn decompose_char(ch: char, out: &mut String) {
+    let (base, tone) = split_tone(ch);
+    let uppercase = ch.is_uppercase();
+    let (letter, letter_mark) = match LETTER_MARKS.iter().find(|(marked, _, _)| *marked == to_lower(base)) {
+        Some(&(_, letter, mark)) => (with_case(letter, uppercase), Some(mark)),
+        None => (base, None),
+    };
+
+    let mut marks: Vec<char> = letter_mark.into_iter().chain(tone_combining_mark(tone)).collect();
+    // Stable, so the letter mark stays before a tone of the same class
+    marks.sort_by_key(|&mark| combining_class(mark));
+
+    out.push(letter);
+    out.extend(marks);
+}
+
+/// Apply a combining mark to the letter before it, if the result is a Vietnamese letter
+fn compose_pair(prev: char, mark: char) -> Option<char> {
+    let (base, tone) = split_tone(prev);
+    if let Some(new_tone) = combining_tone(mark) {
+        return (is_vowel(prev) && tone == ToneMark::None).then(|| with_tone(prev, new_tone));
+    }
+
+    let uppercase = prev.is_uppercase();
+    LETTER_MARKS
+        .iter()
+        .find(|(_, letter, letter_mark)| *letter == to_lower(base) && *letter_mark == mark)
+        .map(|&(marked, _, _)| with_tone(with_case(marked, uppercase), tone))
+}
+
+/// Convert Vietnamese text to the given normalization form.
+/// Only Vietnamese letters and their marks are affected; other characters are kept.
+pub fn normalize(text: &str, form: Normalization) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    match form {
+        Normalization::Nfd => {
+            for ch in text.chars() {
+                decompose_char(ch, &mut out);
+            }
+        }
+        Normalization::Nfc => {
+            for ch in text.chars() {
+                let composed = out.chars().last().and_then(|prev| compose_pair(prev, ch));
+                match composed {
+                    Some(composed) => {
+                        out.pop();
+                        out.push(composed);
+                    }
+                    None => out.push(ch),
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Every Vietnamese letter that is not plain ASCII, lowercase first then uppercase
+pub fn vietnamese_letters() -> Vec<char> {
+    let lower: Vec<char> = VOWEL_TABLE
+        .iter()
+        .flat_map(|forms| forms.iter().copied())
+        .filter(|c| !c.is_ascii())
+        .chain(std::iter::once('đ'))
+        .collect();
+    let upper: Vec<char> = lower.iter().map(|&c| to_upper(c)).collect();
+    lower.into_iter().chain(upper).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{InputType, ProcessingResult, VietnameseInputProcessor};
+
+    /// Apply typed text the way an editor does, where one backspace removes a
+    /// letter together with the combining marks after it
+    fn apply(screen: &mut String, backspaces: usize, text: &str) {
+        for _ in 0..backspaces {
+            while let Some(ch) = screen.pop() {
+                if !('\u{0300}'..='\u{036F}').contains(&ch) {
+                    break;
+                }
+            }
+        }
+        screen.push_str(text);
+    }
+
+    /// Type `keys` through the engine, normalizing injected text to `form`
+    fn type_keys(keys: &str, form: Normalization) -> String {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        let mut screen = String::new();
+        for key in keys.chars() {
+            match processor.process_key(key) {
+                ProcessingResult::ProcessedText { text, buffer_length }
+                | ProcessingResult::RestoreText { text, buffer_length }
+                | ProcessingResult::AcceptSuggestion { text, buffer_length } => {
+                    apply(&mut screen, buffer_length, &normalize(&text, form))
+                }
+                ProcessingResult::PassThrough(ch) => screen.push(ch),
+                ProcessingResult::ClearAndPassBackspace => apply(&mut screen, 1, ""),
+                ProcessingResult::DismissSuggestions => {}
+            }
+        }
+        screen
+    }
+
+    #[test]
+    fn decomposes_in_canonical_order() {
+        assert_eq!(normalize("ế", Normalization::Nfd), "e\u{0302}\u{0301}");
+        assert_eq!(normalize("Ậ", Normalization::Nfd), "A\u{0323}\u{0302}");
+        assert_eq!(normalize("ự", Normalization::Nfd), "u\u{031B}\u{0323}");
+        assert_eq!(normalize("đ", Normalization::Nfd), "đ");
+    }
+
+    #[test]
+    fn round_trips_every_letter() {
+        let letters: String = vietnamese_letters().into_iter().collect();
+        let decomposed = normalize(&letters, Normalization::Nfd);
+        assert!(decomposed.chars().all(|c| c.is_ascii() || c == 'đ' || c == 'Đ' || ('\u{0300}'..='\u{036F}').contains(&c)));
+        assert_eq!(normalize(&decomposed, Normalization::Nfc), letters);
+    }
+
+    #[test]
+    fn clusters_keep_marks_with_their_letter() {
+        let decomposed = normalize("Việt", Normalization::Nfd);
+        assert_eq!(grapheme_clusters(&decomposed), ["V", "i", "e\u{0323}\u{0302}", "t"]);
+        assert_eq!(grapheme_clusters("ạ👍🏽👨\u{200D}👩x"), ["ạ", "👍🏽", "👨\u{200D}👩", "x"]);
+        assert!(grapheme_clusters("").is_empty());
+    }
+
+    #[test]
+    fn both_forms_render_identically() {
+        for keys in ["tieengs vieetj ", "nguwowif ", "ddaays laf Haf Nooij", "thuwr", "bafn phims"] {
+            let composed = type_keys(keys, Normalization::Nfc);
+            let decomposed = type_keys(keys, Normalization::Nfd);
+            assert_ne!(composed, decomposed, "{}", keys);
+            assert_eq!(normalize(&decomposed, Normalization::Nfc), composed, "{}", keys);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::config::AppConfig;
+use crate::core::data_dir::{self, DataKind};
+use crate::core::datafile::{self, COMPAT_FORMAT};
+use crate::error::Result;
+
+/// Injection settings recommended for applications with known quirks
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompatEntry {
+    /// Name shown in the settings and used to switch the entry off
+    pub name: String,
+    /// Matched against the bundle path of the frontmost application
+    pub apps: Vec<String>,
+    /// Type words only once committed, without a preview while composing
+    pub commit_only: bool,
+    /// Never show the word as marked text, even if the app claims to accept it
+    pub no_marked_text: bool,
+    /// Wait in milliseconds between erasing a word and typing its replacement
+    pub injection_delay_ms: u64,
+    /// Paste the replacement from the clipboard instead of typing it
+    pub paste: bool,
+}
+
+impl CompatEntry {
+    fn new(name: &str, apps: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            apps: apps.iter().map(|app| app.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the entry covers the application at `app`
+    pub fn matches(&self, app: &str) -> bool {
+        self.apps.iter().any(|pattern| !pattern.is_empty() && app.contains(pattern.as_str()))
+    }
+}
+
+/// Applications that need gentler injection, with the settings that work
+/// for them. Used only with "strict compatibility" switched on; an entry the
+/// user switched off, or a stricter setting of their own, wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatCatalog {
+    entries: Vec<CompatEntry>,
+}
+
+impl Default for CompatCatalog {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl CompatCatalog {
+    /// The catalog shipped with VKey
+    pub fn builtin() -> Self {
+        let entries = vec![
+            // Cell editing drops a replacement typed right after its backspaces
+            CompatEntry {
+                commit_only: true,
+                injection_delay_ms: 10,
+                ..CompatEntry::new("Microsoft Excel", &["Microsoft Excel.app"])
+            },
+            // Word and PowerPoint drop or reorder text typed right after
+            // backspaces, but take it reliably as a paste
+            CompatEntry {
+                paste: true,
+                injection_delay_ms: 10,
+                ..CompatEntry::new("Microsoft Office", &["Microsoft Word.app", "Microsoft PowerPoint.app", "Microsoft Outlook.app"])
+            },
+            // The editors accept marked text but type it twice on commit
+            CompatEntry {
+                no_marked_text: true,
+                injection_delay_ms: 5,
+                ..CompatEntry::new(
+                    "JetBrains IDEs",
+                    &[
+                        "IntelliJ IDEA", "PyCharm", "WebStorm", "CLion", "GoLand", "RustRover",
+                        "PhpStorm", "RubyMine", "Rider", "DataGrip", "Android Studio",
+                    ],
+                )
+            },
+            // The integrated terminal reorders backspaces and text under load
+            CompatEntry {
+                injection_delay_ms: 15,
+                ..CompatEntry::new("Visual Studio Code", &["Visual Studio Code.app", "VSCodium.app"])
+            },
+            // The message box loses characters when a word is replaced quickly
+            CompatEntry {
+                commit_only: true,
+                injection_delay_ms: 20,
+                ..CompatEntry::new("Discord", &["Discord.app"])
+            },
+        ];
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[CompatEntry] {
+        &self.entries
+    }
+
+    /// Add entries, replacing built-in ones with the same name
+    pub fn merge(&mut self, entries: Vec<CompatEntry>) {
+        for entry in entries {
+            if entry.name.trim().is_empty() || entry.apps.is_empty() {
+                continue;
+            }
+            match self.entries.iter_mut().find(|known| known.name == entry.name) {
+                Some(known) => *known = entry,
+                None => self.entries.push(entry),
+            }
+        }
+    }
+
+    /// The entry to apply to the application at `app`, if strict
+    /// compatibility is on and the user did not switch the entry off
+    pub fn entry_for(&self, app: &str, config: &AppConfig) -> Option<&CompatEntry> {
+        if !config.advanced.strict_compatibility {
+            return None;
+        }
+        self.entries
+            .iter()
+            .filter(|entry| !config.compat_overrides.contains(&entry.name))
+            .find(|entry| entry.matches(app))
+    }
+
+    /// Get the default catalog file path (in the config directory)
+    pub fn get_catalog_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Config, "compat.json")
+    }
+
+    /// The built-in catalog, updated with the entries of the catalog file if
+    /// there is one
+    pub fn load_default() -> Result<Self> {
+        let mut catalog = Self::builtin();
+        let path = Self::get_catalog_path()?;
+        if path.exists() {
+            catalog.merge(Self::load(path.to_str().unwrap_or("compat.json"))?);
+        }
+        Ok(catalog)
+    }
+
+    /// Load catalog entries from a catalog file, or from the copy kept
+    /// beside it if it is damaged
+    pub fn load(path: &str) -> Result<Vec<CompatEntry>> {
+        datafile::read_or_recover(Path::new(path), &COMPAT_FORMAT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalog_applies_only_when_opted_in() {
+        let mut catalog = CompatCatalog::builtin();
+        catalog.merge(serde_json::from_str(r#"[
+            {"name": "Discord", "apps": ["Discord.app"], "injection_delay_ms": 40},
+            {"name": "Zalo", "apps": ["Zalo.app"], "commit_only": true}
+        ]"#).unwrap());
+        let discord = "/Applications/Discord.app";
+
+        let mut config = AppConfig::default();
+        assert!(catalog.entry_for(discord, &config).is_none());
+
+        config.advanced.strict_compatibility = true;
+        let entry = catalog.entry_for(discord, &config).unwrap();
+        assert_eq!(entry.injection_delay_ms, 40);
+        assert!(!entry.commit_only);
+        assert!(catalog.entry_for("/Applications/Zalo.app", &config).unwrap().commit_only);
+        assert!(catalog.entry_for("/Applications/PyCharm CE.app", &config).unwrap().no_marked_text);
+        assert!(catalog.entry_for("/Applications/Microsoft Word.app", &config).unwrap().paste);
+
+        config.compat_overrides.push("Discord".to_string());
+        assert!(catalog.entry_for(discord, &config).is_none());
+    }
+}
@@ -1,20 +1,84 @@
 use serde::{Deserialize, Serialize};
-use crate::core::types::{InputType, Encoding, InputMode, KeyboardConfig, AdvancedSettings};
+use crate::core::corrections::DEFAULT_CORRECTION_RETENTION_DAYS;
+use crate::core::data_dir::{self, DataKind};
+use crate::core::engine::VniKeyMap;
+use crate::core::types::{InputType, Encoding, InputMode, KeyboardConfig, AdvancedSettings, CustomDiacritic, Normalization, RestorePattern, WordOverflow, VNI_STANDARD_KEYS};
 use crate::error::Result;
+use serde_json::Value;
 use std::path::PathBuf;
 
+/// Default number of keystrokes composed into a single word
+pub const DEFAULT_MAX_WORD_LENGTH: usize = 20;
+
+/// Bounds for a user-configured maximum word length
+const MIN_MAX_WORD_LENGTH: usize = 8;
+const MAX_MAX_WORD_LENGTH: usize = 64;
+
+/// Built-in restore patterns: doubled tone keys and letters that rarely repeat in Vietnamese
+pub const DEFAULT_RESTORE_PATTERNS: [&str; 17] = [
+    "ss", "ff", "jj", "rr", "xx", "ww", "kk", "tt", "nn", "mm", "yy", "hh", "ii", "aaa", "eee",
+    "ooo", "ddd",
+];
+
+/// Default hotkey for putting back the keys typed for the last converted word
+pub const DEFAULT_UNDO_CONVERSION_HOTKEY: &str = "ctrl+shift+z";
+
+/// Default hotkey for removing diacritics from the selected text
+pub const DEFAULT_REMOVE_DIACRITICS_HOTKEY: &str = "ctrl+shift+d";
+
+/// Default hotkey for adding the selected word to the user dictionary, or removing it
+pub const DEFAULT_DICTIONARY_HOTKEY: &str = "ctrl+shift+a";
+
+/// Default hotkey that shows the keys typed for the word while it is held
+pub const DEFAULT_PEEK_HOTKEY: &str = "ctrl+shift+r";
+
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub input_type: InputType,
     pub encoding: Encoding,
+    /// Normalization form of Unicode output; legacy encodings ignore it
+    pub output_normalization: Normalization,
     pub input_mode: InputMode,
     pub keyboard: KeyboardConfig,
     pub advanced: AdvancedSettings,
     /// Global hotkey configuration for toggling Vietnamese input
     pub global_hotkey: Option<String>,
+    /// Hotkey that replaces the last converted word with the keys typed for it
+    pub undo_conversion_hotkey: Option<String>,
+    /// Hotkey that replaces the selected text with its diacritic-free form
+    pub remove_diacritics_hotkey: Option<String>,
+    /// Hotkey that adds the selected word to the user dictionary, or removes it
+    pub dictionary_hotkey: Option<String>,
+    /// Hotkey that shows the keys typed for the word being composed in its
+    /// place while it is held
+    pub peek_hotkey: Option<String>,
     /// Auto-save configuration on changes
     pub auto_save: bool,
+    /// Maximum number of keystrokes composed into a single word
+    pub max_word_length: usize,
+    /// Behavior when a word exceeds `max_word_length`
+    pub word_overflow: WordOverflow,
+    /// Applications (matched against the bundle path) where words are only
+    /// typed once committed, for apps that flicker or drop injected edits
+    pub preview_suppressed_apps: Vec<String>,
+    /// Bundle identifiers of applications where every key passes through
+    /// untouched, such as games, virtual machines and remote desktops
+    pub excluded_apps: Vec<String>,
+    /// Applications (matched against the bundle path) where a word is
+    /// replaced by typing over it through accessibility rather than with
+    /// backspaces, for editors that support it
+    pub accessibility_replacement_apps: Vec<String>,
+    /// Compatibility catalog entries, by name, the user switched off
+    pub compat_overrides: Vec<String>,
+    /// Key sequences that stop composing a word, built-in and user-added
+    pub restore_patterns: Vec<RestorePattern>,
+    /// Name of a language pack that composes words instead of the Vietnamese
+    /// input method; None types Vietnamese
+    pub transform_scheme: Option<String>,
+    /// Extra letters substituted into words after the input method composed them
+    pub custom_diacritics: Vec<CustomDiacritic>,
 }
 
 impl Default for AppConfig {
@@ -22,17 +86,46 @@ impl Default for AppConfig {
         Self {
             input_type: InputType::Telex,
             encoding: Encoding::Unicode,
+            output_normalization: Normalization::Nfc,
             input_mode: InputMode::Vietnamese,
             keyboard: KeyboardConfig::default(),
             advanced: AdvancedSettings::default(),
             global_hotkey: Some("cmd+space".to_string()),
+            undo_conversion_hotkey: Some(DEFAULT_UNDO_CONVERSION_HOTKEY.to_string()),
+            remove_diacritics_hotkey: Some(DEFAULT_REMOVE_DIACRITICS_HOTKEY.to_string()),
+            dictionary_hotkey: Some(DEFAULT_DICTIONARY_HOTKEY.to_string()),
+            peek_hotkey: Some(DEFAULT_PEEK_HOTKEY.to_string()),
             auto_save: true,
+            max_word_length: DEFAULT_MAX_WORD_LENGTH,
+            word_overflow: WordOverflow::Commit,
+            preview_suppressed_apps: Vec::new(),
+            excluded_apps: Vec::new(),
+            accessibility_replacement_apps: Vec::new(),
+            compat_overrides: Vec::new(),
+            restore_patterns: DEFAULT_RESTORE_PATTERNS
+                .iter()
+                .map(|pattern| RestorePattern {
+                    pattern: pattern.to_string(),
+                    enabled: true,
+                })
+                .collect(),
+            transform_scheme: None,
+            custom_diacritics: Vec::new(),
         }
     }
 }
 
 impl AppConfig {
-    /// Get the default configuration directory path
+    /// The normalization form to apply before typing. Legacy encodings are
+    /// byte tables with their own composition rules, so they always get NFC.
+    pub fn effective_normalization(&self) -> Normalization {
+        match self.encoding {
+            Encoding::Unicode => self.output_normalization,
+            _ => Normalization::Nfc,
+        }
+    }
+
+    /// Get the directory holding all of VKey's files, laid out by `data_dir`
     pub fn get_config_dir() -> Result<PathBuf> {
         #[cfg(target_os = "macos")]
         {
@@ -70,46 +163,86 @@ impl AppConfig {
     
     /// Get the default configuration file path
     pub fn get_config_path() -> Result<PathBuf> {
-        let mut path = Self::get_config_dir()?;
-        path.push("config.json");
-        Ok(path)
-    }
-    
-    /// Ensure the configuration directory exists
-    pub fn ensure_config_dir() -> Result<PathBuf> {
-        let config_dir = Self::get_config_dir()?;
-        if !config_dir.exists() {
-            std::fs::create_dir_all(&config_dir)
-                .map_err(|e| crate::error::VKeyError::ConfigError(
-                    format!("Failed to create config directory: {}", e)
-                ))?;
-        }
-        Ok(config_dir)
+        data_dir::data_file(DataKind::Config, "config.json")
     }
     
     /// Load configuration from the default location
     pub fn load_default() -> Result<Self> {
-        Self::ensure_config_dir()?;
+        data_dir::ensure_data_dir(DataKind::Config)?;
         let config_path = Self::get_config_path()?;
         
         if config_path.exists() {
             Self::load(config_path.to_str().unwrap_or("config.json"))
         } else {
-            // Create default config if none exists
-            let default_config = Self::default();
+            // Create default config if none exists, starting from the machine-wide defaults
+            let default_config = Self::from_layers(Self::system_defaults().as_ref(), Value::Object(Default::default()))
+                .unwrap_or_default();
             default_config.save_default()?;
             Ok(default_config)
         }
     }
     
-    /// Load configuration from a file
+    /// Machine-wide defaults, set up by an administrator for every user of
+    /// the Mac and layered under each user's config
+    pub fn get_system_defaults_path() -> Option<PathBuf> {
+        #[cfg(target_os = "macos")]
+        {
+            Some(PathBuf::from("/Library/Application Support/VKey/defaults.json"))
+        }
+        #[cfg(target_os = "linux")]
+        {
+            Some(PathBuf::from("/etc/vkey/defaults.json"))
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        {
+            None
+        }
+    }
+
+    /// The machine-wide defaults, if an administrator installed them. A file
+    /// that cannot be read is reported and left out.
+    fn system_defaults() -> Option<Value> {
+        let path = Self::get_system_defaults_path()?;
+        if !path.exists() {
+            return None;
+        }
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<Value>(&text).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(defaults @ Value::Object(_)) => Some(defaults),
+            Ok(_) => {
+                eprintln!("Ignoring system defaults '{}': not a JSON object", path.display());
+                None
+            }
+            Err(e) => {
+                eprintln!("Ignoring system defaults '{}': {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// The built-in settings with the machine-wide defaults over them
+    fn layered_defaults(system: Option<&Value>) -> Value {
+        let mut base = serde_json::to_value(Self::default()).unwrap_or(Value::Null);
+        if let Some(system) = system {
+            merge_json(&mut base, system.clone());
+        }
+        base
+    }
+
+    /// Load configuration from a file, over the machine-wide defaults
     pub fn load(path: &str) -> Result<Self> {
         let config_str = std::fs::read_to_string(path)
             .map_err(|e| crate::error::VKeyError::ConfigError(
                 format!("Failed to read config file '{}': {}", path, e)
             ))?;
         
-        let mut config: Self = serde_json::from_str(&config_str)
+        let user: Value = serde_json::from_str(&config_str)
+            .map_err(|e| crate::error::VKeyError::ConfigError(
+                format!("Failed to parse config file '{}': {}", path, e)
+            ))?;
+        let mut config = Self::from_layers(Self::system_defaults().as_ref(), user)
             .map_err(|e| crate::error::VKeyError::ConfigError(
                 format!("Failed to parse config file '{}': {}", path, e)
             ))?;
@@ -119,17 +252,26 @@ impl AppConfig {
         
         Ok(config)
     }
+
+    /// The user's settings over the machine-wide defaults over the built-in ones
+    fn from_layers(system: Option<&Value>, user: Value) -> std::result::Result<Self, serde_json::Error> {
+        let mut merged = Self::layered_defaults(system);
+        merge_json(&mut merged, user);
+        serde_json::from_value(merged)
+    }
     
     /// Save configuration to the default location
     pub fn save_default(&self) -> Result<()> {
-        Self::ensure_config_dir()?;
+        data_dir::ensure_data_dir(DataKind::Config)?;
         let config_path = Self::get_config_path()?;
         self.save(config_path.to_str().unwrap_or("config.json"))
     }
 
-    /// Save configuration to a file
+    /// Save configuration to a file. With machine-wide defaults installed,
+    /// only the settings that differ from them are written, so the others
+    /// keep following the defaults.
     pub fn save(&self, path: &str) -> Result<()> {
-        let config_str = serde_json::to_string_pretty(self)
+        let config_str = self.to_layer_json(Self::system_defaults().as_ref())
             .map_err(|e| crate::error::VKeyError::ConfigError(
                 format!("Failed to serialize config: {}", e)
             ))?;
@@ -139,6 +281,15 @@ impl AppConfig {
                 format!("Failed to write config file '{}': {}", path, e)
             ))
     }
+
+    /// The JSON written for this config over `system`
+    fn to_layer_json(&self, system: Option<&Value>) -> std::result::Result<String, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if system.is_some() {
+            strip_matching(&mut value, &Self::layered_defaults(system));
+        }
+        serde_json::to_string_pretty(&value)
+    }
     
     /// Toggle Vietnamese input mode
     pub fn toggle_vietnamese_mode(&mut self) -> Result<()> {
@@ -193,12 +344,71 @@ impl AppConfig {
         } else {
             self.global_hotkey = Some("cmd+space".to_string());
         }
+
+        // The undo hotkey may be switched off, but not left unusable
+        if let Some(ref hotkey) = self.undo_conversion_hotkey {
+            if !self.is_valid_hotkey(hotkey) {
+                eprintln!("Invalid undo conversion hotkey '{}', resetting to default", hotkey);
+                self.undo_conversion_hotkey = Some(DEFAULT_UNDO_CONVERSION_HOTKEY.to_string());
+            }
+        }
+        if let Some(ref hotkey) = self.remove_diacritics_hotkey {
+            if !self.is_valid_hotkey(hotkey) {
+                eprintln!("Invalid remove diacritics hotkey '{}', resetting to default", hotkey);
+                self.remove_diacritics_hotkey = Some(DEFAULT_REMOVE_DIACRITICS_HOTKEY.to_string());
+            }
+        }
+        if let Some(ref hotkey) = self.dictionary_hotkey {
+            if !self.is_valid_hotkey(hotkey) {
+                eprintln!("Invalid dictionary hotkey '{}', resetting to default", hotkey);
+                self.dictionary_hotkey = Some(DEFAULT_DICTIONARY_HOTKEY.to_string());
+            }
+        }
+        if let Some(ref hotkey) = self.peek_hotkey {
+            if !self.is_valid_hotkey(hotkey) {
+                eprintln!("Invalid peek hotkey '{}', resetting to default", hotkey);
+                self.peek_hotkey = Some(DEFAULT_PEEK_HOTKEY.to_string());
+            }
+        }
         
         // Validate keyboard config
         self.validate_keyboard_config();
         
         // Validate advanced settings
         self.validate_advanced_settings();
+
+        // Keep the word length within a range that still fits real syllables
+        let clamped = self.max_word_length.clamp(MIN_MAX_WORD_LENGTH, MAX_MAX_WORD_LENGTH);
+        if clamped != self.max_word_length {
+            eprintln!("Invalid max word length {}, using {}", self.max_word_length, clamped);
+            self.max_word_length = clamped;
+        }
+
+        // Patterns are matched against lowercase keystrokes; drop ones that can never match
+        let mut seen = std::collections::HashSet::new();
+        self.restore_patterns.retain_mut(|restore| {
+            restore.pattern = restore.pattern.trim().to_ascii_lowercase();
+            let valid = Self::is_valid_restore_pattern(&restore.pattern);
+            if !valid {
+                eprintln!("Invalid restore pattern '{}', removing", restore.pattern);
+            }
+            valid && seen.insert(restore.pattern.clone())
+        });
+
+        // Composed words are lowercase, and a backspace removes a letter with
+        // its marks, so a substitute made of combining sequences could not be erased
+        self.custom_diacritics.retain_mut(|mapping| {
+            mapping.from = mapping.from.trim().to_lowercase();
+            mapping.to = mapping.to.trim().to_string();
+            let valid = !mapping.from.is_empty()
+                && !mapping.to.is_empty()
+                && !mapping.from.contains(char::is_whitespace)
+                && crate::core::grapheme_clusters(&mapping.to).len() == mapping.to.chars().count();
+            if !valid {
+                eprintln!("Invalid custom diacritic '{}' -> '{}', removing", mapping.from, mapping.to);
+            }
+            valid
+        });
         
         Ok(())
     }
@@ -246,14 +456,95 @@ impl AppConfig {
             eprintln!("No keyboard modifiers enabled, enabling cmd key by default");
             self.keyboard.cmd_enabled = true;
         }
+
+        if self.keyboard.sound_volume > 100 {
+            eprintln!("Invalid sound volume {}, using 100", self.keyboard.sound_volume);
+            self.keyboard.sound_volume = 100;
+        }
     }
     
     /// Validate and fix advanced settings
     fn validate_advanced_settings(&mut self) {
-        // No specific validation needed for advanced settings currently
-        // but this is where we'd add validation for features that depend on each other
+        if VniKeyMap::parse(&self.advanced.vni_key_order).is_none() {
+            eprintln!("Invalid VNI key order '{}', using {}", self.advanced.vni_key_order, VNI_STANDARD_KEYS);
+            self.advanced.vni_key_order = VNI_STANDARD_KEYS.to_string();
+        }
+
+        if self.advanced.correction_retention_days == 0 {
+            eprintln!("Invalid correction retention of 0 days, using {}", DEFAULT_CORRECTION_RETENTION_DAYS);
+            self.advanced.correction_retention_days = DEFAULT_CORRECTION_RETENTION_DAYS;
+        }
+
+        // Letters, digits and spaces are never word boundaries here
+        let boundaries: String = self.advanced.word_boundaries.chars().filter(char::is_ascii_punctuation).collect();
+        if boundaries != self.advanced.word_boundaries {
+            eprintln!("Ignoring word boundaries other than punctuation in '{}'", self.advanced.word_boundaries);
+            self.advanced.word_boundaries = boundaries;
+        }
     }
     
+    /// Check if composition preview is suppressed for an application
+    pub fn is_preview_suppressed_for(&self, app: &str) -> bool {
+        self.preview_suppressed_apps
+            .iter()
+            .any(|pattern| !pattern.is_empty() && app.contains(pattern.as_str()))
+    }
+    
+    /// Check if words are replaced through accessibility in an application
+    pub fn uses_accessibility_replacement(&self, app: &str) -> bool {
+        self.accessibility_replacement_apps
+            .iter()
+            .any(|pattern| !pattern.is_empty() && app.contains(pattern.as_str()))
+    }
+
+    /// Check if VKey leaves every key alone in the application with this
+    /// bundle identifier
+    pub fn is_excluded_app(&self, bundle_id: &str) -> bool {
+        self.excluded_apps
+            .iter()
+            .any(|excluded| !excluded.trim().is_empty() && excluded.trim().eq_ignore_ascii_case(bundle_id))
+    }
+
+    /// A restore pattern needs at least two printable ASCII keys
+    fn is_valid_restore_pattern(pattern: &str) -> bool {
+        pattern.len() >= 2 && pattern.chars().all(|c| c.is_ascii_graphic())
+    }
+
+    /// Enabled restore patterns, in the form the input processor expects
+    pub fn active_restore_patterns(&self) -> Vec<String> {
+        self.restore_patterns
+            .iter()
+            .filter(|restore| restore.enabled)
+            .map(|restore| restore.pattern.clone())
+            .collect()
+    }
+
+    /// Turn a restore pattern on or off
+    pub fn toggle_restore_pattern(&mut self, pattern: &str) {
+        if let Some(restore) = self.restore_patterns.iter_mut().find(|restore| restore.pattern == pattern) {
+            restore.enabled = !restore.enabled;
+        }
+    }
+
+    /// Add an enabled restore pattern. Returns false if it is invalid or already present.
+    pub fn add_restore_pattern(&mut self, pattern: &str) -> bool {
+        let pattern = pattern.trim().to_ascii_lowercase();
+        if !Self::is_valid_restore_pattern(&pattern)
+            || self.restore_patterns.iter().any(|restore| restore.pattern == pattern)
+        {
+            return false;
+        }
+        self.restore_patterns.push(RestorePattern { pattern, enabled: true });
+        true
+    }
+
+    /// Remove a user-added restore pattern. Built-in patterns can only be disabled.
+    pub fn remove_restore_pattern(&mut self, pattern: &str) {
+        if !DEFAULT_RESTORE_PATTERNS.contains(&pattern) {
+            self.restore_patterns.retain(|restore| restore.pattern != pattern);
+        }
+    }
+
     /// Get available hotkey options for the UI
     pub fn get_hotkey_options() -> Vec<(&'static str, &'static str)> {
         vec![
@@ -302,4 +593,91 @@ impl AppConfig {
         }
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+
+/// Lay `overlay` over `base`: objects are merged key by key, anything else,
+/// lists included, replaces what was there
+fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Remove from `value` everything `base` already says, leaving the settings
+/// that differ
+fn strip_matching(value: &mut Value, base: &Value) {
+    if let (Value::Object(value), Value::Object(base)) = (value, base) {
+        value.retain(|key, item| match base.get(key) {
+            Some(default) if default == item => false,
+            Some(default) => {
+                strip_matching(item, default);
+                !matches!(item, Value::Object(fields) if fields.is_empty())
+            }
+            None => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_settings_are_layered_over_system_defaults() {
+        let system = serde_json::json!({
+            "input_type": "VNI",
+            "advanced": {"spell_check": false},
+            "preview_suppressed_apps": ["Excel.app"]
+        });
+        let user = serde_json::json!({
+            "advanced": {"word_suggestions": true},
+            "undo_conversion_hotkey": "ctrl+alt+z"
+        });
+
+        let config = AppConfig::from_layers(Some(&system), user).unwrap();
+        assert_eq!(config.input_type, InputType::VNI);
+        assert!(!config.advanced.spell_check);
+        assert!(config.advanced.word_suggestions);
+        assert_eq!(config.preview_suppressed_apps, vec!["Excel.app".to_string()]);
+        assert_eq!(config.undo_conversion_hotkey.as_deref(), Some("ctrl+alt+z"));
+
+        // Only the user's own choices are written back
+        let saved: Value = serde_json::from_str(&config.to_layer_json(Some(&system)).unwrap()).unwrap();
+        assert_eq!(saved, serde_json::json!({
+            "advanced": {"word_suggestions": true},
+            "undo_conversion_hotkey": "ctrl+alt+z"
+        }));
+    }
+
+    #[test]
+    fn excluded_apps_match_the_whole_bundle_identifier() {
+        let config = AppConfig {
+            excluded_apps: vec!["com.microsoft.rdc.macos".to_string(), " ".to_string()],
+            ..AppConfig::default()
+        };
+        assert!(config.is_excluded_app("com.microsoft.rdc.macos"));
+        assert!(config.is_excluded_app("com.Microsoft.RDC.macos"));
+        assert!(!config.is_excluded_app("com.microsoft"));
+        assert!(!config.is_excluded_app(""));
+    }
+
+    #[test]
+    fn accessibility_replacement_apps_match_the_bundle_path() {
+        let config = AppConfig {
+            accessibility_replacement_apps: vec!["TextEdit.app".to_string(), String::new()],
+            ..AppConfig::default()
+        };
+        assert!(config.uses_accessibility_replacement("/System/Applications/TextEdit.app"));
+        assert!(!config.uses_accessibility_replacement("/Applications/Pages.app"));
+    }
+}
@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::data_dir::{self, DataKind};
+use crate::core::memory::string_bytes;
+use crate::error::{Result, VKeyError};
+
+/// Days a logged correction is kept unless the settings say otherwise
+pub const DEFAULT_CORRECTION_RETENTION_DAYS: u64 = 30;
+
+/// Most corrections kept; the oldest makes room for a new one
+pub const MAX_LOGGED_CORRECTIONS: usize = 1_000;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Why a word was put back to its keys on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CorrectionReason {
+    /// The word was not a valid Vietnamese syllable
+    NotVietnamese,
+    /// The keys looked like an English word
+    LooksEnglish,
+    /// The user had put the word back often enough before
+    Learned,
+}
+
+impl std::fmt::Display for CorrectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CorrectionReason::NotVietnamese => write!(f, "not Vietnamese"),
+            CorrectionReason::LooksEnglish => write!(f, "looks English"),
+            CorrectionReason::Learned => write!(f, "learned"),
+        }
+    }
+}
+
+/// A word VKey put back to its keys without being asked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Correction {
+    /// The word as it was on screen
+    pub shown: String,
+    /// The keys it was replaced with
+    pub keys: String,
+    pub reason: CorrectionReason,
+    /// Bundle path of the application it was typed in
+    pub app: String,
+    /// Seconds since the Unix epoch
+    pub at: u64,
+}
+
+/// Words restored automatically, kept on this Mac only while logging is
+/// switched on, so the user can see what was changed and keep the words
+/// they want left alone. Entries older than the retention period are purged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorrectionLog {
+    entries: Vec<Correction>,
+}
+
+impl CorrectionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, correction: Correction) {
+        if self.entries.len() >= MAX_LOGGED_CORRECTIONS {
+            self.entries.remove(0);
+        }
+        self.entries.push(correction);
+    }
+
+    /// Logged corrections, oldest first
+    pub fn entries(&self) -> &[Correction] {
+        &self.entries
+    }
+
+    /// Drop entries older than `retention_days` at `now`. Returns how many
+    /// were dropped.
+    pub fn purge(&mut self, now: u64, retention_days: u64) -> usize {
+        let cutoff = now.saturating_sub(retention_days.saturating_mul(SECS_PER_DAY));
+        let before = self.entries.len();
+        self.entries.retain(|correction| correction.at >= cutoff);
+        before - self.entries.len()
+    }
+
+    /// Drop every entry for the word typed with `keys`
+    pub fn forget(&mut self, keys: &str) {
+        self.entries.retain(|correction| !correction.keys.eq_ignore_ascii_case(keys));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Approximate bytes held by the logged corrections
+    pub fn approx_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .entries
+                .iter()
+                .map(|c| std::mem::size_of::<Correction>() + string_bytes(&c.shown) + string_bytes(&c.keys) + string_bytes(&c.app))
+                .sum::<usize>()
+    }
+
+    /// Get the default correction log path (in the logs directory)
+    pub fn get_log_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Logs, "corrections.json")
+    }
+
+    /// Load the log from the default location, starting empty if there is none
+    pub fn load_default() -> Result<Self> {
+        let path = Self::get_log_path()?;
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        Self::load(path.to_str().unwrap_or("corrections.json"))
+    }
+
+    /// Load the log from a JSON list of corrections
+    pub fn load(path: &str) -> Result<Self> {
+        let log_str = std::fs::read_to_string(path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read correction log '{}': {}", path, e)
+            ))?;
+
+        let mut entries: Vec<Correction> = serde_json::from_str(&log_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse correction log '{}': {}", path, e)
+            ))?;
+        let excess = entries.len().saturating_sub(MAX_LOGGED_CORRECTIONS);
+        entries.drain(..excess);
+        Ok(Self { entries })
+    }
+
+    /// Save the log to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Logs)?;
+        let path = Self::get_log_path()?;
+        self.save(path.to_str().unwrap_or("corrections.json"))
+    }
+
+    /// Save the log as a JSON list of corrections
+    pub fn save(&self, path: &str) -> Result<()> {
+        let log_str = serde_json::to_string_pretty(&self.entries)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to serialize correction log: {}", e)
+            ))?;
+
+        std::fs::write(path, log_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to write correction log '{}': {}", path, e)
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::habits::RESTORES_TO_LEARN;
+    use crate::core::types::{AdvancedSettings, InputType};
+    use crate::core::vietnamese_input::{ProcessingResult, VietnameseInputProcessor};
+
+    fn type_keys(processor: &mut VietnameseInputProcessor, keys: &str) -> ProcessingResult {
+        let mut result = ProcessingResult::consumed();
+        for key in keys.chars() {
+            result = processor.process_key(key);
+        }
+        result
+    }
+
+    #[test]
+    fn restored_words_are_logged_until_kept() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        processor.apply_settings(&AdvancedSettings {
+            log_corrections: true,
+            ..AdvancedSettings::default()
+        });
+        processor.set_active_app("/Applications/Notes.app");
+
+        for _ in 0..RESTORES_TO_LEARN {
+            type_keys(&mut processor, "text ");
+            processor.undo_conversion();
+        }
+        // Undone by the user, so nothing was logged yet
+        assert!(processor.get_corrections().is_empty());
+
+        type_keys(&mut processor, "text ");
+        let logged = processor.get_corrections().entries().to_vec();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].keys, "text");
+        assert_eq!(logged[0].reason, CorrectionReason::Learned);
+        assert_eq!(logged[0].app, "/Applications/Notes.app");
+
+        processor.keep_corrected_word("text");
+        assert!(processor.get_corrections().is_empty());
+        let result = type_keys(&mut processor, "text ");
+        assert!(!matches!(result, ProcessingResult::RestoreText { .. }));
+    }
+
+    #[test]
+    fn old_corrections_are_purged() {
+        let correction = |at| Correction {
+            shown: "tẽt".to_string(),
+            keys: "text".to_string(),
+            reason: CorrectionReason::NotVietnamese,
+            app: String::new(),
+            at,
+        };
+        let now = 100 * SECS_PER_DAY;
+        let mut log = CorrectionLog::new();
+        log.record(correction(now - 31 * SECS_PER_DAY));
+        log.record(correction(now - SECS_PER_DAY));
+        assert_eq!(log.purge(now, DEFAULT_CORRECTION_RETENTION_DAYS), 1);
+        assert_eq!(log.len(), 1);
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use crate::core::config::AppConfig;
+use crate::error::{Result, VKeyError};
+
+/// Version of the layout below the config directory. Bumped whenever files
+/// move, with a matching step in `migrate_in`.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// File in the config directory recording its layout version
+const VERSION_FILE: &str = "VERSION";
+
+/// Groups of files kept in their own subdirectory of the config directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataKind {
+    /// Application settings
+    Config,
+    /// Macro tables
+    Macros,
+    /// User dictionary and typing history
+    Dicts,
+    /// Daemon output and typing statistics
+    Logs,
+    /// Copies of files replaced by a migration
+    Backups,
+}
+
+impl DataKind {
+    pub const ALL: [DataKind; 5] = [
+        DataKind::Config,
+        DataKind::Macros,
+        DataKind::Dicts,
+        DataKind::Logs,
+        DataKind::Backups,
+    ];
+
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            DataKind::Config => "config",
+            DataKind::Macros => "macros",
+            DataKind::Dicts => "dicts",
+            DataKind::Logs => "logs",
+            DataKind::Backups => "backups",
+        }
+    }
+}
+
+/// Where files of version 0, which all sat in the config directory, belong now
+const LEGACY_FILES: [(&str, DataKind); 5] = [
+    ("config.json", DataKind::Config),
+    ("macros.json", DataKind::Macros),
+    ("dictionary.json", DataKind::Dicts),
+    ("history.json", DataKind::Dicts),
+    ("stats.json", DataKind::Logs),
+];
+
+/// Get the directory for one kind of data
+pub fn data_dir(kind: DataKind) -> Result<PathBuf> {
+    let mut path = AppConfig::get_config_dir()?;
+    path.push(kind.dir_name());
+    Ok(path)
+}
+
+/// Get the path of a file in the directory for `kind`
+pub fn data_file(kind: DataKind, name: &str) -> Result<PathBuf> {
+    let mut path = data_dir(kind)?;
+    path.push(name);
+    Ok(path)
+}
+
+/// Ensure the directory for one kind of data exists
+pub fn ensure_data_dir(kind: DataKind) -> Result<PathBuf> {
+    let path = data_dir(kind)?;
+    create_dir(&path)?;
+    Ok(path)
+}
+
+/// Bring the config directory up to `LAYOUT_VERSION`. Run at startup before
+/// anything is loaded; returns the files that were moved.
+pub fn migrate() -> Result<Vec<PathBuf>> {
+    migrate_in(&AppConfig::get_config_dir()?)
+}
+
+/// Migrate the layout of the config directory at `root`
+pub fn migrate_in(root: &Path) -> Result<Vec<PathBuf>> {
+    let version = read_version(root);
+    if version >= LAYOUT_VERSION {
+        return Ok(Vec::new());
+    }
+
+    let mut moved = Vec::new();
+    if version < 1 {
+        for (name, kind) in LEGACY_FILES {
+            let legacy = root.join(name);
+            if !legacy.is_file() {
+                continue;
+            }
+            let dir = root.join(kind.dir_name());
+            create_dir(&dir)?;
+            // A file already in the new place wins; the old one is kept as a backup
+            let target = if dir.join(name).exists() {
+                let backups = root.join(DataKind::Backups.dir_name()).join("layout-0");
+                create_dir(&backups)?;
+                backups.join(name)
+            } else {
+                dir.join(name)
+            };
+            std::fs::rename(&legacy, &target)
+                .map_err(|e| VKeyError::ConfigError(
+                    format!("Failed to move '{}' to '{}': {}", legacy.display(), target.display(), e)
+                ))?;
+            moved.push(target);
+        }
+    }
+
+    for kind in DataKind::ALL {
+        create_dir(&root.join(kind.dir_name()))?;
+    }
+    std::fs::write(root.join(VERSION_FILE), format!("{}\n", LAYOUT_VERSION))
+        .map_err(|e| VKeyError::ConfigError(
+            format!("Failed to write data layout version: {}", e)
+        ))?;
+    Ok(moved)
+}
+
+/// Layout version of the directory at `root`, 0 when it predates versioning
+fn read_version(root: &Path) -> u32 {
+    std::fs::read_to_string(root.join(VERSION_FILE))
+        .ok()
+        .and_then(|version| version.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn create_dir(path: &Path) -> Result<()> {
+    std::fs::create_dir_all(path)
+        .map_err(|e| VKeyError::ConfigError(
+            format!("Failed to create directory '{}': {}", path.display(), e)
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_legacy_files_once() {
+        let root = std::env::temp_dir().join(format!("vkey-layout-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("config")).unwrap();
+        std::fs::write(root.join("config.json"), "old").unwrap();
+        std::fs::write(root.join("config/config.json"), "new").unwrap();
+        std::fs::write(root.join("dictionary.json"), "[]").unwrap();
+
+        let moved = migrate_in(&root).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert_eq!(std::fs::read_to_string(root.join("config/config.json")).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(root.join("backups/layout-0/config.json")).unwrap(), "old");
+        assert!(root.join("dicts/dictionary.json").is_file());
+        assert!(!root.join("dictionary.json").exists());
+        assert!(root.join("logs").is_dir());
+        assert_eq!(read_version(&root), LAYOUT_VERSION);
+
+        // Up to date, so nothing happens the second time
+        std::fs::write(root.join("macros.json"), "{}").unwrap();
+        assert!(migrate_in(&root).unwrap().is_empty());
+        assert!(root.join("macros.json").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+//! On-disk format of the tables the user builds up or edits by hand: macros,
+//! the user dictionary and the compatibility overrides. Each file records
+//! the table it holds, the version of its format and a checksum of its
+//! entries:
+//!
+//! `{"format": "vkey-macros", "version": 1, "checksum": "fnv1a64:…", "entries": [...]}`
+//!
+//! A file that does not parse or whose entries no longer match the checksum
+//! is never loaded in part, and never overwritten. The copy kept from the
+//! last save, `<file>.bak`, is loaded in its place if it is sound, and the
+//! damaged file is kept as `<file>.damaged`. Files from before versioning,
+//! which hold the bare entries, are read as version 0 and written in the
+//! current format on the next save. A file edited by hand may leave out
+//! the checksum.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{Result, VKeyError};
+
+/// Which table a file holds, and the newest version of its format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataFormat {
+    pub name: &'static str,
+    pub version: u32,
+    /// What the file is called in messages
+    pub label: &'static str,
+}
+
+pub const MACRO_FORMAT: DataFormat = DataFormat {
+    name: "vkey-macros",
+    version: 1,
+    label: "macro file",
+};
+
+pub const DICTIONARY_FORMAT: DataFormat = DataFormat {
+    name: "vkey-dictionary",
+    version: 1,
+    label: "dictionary file",
+};
+
+pub const COMPAT_FORMAT: DataFormat = DataFormat {
+    name: "vkey-compat",
+    version: 1,
+    label: "compatibility catalog",
+};
+
+/// Why a file could not be read
+enum ReadError {
+    /// The file is missing, belongs to another table or comes from a newer
+    /// VKey; it is left as it is
+    Unreadable(VKeyError),
+    /// The file does not parse or fails its checksum
+    Damaged(VKeyError),
+}
+
+impl From<ReadError> for VKeyError {
+    fn from(error: ReadError) -> Self {
+        match error {
+            ReadError::Unreadable(error) | ReadError::Damaged(error) => error,
+        }
+    }
+}
+
+/// The file as written
+#[derive(Serialize)]
+struct Envelope<'a> {
+    format: &'static str,
+    version: u32,
+    checksum: String,
+    entries: &'a Value,
+}
+
+/// Read the entries of a file in `format`
+pub fn read<T: DeserializeOwned>(path: &Path, format: &DataFormat) -> Result<T> {
+    Ok(read_checked(path, format)?)
+}
+
+/// Read the entries of a file in `format`. A damaged file is replaced by the
+/// copy from the last save if that one is sound, keeping the damaged file
+/// aside; otherwise the error is returned and the file left alone.
+pub fn read_or_recover<T: DeserializeOwned>(path: &Path, format: &DataFormat) -> Result<T> {
+    let error = match read_checked(path, format) {
+        Ok(entries) => return Ok(entries),
+        Err(ReadError::Unreadable(error)) => return Err(error),
+        Err(ReadError::Damaged(error)) => error,
+    };
+    let backup = backup_path(path);
+    let Ok(entries) = read_checked(&backup, format) else {
+        return Err(error);
+    };
+    let damaged = damaged_path(path);
+    rename(path, &damaged)?;
+    std::fs::copy(&backup, path)
+        .map_err(|e| VKeyError::ConfigError(
+            format!("Failed to restore '{}' from '{}': {}", path.display(), backup.display(), e)
+        ))?;
+    eprintln!("{}; restored the copy from the last save and kept the damaged file as '{}'", error, damaged.display());
+    Ok(entries)
+}
+
+/// Write `entries` as a file in `format`. The file it replaces is kept as
+/// `<file>.bak` when sound and as `<file>.damaged` when not. A file from a
+/// newer VKey is not overwritten.
+pub fn write<T: Serialize>(path: &Path, format: &DataFormat, entries: &T) -> Result<()> {
+    let entries = serde_json::to_value(entries)
+        .map_err(|e| VKeyError::ConfigError(
+            format!("Failed to serialize {}: {}", format.label, e)
+        ))?;
+
+    if path.exists() {
+        match read_checked::<Value>(path, format) {
+            Ok(_) => {
+                std::fs::copy(path, backup_path(path))
+                    .map_err(|e| VKeyError::ConfigError(
+                        format!("Failed to back up {} '{}': {}", format.label, path.display(), e)
+                    ))?;
+            }
+            Err(ReadError::Damaged(_)) => rename(path, &damaged_path(path))?,
+            Err(ReadError::Unreadable(error)) => return Err(error),
+        }
+    }
+
+    let file = Envelope {
+        format: format.name,
+        version: format.version,
+        checksum: checksum(&entries),
+        entries: &entries,
+    };
+    let text = serde_json::to_string_pretty(&file)
+        .map_err(|e| VKeyError::ConfigError(
+            format!("Failed to serialize {}: {}", format.label, e)
+        ))?;
+
+    // Written next to the file and moved over it, so a crash leaves either
+    // the old file or the new one
+    let temporary = sibling(path, "tmp");
+    std::fs::write(&temporary, text)
+        .map_err(|e| VKeyError::ConfigError(
+            format!("Failed to write {} '{}': {}", format.label, path.display(), e)
+        ))?;
+    rename(&temporary, path)
+}
+
+fn read_checked<T: DeserializeOwned>(path: &Path, format: &DataFormat) -> std::result::Result<T, ReadError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| ReadError::Unreadable(VKeyError::ConfigError(
+            format!("Failed to read {} '{}': {}", format.label, path.display(), e)
+        )))?;
+    let damaged = |reason: String| ReadError::Damaged(VKeyError::ConfigError(
+        format!("The {} '{}' is damaged: {}", format.label, path.display(), reason)
+    ));
+
+    let value: Value = serde_json::from_str(&text).map_err(|e| damaged(e.to_string()))?;
+    let entries = match value {
+        Value::Object(mut file) if file.contains_key("format") => {
+            let name = file.get("format").and_then(Value::as_str).unwrap_or_default();
+            if name != format.name {
+                return Err(ReadError::Unreadable(VKeyError::ConfigError(
+                    format!("'{}' holds '{}', not a {}", path.display(), name, format.label)
+                )));
+            }
+            let version = file.get("version").and_then(Value::as_u64).unwrap_or(0);
+            if version > u64::from(format.version) {
+                return Err(ReadError::Unreadable(VKeyError::ConfigError(
+                    format!("The {} '{}' was written by a newer VKey (format version {})", format.label, path.display(), version)
+                )));
+            }
+            let entries = file.remove("entries").ok_or_else(|| damaged("it has no entries".to_string()))?;
+            if let Some(expected) = file.get("checksum").and_then(Value::as_str) {
+                if checksum(&entries) != expected {
+                    return Err(damaged("its entries do not match the checksum".to_string()));
+                }
+            }
+            entries
+        }
+        // Written before files had a format
+        legacy => legacy,
+    };
+    serde_json::from_value(entries).map_err(|e| damaged(e.to_string()))
+}
+
+/// FNV-1a of the entries written compactly with sorted keys, so reformatting
+/// the file by hand does not change it
+fn checksum(entries: &Value) -> String {
+    let mut canonical = String::new();
+    write_canonical(entries, &mut canonical);
+    let hash = canonical.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("fnv1a64:{:016x}", hash)
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{}:", Value::String(key.clone()));
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        }
+        other => {
+            let _ = write!(out, "{}", other);
+        }
+    }
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, "bak")
+}
+
+fn damaged_path(path: &Path) -> PathBuf {
+    sibling(path, "damaged")
+}
+
+fn rename(from: &Path, to: &Path) -> Result<()> {
+    std::fs::rename(from, to)
+        .map_err(|e| VKeyError::ConfigError(
+            format!("Failed to move '{}' to '{}': {}", from.display(), to.display(), e)
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vkey-datafile-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn files_round_trip_and_old_files_still_load() {
+        let dir = temp_dir("round-trip");
+        let path = dir.join("dictionary.json");
+
+        // A bare list from before versioning
+        std::fs::write(&path, r#"["vkey", "zalo"]"#).unwrap();
+        let words: Vec<String> = read(&path, &DICTIONARY_FORMAT).unwrap();
+        assert_eq!(words, ["vkey", "zalo"]);
+
+        write(&path, &DICTIONARY_FORMAT, &words).unwrap();
+        let file: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(file["format"], "vkey-dictionary");
+        assert_eq!(file["version"], 1);
+        assert_eq!(read::<Vec<String>>(&path, &DICTIONARY_FORMAT).unwrap(), words);
+
+        // A different table or a newer format is refused, not overwritten
+        assert!(read::<Vec<String>>(&path, &MACRO_FORMAT).is_err());
+        std::fs::write(&path, r#"{"format": "vkey-dictionary", "version": 9, "entries": []}"#).unwrap();
+        assert!(read::<Vec<String>>(&path, &DICTIONARY_FORMAT).is_err());
+        assert!(write(&path, &DICTIONARY_FORMAT, &words).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_files_are_recovered_from_the_last_save() {
+        let dir = temp_dir("recover");
+        let path = dir.join("dictionary.json");
+        write(&path, &DICTIONARY_FORMAT, &["một"]).unwrap();
+        write(&path, &DICTIONARY_FORMAT, &["một", "hai"]).unwrap();
+
+        // A changed letter no longer matches the checksum
+        let text = std::fs::read_to_string(&path).unwrap().replace("hai", "hải");
+        std::fs::write(&path, &text).unwrap();
+        let words: Vec<String> = read_or_recover(&path, &DICTIONARY_FORMAT).unwrap();
+        assert_eq!(words, ["một"]);
+        assert_eq!(std::fs::read_to_string(dir.join("dictionary.json.damaged")).unwrap(), text);
+        assert_eq!(read::<Vec<String>>(&path, &DICTIONARY_FORMAT).unwrap(), ["một"]);
+
+        // Without a sound copy the damaged file stays until it is saved over,
+        // and then it is kept aside
+        std::fs::write(&path, "[\"một\", \"h").unwrap();
+        std::fs::remove_file(dir.join("dictionary.json.bak")).unwrap();
+        assert!(read_or_recover::<Vec<String>>(&path, &DICTIONARY_FORMAT).is_err());
+        assert!(path.exists());
+        write(&path, &DICTIONARY_FORMAT, &["ba"]).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("dictionary.json.damaged")).unwrap(), "[\"một\", \"h");
+
+        // Reformatting by hand keeps the checksum valid
+        let file: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::write(&path, file.to_string()).unwrap();
+        assert_eq!(read::<Vec<String>>(&path, &DICTIONARY_FORMAT).unwrap(), ["ba"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::core::data_dir::{self, DataKind};
+use crate::core::datafile::{self, DICTIONARY_FORMAT};
+use crate::core::memory::string_bytes;
+use crate::error::Result;
+
+/// Most words that can be added to the dictionary. A file holding more is
+/// loaded whole, so saving it loses nothing.
+pub const MAX_WORDS: usize = 10_000;
+
+/// Words the user wants accepted as typed: personal names, brand names and
+/// slang that the syllable check would otherwise flag or restore.
+/// Words are compared ignoring case.
+#[derive(Debug, Clone, Default)]
+pub struct UserDictionary {
+    words: BTreeSet<String>,
+}
+
+impl UserDictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a word. Returns false if it is empty, already present, or the
+    /// dictionary is full.
+    pub fn add(&mut self, word: &str) -> bool {
+        let word = normalize(word);
+        if word.is_empty() || self.words.contains(&word) {
+            return false;
+        }
+        if self.words.len() >= MAX_WORDS {
+            eprintln!("User dictionary is full, ignoring '{}'", word);
+            return false;
+        }
+        self.words.insert(word)
+    }
+
+    /// Add a word whatever the size of the dictionary
+    fn store(&mut self, word: &str) {
+        let word = normalize(word);
+        if !word.is_empty() {
+            self.words.insert(word);
+        }
+    }
+
+    /// Remove a word. Returns false if it was not present.
+    pub fn remove(&mut self, word: &str) -> bool {
+        self.words.remove(&normalize(word))
+    }
+
+    /// Add the word if it is missing, remove it otherwise.
+    /// Returns whether the word is in the dictionary afterwards.
+    pub fn toggle(&mut self, word: &str) -> bool {
+        if self.remove(word) {
+            false
+        } else {
+            self.add(word)
+        }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(&normalize(word))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Approximate bytes held by the words
+    pub fn approx_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.words.iter().map(string_bytes).sum::<usize>()
+    }
+
+    /// All words in sorted order, lowercase
+    pub fn words(&self) -> Vec<String> {
+        self.words.iter().cloned().collect()
+    }
+
+    /// Get the default dictionary file path (in the dicts directory)
+    pub fn get_dictionary_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Dicts, "dictionary.json")
+    }
+
+    /// Load the dictionary from the default location, returning an empty one if none exists
+    pub fn load_default() -> Result<Self> {
+        let path = Self::get_dictionary_path()?;
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        Self::load(path.to_str().unwrap_or("dictionary.json"))
+    }
+
+    /// Load the dictionary from a dictionary file, or from the copy of its
+    /// last save if it is damaged
+    pub fn load(path: &str) -> Result<Self> {
+        let words: Vec<String> = datafile::read_or_recover(Path::new(path), &DICTIONARY_FORMAT)?;
+
+        let mut dictionary = Self::new();
+        // Everything in the file is kept, even past `MAX_WORDS`
+        for word in words {
+            dictionary.store(&word);
+        }
+        Ok(dictionary)
+    }
+
+    /// Save the dictionary to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Dicts)?;
+        let path = Self::get_dictionary_path()?;
+        self.save(path.to_str().unwrap_or("dictionary.json"))
+    }
+
+    /// Save the dictionary as a dictionary file
+    pub fn save(&self, path: &str) -> Result<()> {
+        datafile::write(Path::new(path), &DICTIONARY_FORMAT, &self.words())
+    }
+}
+
+fn normalize(word: &str) -> String {
+    word.trim().to_lowercase()
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// One step of a change to the text around the cursor. A key's result is a
+/// list of them, applied in order, so callers can type, mirror or log the
+/// exact edits instead of working them out from a text and a length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditOp {
+    /// Delete this many characters before the cursor
+    DeleteBack(usize),
+    /// Type this text at the cursor
+    Insert(String),
+    /// Move the cursor by this many characters, left when negative
+    MoveCursor(isize),
+}
+
+impl EditOp {
+    /// Delete `length` characters before the cursor and type `text`, leaving
+    /// out the steps that do nothing
+    pub fn replacement(length: usize, text: &str) -> Vec<EditOp> {
+        let mut ops = Vec::with_capacity(2);
+        if length > 0 {
+            ops.push(EditOp::DeleteBack(length));
+        }
+        if !text.is_empty() {
+            ops.push(EditOp::Insert(text.to_string()));
+        }
+        ops
+    }
+}
+
+impl fmt::Display for EditOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditOp::DeleteBack(count) => write!(f, "delete {}", count),
+            EditOp::Insert(text) => write!(f, "insert {:?}", text),
+            EditOp::MoveCursor(offset) => write!(f, "move {:+}", offset),
+        }
+    }
+}
+
+/// Apply `ops` to `text` with the cursor `cursor` characters in, keeping both
+/// in step. Deletes and moves stop at the ends of the text.
+pub fn apply_edits(ops: &[EditOp], text: &mut String, cursor: &mut usize) {
+    let byte_index = |text: &str, chars: usize| text.char_indices().nth(chars).map_or(text.len(), |(index, _)| index);
+    for op in ops {
+        *cursor = (*cursor).min(text.chars().count());
+        match op {
+            EditOp::DeleteBack(count) => {
+                let start = cursor.saturating_sub(*count);
+                let range = byte_index(text, start)..byte_index(text, *cursor);
+                text.replace_range(range, "");
+                *cursor = start;
+            }
+            EditOp::Insert(inserted) => {
+                text.insert_str(byte_index(text, *cursor), inserted);
+                *cursor += inserted.chars().count();
+            }
+            EditOp::MoveCursor(offset) => {
+                let target = *cursor as isize + offset;
+                *cursor = target.clamp(0, text.chars().count() as isize) as usize;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_follow_the_cursor() {
+        let mut text = "tiêng".to_string();
+        let mut cursor = 5;
+        apply_edits(&EditOp::replacement(3, "ếng"), &mut text, &mut cursor);
+        assert_eq!((text.as_str(), cursor), ("tiếng", 5));
+
+        // Replacing a word in the middle, as a selection replace would
+        let mut text = "xin chaof ban".to_string();
+        let mut cursor = 13;
+        let ops = [EditOp::MoveCursor(-4), EditOp::DeleteBack(2), EditOp::Insert("ò".to_string()), EditOp::MoveCursor(99)];
+        apply_edits(&ops, &mut text, &mut cursor);
+        assert_eq!((text.as_str(), cursor), ("xin chaò ban", 12));
+
+        apply_edits(&[EditOp::DeleteBack(99)], &mut text, &mut cursor);
+        assert_eq!((text.as_str(), cursor), ("", 0));
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::core::charset::remove_diacritics;
+use crate::core::data_dir::{self, DataKind};
+use crate::error::{Result, VKeyError};
+
+/// Shortcodes shipped with VKey, named in Vietnamese without diacritics,
+/// with a few English names people type out of habit
+const BUILTIN_EMOJI: &[(&str, &str)] = &[
+    ("cuoi", "😀"),
+    ("cuoito", "😄"),
+    ("haha", "😂"),
+    ("mimcuoi", "🙂"),
+    ("vui", "😊"),
+    ("nhaymat", "😉"),
+    ("yeu", "😍"),
+    ("hon", "😘"),
+    ("tim", "💖"),
+    ("buon", "😞"),
+    ("khoc", "😢"),
+    ("khocto", "😭"),
+    ("gian", "😠"),
+    ("tucgian", "😡"),
+    ("ngac", "😮"),
+    ("soc", "😱"),
+    ("suynghi", "🤔"),
+    ("ngu", "😴"),
+    ("met", "😩"),
+    ("votay", "👏"),
+    ("camon", "🙏"),
+    ("thich", "👍"),
+    ("chan", "👎"),
+    ("ok", "👌"),
+    ("lua", "🔥"),
+    ("sao", "🌟"),
+    ("hoa", "🌸"),
+    ("qua", "🎁"),
+    ("bia", "🍺"),
+    ("caphe", "🍵"),
+    ("pho", "🍜"),
+    ("smile", "😀"),
+    ("sad", "😢"),
+    ("like", "👍"),
+    ("heart", "💖"),
+];
+
+/// Emoji typed for `:shortcode:`. The shipped table can be extended and
+/// overridden by the user; an empty emoji takes a shortcode out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmojiTable {
+    shortcodes: BTreeMap<String, String>,
+}
+
+impl Default for EmojiTable {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl EmojiTable {
+    /// The table shipped with VKey
+    pub fn builtin() -> Self {
+        let shortcodes = BUILTIN_EMOJI
+            .iter()
+            .map(|(name, emoji)| (name.to_string(), emoji.to_string()))
+            .collect();
+        Self { shortcodes }
+    }
+
+    /// Add shortcodes, replacing shipped ones with the same name. An empty
+    /// emoji removes the shortcode.
+    pub fn merge(&mut self, overrides: BTreeMap<String, String>) {
+        for (name, emoji) in overrides {
+            let name = name.trim().to_lowercase();
+            if name.is_empty() {
+                continue;
+            }
+            if emoji.is_empty() {
+                self.shortcodes.remove(&name);
+            } else {
+                self.shortcodes.insert(name, emoji);
+            }
+        }
+    }
+
+    /// The emoji for `name`, in any case and with or without diacritics
+    pub fn lookup(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.shortcodes
+            .get(&name)
+            .or_else(|| self.shortcodes.get(&remove_diacritics(&name)))
+            .map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shortcodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shortcodes.is_empty()
+    }
+
+    /// Get the user's shortcode file path (in the macros directory)
+    pub fn get_emoji_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Macros, "emoji.json")
+    }
+
+    /// The shipped table, updated with the user's shortcode file if there is one
+    pub fn load_default() -> Result<Self> {
+        let mut table = Self::builtin();
+        let path = Self::get_emoji_path()?;
+        if path.exists() {
+            table.merge(Self::load(path.to_str().unwrap_or("emoji.json"))?);
+        }
+        Ok(table)
+    }
+
+    /// Load shortcodes from a JSON object of names and emoji
+    pub fn load(path: &str) -> Result<BTreeMap<String, String>> {
+        let emoji_str = std::fs::read_to_string(path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read emoji file '{}': {}", path, e)
+            ))?;
+
+        serde_json::from_str(&emoji_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse emoji file '{}': {}", path, e)
+            ))
+    }
+}
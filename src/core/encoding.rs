@@ -0,0 +1,229 @@
+use crate::core::charset::{self, ToneMark};
+use crate::core::types::Encoding;
+
+// Byte values generated from the glibc iconv VISCII and CP1258 charmaps.
+// Only the Vietnamese letters are listed; ASCII passes through unchanged.
+// VPS is not offered: there is no reference charmap to generate and check
+// its table against.
+
+const VISCII_TABLE: [(char, u8); 134] = [
+    ('á', 0xE1), ('à', 0xE0), ('ả', 0xE4), ('ã', 0xE3), ('ạ', 0xD5), ('ă', 0xE5),
+    ('ắ', 0xA1), ('ằ', 0xA2), ('ẳ', 0xC6), ('ẵ', 0xC7), ('ặ', 0xA3), ('â', 0xE2),
+    ('ấ', 0xA4), ('ầ', 0xA5), ('ẩ', 0xA6), ('ẫ', 0xE7), ('ậ', 0xA7), ('é', 0xE9),
+    ('è', 0xE8), ('ẻ', 0xEB), ('ẽ', 0xA8), ('ẹ', 0xA9), ('ê', 0xEA), ('ế', 0xAA),
+    ('ề', 0xAB), ('ể', 0xAC), ('ễ', 0xAD), ('ệ', 0xAE), ('í', 0xED), ('ì', 0xEC),
+    ('ỉ', 0xEF), ('ĩ', 0xEE), ('ị', 0xB8), ('ó', 0xF3), ('ò', 0xF2), ('ỏ', 0xF6),
+    ('õ', 0xF5), ('ọ', 0xF7), ('ô', 0xF4), ('ố', 0xAF), ('ồ', 0xB0), ('ổ', 0xB1),
+    ('ỗ', 0xB2), ('ộ', 0xB5), ('ơ', 0xBD), ('ớ', 0xBE), ('ờ', 0xB6), ('ở', 0xB7),
+    ('ỡ', 0xDE), ('ợ', 0xFE), ('ú', 0xFA), ('ù', 0xF9), ('ủ', 0xFC), ('ũ', 0xFB),
+    ('ụ', 0xF8), ('ư', 0xDF), ('ứ', 0xD1), ('ừ', 0xD7), ('ử', 0xD8), ('ữ', 0xE6),
+    ('ự', 0xF1), ('ý', 0xFD), ('ỳ', 0xCF), ('ỷ', 0xD6), ('ỹ', 0xDB), ('ỵ', 0xDC),
+    ('đ', 0xF0), ('Á', 0xC1), ('À', 0xC0), ('Ả', 0xC4), ('Ã', 0xC3), ('Ạ', 0x80),
+    ('Ă', 0xC5), ('Ắ', 0x81), ('Ằ', 0x82), ('Ẳ', 0x02), ('Ẵ', 0x05), ('Ặ', 0x83),
+    ('Â', 0xC2), ('Ấ', 0x84), ('Ầ', 0x85), ('Ẩ', 0x86), ('Ẫ', 0x06), ('Ậ', 0x87),
+    ('É', 0xC9), ('È', 0xC8), ('Ẻ', 0xCB), ('Ẽ', 0x88), ('Ẹ', 0x89), ('Ê', 0xCA),
+    ('Ế', 0x8A), ('Ề', 0x8B), ('Ể', 0x8C), ('Ễ', 0x8D), ('Ệ', 0x8E), ('Í', 0xCD),
+    ('Ì', 0xCC), ('Ỉ', 0x9B), ('Ĩ', 0xCE), ('Ị', 0x98), ('Ó', 0xD3), ('Ò', 0xD2),
+    ('Ỏ', 0x99), ('Õ', 0xA0), ('Ọ', 0x9A), ('Ô', 0xD4), ('Ố', 0x8F), ('Ồ', 0x90),
+    ('Ổ', 0x91), ('Ỗ', 0x92), ('Ộ', 0x93), ('Ơ', 0xB4), ('Ớ', 0x95), ('Ờ', 0x96),
+    ('Ở', 0x97), ('Ỡ', 0xB3), ('Ợ', 0x94), ('Ú', 0xDA), ('Ù', 0xD9), ('Ủ', 0x9C),
+    ('Ũ', 0x9D), ('Ụ', 0x9E), ('Ư', 0xBF), ('Ứ', 0xBA), ('Ừ', 0xBB), ('Ử', 0xBC),
+    ('Ữ', 0xFF), ('Ự', 0xB9), ('Ý', 0xDD), ('Ỳ', 0x9F), ('Ỷ', 0x14), ('Ỹ', 0x19),
+    ('Ỵ', 0x1E), ('Đ', 0xD0),
+];
+
+const CP1258_PRECOMPOSED: [(char, u8); 30] = [
+    ('á', 0xE1), ('à', 0xE0), ('ă', 0xE3), ('â', 0xE2), ('é', 0xE9), ('è', 0xE8),
+    ('ê', 0xEA), ('í', 0xED), ('ó', 0xF3), ('ô', 0xF4), ('ơ', 0xF5), ('ú', 0xFA),
+    ('ù', 0xF9), ('ư', 0xFD), ('đ', 0xF0), ('Á', 0xC1), ('À', 0xC0), ('Ă', 0xC3),
+    ('Â', 0xC2), ('É', 0xC9), ('È', 0xC8), ('Ê', 0xCA), ('Í', 0xCD), ('Ó', 0xD3),
+    ('Ô', 0xD4), ('Ơ', 0xD5), ('Ú', 0xDA), ('Ù', 0xD9), ('Ư', 0xDD), ('Đ', 0xD0),
+];
+
+/// CP1258 combining tone marks, appended after a base letter that has no precomposed form
+const CP1258_TONE_MARKS: [(ToneMark, u8); 5] = [
+    (ToneMark::Grave, 0xCC),
+    (ToneMark::Acute, 0xEC),
+    (ToneMark::HookAbove, 0xD2),
+    (ToneMark::Tilde, 0xDE),
+    (ToneMark::DotBelow, 0xF2),
+];
+
+/// Byte emitted for characters that have no representation in the target encoding
+const REPLACEMENT_BYTE: u8 = b'?';
+
+fn lookup_byte(table: &[(char, u8)], ch: char) -> Option<u8> {
+    table.iter().find(|(c, _)| *c == ch).map(|(_, b)| *b)
+}
+
+fn lookup_char(table: &[(char, u8)], byte: u8) -> Option<char> {
+    table.iter().find(|(_, b)| *b == byte).map(|(c, _)| *c)
+}
+
+fn encode_viscii(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|ch| match lookup_byte(&VISCII_TABLE, ch) {
+            Some(byte) => byte,
+            None if ch.is_ascii() => ch as u8,
+            None => REPLACEMENT_BYTE,
+        })
+        .collect()
+}
+
+fn decode_viscii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match lookup_char(&VISCII_TABLE, byte) {
+            Some(ch) => ch,
+            None if byte.is_ascii() => byte as char,
+            None => char::REPLACEMENT_CHARACTER,
+        })
+        .collect()
+}
+
+fn encode_cp1258_base(ch: char) -> Option<u8> {
+    if ch.is_ascii() {
+        Some(ch as u8)
+    } else {
+        lookup_byte(&CP1258_PRECOMPOSED, ch)
+    }
+}
+
+fn encode_cp1258(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for ch in text.chars() {
+        if let Some(byte) = encode_cp1258_base(ch) {
+            bytes.push(byte);
+            continue;
+        }
+
+        // Letters without a precomposed form are written as base letter + combining tone
+        let (base, tone) = charset::split_tone(ch);
+        let mark = CP1258_TONE_MARKS.iter().find(|(t, _)| *t == tone).map(|(_, b)| *b);
+        match (encode_cp1258_base(base), mark) {
+            (Some(base_byte), Some(mark_byte)) => {
+                bytes.push(base_byte);
+                bytes.push(mark_byte);
+            }
+            _ => bytes.push(REPLACEMENT_BYTE),
+        }
+    }
+    bytes
+}
+
+fn decode_cp1258(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        if let Some((tone, _)) = CP1258_TONE_MARKS.iter().find(|(_, b)| *b == byte) {
+            // Combine with the previous letter when possible
+            if let Some(prev) = text.pop() {
+                if charset::is_vowel(prev) && charset::split_tone(prev).1 == ToneMark::None {
+                    text.push(charset::with_tone(prev, *tone));
+                    continue;
+                }
+                text.push(prev);
+            }
+            text.push(char::REPLACEMENT_CHARACTER);
+            continue;
+        }
+
+        match lookup_char(&CP1258_PRECOMPOSED, byte) {
+            Some(ch) => text.push(ch),
+            None if byte.is_ascii() => text.push(byte as char),
+            None => text.push(char::REPLACEMENT_CHARACTER),
+        }
+    }
+    text
+}
+
+/// Convert Unicode text into the byte sequence of a legacy encoding.
+/// Returns `None` for Unicode and for encodings without a bundled table.
+pub fn encode_legacy(text: &str, encoding: Encoding) -> Option<Vec<u8>> {
+    match encoding {
+        Encoding::VISCII => Some(encode_viscii(text)),
+        Encoding::CP1258 => Some(encode_cp1258(text)),
+        Encoding::Unicode | Encoding::TCVN3 | Encoding::VNIWin => None,
+    }
+}
+
+/// Convert bytes in a legacy encoding back to Unicode text.
+/// Returns `None` for Unicode and for encodings without a bundled table.
+pub fn decode_legacy(bytes: &[u8], encoding: Encoding) -> Option<String> {
+    match encoding {
+        Encoding::VISCII => Some(decode_viscii(bytes)),
+        Encoding::CP1258 => Some(decode_cp1258(bytes)),
+        Encoding::Unicode | Encoding::TCVN3 | Encoding::VNIWin => None,
+    }
+}
+
+/// Convert composed text into the characters that should be injected for the given encoding.
+/// Legacy bytes are sent as their Latin-1 code points, which is what 8-bit fonts render.
+pub fn to_output(text: &str, encoding: Encoding) -> String {
+    match encode_legacy(text, encoding) {
+        Some(bytes) => bytes.into_iter().map(char::from).collect(),
+        None => text.to_string(),
+    }
+}
+
+/// Number of characters the given text occupies on screen once encoded
+pub fn output_len(text: &str, encoding: Encoding) -> usize {
+    match encode_legacy(text, encoding) {
+        Some(bytes) => bytes.len(),
+        None => text.chars().count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The 134 precomposed Vietnamese letters, lowercase then uppercase
+    fn alphabet() -> Vec<char> {
+        let tones = [ToneMark::None, ToneMark::Acute, ToneMark::Grave, ToneMark::HookAbove, ToneMark::Tilde, ToneMark::DotBelow];
+        let lower: Vec<char> = "aăâeêioôơuưy"
+            .chars()
+            .flat_map(|base| tones.iter().map(move |&tone| charset::with_tone(base, tone)))
+            .chain(['đ'])
+            .filter(|ch| !ch.is_ascii())
+            .collect();
+        let upper: Vec<char> = lower.iter().flat_map(|ch| ch.to_uppercase()).collect();
+        lower.into_iter().chain(upper).collect()
+    }
+
+    #[test]
+    fn every_letter_round_trips_through_viscii() {
+        let letters = alphabet();
+        assert_eq!(letters.len(), 134);
+        for letter in letters {
+            let text = letter.to_string();
+            let output = to_output(&text, Encoding::VISCII);
+            assert_eq!(output.chars().count(), 1, "{}", letter);
+            assert_eq!(output_len(&text, Encoding::VISCII), 1, "{}", letter);
+            let bytes: Vec<u8> = output.chars().map(|ch| ch as u8).collect();
+            assert_eq!(decode_legacy(&bytes, Encoding::VISCII).as_deref(), Some(text.as_str()));
+        }
+    }
+
+    #[test]
+    fn every_letter_round_trips_through_cp1258() {
+        for letter in alphabet() {
+            let text = letter.to_string();
+            let output = to_output(&text, Encoding::CP1258);
+            // A letter without its own code is its base letter and a combining tone
+            let expected_len = if lookup_byte(&CP1258_PRECOMPOSED, letter).is_some() { 1 } else { 2 };
+            assert_eq!(output.chars().count(), expected_len, "{}", letter);
+            assert_eq!(output_len(&text, Encoding::CP1258), expected_len, "{}", letter);
+            let bytes: Vec<u8> = output.chars().map(|ch| ch as u8).collect();
+            assert_eq!(decode_legacy(&bytes, Encoding::CP1258).as_deref(), Some(text.as_str()));
+        }
+    }
+
+    #[test]
+    fn words_are_encoded_letter_by_letter() {
+        assert_eq!(encode_legacy("Việt", Encoding::VISCII), Some(vec![b'V', b'i', 0xAE, b't']));
+        assert_eq!(encode_legacy("Việt", Encoding::CP1258), Some(vec![b'V', b'i', 0xEA, 0xF2, b't']));
+        assert_eq!(output_len("Việt Nam", Encoding::CP1258), 9);
+        assert_eq!(to_output("Việt", Encoding::Unicode), "Việt");
+        assert_eq!(encode_legacy("€", Encoding::VISCII), Some(vec![REPLACEMENT_BYTE]));
+    }
+}
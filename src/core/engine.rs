@@ -0,0 +1,455 @@
+use std::fmt;
+
+use crate::core::hybrid;
+use crate::core::memory::string_bytes;
+use crate::core::syllable::{self, ToneStyle};
+use crate::core::transformer::{self, RuleSet, TransformOutcome, Transformer};
+use crate::core::types::InputType;
+
+/// Keys that set or clear the tone, per input method
+const TELEX_TONE_KEYS: [char; 6] = ['s', 'f', 'r', 'x', 'j', 'z'];
+const VNI_TONE_KEYS: [char; 6] = ['0', '1', '2', '3', '4', '5'];
+const SIMPLE_TELEX_TONE_KEYS: [char; 5] = ['s', 'f', 'r', 'x', 'j'];
+
+/// Telex keys that are plain letters in Telex simple, and the private use
+/// characters that stand in for them so the transformer passes them through
+const SIMPLE_TELEX_LITERALS: [(char, char); 2] = [('w', '\u{E000}'), ('z', '\u{E001}')];
+
+/// Which digit types each VNI mark. Position `i` holds the key typed for
+/// standard VNI key `i`, so "0543216789" reverses the five tones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VniKeyMap([char; 10]);
+
+impl Default for VniKeyMap {
+    fn default() -> Self {
+        Self(['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'])
+    }
+}
+
+impl VniKeyMap {
+    /// Read a map written as ten keys in the order of `types::VNI_STANDARD_KEYS`.
+    /// Each digit must appear exactly once.
+    pub fn parse(order: &str) -> Option<Self> {
+        let mut keys = ['0'; 10];
+        let mut count = 0;
+        for key in order.chars() {
+            if count == keys.len() || !key.is_ascii_digit() || keys[..count].contains(&key) {
+                return None;
+            }
+            keys[count] = key;
+            count += 1;
+        }
+        (count == keys.len()).then_some(Self(keys))
+    }
+
+    /// The standard VNI key for a typed key
+    pub fn to_standard(&self, key: char) -> char {
+        self.0
+            .iter()
+            .position(|&mapped| mapped == key)
+            .map_or(key, |digit| char::from(b'0' + digit as u8))
+    }
+
+    /// The key the user types for a standard VNI key
+    pub fn from_standard(&self, key: char) -> char {
+        key.to_digit(10).map_or(key, |digit| self.0[digit as usize])
+    }
+}
+
+/// Settings that change how keys are composed into a word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineOptions {
+    pub input_type: InputType,
+    /// Tone placement for "oa", "oe", "uy" syllables
+    pub tone_style: ToneStyle,
+    /// Telex: [ ] { } type ơ ư Ơ Ư
+    pub bracket_shortcuts: bool,
+    /// Accept the tone key anywhere after the first vowel, not only at the end
+    pub free_tone_placement: bool,
+    /// Treat a leading z, w, j or f as a plain consonant instead of a Telex key
+    pub allow_silent_consonants: bool,
+    /// VNI and Telex + VNI: which digit types each mark
+    pub vni_keys: VniKeyMap,
+}
+
+impl EngineOptions {
+    pub fn new(input_type: InputType) -> Self {
+        Self {
+            input_type,
+            tone_style: ToneStyle::Classic,
+            bracket_shortcuts: false,
+            free_tone_placement: false,
+            allow_silent_consonants: false,
+            vni_keys: VniKeyMap::default(),
+        }
+    }
+}
+
+/// What a key did to the word, beyond changing its text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyEffect {
+    /// The key took a tone or letter mark back off, so the user is typing the
+    /// key itself (Telex "ss", VNI "11")
+    pub mark_removed: bool,
+}
+
+/// Composes the keys of one word into Vietnamese text.
+///
+/// `VietnameseInputProcessor` decides where words start and end, restores,
+/// expands macros and talks to the injection layer; the engine only turns
+/// the keys of the current word into its text. `ViEngine` is the
+/// implementation used by default, on vi-rs or VKey's own rules,
+/// `scheme::SchemeEngine` types other languages, and
+/// `VietnameseInputProcessor::set_engine` swaps in another one.
+pub trait InputMethodEngine: fmt::Debug + Send {
+    /// Add a key to the current word
+    fn process_key(&mut self, key: char) -> KeyEffect;
+
+    /// Remove the last key of the current word
+    fn handle_backspace(&mut self);
+
+    /// Finish the current word and start a new one, returning the composed text
+    fn commit(&mut self) -> String;
+
+    /// Drop the current word
+    fn reset(&mut self);
+
+    /// Keys typed for the current word
+    fn keys(&self) -> &str;
+
+    /// The current word as composed so far
+    fn output(&self) -> &str;
+
+    /// Follow changes to the input method and word options. Takes effect
+    /// from the next key.
+    fn set_options(&mut self, _options: EngineOptions) {}
+
+    /// Whether a composed word is spelled right, for auto-restore. None
+    /// leaves it to the processor, which checks Vietnamese syllables.
+    fn is_valid_word(&self, _word: &str) -> Option<bool> {
+        None
+    }
+
+    /// Approximate bytes held for the current word
+    fn approx_bytes(&self) -> usize {
+        self.keys().len() + self.output().len()
+    }
+
+    fn box_clone(&self) -> Box<dyn InputMethodEngine>;
+}
+
+impl Clone for Box<dyn InputMethodEngine> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Engine for the Vietnamese input methods, built on a `Transformer`: vi-rs
+/// unless VKey is built with its own rules. Every key re-transforms the whole
+/// word, so keys can change letters typed earlier ("vieetj" -> "việt"). The
+/// transformer is given the keys in lowercase, and the processor puts back
+/// the case they were typed in.
+#[derive(Debug, Clone)]
+pub struct ViEngine {
+    options: EngineOptions,
+    transformer: &'static dyn Transformer,
+    keys: String,
+    output: String,
+}
+
+impl ViEngine {
+    pub fn new(options: EngineOptions) -> Self {
+        Self::with_transformer(options, transformer::default_transformer())
+    }
+
+    /// An engine composing with `transformer` instead of the default one
+    pub fn with_transformer(options: EngineOptions, transformer: &'static dyn Transformer) -> Self {
+        Self {
+            options,
+            transformer,
+            keys: String::new(),
+            output: String::new(),
+        }
+    }
+
+    /// Transform the keys with the current input method and tone style.
+    /// Punctuation kept in the word because it is not a word boundary, such
+    /// as the hyphen of "ê-kíp", splits it into syllables composed on their
+    /// own, so a tone key only reaches the syllable it was typed in.
+    fn transform(&self) -> (String, TransformOutcome) {
+        if self.options.input_type == InputType::VIQR {
+            return self.transform_syllable(&self.keys);
+        }
+        let mut output = String::new();
+        let mut rest = self.keys.as_str();
+        while let Some(index) = rest.find(is_syllable_joiner) {
+            let (syllable, joined) = rest.split_at(index);
+            output.push_str(&self.transform_syllable(syllable).0);
+            let mut joined = joined.chars();
+            output.extend(joined.next());
+            rest = joined.as_str();
+        }
+        let (last, transform_result) = self.transform_syllable(rest);
+        output.push_str(&last);
+        (output, transform_result)
+    }
+
+    /// Transform the keys of one syllable
+    fn transform_syllable(&self, keys: &str) -> (String, TransformOutcome) {
+        let mut result = String::new();
+        let mut folded = keys.to_ascii_lowercase();
+        if matches!(self.options.input_type, InputType::VNI | InputType::Hybrid) {
+            folded = folded.chars().map(|key| self.options.vni_keys.to_standard(key)).collect();
+        }
+        let transform_result = match self.options.input_type {
+            InputType::Telex | InputType::Hybrid | InputType::TelexSimple => {
+                let simple = self.options.input_type == InputType::TelexSimple;
+                let mut telex = match self.options.input_type {
+                    InputType::Hybrid => hybrid::to_telex_keys(&folded),
+                    InputType::TelexSimple => hide_simple_telex_literals(&folded),
+                    _ => folded,
+                };
+                if self.options.bracket_shortcuts && !simple {
+                    telex = expand_bracket_keys(&telex);
+                }
+                if self.options.free_tone_placement {
+                    let tone_keys: &[char] = if simple { &SIMPLE_TELEX_TONE_KEYS } else { &TELEX_TONE_KEYS };
+                    telex = syllable::defer_tone_keys(&telex, tone_keys);
+                }
+
+                // Keep a leading z/w/j/f literal so "wa" stays "wa" instead of "ưa"
+                let mut chars = telex.chars();
+                let literal_initial = chars
+                    .clone()
+                    .next()
+                    .filter(|c| self.options.allow_silent_consonants && matches!(c.to_ascii_lowercase(), 'z' | 'w' | 'j' | 'f'));
+                if literal_initial.is_some() {
+                    chars.next();
+                }
+                let transform_result = self.transformer.transform(RuleSet::Telex, chars.as_str(), &mut result);
+                if let Some(initial) = literal_initial {
+                    result.insert(0, initial);
+                }
+                if simple {
+                    result = show_simple_telex_literals(&result);
+                }
+
+                // Undoing a mark leaves the key itself behind; show the digit
+                // the user typed rather than its Telex translation
+                let removed = transform_result.tone_mark_removed || transform_result.letter_modification_removed;
+                if let (true, Some(digit)) = (removed, keys.chars().last().filter(|c| c.is_ascii_digit())) {
+                    if result.pop().is_some() {
+                        result.push(digit);
+                    }
+                }
+                transform_result
+            }
+            InputType::VNI => {
+                if self.options.free_tone_placement {
+                    let keys = syllable::defer_tone_keys(&folded, &VNI_TONE_KEYS);
+                    self.transformer.transform(RuleSet::Vni, &keys, &mut result)
+                } else {
+                    self.transformer.transform(RuleSet::Vni, &folded, &mut result)
+                }
+            }
+            InputType::VIQR => {
+                // VIQR has no transformer rules, fallback to raw input
+                result = keys.to_string();
+                TransformOutcome::default()
+            }
+        };
+
+        // Digits left in the word are keys that did nothing; show them as typed
+        if matches!(self.options.input_type, InputType::VNI | InputType::Hybrid) {
+            result = result.chars().map(|ch| self.options.vni_keys.from_standard(ch)).collect();
+        }
+
+        (syllable::apply_tone_style(&result, self.options.tone_style), transform_result)
+    }
+}
+
+impl InputMethodEngine for ViEngine {
+    fn process_key(&mut self, key: char) -> KeyEffect {
+        self.keys.push(key);
+        let (output, transform_result) = self.transform();
+        self.output = output;
+        KeyEffect {
+            mark_removed: transform_result.letter_modification_removed || transform_result.tone_mark_removed,
+        }
+    }
+
+    fn handle_backspace(&mut self) {
+        self.keys.pop();
+        self.output = if self.keys.is_empty() {
+            String::new()
+        } else {
+            self.transform().0
+        };
+    }
+
+    fn commit(&mut self) -> String {
+        self.keys.clear();
+        std::mem::take(&mut self.output)
+    }
+
+    fn reset(&mut self) {
+        self.keys.clear();
+        self.output.clear();
+    }
+
+    fn keys(&self) -> &str {
+        &self.keys
+    }
+
+    fn output(&self) -> &str {
+        &self.output
+    }
+
+    fn set_options(&mut self, options: EngineOptions) {
+        self.options = options;
+    }
+
+    fn approx_bytes(&self) -> usize {
+        string_bytes(&self.keys) + string_bytes(&self.output)
+    }
+
+    fn box_clone(&self) -> Box<dyn InputMethodEngine> {
+        Box::new(self.clone())
+    }
+}
+
+/// Replace the Telex bracket shortcuts with the keys they stand for:
+/// [ and ] are "ow" and "uw", { and } their uppercase forms
+fn expand_bracket_keys(keys: &str) -> String {
+    let mut expanded = String::with_capacity(keys.len() + 2);
+    for key in keys.chars() {
+        match key {
+            '[' => expanded.push_str("ow"),
+            ']' => expanded.push_str("uw"),
+            '{' => expanded.push_str("Ow"),
+            '}' => expanded.push_str("Uw"),
+            _ => expanded.push(key),
+        }
+    }
+    expanded
+}
+
+/// Punctuation that stays in the word and separates its syllables. Bracket
+/// keys are Telex shortcuts for ơ and ư, so they belong to the syllable.
+fn is_syllable_joiner(key: char) -> bool {
+    key.is_ascii_punctuation() && !matches!(key, '[' | ']' | '{' | '}')
+}
+
+/// Swap the keys Telex simple types as letters for their stand-ins
+fn hide_simple_telex_literals(keys: &str) -> String {
+    keys.chars()
+        .map(|key| {
+            SIMPLE_TELEX_LITERALS
+                .iter()
+                .find(|(literal, _)| *literal == key)
+                .map_or(key, |(_, stand_in)| *stand_in)
+        })
+        .collect()
+}
+
+/// Put the letters back in place of their stand-ins
+fn show_simple_telex_literals(text: &str) -> String {
+    text.chars()
+        .map(|ch| {
+            SIMPLE_TELEX_LITERALS
+                .iter()
+                .find(|(_, stand_in)| *stand_in == ch)
+                .map_or(ch, |(literal, _)| *literal)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{AdvancedSettings, DEFAULT_WORD_BOUNDARIES};
+    use crate::core::vietnamese_input::VietnameseInputProcessor;
+
+    /// Types keys as they are, like having no input method
+    #[derive(Debug, Clone, Default)]
+    struct Literal {
+        keys: String,
+    }
+
+    impl InputMethodEngine for Literal {
+        fn process_key(&mut self, key: char) -> KeyEffect {
+            self.keys.push(key);
+            KeyEffect::default()
+        }
+
+        fn handle_backspace(&mut self) {
+            self.keys.pop();
+        }
+
+        fn commit(&mut self) -> String {
+            std::mem::take(&mut self.keys)
+        }
+
+        fn reset(&mut self) {
+            self.keys.clear();
+        }
+
+        fn keys(&self) -> &str {
+            &self.keys
+        }
+
+        fn output(&self) -> &str {
+            &self.keys
+        }
+
+        fn box_clone(&self) -> Box<dyn InputMethodEngine> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn processor_drives_any_engine() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        assert_eq!(processor.transform_text("vieetj nam"), "việt nam");
+
+        processor.set_engine(Box::new(Literal::default()));
+        assert_eq!(processor.transform_text("vieetj nam"), "vieetj nam");
+        processor.process_key('a');
+        processor.process_key('s');
+        assert_eq!(processor.get_display_buffer(), "as");
+        processor.handle_backspace();
+        assert_eq!(processor.get_current_buffer(), "a");
+    }
+
+    #[test]
+    fn vni_keys_can_be_remapped() {
+        let mut processor = VietnameseInputProcessor::new(InputType::VNI);
+        processor.apply_settings(&AdvancedSettings {
+            vni_key_order: "0543216789".to_string(),
+            ..AdvancedSettings::default()
+        });
+        assert_eq!(processor.transform_text("a5 a1 2024"), "á ạ 2024");
+        assert_eq!(processor.reverse_transform("việt 2024"), "vie61t 2024");
+        assert!(VniKeyMap::parse("0123456788").is_none());
+    }
+
+    #[test]
+    fn punctuation_kept_in_a_word_splits_its_syllables() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        processor.apply_settings(&AdvancedSettings {
+            word_boundaries: DEFAULT_WORD_BOUNDARIES.replace('-', ""),
+            ..AdvancedSettings::default()
+        });
+        assert_eq!(processor.transform_text("ee-kips xif-tai"), "ê-kíp xì-tai");
+        // Not part of a word, so it still ends one
+        assert_eq!(processor.transform_text("-as"), "-á");
+    }
+
+    #[test]
+    fn telex_simple_keeps_w_and_brackets_as_typed() {
+        let processor = VietnameseInputProcessor::new(InputType::TelexSimple);
+        assert_eq!(processor.transform_text("tieengs vieetj ddaays"), "tiếng việt đấy");
+        assert_eq!(processor.transform_text("away wow"), "away wow");
+        assert_eq!(processor.transform_text("tuw"), "tuw");
+    }
+}
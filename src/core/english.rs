@@ -0,0 +1,124 @@
+// English word detection
+// Typing English in Vietnamese mode garbles words that contain Telex keys
+// ("windows" -> "ưindơ"). A word is treated as English when its keystrokes
+// are a known English word or contain letter clusters Vietnamese never uses.
+
+/// Common English words whose keystrokes contain Telex modifier or tone keys.
+/// Must stay sorted for binary search.
+const COMMON_WORDS: [&str; 297] = [
+    "across", "after", "against", "air", "also", "always", "answer", "are", "area", "around", "as",
+    "ask", "asked", "away", "base", "because", "been", "before", "best", "better", "between",
+    "box", "boys", "bus", "business", "car", "care", "case", "cause", "chair", "class", "clear",
+    "close", "coffee", "color", "comes", "computer", "cost", "course", "cross", "days", "deep",
+    "desk", "does", "door", "down", "draw", "dress", "drive", "during", "early", "easy", "else",
+    "enter", "error", "ever", "every", "eyes", "face", "fact", "fall", "family", "far", "fast",
+    "father", "few", "file", "files", "find", "fire", "first", "five", "fix", "floor", "for",
+    "form", "four", "free", "friend", "from", "front", "full", "gas", "girl", "glass", "good",
+    "great", "green", "group", "grow", "guess", "hair", "has", "her", "here", "his", "horse",
+    "hours", "house", "how", "if", "is", "issue", "its", "jazz", "job", "join", "just", "keep",
+    "keys", "kids", "kiss", "know", "last", "later", "law", "learn", "less", "letter", "list",
+    "look", "lose", "loss", "mass", "max", "meet", "message", "mix", "more", "most", "mrs", "must",
+    "need", "never", "new", "news", "next", "north", "now", "number", "of", "off", "offer",
+    "office", "often", "or", "order", "other", "our", "over", "own", "paper", "part", "pass",
+    "past", "per", "person", "player", "please", "power", "press", "price", "process", "program",
+    "quiz", "race", "raise", "read", "ready", "real", "reason", "red", "rest", "result", "return",
+    "rise", "river", "road", "room", "rose", "row", "rule", "run", "safe", "said", "same", "save",
+    "saw", "say", "school", "score", "screen", "search", "see", "seem", "seen", "sell", "send",
+    "server", "set", "seven", "she", "short", "show", "side", "sister", "size", "sky", "slow",
+    "small", "so", "some", "son", "sorry", "sort", "space", "speak", "star", "start", "stay",
+    "still", "stop", "store", "story", "street", "such", "sure", "system", "task", "tax", "test",
+    "text", "their", "there", "these", "this", "those", "three", "through", "too", "tour", "tree",
+    "true", "try", "two", "us", "use", "used", "user", "users", "uses", "using", "very", "view",
+    "visit", "wait", "walk", "wall", "want", "war", "was", "watch", "water", "way", "we", "week",
+    "well", "were", "west", "what", "when", "where", "which", "while", "white", "who", "why",
+    "wife", "will", "win", "window", "windows", "with", "woman", "women", "word", "words", "work",
+    "world", "would", "write", "wrong", "yes", "your", "zero", "zone", "zoo",
+];
+
+/// Word-initial clusters that are English but never Vietnamese
+const ENGLISH_INITIALS: [&str; 23] = [
+    "bl", "br", "cl", "cr", "dr", "fl", "fr", "gl", "gr", "pl", "pr", "sc", "sh", "sk", "sl",
+    "sm", "sn", "sp", "st", "sw", "tw", "wh", "wr",
+];
+
+/// Word endings that are English but never Vietnamese
+const ENGLISH_ENDINGS: [&str; 6] = ["ck", "ll", "ing", "tion", "ght", "ous"];
+
+/// Check if raw keystrokes look like an English word
+pub fn looks_english(raw: &str) -> bool {
+    let word = raw.to_ascii_lowercase();
+    if word.chars().count() < 2 || !word.chars().all(|c| c.is_ascii_alphabetic()) {
+        return false;
+    }
+
+    if COMMON_WORDS.binary_search(&word.as_str()).is_ok() {
+        return true;
+    }
+
+    // "l" is only ever a Vietnamese initial, and "q" always comes with "u"
+    let has_inner_l = word.chars().skip(1).any(|c| c == 'l');
+    let has_lone_q = word.contains('q') && !word.contains("qu");
+
+    ENGLISH_INITIALS.iter().any(|initial| word.starts_with(initial))
+        || ENGLISH_ENDINGS.iter().any(|ending| word.ends_with(ending))
+        || has_inner_l
+        || has_lone_q
+}
+
+/// Check if the keystrokes typed so far begin a word that can only be
+/// English, so it can be typed as English before it is finished. Clusters
+/// with r or w are left out, since those are Telex keys that can still make
+/// the word Vietnamese ("tw" is "tư").
+pub fn starts_english(raw: &str) -> bool {
+    let word = raw.to_ascii_lowercase();
+    if !word.chars().all(|c| c.is_ascii_alphabetic()) {
+        return false;
+    }
+    ENGLISH_INITIALS
+        .iter()
+        .filter(|initial| !initial.contains(['r', 'w']))
+        .any(|initial| word.starts_with(initial))
+        || word.chars().skip(1).any(|c| c == 'l')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_words_are_sorted() {
+        assert!(COMMON_WORDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn detects_english_words() {
+        let words = [
+            "windows", "offer", "user", "street", "class", "black", "english", "string", "station",
+            "thought", "famous", "quick", "spring", "hello", "Windows", "OFFER",
+        ];
+        for word in words {
+            assert!(looks_english(word), "{} should look English", word);
+        }
+    }
+
+    #[test]
+    fn ignores_vietnamese_keystrokes() {
+        let words = [
+            "tieengs", "vieetj", "nguwowif", "khoong", "dduwowcj", "thuwowngf", "chaof", "banj",
+            "truwowngf", "nghieeng", "quaan", "giaf", "nhaf", "phaanf", "khuyeen", "a",
+        ];
+        for word in words {
+            assert!(!looks_english(word), "{} should not look English", word);
+        }
+    }
+
+    #[test]
+    fn detects_english_from_the_first_keys() {
+        for keys in ["st", "Sh", "fl", "hel", "spr"] {
+            assert!(starts_english(keys), "{} should start an English word", keys);
+        }
+        for keys in ["tw", "tr", "dr", "ngh", "s", "l", "vieet"] {
+            assert!(!starts_english(keys), "{} may still be Vietnamese", keys);
+        }
+    }
+}
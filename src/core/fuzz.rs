@@ -0,0 +1,138 @@
+//! Randomized key sequences run through `VietnameseInputProcessor`, checking
+//! that the screen the results describe always matches the processor's own
+//! idea of it. The sequences come from a fixed seed, so a failure reports the
+//! keys that caused it and reruns the same way.
+
+use crate::core::types::{AdvancedSettings, InputType};
+use crate::core::vietnamese_input::{ProcessingResult, VietnameseInputProcessor};
+
+/// Sequences typed per input method and settings
+const SEQUENCES: usize = 400;
+
+/// Longest sequence typed
+const MAX_KEYS: usize = 40;
+
+/// Stand-ins for the keys that do not type a character
+const BACKSPACE: char = '\u{8}';
+const ESCAPE: char = '\u{1B}';
+
+/// xorshift64, enough to spread key choices without a dependency
+pub(super) struct Rng(pub(super) u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub(super) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub(super) fn pick(&mut self, chars: &str) -> char {
+        let chars: Vec<char> = chars.chars().collect();
+        chars[self.below(chars.len())]
+    }
+}
+
+/// A key sequence weighted towards the keys the input method composes with
+fn random_keys(rng: &mut Rng, input_type: InputType) -> String {
+    let marks = match input_type {
+        InputType::VNI => "0123456789",
+        InputType::Hybrid => "sfrxjwd0123456789",
+        _ => "sfrxjzwd[]",
+    };
+    let length = 1 + rng.below(MAX_KEYS);
+    (0..length)
+        .map(|_| match rng.below(20) {
+            0..=8 => rng.pick("aeiouyaeo"),
+            9..=12 => rng.pick("bcdghklmnpqtvnght"),
+            13..=15 => rng.pick(marks),
+            16 => rng.pick("AEODVT"),
+            17 => ' ',
+            18 => BACKSPACE,
+            _ => rng.pick(&format!("{}-.,", ESCAPE)),
+        })
+        .collect()
+}
+
+/// Type `keys`, mirroring the results on a screen, and check after every key
+/// that the screen ends with the word the processor thinks it shows
+fn check_sequence(processor: &mut VietnameseInputProcessor, keys: &str) {
+    processor.reset();
+    let mut screen = String::new();
+    for key in keys.chars() {
+        let typed = processor.get_current_buffer().to_string();
+        let was_tracking = processor.is_tracking() && !typed.is_empty();
+        let result = match key {
+            BACKSPACE => processor.handle_backspace(),
+            ' ' => processor.handle_space(),
+            _ => processor.process_key(key),
+        };
+
+        if let ProcessingResult::ProcessedText { buffer_length, .. }
+        | ProcessingResult::RestoreText { buffer_length, .. } = &result
+        {
+            assert!(
+                *buffer_length <= screen.chars().count(),
+                "deleted {} of {:?} after {:?} in {:?}",
+                buffer_length, screen, key, keys
+            );
+        }
+        if let ProcessingResult::RestoreText { text, .. } = &result {
+            if was_tracking {
+                assert!(
+                    text.starts_with(&typed),
+                    "restored {:?} instead of {:?} in {:?}",
+                    text, typed, keys
+                );
+            }
+        }
+        result.apply_to(&mut screen);
+
+        if key == ESCAPE && was_tracking {
+            assert!(screen.ends_with(&typed), "Escape left {:?} for {:?} in {:?}", screen, typed, keys);
+        }
+        if processor.is_tracking() && !processor.is_buffer_empty() {
+            let shown = processor.get_display_output();
+            assert_eq!(processor.get_display_output_length(), shown.chars().count(), "in {:?}", keys);
+            assert!(
+                screen.ends_with(&shown),
+                "screen {:?} does not end with {:?} after {:?} in {:?}",
+                screen, shown, key, keys
+            );
+        }
+    }
+}
+
+fn fuzz(input_type: InputType, settings: AdvancedSettings, seed: u64) {
+    let mut rng = Rng(seed);
+    let mut processor = VietnameseInputProcessor::new(input_type);
+    processor.apply_settings(&settings);
+    for _ in 0..SEQUENCES {
+        let keys = random_keys(&mut rng, input_type);
+        check_sequence(&mut processor, &keys);
+    }
+}
+
+#[test]
+fn random_keys_keep_the_screen_in_step() {
+    let input_types = [InputType::Telex, InputType::VNI, InputType::Hybrid, InputType::TelexSimple];
+    for (index, input_type) in input_types.into_iter().enumerate() {
+        let seed = 0x9E37_79B9_7F4A_7C15 ^ index as u64;
+        fuzz(input_type, AdvancedSettings::default(), seed);
+        fuzz(
+            input_type,
+            AdvancedSettings {
+                spell_check: true,
+                auto_restart_typos: true,
+                free_tone_placement: true,
+                telex_bracket_shortcuts: true,
+                ..AdvancedSettings::default()
+            },
+            seed.rotate_left(17),
+        );
+    }
+}
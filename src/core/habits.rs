@@ -0,0 +1,228 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::data_dir::{self, DataKind};
+use crate::core::memory::string_bytes;
+use crate::error::{Result, VKeyError};
+
+/// Times a word must be put back to its keys, more often than it was kept,
+/// before it is restored automatically
+pub const RESTORES_TO_LEARN: u32 = 3;
+
+/// Times a word must be kept, more often than it was put back, before it is
+/// no longer restored automatically
+pub const KEEPS_TO_LEARN: u32 = 3;
+
+/// Most words remembered; the least used one makes room for a new word
+pub const MAX_LEARNED_WORDS: usize = 2_000;
+
+/// How often the user kept a transformed word and how often they put it
+/// back to the keys they typed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WordHabit {
+    pub kept: u32,
+    pub restored: u32,
+}
+
+impl WordHabit {
+    fn uses(&self) -> u32 {
+        self.kept + self.restored
+    }
+}
+
+/// What the user's habits say about a word being committed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LearnedRestore {
+    /// Put the keys back, whether or not the word is valid Vietnamese
+    Restore,
+    /// Keep the transformed word, even if it would be restored
+    Keep,
+}
+
+/// Learns which transformed words the user keeps and which they put back to
+/// the typed keys, with Escape or the undo hotkey, so auto-restore follows
+/// their habits: after "expression" was put back three times it stays as typed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreHabits {
+    /// Keyed by the lowercase keys typed for the word
+    words: HashMap<String, WordHabit>,
+}
+
+impl RestoreHabits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The user kept the word typed with `keys`
+    pub fn record_kept(&mut self, keys: &str) {
+        if let Some(habit) = self.entry(keys) {
+            habit.kept += 1;
+        }
+    }
+
+    /// The user put the word typed with `keys` back to its keys. `was_kept`
+    /// says the word had been committed, and so counted as kept, first.
+    pub fn record_restored(&mut self, keys: &str, was_kept: bool) {
+        if let Some(habit) = self.entry(keys) {
+            if was_kept {
+                habit.kept = habit.kept.saturating_sub(1);
+            }
+            habit.restored += 1;
+        }
+    }
+
+    /// What to do with the word typed with `keys`, once it was seen often enough
+    pub fn verdict(&self, keys: &str) -> Option<LearnedRestore> {
+        let habit = self.words.get(&keys.to_lowercase())?;
+        if habit.restored >= RESTORES_TO_LEARN && habit.restored > habit.kept {
+            Some(LearnedRestore::Restore)
+        } else if habit.kept >= KEEPS_TO_LEARN && habit.kept > habit.restored {
+            Some(LearnedRestore::Keep)
+        } else {
+            None
+        }
+    }
+
+    /// Forget what was learned about the word typed with `keys`
+    pub fn forget(&mut self, keys: &str) {
+        self.words.remove(&keys.trim().to_lowercase());
+    }
+
+    fn entry(&mut self, keys: &str) -> Option<&mut WordHabit> {
+        let keys = keys.trim().to_lowercase();
+        if keys.is_empty() {
+            return None;
+        }
+        if !self.words.contains_key(&keys) && self.words.len() >= MAX_LEARNED_WORDS {
+            let least_used = self
+                .words
+                .iter()
+                .min_by_key(|(_, habit)| habit.uses())
+                .map(|(keys, _)| keys.clone());
+            if let Some(least_used) = least_used {
+                self.words.remove(&least_used);
+            }
+        }
+        Some(self.words.entry(keys).or_default())
+    }
+
+    /// Number of words with a learned habit
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Approximate bytes held by the learned words
+    pub fn approx_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.words.keys().map(|keys| string_bytes(keys) + std::mem::size_of::<WordHabit>()).sum::<usize>()
+    }
+
+    /// Get the default habits file path (next to the settings in the config directory)
+    pub fn get_habits_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Config, "habits.json")
+    }
+
+    /// Load the learned habits from the default location, starting empty if none exist
+    pub fn load_default() -> Result<Self> {
+        let path = Self::get_habits_path()?;
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        Self::load(path.to_str().unwrap_or("habits.json"))
+    }
+
+    /// Load the learned habits from a JSON object keyed by the typed keys
+    pub fn load(path: &str) -> Result<Self> {
+        let habits_str = std::fs::read_to_string(path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read habits file '{}': {}", path, e)
+            ))?;
+
+        let words: HashMap<String, WordHabit> = serde_json::from_str(&habits_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse habits file '{}': {}", path, e)
+            ))?;
+
+        let mut words: Vec<(String, WordHabit)> = words.into_iter().collect();
+        // Keep the most used words if the file holds more than fits
+        words.sort_by_key(|(_, habit)| Reverse(habit.uses()));
+        let words = words
+            .into_iter()
+            .take(MAX_LEARNED_WORDS)
+            .map(|(keys, habit)| (keys.trim().to_lowercase(), habit))
+            .filter(|(keys, habit)| !keys.is_empty() && habit.uses() > 0)
+            .collect();
+        Ok(Self { words })
+    }
+
+    /// Save the learned habits to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Config)?;
+        let path = Self::get_habits_path()?;
+        self.save(path.to_str().unwrap_or("habits.json"))
+    }
+
+    /// Save the learned habits as a JSON object keyed by the typed keys
+    pub fn save(&self, path: &str) -> Result<()> {
+        let habits_str = serde_json::to_string_pretty(&self.words)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to serialize habits: {}", e)
+            ))?;
+
+        std::fs::write(path, habits_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to write habits file '{}': {}", path, e)
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{AdvancedSettings, InputType};
+    use crate::core::vietnamese_input::{ProcessingResult, VietnameseInputProcessor};
+
+    fn type_keys(processor: &mut VietnameseInputProcessor, keys: &str) -> ProcessingResult {
+        let mut result = ProcessingResult::consumed();
+        for key in keys.chars() {
+            result = processor.process_key(key);
+        }
+        result
+    }
+
+    #[test]
+    fn restoring_a_word_three_times_stops_transforming_it() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        processor.apply_settings(&AdvancedSettings::default());
+
+        for _ in 0..RESTORES_TO_LEARN {
+            type_keys(&mut processor, "text ");
+            assert!(processor.undo_conversion().is_some());
+        }
+        assert_eq!(processor.get_habits().verdict("Text"), Some(LearnedRestore::Restore));
+
+        let result = type_keys(&mut processor, "text ");
+        assert!(matches!(result, ProcessingResult::RestoreText { ref text, .. } if text == "text "));
+    }
+
+    #[test]
+    fn kept_words_outweigh_restores() {
+        let mut habits = RestoreHabits::new();
+        habits.record_restored("vieetj", false);
+        habits.record_restored("vieetj", false);
+        for _ in 0..KEEPS_TO_LEARN + 2 {
+            habits.record_kept("vieetj");
+        }
+        habits.record_restored("vieetj", true);
+        assert_eq!(habits.verdict("vieetj"), Some(LearnedRestore::Keep));
+        assert_eq!(habits.verdict("nam"), None);
+    }
+}
@@ -0,0 +1,44 @@
+// Telex + VNI hybrid input
+// Letters keep their Telex meaning while digits act as VNI keys. Each digit
+// is translated into the Telex key with the same effect, so one Telex
+// transformation handles the whole word.
+
+/// Telex tone keys for the VNI tone digits 0-5
+const TONE_KEYS: [char; 6] = ['z', 's', 'f', 'r', 'x', 'j'];
+
+/// Translate hybrid keystrokes into Telex keystrokes.
+///
+/// Conflict rules:
+/// - a digit only acts as a key when there is a letter it can modify,
+///   so numbers and digits typed before any vowel stay literal
+/// - `6` (circumflex) applies to the last a, e or o in the word
+/// - `7` (horn) and `8` (breve) both map to Telex `w`, which picks the
+///   right mark from the vowels present
+/// - `9` (đ) needs a d in the word
+pub fn to_telex_keys(keys: &str) -> String {
+    let mut telex = String::with_capacity(keys.len());
+
+    for key in keys.chars() {
+        let translated = match key {
+            '0'..='5' if has_any(&telex, &['a', 'e', 'i', 'o', 'u', 'y']) => {
+                key.to_digit(10).map(|digit| TONE_KEYS[digit as usize])
+            }
+            '6' => telex
+                .chars()
+                .rev()
+                .map(|c| c.to_ascii_lowercase())
+                .find(|c| matches!(c, 'a' | 'e' | 'o')),
+            '7' | '8' if has_any(&telex, &['a', 'o', 'u']) => Some('w'),
+            '9' if has_any(&telex, &['d']) => Some('d'),
+            _ => None,
+        };
+        telex.push(translated.unwrap_or(key));
+    }
+
+    telex
+}
+
+/// Check if the keys contain any of the given letters, ignoring case
+fn has_any(keys: &str, letters: &[char]) -> bool {
+    keys.chars().any(|c| letters.contains(&c.to_ascii_lowercase()))
+}
@@ -0,0 +1,106 @@
+//! Carrying out a key's result in the focused application. The key handler,
+//! backspace, Escape, restoring a word and undoing a conversion all type
+//! their result through `apply_result`, so what reaches the screen cannot
+//! differ between them. The platform layer supplies the `Injector`.
+
+use crate::core::edit::EditOp;
+use crate::core::types::SoundEvent;
+use crate::core::vietnamese_input::ProcessingResult;
+
+/// The focused application, as results are typed into it
+pub trait Injector {
+    /// Make `edits` in the focused field, in order. The injector reports its
+    /// own failures; the key is blocked either way.
+    fn inject(&mut self, edits: &[EditOp]);
+
+    /// Clear a selection the application put over the text, such as a
+    /// browser's address completion, before text is erased
+    fn dismiss_selection(&mut self) {}
+
+    /// Play the sound for `event`, if sounds are on
+    fn feedback(&mut self, _event: SoundEvent) {}
+}
+
+/// Type `result` through `injector`. Returns true when the key that gave it
+/// must be blocked, false when the application should receive the key.
+pub fn apply_result(injector: &mut dyn Injector, result: &ProcessingResult) -> bool {
+    let event = match result {
+        ProcessingResult::PassThrough(_) | ProcessingResult::ClearAndPassBackspace => return false,
+        ProcessingResult::DismissSuggestions => return true,
+        ProcessingResult::RestoreText { .. } => SoundEvent::Restore,
+        ProcessingResult::ProcessedText { .. } | ProcessingResult::AcceptSuggestion { .. } => SoundEvent::Transform,
+    };
+    let edits = result.edit_ops();
+    // A key swallowed while a word is hidden changes nothing on screen
+    if edits.is_empty() {
+        return true;
+    }
+    injector.dismiss_selection();
+    injector.inject(&edits);
+    injector.feedback(event);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records what the application is asked to do
+    #[derive(Debug, Default)]
+    struct Recorder {
+        calls: Vec<String>,
+    }
+
+    impl Injector for Recorder {
+        fn inject(&mut self, edits: &[EditOp]) {
+            for edit in edits {
+                self.calls.push(edit.to_string());
+            }
+        }
+
+        fn dismiss_selection(&mut self) {
+            self.calls.push("dismiss".to_string());
+        }
+
+        fn feedback(&mut self, event: SoundEvent) {
+            self.calls.push(format!("{:?}", event));
+        }
+    }
+
+    fn apply(result: ProcessingResult) -> (bool, Vec<String>) {
+        let mut recorder = Recorder::default();
+        let blocked = apply_result(&mut recorder, &result);
+        (blocked, recorder.calls)
+    }
+
+    #[test]
+    fn results_are_typed_the_same_way() {
+        let (blocked, calls) = apply(ProcessingResult::ProcessedText {
+            text: "ệt".to_string(),
+            buffer_length: 2,
+        });
+        assert!(blocked);
+        assert_eq!(calls, ["dismiss", "delete 2", "insert \"ệt\"", "Transform"]);
+
+        let (blocked, calls) = apply(ProcessingResult::RestoreText {
+            text: "vieet".to_string(),
+            buffer_length: 4,
+        });
+        assert!(blocked);
+        assert_eq!(calls, ["dismiss", "delete 4", "insert \"vieet\"", "Restore"]);
+
+        let (_, calls) = apply(ProcessingResult::AcceptSuggestion {
+            text: "việt".to_string(),
+            buffer_length: 2,
+        });
+        assert_eq!(calls.last().map(String::as_str), Some("Transform"));
+    }
+
+    #[test]
+    fn keys_left_to_the_application_do_nothing() {
+        assert_eq!(apply(ProcessingResult::PassThrough('a')), (false, Vec::new()));
+        assert_eq!(apply(ProcessingResult::ClearAndPassBackspace), (false, Vec::new()));
+        assert_eq!(apply(ProcessingResult::DismissSuggestions), (true, Vec::new()));
+        assert_eq!(apply(ProcessingResult::consumed()), (true, Vec::new()));
+    }
+}
@@ -0,0 +1,382 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::core::charset::{base_letter, remove_diacritics};
+use crate::core::data_dir::{self, DataKind};
+use crate::core::datafile::{self, MACRO_FORMAT};
+use crate::core::memory::string_bytes;
+use crate::error::Result;
+
+/// A single abbreviation ("Gõ tắt") definition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Macro {
+    /// Text the user types, e.g. "vn"
+    pub trigger: String,
+    /// Text it expands to, e.g. "Việt Nam"
+    pub expansion: String,
+    #[serde(flatten)]
+    pub options: MacroOptions,
+}
+
+/// How an expansion takes the case of the trigger typed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroCase {
+    /// The expansion is typed as written, and the trigger must match its case
+    #[default]
+    Fixed,
+    /// The trigger matches in any case: "Vn" types "Việt Nam" and "VN"
+    /// "VIỆT NAM"
+    MatchTrigger,
+}
+
+/// Where a macro expands and how
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroOptions {
+    /// Expand only in these applications, matched against the bundle path.
+    /// Empty means everywhere.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub apps: Vec<String>,
+    /// Never expand in these applications, such as terminals
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub excluded_apps: Vec<String>,
+    #[serde(skip_serializing_if = "is_fixed")]
+    pub case: MacroCase,
+}
+
+fn is_fixed(case: &MacroCase) -> bool {
+    *case == MacroCase::Fixed
+}
+
+impl MacroOptions {
+    /// Whether the macro expands in the application at `app`
+    pub fn applies_in(&self, app: &str) -> bool {
+        let matches = |pattern: &String| !pattern.is_empty() && app.contains(pattern.as_str());
+        !self.excluded_apps.iter().any(matches) && (self.apps.is_empty() || self.apps.iter().any(matches))
+    }
+}
+
+/// An expansion with the options it was defined with
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expansion {
+    text: String,
+    options: MacroOptions,
+}
+
+/// Most abbreviations that can be added; more than anyone types by hand.
+/// A file holding more is loaded whole, so saving it loses nothing.
+pub const MAX_MACROS: usize = 5000;
+
+#[derive(Debug, Clone, Default)]
+struct TrieNode {
+    children: BTreeMap<char, TrieNode>,
+    expansion: Option<Expansion>,
+}
+
+/// `expansion` in the case `typed` was typed in: all capitals for a trigger
+/// typed in capitals, a capital first letter for one typed with it
+fn match_case(typed: &str, expansion: &str) -> String {
+    let letters: Vec<char> = typed.chars().filter(|ch| ch.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(|ch| ch.is_uppercase()) {
+        return expansion.to_uppercase();
+    }
+    if !letters.first().is_some_and(|ch| ch.is_uppercase()) {
+        return expansion.to_string();
+    }
+    let mut chars = expansion.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Abbreviation table stored as a character trie keyed by trigger
+#[derive(Debug, Clone, Default)]
+pub struct MacroTable {
+    root: TrieNode,
+    len: usize,
+}
+
+impl MacroTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace a macro that expands everywhere, as written
+    pub fn insert(&mut self, trigger: &str, expansion: &str) {
+        self.insert_with(trigger, expansion, MacroOptions::default());
+    }
+
+    /// Add or replace a macro. Empty triggers are ignored, and so are new
+    /// triggers once the table holds `MAX_MACROS`.
+    pub fn insert_with(&mut self, trigger: &str, expansion: &str, options: MacroOptions) {
+        if self.len >= MAX_MACROS && self.lookup(trigger).is_none() {
+            eprintln!("Abbreviation table is full, ignoring '{}'", trigger);
+            return;
+        }
+        self.store(trigger, expansion, options);
+    }
+
+    /// Add or replace a macro whatever the size of the table
+    fn store(&mut self, trigger: &str, expansion: &str, options: MacroOptions) {
+        if trigger.is_empty() {
+            return;
+        }
+
+        let mut node = &mut self.root;
+        for ch in trigger.chars() {
+            node = node.children.entry(ch).or_default();
+        }
+        if node.expansion.is_none() {
+            self.len += 1;
+        }
+        node.expansion = Some(Expansion {
+            text: expansion.to_string(),
+            options,
+        });
+    }
+
+    /// Remove a macro, returning its expansion if it existed
+    pub fn remove(&mut self, trigger: &str) -> Option<String> {
+        let mut node = &mut self.root;
+        for ch in trigger.chars() {
+            node = node.children.get_mut(&ch)?;
+        }
+        let removed = node.expansion.take();
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed.map(|expansion| expansion.text)
+    }
+
+    fn find(&self, trigger: &str) -> Option<&Expansion> {
+        let mut node = &self.root;
+        for ch in trigger.chars() {
+            node = node.children.get(&ch)?;
+        }
+        node.expansion.as_ref()
+    }
+
+    fn find_folded(&self, trigger: &str) -> Option<&Expansion> {
+        fn find<'a>(node: &'a TrieNode, rest: &[char]) -> Option<&'a Expansion> {
+            let Some((&wanted, rest)) = rest.split_first() else {
+                return node.expansion.as_ref();
+            };
+            node.children
+                .iter()
+                .filter(|(ch, _)| base_letter(**ch) == wanted)
+                .find_map(|(_, child)| find(child, rest))
+        }
+
+        if let Some(expansion) = self.find(trigger) {
+            return Some(expansion);
+        }
+        let folded: Vec<char> = remove_diacritics(trigger).chars().collect();
+        find(&self.root, &folded)
+    }
+
+    /// Find the expansion for an exact trigger
+    pub fn lookup(&self, trigger: &str) -> Option<&str> {
+        self.find(trigger).map(|expansion| expansion.text.as_str())
+    }
+
+    /// Find the expansion for a trigger, ignoring the diacritics on either
+    /// side, so "việtnd" finds "vietnd" and "vnd" finds "vnđ". An exact
+    /// match wins; otherwise the first folded match in trigger order.
+    pub fn lookup_folded(&self, trigger: &str) -> Option<&str> {
+        self.find_folded(trigger).map(|expansion| expansion.text.as_str())
+    }
+
+    /// The text to type for `word`, composed from `keys`, in the application
+    /// at `app`. The trigger is matched against the word, then the keys, then
+    /// the word without diacritics; macros that match the trigger's case also
+    /// match in any case. Macros scoped away from `app` are skipped.
+    pub fn expand(&self, word: &str, keys: &str, app: &str) -> Option<String> {
+        let applies = |expansion: &&Expansion| expansion.options.applies_in(app);
+        if let Some(expansion) = self
+            .find(word)
+            .filter(applies)
+            .or_else(|| self.find(keys).filter(applies))
+            .or_else(|| self.find_folded(word).filter(applies))
+        {
+            return Some(expansion.text.clone());
+        }
+        let expansion = self
+            .find_folded(&word.to_lowercase())
+            .filter(|expansion| expansion.options.case == MacroCase::MatchTrigger)
+            .filter(applies)?;
+        Some(match_case(word, &expansion.text))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Approximate bytes held by the trie
+    pub fn approx_bytes(&self) -> usize {
+        fn node_bytes(node: &TrieNode) -> usize {
+            let expansion = node.expansion.as_ref().map(|expansion| string_bytes(&expansion.text)).unwrap_or(0);
+            node.children
+                .values()
+                .map(|child| std::mem::size_of::<(char, TrieNode)>() + node_bytes(child))
+                .sum::<usize>()
+                + expansion
+        }
+        std::mem::size_of::<Self>() + node_bytes(&self.root)
+    }
+
+    /// All macros in trigger order
+    pub fn entries(&self) -> Vec<Macro> {
+        fn collect(node: &TrieNode, prefix: &mut String, out: &mut Vec<Macro>) {
+            if let Some(ref expansion) = node.expansion {
+                out.push(Macro {
+                    trigger: prefix.clone(),
+                    expansion: expansion.text.clone(),
+                    options: expansion.options.clone(),
+                });
+            }
+            for (ch, child) in &node.children {
+                prefix.push(*ch);
+                collect(child, prefix, out);
+                prefix.pop();
+            }
+        }
+
+        let mut out = Vec::with_capacity(self.len);
+        collect(&self.root, &mut String::new(), &mut out);
+        out
+    }
+
+    /// Get the default macro file path (in the macros directory)
+    pub fn get_macros_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Macros, "macros.json")
+    }
+
+    /// Load macros from the default location, returning an empty table if none exist
+    pub fn load_default() -> Result<Self> {
+        let path = Self::get_macros_path()?;
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        Self::load(path.to_str().unwrap_or("macros.json"))
+    }
+
+    /// Load macros from a macro file, or from the copy of its last save if
+    /// it is damaged
+    pub fn load(path: &str) -> Result<Self> {
+        let macros: Vec<Macro> = datafile::read_or_recover(Path::new(path), &MACRO_FORMAT)?;
+
+        let mut table = Self::new();
+        // Everything in the file is kept, even past `MAX_MACROS`
+        for m in macros {
+            table.store(&m.trigger, &m.expansion, m.options);
+        }
+        Ok(table)
+    }
+
+    /// Save macros to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Macros)?;
+        let path = Self::get_macros_path()?;
+        self.save(path.to_str().unwrap_or("macros.json"))
+    }
+
+    /// Save macros to a macro file
+    pub fn save(&self, path: &str) -> Result<()> {
+        datafile::write(Path::new(path), &MACRO_FORMAT, &self.entries())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folded_lookup_ignores_diacritics() {
+        let mut table = MacroTable::new();
+        table.insert("vietnd", "Việt Nam đồng");
+        table.insert("vnđ", "Việt Nam đồng");
+        table.insert("tôi", "tôi là");
+        table.insert("toi", "to in");
+
+        assert_eq!(table.lookup("việtnd"), None);
+        assert_eq!(table.lookup_folded("việtnd"), Some("Việt Nam đồng"));
+        assert_eq!(table.lookup_folded("vnd"), Some("Việt Nam đồng"));
+        assert_eq!(table.lookup_folded("tôi"), Some("tôi là"));
+        assert_eq!(table.lookup_folded("toi"), Some("to in"));
+        assert_eq!(table.lookup_folded("tối"), Some("to in"));
+        assert_eq!(table.lookup_folded("vietn"), None);
+    }
+
+    #[test]
+    fn scoped_macros_expand_where_allowed_in_their_case() {
+        let mut table = MacroTable::new();
+        table.insert_with(
+            "ko",
+            "không",
+            MacroOptions {
+                excluded_apps: vec!["Terminal.app".to_string()],
+                case: MacroCase::MatchTrigger,
+                ..MacroOptions::default()
+            },
+        );
+        table.insert_with(
+            "tks",
+            "Thanks,",
+            MacroOptions {
+                apps: vec!["Mail.app".to_string()],
+                ..MacroOptions::default()
+            },
+        );
+
+        let notes = "/System/Applications/Notes.app";
+        assert_eq!(table.expand("ko", "ko", notes).as_deref(), Some("không"));
+        assert_eq!(table.expand("Ko", "Ko", notes).as_deref(), Some("Không"));
+        assert_eq!(table.expand("KO", "KO", notes).as_deref(), Some("KHÔNG"));
+        assert_eq!(table.expand("ko", "ko", "/System/Applications/Utilities/Terminal.app"), None);
+
+        assert_eq!(table.expand("tks", "tks", "/System/Applications/Mail.app").as_deref(), Some("Thanks,"));
+        assert_eq!(table.expand("tks", "tks", notes), None);
+        assert_eq!(table.expand("Tks", "Tks", "/System/Applications/Mail.app"), None);
+
+        // Options survive a save and load
+        let entries = table.entries();
+        let json = serde_json::to_string(&entries).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Macro>>(&json).unwrap(), entries);
+        let plain: Vec<Macro> = serde_json::from_str(r#"[{ "trigger": "vn", "expansion": "Việt Nam" }]"#).unwrap();
+        assert_eq!(plain[0].options, MacroOptions::default());
+    }
+
+    #[test]
+    fn loading_keeps_macros_past_the_limit() {
+        let dir = std::env::temp_dir().join(format!("vkey-macros-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("macros.json");
+        let macros: Vec<Macro> = (0..=MAX_MACROS)
+            .map(|index| Macro {
+                trigger: format!("m{}", index),
+                expansion: index.to_string(),
+                options: MacroOptions::default(),
+            })
+            .collect();
+        datafile::write(&path, &MACRO_FORMAT, &macros).unwrap();
+
+        let mut table = MacroTable::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(table.len(), MAX_MACROS + 1);
+        // Nothing new is added past the limit, but existing triggers change
+        table.insert("extra", "ignored");
+        assert_eq!(table.lookup("extra"), None);
+        table.insert("m0", "changed");
+        assert_eq!(table.lookup("m0"), Some("changed"));
+
+        table.save(path.to_str().unwrap()).unwrap();
+        assert_eq!(MacroTable::load(path.to_str().unwrap()).unwrap().len(), MAX_MACROS + 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
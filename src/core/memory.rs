@@ -0,0 +1,54 @@
+// Memory accounting
+// Rough sizes of what VKey keeps in memory, shown in the diagnostics window.
+// VKey runs all day, so structures that grow with use are also capped.
+
+/// Approximate memory held by one structure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub name: &'static str,
+    /// Number of entries held
+    pub items: usize,
+    /// Approximate bytes, including heap allocations
+    pub bytes: usize,
+    /// Maximum number of entries, if capped
+    pub cap: Option<usize>,
+}
+
+/// Memory usage of everything VKey keeps around
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub entries: Vec<MemoryUsage>,
+}
+
+impl MemoryReport {
+    pub fn add(&mut self, name: &'static str, items: usize, bytes: usize, cap: Option<usize>) {
+        self.entries.push(MemoryUsage { name, items, bytes, cap });
+    }
+
+    /// Append the entries of another report
+    pub fn extend(&mut self, other: MemoryReport) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.entries.iter().map(|entry| entry.bytes).sum()
+    }
+}
+
+/// Bytes held by a string, including its heap buffer
+pub fn string_bytes(s: &String) -> usize {
+    std::mem::size_of::<String>() + s.capacity()
+}
+
+/// Human readable size, e.g. "512 B" or "12.3 KB"
+pub fn format_bytes(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    let bytes_f = bytes as f64;
+    if bytes_f < KB {
+        format!("{} B", bytes)
+    } else if bytes_f < KB * KB {
+        format!("{:.1} KB", bytes_f / KB)
+    } else {
+        format!("{:.1} MB", bytes_f / (KB * KB))
+    }
+}
@@ -1,7 +1,57 @@
 pub mod types;
+pub mod case;
+pub mod charset;
+pub mod compat;
+pub mod edit;
+pub mod emoji;
+pub mod encoding;
+pub mod syllable;
+pub mod engine;
+pub mod english;
+pub mod habits;
+pub mod inject;
+pub mod hybrid;
+pub mod reverse;
+pub mod routing;
+pub mod rules;
+pub mod scheme;
 pub mod config;
+pub mod corrections;
+pub mod data_dir;
+pub mod datafile;
+pub mod dictionary;
+pub mod macros;
+pub mod memory;
+pub mod paste;
+pub mod placeholders;
+pub mod stats;
+pub mod suggest;
+pub mod transformer;
 pub mod vietnamese_input;
 
-pub use types::{InputType, Encoding, InputMode};
+#[cfg(test)]
+mod fuzz;
+#[cfg(test)]
+mod simulation;
+
+pub use types::{InputType, Encoding, InputMode, Normalization, NumericPrefix, SoundEvent, SoundTheme};
+pub use charset::{grapheme_clusters, normalize, remove_diacritics};
+pub use compat::{CompatCatalog, CompatEntry};
+pub use edit::{apply_edits, EditOp};
+pub use emoji::EmojiTable;
 pub use config::AppConfig;
-pub use vietnamese_input::{VietnameseInputProcessor, ProcessingResult}; 
\ No newline at end of file
+pub use corrections::{Correction, CorrectionLog};
+pub use dictionary::UserDictionary;
+pub use engine::{EngineOptions, InputMethodEngine, KeyEffect, ViEngine, VniKeyMap};
+pub use transformer::{RuleSet, Transformer};
+pub use habits::RestoreHabits;
+pub use inject::{apply_result, Injector};
+pub use routing::{finish_word, route_key, KeyOutcome, TypingKey};
+pub use macros::{Macro, MacroCase, MacroOptions, MacroTable};
+pub use memory::MemoryReport;
+pub use paste::{Pasteboard, PasteboardItems, Pastes};
+pub use placeholders::{LocalTime, PlaceholderSource};
+pub use scheme::{SchemeCatalog, SchemeEngine, SchemePack, TransformScheme};
+pub use stats::{QualityCounts, QualityMetrics, TypingStats, WeekStart};
+pub use suggest::SuggestionEngine;
+pub use vietnamese_input::{BufferSnapshot, VietnameseInputProcessor, ProcessingResult}; 
\ No newline at end of file
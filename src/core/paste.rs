@@ -0,0 +1,234 @@
+//! Pasting replacements over the user's clipboard, in turn. The application
+//! reads the pasteboard whenever it gets to the Command+V, so text offered
+//! for the next paste before then would be pasted in place of this one.
+//! Each paste therefore waits for the application to read the one before
+//! it. The clipboard the user had is saved before the first paste of a run
+//! and written back once the last one has been read, unless the user copied
+//! something in between. The platform layer supplies the `Pasteboard`.
+
+use std::time::Duration;
+
+/// Longest a paste, or putting the clipboard back, waits for the
+/// application to read the paste before it, for one that never does
+pub const PASTE_READ_DEADLINE: Duration = Duration::from_millis(200);
+
+/// Each item on a pasteboard, with the data of each of its types
+pub type PasteboardItems = Vec<Vec<(String, Vec<u8>)>>;
+
+/// The general pasteboard, as pastes use it
+pub trait Pasteboard {
+    /// Changes to the pasteboard so far, by anyone
+    fn change_count(&self) -> i64;
+
+    /// Everything on the pasteboard
+    fn read_items(&self) -> PasteboardItems;
+
+    /// Replace the pasteboard with `items`
+    fn write_items(&self, items: &PasteboardItems);
+
+    /// Replace the pasteboard with `text` for paste number `paste`, marked
+    /// so clipboard managers do not keep it. The change count afterwards,
+    /// None if it could not be written.
+    fn offer(&self, text: &str, paste: u64) -> Option<i64>;
+
+    /// Wait until an application has read paste number `paste`, at most
+    /// `deadline`. False if it did not.
+    fn wait_until_read(&self, paste: u64, deadline: Duration) -> bool;
+}
+
+/// The clipboard as it was before replacements were pasted over it
+#[derive(Debug)]
+struct SavedClipboard {
+    items: PasteboardItems,
+    /// Change count of the pasteboard after the last paste; any other count
+    /// means the user copied something since
+    change_count: i64,
+    /// The last paste, after which the clipboard is put back
+    paste: u64,
+}
+
+/// Pastes over one pasteboard, one at a time
+#[derive(Debug)]
+pub struct Pastes<P> {
+    pasteboard: P,
+    saved: Option<SavedClipboard>,
+    /// Pastes offered so far
+    count: u64,
+}
+
+impl<P: Pasteboard> Pastes<P> {
+    pub const fn new(pasteboard: P) -> Self {
+        Self {
+            pasteboard,
+            saved: None,
+            count: 0,
+        }
+    }
+
+    /// Put `text` on the pasteboard for the next paste, once the application
+    /// has read the one before it. Its number, to restore the clipboard
+    /// after, or None if the pasteboard could not be written.
+    pub fn stage(&mut self, text: &str) -> Option<u64> {
+        if let Some(previous) = &self.saved {
+            if !self.pasteboard.wait_until_read(previous.paste, PASTE_READ_DEADLINE) {
+                eprintln!("Paste {} was not read in time, offering the next one anyway", previous.paste);
+            }
+        }
+        // Pastes in quick succession keep the clipboard saved by the first
+        let items = match self.saved.take() {
+            Some(previous) => previous.items,
+            None => self.pasteboard.read_items(),
+        };
+        self.count += 1;
+        match self.pasteboard.offer(text, self.count) {
+            Some(change_count) => {
+                self.saved = Some(SavedClipboard {
+                    items,
+                    change_count,
+                    paste: self.count,
+                });
+                Some(self.count)
+            }
+            None => {
+                self.pasteboard.write_items(&items);
+                None
+            }
+        }
+    }
+
+    /// Put back the clipboard saved before `paste` once the application has
+    /// read it, unless a later paste will
+    pub fn restore(&mut self, paste: u64) {
+        if self.saved.as_ref().map(|clipboard| clipboard.paste) != Some(paste) {
+            return;
+        }
+        if !self.pasteboard.wait_until_read(paste, PASTE_READ_DEADLINE) {
+            eprintln!("Paste {} was not read in time, putting the clipboard back anyway", paste);
+        }
+        if let Some(clipboard) = self.saved.take() {
+            // Whatever the user copied since the paste stays
+            if self.pasteboard.change_count() == clipboard.change_count {
+                self.pasteboard.write_items(&clipboard.items);
+            }
+        }
+    }
+
+    pub fn pasteboard(&self) -> &P {
+        &self.pasteboard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// A pasteboard holding text, that records what is done to it
+    #[derive(Debug, Default)]
+    struct Recorder {
+        text: RefCell<String>,
+        change_count: RefCell<i64>,
+        /// Pastes the application has read
+        read: RefCell<Vec<u64>>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn with_text(text: &str) -> Self {
+            let pasteboard = Self::default();
+            *pasteboard.text.borrow_mut() = text.to_string();
+            pasteboard
+        }
+
+        fn replace(&self, text: &str) {
+            *self.text.borrow_mut() = text.to_string();
+            *self.change_count.borrow_mut() += 1;
+        }
+
+        /// The application reads the pasteboard
+        fn paste(&self, paste: u64) -> String {
+            self.read.borrow_mut().push(paste);
+            self.text.borrow().clone()
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.borrow_mut().drain(..).collect()
+        }
+    }
+
+    impl Pasteboard for Recorder {
+        fn change_count(&self) -> i64 {
+            *self.change_count.borrow()
+        }
+
+        fn read_items(&self) -> PasteboardItems {
+            self.calls.borrow_mut().push("save".to_string());
+            vec![vec![("text".to_string(), self.text.borrow().as_bytes().to_vec())]]
+        }
+
+        fn write_items(&self, items: &PasteboardItems) {
+            let text = String::from_utf8(items[0][0].1.clone()).unwrap();
+            self.calls.borrow_mut().push(format!("restore {:?}", text));
+            self.replace(&text);
+        }
+
+        fn offer(&self, text: &str, paste: u64) -> Option<i64> {
+            self.calls.borrow_mut().push(format!("offer {} {:?}", paste, text));
+            self.replace(text);
+            Some(self.change_count())
+        }
+
+        fn wait_until_read(&self, paste: u64, _deadline: Duration) -> bool {
+            let read = self.read.borrow().contains(&paste);
+            self.calls.borrow_mut().push(format!("wait {} {}", paste, if read { "read" } else { "unread" }));
+            read
+        }
+    }
+
+    #[test]
+    fn each_paste_waits_for_the_one_before() {
+        let mut pastes = Pastes::new(Recorder::with_text("copied"));
+        assert_eq!(pastes.stage("ệ"), Some(1));
+        assert_eq!(pastes.pasteboard().calls(), ["save", "offer 1 \"ệ\""]);
+
+        // The application reads the first paste before the second is offered
+        assert_eq!(pastes.pasteboard().paste(1), "ệ");
+        assert_eq!(pastes.stage("ết"), Some(2));
+        assert_eq!(pastes.pasteboard().calls(), ["wait 1 read", "offer 2 \"ết\""]);
+
+        // Only the last paste puts the clipboard back, once it has been read
+        pastes.restore(1);
+        assert_eq!(pastes.pasteboard().calls(), Vec::<String>::new());
+        assert_eq!(pastes.pasteboard().paste(2), "ết");
+        pastes.restore(2);
+        assert_eq!(pastes.pasteboard().calls(), ["wait 2 read", "restore \"copied\""]);
+        assert_eq!(*pastes.pasteboard().text.borrow(), "copied");
+
+        // The next run saves the clipboard again
+        pastes.stage("a");
+        assert_eq!(pastes.pasteboard().calls(), ["save", "offer 3 \"a\""]);
+    }
+
+    #[test]
+    fn a_paste_never_read_still_lets_the_next_one_through() {
+        let mut pastes = Pastes::new(Recorder::with_text("copied"));
+        pastes.stage("ệ");
+        pastes.pasteboard().calls();
+        assert_eq!(pastes.stage("ết"), Some(2));
+        assert_eq!(pastes.pasteboard().calls(), ["wait 1 unread", "offer 2 \"ết\""]);
+        pastes.restore(2);
+        assert_eq!(pastes.pasteboard().calls(), ["wait 2 unread", "restore \"copied\""]);
+    }
+
+    #[test]
+    fn text_copied_after_a_paste_is_kept() {
+        let mut pastes = Pastes::new(Recorder::with_text("copied"));
+        pastes.stage("ệ");
+        pastes.pasteboard().paste(1);
+        pastes.pasteboard().replace("copied later");
+        pastes.pasteboard().calls();
+        pastes.restore(1);
+        assert_eq!(pastes.pasteboard().calls(), ["wait 1 read"]);
+        assert_eq!(*pastes.pasteboard().text.borrow(), "copied later");
+    }
+}
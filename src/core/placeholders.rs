@@ -0,0 +1,192 @@
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// Format of `{date}` without one of its own
+pub const DEFAULT_DATE_FORMAT: &str = "%d/%m/%Y";
+/// Format of `{time}` without one of its own
+pub const DEFAULT_TIME_FORMAT: &str = "%H:%M";
+
+/// Names of the days of the week for `%A`, from Sunday
+const WEEKDAYS: [&str; 7] = ["Chủ nhật", "Thứ hai", "Thứ ba", "Thứ tư", "Thứ năm", "Thứ sáu", "Thứ bảy"];
+
+/// A moment on the local clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// Day of the week, 0 for Sunday
+    pub weekday: u32,
+}
+
+impl LocalTime {
+    /// The time `secs` seconds after the Unix epoch, in a time zone
+    /// `offset_secs` seconds east of UTC
+    pub fn from_unix(secs: i64, offset_secs: i64) -> Self {
+        let local = secs + offset_secs;
+        let days = local.div_euclid(86_400);
+        let of_day = local.rem_euclid(86_400);
+
+        // Civil date from days since the epoch, in 400-year eras of 146097 days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month: month as u32,
+            day: day as u32,
+            hour: (of_day / 3600) as u32,
+            minute: (of_day % 3600 / 60) as u32,
+            second: (of_day % 60) as u32,
+            weekday: (days + 4).rem_euclid(7) as u32,
+        }
+    }
+
+    /// Write the time in `format`: `%d` day, `%m` month, `%Y` year, `%y`
+    /// year in two digits, `%H` hour, `%M` minute, `%S` second, `%A` day of
+    /// the week and `%%` a percent sign. Anything else is copied.
+    pub fn format(&self, format: &str) -> String {
+        let mut out = String::with_capacity(format.len() + 8);
+        let mut chars = format.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                out.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('d') => out.push_str(&format!("{:02}", self.day)),
+                Some('m') => out.push_str(&format!("{:02}", self.month)),
+                Some('Y') => out.push_str(&self.year.to_string()),
+                Some('y') => out.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('H') => out.push_str(&format!("{:02}", self.hour)),
+                Some('M') => out.push_str(&format!("{:02}", self.minute)),
+                Some('S') => out.push_str(&format!("{:02}", self.second)),
+                Some('A') => out.push_str(WEEKDAYS[self.weekday as usize % 7]),
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+}
+
+/// Where the values of placeholders come from when a macro is expanded
+pub trait PlaceholderSource: fmt::Debug + Send + Sync {
+    fn now(&self) -> LocalTime;
+    /// Text on the clipboard, if it holds any that may be typed
+    fn clipboard(&self) -> Option<String>;
+}
+
+/// The clock in UTC and no clipboard, for builds without a platform layer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UtcClock;
+
+impl PlaceholderSource for UtcClock {
+    fn now(&self) -> LocalTime {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        LocalTime::from_unix(secs, 0)
+    }
+
+    fn clipboard(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Replace the placeholders in a macro expansion: `{date}`, `{time}`, each
+/// optionally with a format after a colon (`{date:%d/%m/%Y}`), and
+/// `{clipboard}`. Anything else in braces is typed as written, and the
+/// clipboard is only read when the expansion asks for it.
+pub fn expand(template: &str, source: &dyn PlaceholderSource) -> String {
+    if !template.contains('{') {
+        return template.to_string();
+    }
+    let mut now = None;
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let token = &rest[open + 1..];
+        let Some(close) = token.find('}') else {
+            rest = &rest[open..];
+            break;
+        };
+        let (name, format) = match token[..close].split_once(':') {
+            Some((name, format)) => (name, Some(format)),
+            None => (&token[..close], None),
+        };
+        let value = match name {
+            "date" => Some(now.get_or_insert_with(|| source.now()).format(format.unwrap_or(DEFAULT_DATE_FORMAT))),
+            "time" => Some(now.get_or_insert_with(|| source.now()).format(format.unwrap_or(DEFAULT_TIME_FORMAT))),
+            "clipboard" if format.is_none() => Some(source.clipboard().unwrap_or_default()),
+            _ => None,
+        };
+        match value {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[open..open + close + 2]),
+        }
+        rest = &token[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Fixed;
+
+    impl PlaceholderSource for Fixed {
+        fn now(&self) -> LocalTime {
+            // 2024-09-02 14:05:09 in Hà Nội, UTC+7
+            LocalTime::from_unix(1_725_260_709, 7 * 3600)
+        }
+
+        fn clipboard(&self) -> Option<String> {
+            Some("0912 345 678".to_string())
+        }
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(expand("Hà Nội, {date}", &Fixed), "Hà Nội, 02/09/2024");
+        assert_eq!(expand("{date:%A, %d-%m-%y} {time}", &Fixed), "Thứ hai, 02-09-24 14:05");
+        assert_eq!(expand("{time:%H:%M:%S}", &Fixed), "14:05:09");
+        assert_eq!(expand("SĐT: {clipboard}", &Fixed), "SĐT: 0912 345 678");
+        assert_eq!(expand("{clipboard}", &UtcClock), "");
+
+        // Text that is not a placeholder stays as written
+        assert_eq!(expand("{ten} {date", &Fixed), "{ten} {date");
+        assert_eq!(expand("100% {}", &Fixed), "100% {}");
+    }
+
+    #[test]
+    fn dates_before_and_after_month_ends() {
+        let at = |secs| LocalTime::from_unix(secs, 0).format("%Y-%m-%d %H:%M %A");
+        assert_eq!(at(0), "1970-01-01 00:00 Thứ năm");
+        assert_eq!(at(951_825_600), "2000-02-29 12:00 Thứ ba");
+        assert_eq!(at(-1), "1969-12-31 23:59 Thứ tư");
+    }
+}
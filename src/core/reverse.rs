@@ -0,0 +1,187 @@
+// Reverse transform
+// Turns composed Vietnamese text back into the keys that type it, e.g.
+// "Việt Nam" -> "Vieetj Nam" (Telex) or "Vie65t Nam" (VNI).
+
+use super::charset::{split_tone, ToneMark};
+use super::engine::VniKeyMap;
+use super::types::InputType;
+
+/// Keys that type `text` with the given input method.
+///
+/// Letter marks follow the vowel they belong to. In Telex the tone key ends
+/// the word, in VNI and VIQR it follows the toned vowel. Plain ASCII is kept
+/// as is, so words that were never transformed ("as", "dd") will be
+/// transformed when the keys are typed again.
+///
+/// VNI marks are typed on the keys of `vni_keys`, while digits in the text
+/// itself are kept as they are.
+pub fn reverse_transform(text: &str, input_type: InputType, vni_keys: VniKeyMap) -> String {
+    let keys_for = |word: &str| -> String {
+        let keys = word_keys(word, input_type);
+        match input_type {
+            InputType::VNI => keys.chars().map(|key| vni_keys.from_standard(key)).collect(),
+            _ => keys,
+        }
+    };
+    let mut keys = String::with_capacity(text.len() * 2);
+    let mut word = String::new();
+
+    for ch in text.chars() {
+        if ch.is_alphabetic() {
+            word.push(ch);
+        } else {
+            keys.push_str(&keys_for(&word));
+            word.clear();
+            keys.push(ch);
+        }
+    }
+    keys.push_str(&keys_for(&word));
+    keys
+}
+
+/// Keys for one word of letters
+fn word_keys(word: &str, input_type: InputType) -> String {
+    let mut keys = String::with_capacity(word.len() * 2);
+    let mut tone = ToneMark::None;
+    // A Telex tone key is typed in the case of the vowel it marks
+    let mut tone_upper = false;
+
+    for ch in word.chars() {
+        let (base, ch_tone) = split_tone(ch);
+        let lower = base.to_lowercase().next().unwrap_or(base);
+        let plain = plain_letter(lower);
+        keys.push(if base.is_uppercase() { plain.to_ascii_uppercase() } else { plain });
+
+        if let Some(mark) = mark_key(lower, plain, input_type) {
+            if mark.is_ascii_alphabetic() && base.is_uppercase() {
+                keys.push(mark.to_ascii_uppercase());
+            } else {
+                keys.push(mark);
+            }
+        }
+
+        if ch_tone != ToneMark::None && tone == ToneMark::None {
+            tone = ch_tone;
+            tone_upper = base.is_uppercase();
+            if !matches!(input_type, InputType::Telex | InputType::Hybrid | InputType::TelexSimple) {
+                keys.extend(tone_key(tone, input_type));
+            }
+        }
+    }
+
+    if matches!(input_type, InputType::Telex | InputType::Hybrid | InputType::TelexSimple) {
+        keys.extend(tone_key(tone, input_type).map(|key| if tone_upper { key.to_ascii_uppercase() } else { key }));
+    }
+    keys
+}
+
+/// The ASCII letter a Vietnamese letter is typed from
+fn plain_letter(lower: char) -> char {
+    match lower {
+        'ă' | 'â' => 'a',
+        'ê' => 'e',
+        'ô' | 'ơ' => 'o',
+        'ư' => 'u',
+        'đ' => 'd',
+        other => other,
+    }
+}
+
+/// The key that adds the letter mark of `lower` after its plain letter
+fn mark_key(lower: char, plain: char, input_type: InputType) -> Option<char> {
+    match input_type {
+        InputType::Telex | InputType::Hybrid => match lower {
+            'â' | 'ê' | 'ô' | 'đ' => Some(plain),
+            'ă' | 'ơ' | 'ư' => Some('w'),
+            _ => None,
+        },
+        // ă, ơ and ư cannot be typed, so they come back as their plain letter
+        InputType::TelexSimple => match lower {
+            'â' | 'ê' | 'ô' | 'đ' => Some(plain),
+            _ => None,
+        },
+        InputType::VNI => match lower {
+            'â' | 'ê' | 'ô' => Some('6'),
+            'ơ' | 'ư' => Some('7'),
+            'ă' => Some('8'),
+            'đ' => Some('9'),
+            _ => None,
+        },
+        InputType::VIQR => match lower {
+            'â' | 'ê' | 'ô' => Some('^'),
+            'ơ' | 'ư' => Some('+'),
+            'ă' => Some('('),
+            'đ' => Some('d'),
+            _ => None,
+        },
+    }
+}
+
+/// The key that types a tone, if any
+fn tone_key(tone: ToneMark, input_type: InputType) -> Option<char> {
+    let keys = match input_type {
+        InputType::Telex | InputType::Hybrid | InputType::TelexSimple => ['s', 'f', 'r', 'x', 'j'],
+        InputType::VNI => ['1', '2', '3', '4', '5'],
+        InputType::VIQR => ['\'', '`', '?', '~', '.'],
+    };
+    match tone {
+        ToneMark::None => None,
+        ToneMark::Acute => Some(keys[0]),
+        ToneMark::Grave => Some(keys[1]),
+        ToneMark::HookAbove => Some(keys[2]),
+        ToneMark::Tilde => Some(keys[3]),
+        ToneMark::DotBelow => Some(keys[4]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vietnamese_input::VietnameseInputProcessor;
+
+    fn telex(text: &str) -> String {
+        reverse_transform(text, InputType::Telex, VniKeyMap::default())
+    }
+
+    fn vni(text: &str) -> String {
+        reverse_transform(text, InputType::VNI, VniKeyMap::default())
+    }
+
+    #[test]
+    fn letters_come_back_as_their_keys() {
+        assert_eq!(telex("Việt Nam"), "Vieetj Nam");
+        assert_eq!(vni("Việt Nam"), "Vie65t Nam");
+
+        assert_eq!(telex("đường"), "dduwowngf");
+        assert_eq!(vni("đường"), "d9u7o72ng");
+        assert_eq!(telex("mơ ư"), "mow uw");
+        assert_eq!(vni("mơ ư"), "mo7 u7");
+
+        // Marks on capitals are typed in the same case, digits stay digits
+        assert_eq!(telex("ĐẶNG"), "DDAWNGJ");
+        assert_eq!(vni("ĐẶNG"), "D9A85NG");
+        assert_eq!(telex("Đà Nẵng, 2024"), "DDaf Nawngx, 2024");
+    }
+
+    #[test]
+    fn vni_marks_follow_a_remapped_key_order() {
+        let reversed = VniKeyMap::parse("0543216789").unwrap();
+        assert_eq!(reverse_transform("Việt", InputType::VNI, reversed), "Vie61t");
+    }
+
+    #[test]
+    fn reversed_text_types_the_same_text() {
+        let corpus = [
+            "Việt Nam", "tiếng Việt", "người", "được", "khuya", "nghiêng", "Đà Nẵng",
+            "quyển sách", "giường", "ĐƯỜNG PHỐ", "Nguyễn Trãi", "chuyện", "thuyền", "mười",
+            "hướng dẫn", "ăn uống", "bây giờ", "kỷ niệm", "rượu", "trăng",
+        ];
+        for input_type in [InputType::Telex, InputType::VNI] {
+            let processor = VietnameseInputProcessor::new(input_type);
+            for text in corpus {
+                let keys = processor.reverse_transform(text);
+                assert_eq!(processor.transform_text(&keys), text, "{:?} keys {:?}", input_type, keys);
+            }
+        }
+    }
+}
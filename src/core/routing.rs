@@ -0,0 +1,107 @@
+//! What a key does to the word being composed. The event tap takes out the
+//! hotkeys and names the key; `route_key` then decides, on the processor,
+//! whether the word goes on, ends or follows the cursor, and hands back what
+//! the platform layer has to type and whether the key reaches the
+//! application. The simulation tests call the same function, so they cannot
+//! drift from the event handler.
+
+use crate::core::vietnamese_input::{ProcessingResult, VietnameseInputProcessor};
+
+/// A key as it reaches the word being composed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypingKey {
+    /// A character, including space, Escape, Enter and Tab
+    Char(char),
+    /// A character from the numeric keypad
+    Keypad(char),
+    Backspace,
+    /// Option+Backspace, deleting the word before the caret
+    WordBackspace,
+    /// fn+Delete, deleting the letter after the caret
+    ForwardDelete,
+    Left,
+    Right,
+    /// A cursor move the word cannot follow, like Up or a click
+    Jump,
+    /// A key the application handles itself, such as a shortcut or a dead key
+    Shortcut,
+    /// Undo or redo, changing the text around the caret behind our back
+    Undo,
+}
+
+/// What the platform layer does with a key once the processor has seen it
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyOutcome {
+    /// A word preview suppression held back, typed before anything else
+    pub pending: Option<String>,
+    /// The processor's answer, typed through `apply_result`
+    pub result: Option<ProcessingResult>,
+    /// Without a result, whether the key is kept from the application
+    pub blocked: bool,
+    /// The word ended because the caret left it; the word before the caret
+    /// is worth a look when the next key is typed
+    pub cursor_moved: bool,
+    /// The word still being composed, for marked text
+    pub preedit: String,
+}
+
+/// Carry out `key` on the processor
+pub fn route_key(processor: &mut VietnameseInputProcessor, key: TypingKey) -> KeyOutcome {
+    let mut pending = None;
+    let mut result = None;
+    let mut blocked = false;
+    let mut cursor_moved = false;
+    match key {
+        // A hidden word is typed before Tab or Enter, unless Tab picks a
+        // suggestion and replaces it
+        TypingKey::Char(ch @ ('\t' | '\r')) => {
+            if !(ch == '\t' && processor.has_suggestions()) {
+                pending = finish_word(processor);
+            }
+            result = Some(processor.process_key(ch));
+        }
+        // A word left alone past the idle timeout is finished; this key starts a new one
+        TypingKey::Char(ch) | TypingKey::Keypad(ch) => {
+            if processor.is_idle() {
+                pending = finish_word(processor);
+            }
+            result = Some(match key {
+                TypingKey::Keypad(_) => processor.process_keypad_key(ch),
+                _ => processor.process_key(ch),
+            });
+        }
+        TypingKey::Backspace => result = Some(processor.handle_backspace()),
+        // The application deletes the whole word, so it leaves the buffer too.
+        // A hidden word was never typed, so deleting it is all there is to do.
+        TypingKey::WordBackspace => blocked = processor.handle_word_deletion(),
+        // The application deletes the letter after the caret, which may be part of the word
+        TypingKey::ForwardDelete => processor.handle_forward_delete(),
+        TypingKey::Left | TypingKey::Right => {
+            let step = if key == TypingKey::Left { -1 } else { 1 };
+            if !processor.move_cursor(step) {
+                pending = finish_word(processor);
+                cursor_moved = true;
+            }
+        }
+        TypingKey::Jump => {
+            pending = finish_word(processor);
+            cursor_moved = true;
+        }
+        TypingKey::Shortcut => pending = finish_word(processor),
+        TypingKey::Undo => processor.handle_undo(),
+    }
+    KeyOutcome {
+        pending,
+        result,
+        blocked,
+        cursor_moved,
+        preedit: processor.get_preedit_text(),
+    }
+}
+
+/// End the word, returning what preview suppression kept back
+pub fn finish_word(processor: &mut VietnameseInputProcessor) -> Option<String> {
+    let pending = processor.take_pending_output();
+    processor.new_word();
+    pending
+}
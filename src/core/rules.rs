@@ -0,0 +1,372 @@
+//! Telex and VNI rules of VKey's own, used instead of vi-rs when VKey is built
+//! with the `vendored-rules` feature. They follow vi-rs: a key marks the
+//! vowel it applies to anywhere in the syllable, typing a mark key again
+//! takes the mark off and types the key, and the tone moves when the vowels
+//! change. Tones are placed the classic way (hòa, thúy); the engine converts
+//! to the modern placement afterwards.
+
+use crate::core::transformer::{RuleSet, TransformOutcome, Transformer};
+
+/// Each vowel followed by its five tones, in the order sắc, huyền, hỏi, ngã, nặng
+const TONED_VOWELS: [&str; 12] = [
+    "aáàảãạ", "ăắằẳẵặ", "âấầẩẫậ", "eéèẻẽẹ", "êếềểễệ", "iíìỉĩị", "oóòỏõọ", "ôốồổỗộ", "ơớờởỡợ", "uúùủũụ", "ưứừửữự",
+    "yýỳỷỹỵ",
+];
+
+/// Letters that carry a circumflex, horn or breve
+const MARKED_VOWELS: [char; 6] = ['ă', 'â', 'ê', 'ô', 'ơ', 'ư'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Circumflex,
+    Horn,
+    Breve,
+}
+
+/// What a key does in a rule set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Set tone 1-5, or clear it with 0
+    Tone(u8),
+    /// Telex aa ee oo and VNI 6. Telex only marks the vowel of the key.
+    Circumflex(Option<char>),
+    /// Telex w: a horn on u and o, else a breve on a, else ư on its own
+    HornOrBreve,
+    /// VNI 7
+    Horn,
+    /// VNI 8
+    Breve,
+    /// Telex dd and VNI 9
+    Stroke,
+    Letter,
+}
+
+fn action(rules: RuleSet, key: char) -> Action {
+    match (rules, key) {
+        (RuleSet::Telex, 's') => Action::Tone(1),
+        (RuleSet::Telex, 'f') => Action::Tone(2),
+        (RuleSet::Telex, 'r') => Action::Tone(3),
+        (RuleSet::Telex, 'x') => Action::Tone(4),
+        (RuleSet::Telex, 'j') => Action::Tone(5),
+        (RuleSet::Telex, 'z') => Action::Tone(0),
+        (RuleSet::Telex, 'a' | 'e' | 'o') => Action::Circumflex(Some(key)),
+        (RuleSet::Telex, 'w') => Action::HornOrBreve,
+        (RuleSet::Telex, 'd') => Action::Stroke,
+        (RuleSet::Vni, '0'..='5') => Action::Tone(key as u8 - b'0'),
+        (RuleSet::Vni, '6') => Action::Circumflex(None),
+        (RuleSet::Vni, '7') => Action::Horn,
+        (RuleSet::Vni, '8') => Action::Breve,
+        (RuleSet::Vni, '9') => Action::Stroke,
+        _ => Action::Letter,
+    }
+}
+
+/// The letter without a circumflex, horn or breve
+fn base(letter: char) -> char {
+    match letter {
+        'ă' | 'â' => 'a',
+        'ê' => 'e',
+        'ô' | 'ơ' => 'o',
+        'ư' => 'u',
+        other => other,
+    }
+}
+
+fn with_mark(letter: char, mark: Mark) -> Option<char> {
+    match (base(letter), mark) {
+        ('a', Mark::Circumflex) => Some('â'),
+        ('e', Mark::Circumflex) => Some('ê'),
+        ('o', Mark::Circumflex) => Some('ô'),
+        ('o', Mark::Horn) => Some('ơ'),
+        ('u', Mark::Horn) => Some('ư'),
+        ('a', Mark::Breve) => Some('ă'),
+        _ => None,
+    }
+}
+
+fn is_vowel(letter: char) -> bool {
+    TONED_VOWELS.iter().any(|row| row.starts_with(letter))
+}
+
+fn toned(letter: char, tone: u8) -> char {
+    TONED_VOWELS
+        .iter()
+        .find(|row| row.starts_with(letter))
+        .and_then(|row| row.chars().nth(tone as usize))
+        .unwrap_or(letter)
+}
+
+fn same_case(letter: char, like: char) -> char {
+    if like.is_uppercase() {
+        letter.to_uppercase().next().unwrap_or(letter)
+    } else {
+        letter
+    }
+}
+
+/// A syllable being composed: its letters without tone, and the tone
+#[derive(Debug, Default)]
+struct Syllable {
+    letters: Vec<char>,
+    tone: u8,
+    /// Index of an ư typed by a lone Telex w, which a second w takes back
+    lone_horn: Option<usize>,
+}
+
+impl Syllable {
+    fn lower(&self, index: usize) -> char {
+        self.letters[index].to_lowercase().next().unwrap_or(self.letters[index])
+    }
+
+    /// Indices of the vowels, without the u of "qu" and the i of "gi"
+    /// before another vowel, which belong to the initial consonant. A key
+    /// that is not a letter ends the syllable, so marks only reach the
+    /// letters after it.
+    fn vowels(&self) -> Vec<usize> {
+        let start = self
+            .letters
+            .iter()
+            .rposition(|letter| !letter.is_alphabetic())
+            .map_or(0, |index| index + 1);
+        let mut vowels: Vec<usize> = Vec::new();
+        for index in start..self.letters.len() {
+            if !is_vowel(self.lower(index)) {
+                if !vowels.is_empty() {
+                    break;
+                }
+                continue;
+            }
+            vowels.push(index);
+        }
+        if vowels.len() > 1 && vowels[0] == start + 1 {
+            let initial = (self.lower(start), self.lower(start + 1));
+            if matches!(initial, ('q', 'u') | ('g', 'i')) {
+                vowels.remove(0);
+            }
+        }
+        vowels
+    }
+
+    fn set_mark(&mut self, index: usize, mark: Option<Mark>) {
+        let letter = self.lower(index);
+        let marked = match mark {
+            Some(mark) => with_mark(letter, mark).unwrap_or(letter),
+            None => base(letter),
+        };
+        self.letters[index] = same_case(marked, self.letters[index]);
+    }
+
+    /// Put `mark` on the vowels at `targets`, or take it off with the key
+    /// typed again if they carry it already
+    fn toggle_mark(&mut self, targets: &[usize], mark: Mark, key: char, outcome: &mut TransformOutcome) {
+        let marked = targets
+            .iter()
+            .all(|&index| with_mark(self.lower(index), mark) == Some(self.lower(index)));
+        for &index in targets {
+            self.set_mark(index, (!marked).then_some(mark));
+        }
+        if marked {
+            self.letters.push(key);
+            outcome.letter_modification_removed = true;
+        }
+    }
+
+    fn circumflex(&mut self, vowel: Option<char>, key: char, outcome: &mut TransformOutcome) {
+        let target = self.vowels().into_iter().rev().find(|&index| {
+            let letter = base(self.lower(index));
+            match vowel {
+                Some(vowel) => letter == vowel,
+                None => matches!(letter, 'a' | 'e' | 'o'),
+            }
+        });
+        match target {
+            Some(index) => self.toggle_mark(&[index], Mark::Circumflex, key, outcome),
+            None => self.letters.push(key),
+        }
+    }
+
+    /// The u and o of "uo", which take the horn together
+    fn horn_pair(&self, vowels: &[usize]) -> Option<[usize; 2]> {
+        vowels
+            .windows(2)
+            .find(|pair| base(self.lower(pair[0])) == 'u' && base(self.lower(pair[1])) == 'o')
+            .map(|pair| [pair[0], pair[1]])
+    }
+
+    fn horn(&mut self, key: char, breve_fallback: bool, outcome: &mut TransformOutcome) {
+        let vowels = self.vowels();
+        if let Some(pair) = self.horn_pair(&vowels) {
+            return self.toggle_mark(&pair, Mark::Horn, key, outcome);
+        }
+        let find = |wanted: char| vowels.iter().rev().copied().find(|&index| base(self.lower(index)) == wanted);
+        // "oa" takes a breve on the a, as in "hoặc"
+        let oa = find('o').is_some_and(|o| find('a').is_some_and(|a| a > o));
+        if let Some(index) = find('u').or_else(|| find('o').filter(|_| !oa)) {
+            return self.toggle_mark(&[index], Mark::Horn, key, outcome);
+        }
+        if breve_fallback {
+            self.breve(key, true, outcome);
+        } else {
+            self.letters.push(key);
+        }
+    }
+
+    fn breve(&mut self, key: char, lone_horn: bool, outcome: &mut TransformOutcome) {
+        let vowels = self.vowels();
+        if let Some(index) = vowels.iter().rev().copied().find(|&index| base(self.lower(index)) == 'a') {
+            return self.toggle_mark(&[index], Mark::Breve, key, outcome);
+        }
+        if lone_horn && vowels.is_empty() {
+            self.lone_horn = Some(self.letters.len());
+            self.letters.push(same_case('ư', key));
+        } else {
+            self.letters.push(key);
+        }
+    }
+
+    fn stroke(&mut self, key: char, outcome: &mut TransformOutcome) {
+        match self.letters.first().map(|_| self.lower(0)) {
+            Some('d') => self.letters[0] = same_case('đ', self.letters[0]),
+            Some('đ') => {
+                self.letters[0] = same_case('d', self.letters[0]);
+                self.letters.push(key);
+                outcome.letter_modification_removed = true;
+            }
+            _ => self.letters.push(key),
+        }
+    }
+
+    fn set_tone(&mut self, tone: u8, key: char, outcome: &mut TransformOutcome) {
+        if self.vowels().is_empty() || (tone == 0 && self.tone == 0) {
+            self.letters.push(key);
+        } else if tone == self.tone {
+            self.tone = 0;
+            self.letters.push(key);
+            outcome.tone_mark_removed = true;
+        } else {
+            self.tone = tone;
+        }
+    }
+
+    fn press(&mut self, rules: RuleSet, key: char) -> TransformOutcome {
+        let mut outcome = TransformOutcome::default();
+        let lower = key.to_lowercase().next().unwrap_or(key);
+        // A second w takes back the ư typed by the first
+        if rules == RuleSet::Telex && lower == 'w' {
+            if let Some(index) = self.lone_horn.take().filter(|&index| index + 1 == self.letters.len()) {
+                self.letters[index] = key;
+                outcome.letter_modification_removed = true;
+                return outcome;
+            }
+        }
+        match action(rules, lower) {
+            Action::Tone(tone) => self.set_tone(tone, key, &mut outcome),
+            Action::Circumflex(vowel) => self.circumflex(vowel, key, &mut outcome),
+            Action::HornOrBreve => self.horn(key, true, &mut outcome),
+            Action::Horn => self.horn(key, false, &mut outcome),
+            Action::Breve => self.breve(key, false, &mut outcome),
+            Action::Stroke => self.stroke(key, &mut outcome),
+            Action::Letter => self.letters.push(key),
+        }
+        outcome
+    }
+
+    /// Where the tone goes: on the marked vowel, the last one before a final
+    /// consonant, or else the first of two vowels and the middle of three
+    fn tone_position(&self) -> Option<usize> {
+        let vowels = self.vowels();
+        let (&first, &last) = (vowels.first()?, vowels.last()?);
+        if let Some(&marked) = vowels.iter().rev().find(|&&index| MARKED_VOWELS.contains(&self.lower(index))) {
+            return Some(marked);
+        }
+        if last + 1 < self.letters.len() {
+            return Some(last);
+        }
+        Some(match vowels.len() {
+            1 | 2 => first,
+            _ => vowels[1],
+        })
+    }
+
+    fn write(&self, output: &mut String) {
+        let position = self.tone_position().filter(|_| self.tone > 0);
+        for (index, &letter) in self.letters.iter().enumerate() {
+            if Some(index) == position {
+                output.push(same_case(toned(self.lower(index), self.tone), letter));
+            } else {
+                output.push(letter);
+            }
+        }
+    }
+}
+
+/// VKey's own Telex and VNI rules
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VendoredRules;
+
+impl Transformer for VendoredRules {
+    fn transform(&self, rules: RuleSet, keys: &str, output: &mut String) -> TransformOutcome {
+        let mut syllable = Syllable::default();
+        let mut outcome = TransformOutcome::default();
+        for key in keys.chars() {
+            outcome = syllable.press(rules, key);
+        }
+        syllable.write(output);
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transformer::ViRs;
+
+    fn compose(transformer: &dyn Transformer, rules: RuleSet, keys: &str) -> String {
+        let mut output = String::new();
+        transformer.transform(rules, keys, &mut output);
+        output
+    }
+
+    /// Words whose keys both transformers must compose the same way. A
+    /// vi-rs release that types one differently fails here before users
+    /// notice; the vendored rules are the fallback until it is fixed.
+    const WATCHED: [(RuleSet, &str, &str); 23] = [
+        (RuleSet::Telex, "tieengs", "tiếng"),
+        (RuleSet::Telex, "vieetj", "việt"),
+        (RuleSet::Telex, "ddi", "đi"),
+        (RuleSet::Telex, "hoaf", "hòa"),
+        (RuleSet::Telex, "thuys", "thúy"),
+        (RuleSet::Telex, "hoangf", "hoàng"),
+        (RuleSet::Telex, "gias", "giá"),
+        (RuleSet::Telex, "quas", "quá"),
+        (RuleSet::Telex, "nguowif", "người"),
+        (RuleSet::Telex, "dduowcj", "được"),
+        (RuleSet::Telex, "muwa", "mưa"),
+        (RuleSet::Telex, "chuyeenj", "chuyện"),
+        (RuleSet::Telex, "ass", "as"),
+        (RuleSet::Telex, "aaa", "aa"),
+        (RuleSet::Telex, "ddd", "dd"),
+        (RuleSet::Vni, "tie61ng", "tiếng"),
+        (RuleSet::Vni, "vie65t", "việt"),
+        (RuleSet::Vni, "d9i", "đi"),
+        (RuleSet::Vni, "hoa2", "hòa"),
+        (RuleSet::Vni, "nguo72i", "người"),
+        (RuleSet::Vni, "d9uo75c", "được"),
+        (RuleSet::Vni, "a11", "a1"),
+        (RuleSet::Vni, "a66", "a6"),
+    ];
+
+    #[test]
+    fn vendored_rules_compose_like_vi_rs() {
+        for (rules, keys, expected) in WATCHED {
+            assert_eq!(compose(&VendoredRules, rules, keys), expected, "vendored rules, {:?} {}", rules, keys);
+            assert_eq!(compose(&ViRs, rules, keys), expected, "vi-rs, {:?} {}", rules, keys);
+        }
+
+        let mut output = String::new();
+        let outcome = VendoredRules.transform(RuleSet::Telex, "ass", &mut output);
+        assert!(outcome.tone_mark_removed);
+        assert_eq!(compose(&VendoredRules, RuleSet::Telex, "ww"), "w");
+        assert_eq!(compose(&VendoredRules, RuleSet::Telex, "hoawcj"), "hoặc");
+    }
+}
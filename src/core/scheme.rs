@@ -0,0 +1,283 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::data_dir::{self, DataKind};
+use crate::core::engine::{EngineOptions, InputMethodEngine, KeyEffect};
+use crate::core::memory::string_bytes;
+use crate::error::{Result, VKeyError};
+
+/// Turns the keys of one word into text for a language other than
+/// Vietnamese. The Vietnamese input methods stay with `ViEngine`; a scheme is
+/// driven by `SchemeEngine`, which re-transforms the whole word on every key
+/// like it does.
+pub trait TransformScheme: fmt::Debug + Send + Sync {
+    /// Name the scheme is chosen by in the settings
+    fn name(&self) -> &str;
+
+    /// Compose the keys typed so far, in lowercase, and say what the last key did
+    fn transform(&self, keys: &str) -> (String, KeyEffect);
+
+    /// Whether a composed word is spelled right, for auto-restore. Schemes
+    /// without spelling rules accept every word.
+    fn is_valid_word(&self, _word: &str) -> bool {
+        true
+    }
+}
+
+/// One key sequence of a language pack and the text it types
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemeRule {
+    pub keys: String,
+    pub output: String,
+}
+
+/// A scheme read from data: keys are matched left to right against the
+/// longest rule that fits, and anything else is typed as it is. Typing the
+/// last key of a rule again takes the rule back, like "aaa" in Telex.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchemePack {
+    pub name: String,
+    pub rules: Vec<SchemeRule>,
+}
+
+impl SchemePack {
+    /// Pali romanization typed Telex style: a doubled vowel is long, q adds
+    /// the dot below, x and z type ñ and ṅ
+    pub fn pali() -> Self {
+        let rules = [
+            ("aa", "ā"), ("ii", "ī"), ("uu", "ū"),
+            ("tq", "ṭ"), ("dq", "ḍ"), ("nq", "ṇ"), ("lq", "ḷ"), ("mq", "ṃ"),
+            ("nx", "ñ"), ("nz", "ṅ"),
+        ];
+        Self {
+            name: "Pali".to_string(),
+            rules: rules
+                .iter()
+                .map(|(keys, output)| SchemeRule {
+                    keys: keys.to_string(),
+                    output: output.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The longest rule whose keys start `keys`
+    fn rule_at(&self, keys: &[char]) -> Option<&SchemeRule> {
+        self.rules
+            .iter()
+            .filter(|rule| !rule.keys.is_empty())
+            .filter(|rule| {
+                let count = rule.keys.chars().count();
+                count <= keys.len() && rule.keys.chars().eq(keys[..count].iter().copied())
+            })
+            .max_by_key(|rule| rule.keys.len())
+    }
+}
+
+impl TransformScheme for SchemePack {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn transform(&self, keys: &str) -> (String, KeyEffect) {
+        let keys: Vec<char> = keys.to_lowercase().chars().collect();
+        let mut output = String::new();
+        let mut effect = KeyEffect::default();
+        let mut index = 0;
+        while index < keys.len() {
+            let Some(rule) = self.rule_at(&keys[index..]) else {
+                output.push(keys[index]);
+                index += 1;
+                continue;
+            };
+            let end = index + rule.keys.chars().count();
+            if keys.get(end) == Some(&keys[end - 1]) {
+                // The last key typed again: keep the keys instead
+                output.extend(&keys[index..end]);
+                effect.mark_removed = end + 1 == keys.len();
+                index = end + 1;
+            } else {
+                output.push_str(&rule.output);
+                index = end;
+            }
+        }
+        (output, effect)
+    }
+}
+
+/// Composes words with a `TransformScheme`
+#[derive(Debug, Clone)]
+pub struct SchemeEngine {
+    scheme: Arc<dyn TransformScheme>,
+    keys: String,
+    output: String,
+}
+
+impl SchemeEngine {
+    pub fn new(scheme: Arc<dyn TransformScheme>) -> Self {
+        Self {
+            scheme,
+            keys: String::new(),
+            output: String::new(),
+        }
+    }
+
+    pub fn scheme(&self) -> &dyn TransformScheme {
+        self.scheme.as_ref()
+    }
+}
+
+impl InputMethodEngine for SchemeEngine {
+    fn process_key(&mut self, key: char) -> KeyEffect {
+        self.keys.push(key);
+        let (output, effect) = self.scheme.transform(&self.keys);
+        self.output = output;
+        effect
+    }
+
+    fn handle_backspace(&mut self) {
+        self.keys.pop();
+        self.output = self.scheme.transform(&self.keys).0;
+    }
+
+    fn commit(&mut self) -> String {
+        self.keys.clear();
+        std::mem::take(&mut self.output)
+    }
+
+    fn reset(&mut self) {
+        self.keys.clear();
+        self.output.clear();
+    }
+
+    fn keys(&self) -> &str {
+        &self.keys
+    }
+
+    fn output(&self) -> &str {
+        &self.output
+    }
+
+    // The Vietnamese options do not apply to other languages
+    fn set_options(&mut self, _options: EngineOptions) {}
+
+    fn is_valid_word(&self, word: &str) -> Option<bool> {
+        Some(self.scheme.is_valid_word(word))
+    }
+
+    fn approx_bytes(&self) -> usize {
+        string_bytes(&self.keys) + string_bytes(&self.output)
+    }
+
+    fn box_clone(&self) -> Box<dyn InputMethodEngine> {
+        Box::new(self.clone())
+    }
+}
+
+/// Language packs that can replace the Vietnamese input method, the built-in
+/// ones and those from the packs file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeCatalog {
+    packs: Vec<SchemePack>,
+}
+
+impl Default for SchemeCatalog {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl SchemeCatalog {
+    /// The packs shipped with VKey
+    pub fn builtin() -> Self {
+        Self {
+            packs: vec![SchemePack::pali()],
+        }
+    }
+
+    pub fn packs(&self) -> &[SchemePack] {
+        &self.packs
+    }
+
+    /// Add packs, replacing built-in ones with the same name
+    pub fn merge(&mut self, packs: Vec<SchemePack>) {
+        for pack in packs {
+            if pack.name.trim().is_empty() || pack.rules.is_empty() {
+                continue;
+            }
+            match self.packs.iter_mut().find(|known| known.name == pack.name) {
+                Some(known) => *known = pack,
+                None => self.packs.push(pack),
+            }
+        }
+    }
+
+    /// The pack called `name`, ready for `SchemeEngine`
+    pub fn get(&self, name: &str) -> Option<Arc<dyn TransformScheme>> {
+        self.packs
+            .iter()
+            .find(|pack| pack.name == name)
+            .map(|pack| Arc::new(pack.clone()) as Arc<dyn TransformScheme>)
+    }
+
+    /// Get the default packs file path (in the config directory)
+    pub fn get_packs_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Config, "schemes.json")
+    }
+
+    /// The built-in packs, updated with those of the packs file if there is one
+    pub fn load_default() -> Result<Self> {
+        let mut catalog = Self::builtin();
+        let path = Self::get_packs_path()?;
+        if path.exists() {
+            catalog.merge(Self::load(path.to_str().unwrap_or("schemes.json"))?);
+        }
+        Ok(catalog)
+    }
+
+    /// Load language packs from a JSON list
+    pub fn load(path: &str) -> Result<Vec<SchemePack>> {
+        let packs_str = std::fs::read_to_string(path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read language packs '{}': {}", path, e)
+            ))?;
+
+        serde_json::from_str(&packs_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse language packs '{}': {}", path, e)
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{AdvancedSettings, InputType};
+    use crate::core::vietnamese_input::{ProcessingResult, VietnameseInputProcessor};
+
+    #[test]
+    fn pali_pack_composes_and_is_never_restored() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        processor.apply_settings(&AdvancedSettings {
+            spell_check: true,
+            auto_restart_typos: true,
+            ..AdvancedSettings::default()
+        });
+        processor.set_transform_scheme(SchemeCatalog::builtin().get("Pali"));
+        assert_eq!(processor.transform_text("nibbaana Aanandaa samqgha"), "nibbāna Ānandā saṃgha");
+        assert_eq!(processor.transform_text("aaa"), "aa");
+
+        let mut result = ProcessingResult::consumed();
+        for key in "dhammq ".chars() {
+            result = processor.process_key(key);
+        }
+        assert!(!matches!(result, ProcessingResult::RestoreText { .. }));
+
+        processor.set_transform_scheme(None);
+        assert_eq!(processor.transform_text("vieetj"), "việt");
+    }
+}
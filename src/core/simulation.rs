@@ -0,0 +1,311 @@
+//! Deterministic simulation of typing into an application. `FakeApp` is a
+//! text field: a text and a caret that keys and injected edits act on the
+//! way a real field does, a backspace erasing a whole letter with its
+//! combining marks. `Simulator` sends every key through `route_key`, as the
+//! event handler in main.rs does: a result the processor handles is injected
+//! as edits, decomposed when the output is NFD, and the key is blocked;
+//! anything else reaches the application. After every key the word the
+//! processor thinks it shows must be on screen around the caret.
+
+use crate::core::charset::{grapheme_clusters, normalize};
+use crate::core::edit::EditOp;
+use crate::core::fuzz::Rng;
+use crate::core::inject::{apply_result, Injector};
+use crate::core::routing::{route_key, TypingKey};
+use crate::core::types::{AdvancedSettings, InputType, Normalization};
+use crate::core::vietnamese_input::VietnameseInputProcessor;
+
+/// A text field: its text and the caret, counted in letters as seen on screen
+#[derive(Debug, Default)]
+struct FakeApp {
+    text: String,
+    /// Byte offset of the caret in `text`
+    caret: usize,
+}
+
+impl FakeApp {
+    fn with_text(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            caret: text.len(),
+        }
+    }
+
+    fn before_caret(&self) -> &str {
+        &self.text[..self.caret]
+    }
+
+    fn after_caret(&self) -> &str {
+        &self.text[self.caret..]
+    }
+
+    fn insert(&mut self, text: &str) {
+        self.text.insert_str(self.caret, text);
+        self.caret += text.len();
+    }
+
+    /// Erase `count` letters before the caret, each with its combining marks
+    fn delete_back(&mut self, count: usize) {
+        let clusters = grapheme_clusters(self.before_caret());
+        let erased: usize = clusters.iter().rev().take(count).map(|cluster| cluster.len()).sum();
+        self.text.replace_range(self.caret - erased..self.caret, "");
+        self.caret -= erased;
+    }
+
+    /// Erase the letter after the caret with its combining marks
+    fn delete_forward(&mut self) {
+        if let Some(cluster) = grapheme_clusters(self.after_caret()).first() {
+            let erased = cluster.len();
+            self.text.replace_range(self.caret..self.caret + erased, "");
+        }
+    }
+
+    /// Option+Backspace: the spaces before the caret, then the word before them
+    fn delete_word_back(&mut self) {
+        let before = self.before_caret();
+        let trimmed = before.trim_end_matches(' ');
+        let start = trimmed.rfind(' ').map_or(0, |index| index + 1);
+        self.text.replace_range(start..self.caret, "");
+        self.caret = start;
+    }
+
+    fn move_caret(&mut self, step: isize) {
+        if step < 0 {
+            if let Some(cluster) = grapheme_clusters(self.before_caret()).last() {
+                self.caret -= cluster.len();
+            }
+        } else if let Some(cluster) = grapheme_clusters(self.after_caret()).first() {
+            self.caret += cluster.len();
+        }
+    }
+
+    /// What the application does with a key nobody blocked
+    fn receive(&mut self, key: TypingKey) {
+        match key {
+            TypingKey::Char(ch) | TypingKey::Keypad(ch) => self.insert(&ch.to_string()),
+            TypingKey::Backspace => self.delete_back(1),
+            TypingKey::WordBackspace => self.delete_word_back(),
+            TypingKey::ForwardDelete => self.delete_forward(),
+            TypingKey::Left => self.move_caret(-1),
+            TypingKey::Right => self.move_caret(1),
+            TypingKey::Jump | TypingKey::Shortcut | TypingKey::Undo => {}
+        }
+    }
+
+    fn apply(&mut self, edits: &[EditOp]) {
+        for edit in edits {
+            match edit {
+                EditOp::DeleteBack(count) => self.delete_back(*count),
+                EditOp::Insert(text) => self.insert(text),
+                EditOp::MoveCursor(offset) => {
+                    for _ in 0..offset.unsigned_abs() {
+                        self.move_caret(offset.signum());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The processor and the application, connected the way main.rs connects them
+struct Simulator {
+    processor: VietnameseInputProcessor,
+    app: FakeApp,
+    /// Text is typed decomposed (NFD)
+    decomposed: bool,
+    /// Letters of the word after the caret, after arrows within the word
+    caret_in_word: usize,
+    /// Keys pressed so far, for failure messages
+    log: Vec<TypingKey>,
+}
+
+impl Simulator {
+    fn new(input_type: InputType, settings: AdvancedSettings) -> Self {
+        let mut processor = VietnameseInputProcessor::new(input_type);
+        processor.apply_settings(&settings);
+        Self {
+            processor,
+            app: FakeApp::default(),
+            decomposed: false,
+            caret_in_word: 0,
+            log: Vec::new(),
+        }
+    }
+
+    /// The edits injected for `edits`, as `inject_edits` sends them
+    fn inject(&mut self, edits: &[EditOp]) {
+        let edits: Vec<EditOp> = edits
+            .iter()
+            .map(|edit| match edit {
+                EditOp::Insert(text) if self.decomposed => EditOp::Insert(normalize(text, Normalization::Nfd)),
+                other => other.clone(),
+            })
+            .collect();
+        self.app.apply(&edits);
+    }
+
+    fn press(&mut self, key: TypingKey) {
+        self.log.push(key);
+        let outcome = route_key(&mut self.processor, key);
+        if let Some(pending) = &outcome.pending {
+            self.inject(&EditOp::replacement(0, pending));
+        }
+        self.caret_in_word = match key {
+            TypingKey::Left if !outcome.cursor_moved => self.caret_in_word + 1,
+            TypingKey::Right if !outcome.cursor_moved => self.caret_in_word - 1,
+            TypingKey::ForwardDelete if !self.processor.is_buffer_empty() => self.caret_in_word,
+            _ => 0,
+        };
+        let blocked = match &outcome.result {
+            Some(result) => apply_result(self, result),
+            None => outcome.blocked,
+        };
+        if !blocked {
+            self.app.receive(key);
+        }
+        self.check();
+    }
+
+    fn type_keys(&mut self, keys: &str) {
+        for key in keys.chars() {
+            self.press(match key {
+                '\u{8}' => TypingKey::Backspace,
+                key => TypingKey::Char(key),
+            });
+        }
+    }
+
+    /// Backspace until the processor has no word left
+    fn erase_word(&mut self) {
+        while !self.processor.is_buffer_empty() {
+            self.press(TypingKey::Backspace);
+        }
+    }
+
+    /// The word the processor shows is on screen, with the caret where the
+    /// arrows left it
+    fn check(&self) {
+        if !self.processor.is_tracking() || self.processor.is_buffer_empty() {
+            return;
+        }
+        if !self.processor.get_preedit_text().is_empty() {
+            return;
+        }
+        let after: String = grapheme_clusters(self.app.after_caret())
+            .into_iter()
+            .take(self.caret_in_word)
+            .collect();
+        let around = normalize(&format!("{}{}", self.app.before_caret(), after), Normalization::Nfc);
+        let shown = self.processor.get_display_output();
+        assert!(
+            around.ends_with(&shown),
+            "screen {:?} (caret at {}) does not show {:?} after {:?}",
+            self.app.text, self.app.caret, shown, self.log
+        );
+    }
+
+    /// The whole text on screen, composed
+    fn screen(&self) -> String {
+        normalize(&self.app.text, Normalization::Nfc)
+    }
+}
+
+impl Injector for Simulator {
+    fn inject(&mut self, edits: &[EditOp]) {
+        Simulator::inject(self, edits);
+    }
+}
+
+#[test]
+fn words_and_backspaces_land_on_screen() {
+    let mut simulator = Simulator::new(InputType::Telex, AdvancedSettings::default());
+    simulator.type_keys("tieengs Vieetj");
+    assert_eq!(simulator.screen(), "tiếng Việt");
+
+    // Backspaces erase the composed letters, not the keys typed for them
+    simulator.erase_word();
+    assert_eq!(simulator.screen(), "tiếng ");
+}
+
+#[test]
+fn decomposed_output_counts_letters_with_their_marks() {
+    let mut simulator = Simulator::new(InputType::Telex, AdvancedSettings::default());
+    simulator.decomposed = true;
+    simulator.type_keys("tieengs Vieetj");
+    assert_eq!(simulator.screen(), "tiếng Việt");
+    assert_ne!(simulator.app.text, simulator.screen());
+    simulator.erase_word();
+    assert_eq!(simulator.screen(), "tiếng ");
+}
+
+#[test]
+fn caret_moves_keep_the_text_around_it() {
+    let mut simulator = Simulator::new(InputType::Telex, AdvancedSettings::default());
+    simulator.app = FakeApp::with_text("xin ");
+    simulator.type_keys("chaof");
+    simulator.press(TypingKey::Left);
+    simulator.press(TypingKey::Left);
+    simulator.press(TypingKey::Right);
+    simulator.press(TypingKey::Right);
+    simulator.type_keys(" ban");
+    assert_eq!(simulator.screen(), "xin chào ban");
+
+    simulator.press(TypingKey::Left);
+    simulator.type_keys("j");
+    assert_eq!(simulator.screen(), "xin chào bajn");
+    simulator.press(TypingKey::WordBackspace);
+    assert_eq!(simulator.screen(), "xin chào n");
+}
+
+#[test]
+fn forward_delete_inside_the_word_leaves_it() {
+    let mut simulator = Simulator::new(InputType::Telex, AdvancedSettings::default());
+    simulator.type_keys("chaof");
+    simulator.press(TypingKey::Left);
+    simulator.press(TypingKey::ForwardDelete);
+    assert_eq!(simulator.screen(), "chà");
+    // The keys typed no longer match the word, so a tone key is a letter
+    simulator.type_keys("s");
+    assert_eq!(simulator.screen(), "chàs");
+}
+
+#[test]
+fn hidden_words_are_typed_once_committed() {
+    let mut simulator = Simulator::new(InputType::Telex, AdvancedSettings::default());
+    simulator.processor.set_preview_suppressed(true);
+    simulator.type_keys("Vieetj");
+    assert_eq!(simulator.screen(), "");
+    simulator.press(TypingKey::Char('\r'));
+    assert_eq!(simulator.screen(), "Việt\r");
+}
+
+#[test]
+fn random_sequences_keep_the_word_on_screen() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for input_type in [InputType::Telex, InputType::VNI] {
+        for decomposed in [false, true] {
+            let marks = if input_type == InputType::VNI { "1234567890" } else { "sfrxjwd" };
+            for _ in 0..200 {
+                let mut simulator = Simulator::new(input_type, AdvancedSettings::default());
+                simulator.decomposed = decomposed;
+                simulator.app = FakeApp::with_text("đã ");
+                for _ in 0..1 + rng.below(30) {
+                    let key = match rng.below(25) {
+                        0..=10 => TypingKey::Char(rng.pick("aeiouydnghtc")),
+                        11..=14 => TypingKey::Char(rng.pick(marks)),
+                        15..=16 => TypingKey::Backspace,
+                        17 => TypingKey::Char(' '),
+                        18 => TypingKey::Left,
+                        19 => TypingKey::Right,
+                        20 => TypingKey::WordBackspace,
+                        21 => TypingKey::Jump,
+                        22 => TypingKey::Char('\u{1B}'),
+                        23 => TypingKey::ForwardDelete,
+                        _ => TypingKey::Char('\r'),
+                    };
+                    simulator.press(key);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::core::data_dir::{self, DataKind};
+use crate::core::types::InputType;
+use crate::error::{Result, VKeyError};
+
+/// Running totals of what the processor did, so users can see how they type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypingStats {
+    /// Keys handled by the processor, backspaces included
+    pub keystrokes: u64,
+    /// Words committed while being composed
+    pub words: u64,
+    /// Words put back to the keys typed for them: invalid or English words,
+    /// Escape and the undo hotkey
+    pub restores: u64,
+    /// Characters produced by abbreviations and suggestions beyond the keys
+    /// typed to trigger them
+    pub keystrokes_saved: u64,
+}
+
+/// Length of the period covered by the weekly summary
+pub const WEEK_SECS: u64 = 7 * 24 * 60 * 60;
+
+impl TypingStats {
+    /// Count `produced` characters typed with `typed` keys
+    pub fn add_saved(&mut self, produced: usize, typed: usize) {
+        self.keystrokes_saved += produced.saturating_sub(typed) as u64;
+    }
+
+    /// What was counted since the totals were `earlier`
+    pub fn since(&self, earlier: &TypingStats) -> TypingStats {
+        TypingStats {
+            keystrokes: self.keystrokes.saturating_sub(earlier.keystrokes),
+            words: self.words.saturating_sub(earlier.words),
+            restores: self.restores.saturating_sub(earlier.restores),
+            keystrokes_saved: self.keystrokes_saved.saturating_sub(earlier.keystrokes_saved),
+        }
+    }
+
+    /// Text of the weekly summary notification for the counts of one week
+    pub fn weekly_summary(&self) -> String {
+        format!(
+            "You typed {} Vietnamese words this week; {} auto-restores",
+            group_thousands(self.words),
+            group_thousands(self.restores),
+        )
+    }
+
+    /// Get the default statistics file path (in the logs directory)
+    pub fn get_stats_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Logs, "stats.json")
+    }
+
+    /// Load statistics from the default location, starting from zero if none exist
+    pub fn load_default() -> Result<Self> {
+        let path = Self::get_stats_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load(path.to_str().unwrap_or("stats.json"))
+    }
+
+    /// Load statistics from a JSON file
+    pub fn load(path: &str) -> Result<Self> {
+        let stats_str = std::fs::read_to_string(path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read statistics file '{}': {}", path, e)
+            ))?;
+
+        serde_json::from_str(&stats_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse statistics file '{}': {}", path, e)
+            ))
+    }
+
+    /// Save statistics to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Logs)?;
+        let path = Self::get_stats_path()?;
+        self.save(path.to_str().unwrap_or("stats.json"))
+    }
+
+    /// Save statistics as JSON
+    pub fn save(&self, path: &str) -> Result<()> {
+        let stats_str = serde_json::to_string_pretty(self)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to serialize statistics: {}", e)
+            ))?;
+
+        std::fs::write(path, stats_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to write statistics file '{}': {}", path, e)
+            ))
+    }
+}
+
+/// How often words typed with one input method had to be undone
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityCounts {
+    /// Words committed while being composed
+    pub words: u64,
+    /// Words put back to their keys automatically, as invalid or English
+    pub restores: u64,
+    /// Words put back to their keys with Escape
+    pub escapes: u64,
+    /// Transformed words edited with Backspace, and conversions undone with
+    /// the undo hotkey
+    pub corrections: u64,
+}
+
+impl QualityCounts {
+    /// Percentage of words that were restored, escaped or corrected
+    pub fn failure_rate(&self) -> f64 {
+        if self.words == 0 {
+            return 0.0;
+        }
+        let failures = self.restores + self.escapes + self.corrections;
+        failures as f64 * 100.0 / self.words as f64
+    }
+}
+
+/// Transform failures per input method, to help tune the settings. Only
+/// counts are kept, never the words, and they never leave this Mac.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityMetrics {
+    pub telex: QualityCounts,
+    pub vni: QualityCounts,
+    pub viqr: QualityCounts,
+    pub hybrid: QualityCounts,
+    pub telex_simple: QualityCounts,
+}
+
+impl QualityMetrics {
+    pub fn counts(&self, input_type: InputType) -> &QualityCounts {
+        match input_type {
+            InputType::Telex => &self.telex,
+            InputType::VNI => &self.vni,
+            InputType::VIQR => &self.viqr,
+            InputType::Hybrid => &self.hybrid,
+            InputType::TelexSimple => &self.telex_simple,
+        }
+    }
+
+    pub fn counts_mut(&mut self, input_type: InputType) -> &mut QualityCounts {
+        match input_type {
+            InputType::Telex => &mut self.telex,
+            InputType::VNI => &mut self.vni,
+            InputType::VIQR => &mut self.viqr,
+            InputType::Hybrid => &mut self.hybrid,
+            InputType::TelexSimple => &mut self.telex_simple,
+        }
+    }
+
+    /// Input methods that were used, with their counts
+    pub fn used(&self) -> Vec<(InputType, QualityCounts)> {
+        [InputType::Telex, InputType::VNI, InputType::VIQR, InputType::Hybrid, InputType::TelexSimple]
+            .into_iter()
+            .map(|input_type| (input_type, *self.counts(input_type)))
+            .filter(|(_, counts)| *counts != QualityCounts::default())
+            .collect()
+    }
+
+    /// Get the default quality metrics file path (in the logs directory)
+    pub fn get_quality_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Logs, "quality.json")
+    }
+
+    /// Load quality metrics from the default location, starting from zero if none exist
+    pub fn load_default() -> Result<Self> {
+        let path = Self::get_quality_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load(path.to_str().unwrap_or("quality.json"))
+    }
+
+    /// Load quality metrics from a JSON file
+    pub fn load(path: &str) -> Result<Self> {
+        let quality_str = std::fs::read_to_string(path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read quality metrics file '{}': {}", path, e)
+            ))?;
+
+        serde_json::from_str(&quality_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse quality metrics file '{}': {}", path, e)
+            ))
+    }
+
+    /// Save quality metrics to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Logs)?;
+        let path = Self::get_quality_path()?;
+        self.save(path.to_str().unwrap_or("quality.json"))
+    }
+
+    /// Save quality metrics as JSON
+    pub fn save(&self, path: &str) -> Result<()> {
+        let quality_str = serde_json::to_string_pretty(self)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to serialize quality metrics: {}", e)
+            ))?;
+
+        std::fs::write(path, quality_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to write quality metrics file '{}': {}", path, e)
+            ))
+    }
+}
+
+/// Totals when the current week of the weekly summary began
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeekStart {
+    /// Unix time in seconds
+    pub started_at: u64,
+    pub totals: TypingStats,
+}
+
+impl WeekStart {
+    pub fn new(started_at: u64, totals: TypingStats) -> Self {
+        Self { started_at, totals }
+    }
+
+    /// Whether a full week has passed at `now`
+    pub fn is_over(&self, now: u64) -> bool {
+        now.saturating_sub(self.started_at) >= WEEK_SECS
+    }
+
+    /// Get the default week start file path (in the logs directory)
+    pub fn get_week_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Logs, "week.json")
+    }
+
+    /// Load the week start from the default location, if one was saved
+    pub fn load_default() -> Result<Option<Self>> {
+        let path = Self::get_week_path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let week_str = std::fs::read_to_string(&path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read week file '{}': {}", path.display(), e)
+            ))?;
+
+        serde_json::from_str(&week_str)
+            .map(Some)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse week file '{}': {}", path.display(), e)
+            ))
+    }
+
+    /// Save the week start to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Logs)?;
+        let path = Self::get_week_path()?;
+        let week_str = serde_json::to_string_pretty(self)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to serialize week start: {}", e)
+            ))?;
+
+        std::fs::write(&path, week_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to write week file '{}': {}", path.display(), e)
+            ))
+    }
+}
+
+/// Write `count` with commas between groups of three digits: 12,450
+fn group_thousands(count: u64) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_one_week() {
+        let start = TypingStats { keystrokes: 500, words: 100, restores: 3, keystrokes_saved: 10 };
+        let week = WeekStart::new(1_000, start);
+        assert!(!week.is_over(1_000 + WEEK_SECS - 1));
+        assert!(week.is_over(1_000 + WEEK_SECS));
+
+        let now = TypingStats { keystrokes: 90_000, words: 12_550, restores: 40, keystrokes_saved: 10 };
+        let summary = now.since(&week.totals);
+        assert_eq!(summary.words, 12_450);
+        assert_eq!(
+            summary.weekly_summary(),
+            "You typed 12,450 Vietnamese words this week; 37 auto-restores"
+        );
+        // Totals that were reset never give a negative week
+        assert_eq!(TypingStats::default().since(&start), TypingStats::default());
+    }
+
+    #[test]
+    fn counts_quality_per_input_type() {
+        use crate::core::vietnamese_input::VietnameseInputProcessor;
+
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        for key in "vieetj ".chars() {
+            processor.process_key(key);
+        }
+        // Two backspaces into one transformed word are one correction
+        for key in "ddaa".chars() {
+            processor.process_key(key);
+        }
+        processor.handle_backspace();
+        processor.handle_backspace();
+        processor.process_key('\u{1B}');
+        // Deleting from a word typed as is corrects nothing
+        processor.set_input_type(InputType::VNI);
+        for key in "ab".chars() {
+            processor.process_key(key);
+        }
+        processor.handle_backspace();
+
+        let quality = processor.get_quality();
+        assert_eq!(quality.telex, QualityCounts { words: 1, restores: 0, escapes: 1, corrections: 1 });
+        assert_eq!(quality.vni, QualityCounts::default());
+        assert_eq!(quality.used().len(), 1);
+
+        let counts = QualityCounts { words: 50, restores: 1, escapes: 1, corrections: 3 };
+        assert_eq!(counts.failure_rate(), 10.0);
+    }
+
+    #[test]
+    fn groups_thousands() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1_000), "1,000");
+        assert_eq!(group_thousands(1_234_567), "1,234,567");
+    }
+}
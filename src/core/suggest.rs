@@ -0,0 +1,217 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::core::charset;
+use crate::core::data_dir::{self, DataKind};
+use crate::core::memory::string_bytes;
+use crate::error::{Result, VKeyError};
+
+/// Most words remembered from what the user typed
+pub const MAX_HISTORY_WORDS: usize = 5_000;
+
+/// Most suggestions offered at once, one per digit key
+pub const MAX_SUGGESTIONS: usize = 5;
+
+/// Characters typed before anything is suggested
+pub const MIN_PREFIX_CHARS: usize = 2;
+
+/// How much one use of a word counts against the built-in frequencies
+const HISTORY_WEIGHT: u32 = 50;
+
+/// Common words and phrases with a rough relative frequency
+const COMMON_WORDS: [(&str, u32); 117] = [
+    ("không", 1000), ("người", 950), ("những", 900), ("được", 900), ("nhưng", 700),
+    ("trong", 850), ("chúng ta", 600), ("chúng tôi", 600), ("nhiều", 650), ("thường", 400),
+    ("thời gian", 450), ("thông tin", 450), ("trường hợp", 350), ("phát triển", 400), ("quan trọng", 350),
+    ("cảm ơn", 500), ("xin chào", 300), ("học sinh", 300), ("sinh viên", 300), ("giáo viên", 250),
+    ("công ty", 400), ("công việc", 400), ("làm việc", 350), ("gia đình", 350), ("bây giờ", 400),
+    ("hôm nay", 450), ("ngày mai", 300), ("hôm qua", 300), ("thành phố", 300), ("Hà Nội", 350),
+    ("Sài Gòn", 250), ("Việt Nam", 600), ("tiếng Việt", 300), ("điện thoại", 300), ("máy tính", 300),
+    ("bởi vì", 300), ("tuy nhiên", 350), ("chương trình", 300), ("trình bày", 150), ("chính phủ", 200),
+    ("chính sách", 200), ("kinh tế", 250), ("kinh nghiệm", 200), ("xã hội", 250), ("văn hóa", 200),
+    ("giải quyết", 250), ("hướng dẫn", 250), ("khách hàng", 250), ("sản phẩm", 250), ("dịch vụ", 250),
+    ("thị trường", 200), ("đầu tư", 200), ("quốc gia", 200), ("quốc tế", 200), ("nguyên nhân", 200),
+    ("Nguyễn", 250), ("ngôn ngữ", 200), ("người dùng", 200), ("nghiên cứu", 300), ("nghiêm túc", 150),
+    ("nghỉ ngơi", 120), ("nghĩ", 300), ("nghe", 300), ("nghề nghiệp", 120), ("ngay lập tức", 150),
+    ("luôn luôn", 150), ("đương nhiên", 150), ("đặc biệt", 250), ("điều kiện", 200), ("tiếp tục", 250),
+    ("tất cả", 400), ("thực hiện", 300), ("thực sự", 250), ("trả lời", 250), ("câu hỏi", 250),
+    ("vấn đề", 350), ("ý kiến", 200), ("yêu cầu", 250), ("kết quả", 250), ("khoảng", 250),
+    ("không thể", 350), ("không có", 400), ("chuyện", 250), ("chuyển", 250), ("chúc mừng", 150),
+    ("mọi người", 300), ("bạn bè", 150), ("cuộc sống", 250), ("hạnh phúc", 150), ("tương lai", 150),
+    ("hiện tại", 250), ("hiện nay", 250), ("hoạt động", 250), ("hệ thống", 250), ("ứng dụng", 250),
+    ("phần mềm", 200), ("phương pháp", 200), ("phương tiện", 150), ("quản lý", 250), ("tài liệu", 200),
+    ("tổ chức", 250), ("trách nhiệm", 200), ("chúng", 300), ("cũng", 600), ("đang", 500),
+    ("nhất", 350), ("những người", 200), ("thế giới", 200), ("trước", 350), ("sau khi", 250),
+    ("biết", 400), ("muốn", 350), ("được không", 150), ("nhanh chóng", 120), ("tuyệt vời", 120),
+    ("dễ dàng", 120), ("khó khăn", 150),
+];
+
+/// Proposes completions for the word being typed ("ngh" -> "nghiên cứu"),
+/// from a built-in frequency list and the words the user commits.
+#[derive(Debug, Clone)]
+pub struct SuggestionEngine {
+    /// Built-in words, keyed by their lowercase form
+    frequencies: HashMap<String, (&'static str, u32)>,
+    /// Lowercase words the user committed, with how often
+    history: HashMap<String, u32>,
+}
+
+impl Default for SuggestionEngine {
+    fn default() -> Self {
+        Self {
+            frequencies: COMMON_WORDS
+                .iter()
+                .map(|&(word, frequency)| (word.to_lowercase(), (word, frequency)))
+                .collect(),
+            history: HashMap::new(),
+        }
+    }
+}
+
+impl SuggestionEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember that a word was typed. When the history is full the least
+    /// used word makes room for it.
+    pub fn learn(&mut self, word: &str) {
+        let word = word.trim().to_lowercase();
+        if word.chars().count() <= MIN_PREFIX_CHARS {
+            return;
+        }
+        if !self.history.contains_key(&word) && self.history.len() >= MAX_HISTORY_WORDS {
+            let least_used = self
+                .history
+                .iter()
+                .min_by_key(|(_, count)| **count)
+                .map(|(word, _)| word.clone());
+            if let Some(least_used) = least_used {
+                self.history.remove(&least_used);
+            }
+        }
+        *self.history.entry(word).or_insert(0) += 1;
+    }
+
+    /// Up to `limit` words starting with `prefix`, most likely first.
+    /// Letters typed without marks match any marked form ("nghie" matches
+    /// "nghiên"), and a capitalized prefix capitalizes the suggestions.
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<String> {
+        if prefix.chars().count() < MIN_PREFIX_CHARS {
+            return Vec::new();
+        }
+        let typed = prefix.to_lowercase();
+
+        let built_in = self.frequencies.iter().map(|(key, &(word, frequency))| {
+            let uses = self.history.get(key).copied().unwrap_or(0);
+            (key, word, frequency + uses * HISTORY_WEIGHT)
+        });
+        let learned = self
+            .history
+            .iter()
+            .filter(|(key, _)| !self.frequencies.contains_key(*key))
+            .map(|(key, &uses)| (key, key.as_str(), uses * HISTORY_WEIGHT));
+
+        let mut candidates: Vec<(&str, u32)> = built_in
+            .chain(learned)
+            .filter(|(key, _, _)| **key != typed && matches_prefix(key, &typed))
+            .map(|(_, word, score)| (word, score))
+            .collect();
+        candidates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        let capitalize = prefix.chars().next().is_some_and(char::is_uppercase);
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(word, _)| if capitalize { capitalize_first(word) } else { word.to_string() })
+            .collect()
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Approximate bytes held by the learned words
+    pub fn approx_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.history.keys().map(|word| string_bytes(word) + std::mem::size_of::<u32>()).sum::<usize>()
+    }
+
+    /// Get the default history file path (in the dicts directory)
+    pub fn get_history_path() -> Result<PathBuf> {
+        data_dir::data_file(DataKind::Dicts, "history.json")
+    }
+
+    /// Load the typing history from the default location, starting empty if none exists
+    pub fn load_default() -> Result<Self> {
+        let path = Self::get_history_path()?;
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        Self::load(path.to_str().unwrap_or("history.json"))
+    }
+
+    /// Load the typing history from a JSON object of word counts
+    pub fn load(path: &str) -> Result<Self> {
+        let history_str = std::fs::read_to_string(path)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to read history file '{}': {}", path, e)
+            ))?;
+
+        let history: HashMap<String, u32> = serde_json::from_str(&history_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to parse history file '{}': {}", path, e)
+            ))?;
+
+        let mut engine = Self::new();
+        let mut words: Vec<(String, u32)> = history.into_iter().collect();
+        // Keep the most used words if the file holds more than fits
+        words.sort_by_key(|(_, count)| Reverse(*count));
+        for (word, count) in words.into_iter().take(MAX_HISTORY_WORDS) {
+            let word = word.trim().to_lowercase();
+            if !word.is_empty() && count > 0 {
+                engine.history.insert(word, count);
+            }
+        }
+        Ok(engine)
+    }
+
+    /// Save the typing history to the default location
+    pub fn save_default(&self) -> Result<()> {
+        data_dir::ensure_data_dir(DataKind::Dicts)?;
+        let path = Self::get_history_path()?;
+        self.save(path.to_str().unwrap_or("history.json"))
+    }
+
+    /// Save the typing history as a JSON object of word counts
+    pub fn save(&self, path: &str) -> Result<()> {
+        let history_str = serde_json::to_string_pretty(&self.history)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to serialize history: {}", e)
+            ))?;
+
+        std::fs::write(path, history_str)
+            .map_err(|e| VKeyError::ConfigError(
+                format!("Failed to write history file '{}': {}", path, e)
+            ))
+    }
+}
+
+/// Whether a lowercase word starts with the lowercase typed prefix, where a
+/// letter typed without marks also matches its marked forms
+fn matches_prefix(word: &str, typed: &str) -> bool {
+    let mut letters = word.chars();
+    typed.chars().all(|typed| match letters.next() {
+        Some(letter) => letter == typed || (charset::base_letter(typed) == typed && charset::base_letter(letter) == typed),
+        None => false,
+    })
+}
+
+fn capitalize_first(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
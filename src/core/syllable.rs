@@ -0,0 +1,298 @@
+use crate::core::charset::{self, ToneMark};
+
+/// Where the tone mark goes in the open syllables "oa", "oe" and "uy"
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ToneStyle {
+    /// Traditional placement: hòa, khỏe, thúy
+    Classic,
+    /// Modern placement: hoà, khoẻ, thuý
+    Modern,
+}
+
+/// Initial consonants, longest first so that prefixes match greedily
+const INITIAL_CONSONANTS: [&str; 28] = [
+    "ngh", "ng", "nh", "ch", "gh", "gi", "kh", "ph", "qu", "th", "tr",
+    "b", "c", "d", "đ", "g", "h", "k", "l", "m", "n", "p", "q", "r", "s", "t", "v", "x",
+];
+
+/// Loanword and slang initials (zui, wá, fim), only valid when "z w j f"
+/// are allowed as consonants
+const LOANWORD_INITIALS: [&str; 4] = ["f", "j", "w", "z"];
+
+/// Two-vowel open syllables whose tone position depends on the style
+const STYLE_DEPENDENT_VOWELS: [&str; 3] = ["oa", "oe", "uy"];
+
+/// A word split into initial consonant, vowel cluster and final consonant.
+/// Ranges are character indices into the original word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syllable {
+    /// Lowercase, toneless letters of the word
+    pub letters: Vec<char>,
+    /// Number of characters in the initial consonant
+    pub initial_len: usize,
+    /// Index one past the last vowel
+    pub vowel_end: usize,
+}
+
+impl Syllable {
+    /// Split a word into its parts. Returns `None` for words that contain
+    /// non-letters or no vowel at all.
+    pub fn parse(word: &str) -> Option<Self> {
+        let letters: Vec<char> = word
+            .chars()
+            .map(|c| {
+                let (base, _) = charset::split_tone(c);
+                base.to_lowercase().next().unwrap_or(base)
+            })
+            .collect();
+
+        if letters.is_empty() || !letters.iter().all(|c| c.is_alphabetic()) {
+            return None;
+        }
+
+        let lower: String = letters.iter().collect();
+        let mut initial_len = INITIAL_CONSONANTS
+            .iter()
+            .chain(LOANWORD_INITIALS.iter())
+            .find(|initial| lower.starts_with(*initial))
+            .map(|initial| initial.chars().count())
+            .unwrap_or(0);
+
+        // "gi" and "qu" only act as initials when another vowel follows (gìn = g + ìn)
+        let has_vowel_after = |start: usize| letters[start..].iter().any(|&c| charset::is_vowel(c));
+        if initial_len == 2 && (lower.starts_with("gi") || lower.starts_with("qu")) && !has_vowel_after(2) {
+            initial_len = 1;
+        }
+
+        let vowel_len = letters[initial_len..]
+            .iter()
+            .take_while(|&&c| charset::is_vowel(c))
+            .count();
+        if vowel_len == 0 {
+            return None;
+        }
+
+        Some(Self {
+            letters,
+            initial_len,
+            vowel_end: initial_len + vowel_len,
+        })
+    }
+
+    /// The vowel cluster as a lowercase toneless string
+    pub fn vowels(&self) -> String {
+        self.letters[self.initial_len..self.vowel_end].iter().collect()
+    }
+
+    /// The final consonant as a lowercase string
+    pub fn final_consonant(&self) -> String {
+        self.letters[self.vowel_end..].iter().collect()
+    }
+}
+
+/// Move the tone mark of an open "oa", "oe" or "uy" syllable to the position
+/// required by the given style. Other words are returned unchanged.
+pub fn apply_tone_style(word: &str, style: ToneStyle) -> String {
+    let syllable = match Syllable::parse(word) {
+        Some(syllable) => syllable,
+        None => return word.to_string(),
+    };
+
+    if !syllable.final_consonant().is_empty()
+        || !STYLE_DEPENDENT_VOWELS.contains(&syllable.vowels().as_str())
+    {
+        return word.to_string();
+    }
+
+    let mut chars: Vec<char> = word.chars().collect();
+    let toned: Vec<(usize, ToneMark)> = chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| (i, charset::split_tone(c).1))
+        .filter(|(_, tone)| *tone != ToneMark::None)
+        .collect();
+
+    // Leave words with no tone or ambiguous multiple tones alone
+    let (current, tone) = match toned.as_slice() {
+        [(index, tone)] => (*index, *tone),
+        _ => return word.to_string(),
+    };
+
+    let target = match style {
+        ToneStyle::Classic => syllable.initial_len,
+        ToneStyle::Modern => syllable.initial_len + 1,
+    };
+
+    if current != target {
+        chars[current] = charset::with_tone(chars[current], ToneMark::None);
+        chars[target] = charset::with_tone(chars[target], tone);
+    }
+    chars.into_iter().collect()
+}
+
+/// Move tone keys typed after the first vowel to the end of the keystrokes, so
+/// a tone typed in the middle of a syllable lands where it would if typed last
+/// ("hofa" is read as "hoaf"). Tone keys are never final consonants, so any of
+/// them after a vowel can only be a tone.
+pub fn defer_tone_keys(keys: &str, tone_keys: &[char]) -> String {
+    let mut reordered = String::with_capacity(keys.len());
+    let mut tones = String::new();
+    let mut seen_vowel = false;
+
+    for key in keys.chars() {
+        let lower = key.to_ascii_lowercase();
+        if seen_vowel && tone_keys.contains(&lower) {
+            tones.push(key);
+        } else {
+            seen_vowel |= matches!(lower, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+            reordered.push(key);
+        }
+    }
+
+    reordered.push_str(&tones);
+    reordered
+}
+
+/// Vowel clusters that may appear in a Vietnamese syllable
+const VALID_VOWELS: [&str; 51] = [
+    "a", "ă", "â", "e", "ê", "i", "o", "ô", "ơ", "u", "ư", "y",
+    "ai", "ao", "au", "ay", "âu", "ây", "eo", "êu", "ia", "iê", "iu", "oa", "oă", "oe", "oi",
+    "oo", "ôi", "ơi", "ua", "uâ", "uê", "ui", "uô", "uơ", "uy", "ưa", "ưi", "ươ", "ưu", "yê",
+    "iêu", "oai", "oay", "oeo", "uây", "uôi", "uyê", "ươi", "ươu",
+];
+
+/// Three-letter vowel clusters that are only valid in specific words (khuya, khuỷu, yêu, ngoao)
+const VALID_RARE_VOWELS: [&str; 4] = ["uya", "uyu", "yêu", "oao"];
+
+/// Vowel clusters that must be followed by a final consonant (ăn, ân, tiên, muốn, ...)
+const VOWELS_REQUIRING_FINAL: [&str; 9] = ["ă", "â", "iê", "yê", "uô", "ươ", "uâ", "oă", "uyê"];
+
+/// Final consonants allowed in a Vietnamese syllable
+const VALID_FINALS: [&str; 9] = ["", "c", "ch", "m", "n", "ng", "nh", "p", "t"];
+
+/// Finals that only take the sắc or nặng tone
+const STOP_FINALS: [&str; 4] = ["c", "ch", "p", "t"];
+
+/// Initials that are only written before e, ê, i, y
+const FRONT_VOWEL_INITIALS: [&str; 3] = ["k", "gh", "ngh"];
+
+/// Initials that are never written before e, ê, i
+const BACK_VOWEL_INITIALS: [&str; 3] = ["c", "g", "ng"];
+
+/// Check if a word is a well-formed Vietnamese syllable.
+/// This is a structural check (initial, vowel cluster, final and tone rules),
+/// not a dictionary lookup. `allow_loanword_initials` accepts f, j, w and z
+/// as initial consonants.
+pub fn is_valid_syllable(word: &str, allow_loanword_initials: bool) -> bool {
+    let syllable = match Syllable::parse(word) {
+        Some(syllable) => syllable,
+        None => return false,
+    };
+
+    let initial: String = syllable.letters[..syllable.initial_len].iter().collect();
+    let vowels = syllable.vowels();
+    let final_consonant = syllable.final_consonant();
+
+    // "q" is only valid as part of "qu", and f/j/w/z are not Vietnamese initials
+    if initial.is_empty() && !charset::is_vowel(syllable.letters[0]) {
+        return false;
+    }
+    if initial == "q" {
+        return false;
+    }
+    if !allow_loanword_initials && LOANWORD_INITIALS.contains(&initial.as_str()) {
+        return false;
+    }
+
+    if !VALID_VOWELS.contains(&vowels.as_str()) && !VALID_RARE_VOWELS.contains(&vowels.as_str()) {
+        return false;
+    }
+    if !VALID_FINALS.contains(&final_consonant.as_str()) {
+        return false;
+    }
+    if final_consonant.is_empty() && VOWELS_REQUIRING_FINAL.contains(&vowels.as_str()) {
+        return false;
+    }
+
+    // Clusters ending in a glide (ai, ao, êu, ươi, ...) cannot take a final consonant
+    let ends_with_glide = vowels.chars().count() > 1
+        && matches!(vowels.chars().last(), Some('i' | 'o' | 'u' | 'y'))
+        && !matches!(vowels.as_str(), "oo" | "uy");
+    if ends_with_glide && !final_consonant.is_empty() {
+        return false;
+    }
+
+    let first_vowel = vowels.chars().next().unwrap_or(' ');
+    let is_front_vowel = matches!(first_vowel, 'e' | 'ê' | 'i' | 'y');
+    if FRONT_VOWEL_INITIALS.contains(&initial.as_str()) && !is_front_vowel {
+        return false;
+    }
+    // "gì", "gìn" are the initial "gi" with the i doubling as the vowel
+    let is_gi_with_i = initial == "g" && first_vowel == 'i';
+    if BACK_VOWEL_INITIALS.contains(&initial.as_str()) && matches!(first_vowel, 'e' | 'ê' | 'i') && !is_gi_with_i {
+        return false;
+    }
+
+    // Only one tone per syllable, and stop finals only take sắc or nặng
+    let tones: Vec<ToneMark> = word
+        .chars()
+        .map(|c| charset::split_tone(c).1)
+        .filter(|tone| *tone != ToneMark::None)
+        .collect();
+    if tones.len() > 1 {
+        return false;
+    }
+    if STOP_FINALS.contains(&final_consonant.as_str()) {
+        if let Some(tone) = tones.first() {
+            if !matches!(tone, ToneMark::Acute | ToneMark::DotBelow) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn real_syllables_are_valid() {
+        for word in [
+            "gì", "gìn", "giá", "giếng", "quý", "quyển", "quốc",
+            "nguyễn", "chuyện", "huyền", "thuyền", "khuyên", "truyện", "tuyết", "xuyên",
+            "người", "được", "trường", "hươu", "khuya", "khuỷu", "yêu",
+            "học", "hát", "tiếp", "sách", "kẻ", "ghế", "nghĩ", "việt",
+        ] {
+            assert!(is_valid_syllable(word, false), "{} should be valid", word);
+        }
+    }
+
+    #[test]
+    fn malformed_syllables_are_invalid() {
+        for word in [
+            "expression", "class", "hỏc", "huyê", "tiê", "ka", "ca1", "qa", "kan", "ngi", "gha", "áà",
+        ] {
+            assert!(!is_valid_syllable(word, false), "{} should be invalid", word);
+        }
+        assert!(!is_valid_syllable("zui", false));
+        assert!(is_valid_syllable("zui", true));
+    }
+
+    #[test]
+    fn tone_moves_between_classic_and_modern_placement() {
+        for (classic, modern) in [("hòa", "hoà"), ("thúy", "thuý"), ("khỏe", "khoẻ"), ("Hóa", "Hoá")] {
+            assert_eq!(apply_tone_style(classic, ToneStyle::Modern), modern);
+            assert_eq!(apply_tone_style(modern, ToneStyle::Classic), classic);
+            assert_eq!(apply_tone_style(classic, ToneStyle::Classic), classic);
+            assert_eq!(apply_tone_style(modern, ToneStyle::Modern), modern);
+        }
+
+        // Clusters the styles agree on, and syllables closed by a final
+        for word in ["quý", "giá", "hoàng", "thuyền", "hoa"] {
+            assert_eq!(apply_tone_style(word, ToneStyle::Classic), word);
+            assert_eq!(apply_tone_style(word, ToneStyle::Modern), word);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+use vi::{TELEX, VNI};
+
+use crate::core::rules::VendoredRules;
+
+/// The key rules a syllable is composed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleSet {
+    Telex,
+    Vni,
+}
+
+/// What the last key of a syllable did besides adding to it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransformOutcome {
+    /// The key took a tone off again (Telex "ss", VNI "11")
+    pub tone_mark_removed: bool,
+    /// The key took a letter mark off again (Telex "aaa", VNI "a66")
+    pub letter_modification_removed: bool,
+}
+
+/// Turns the keys of one syllable into its text. `ViEngine` composes every
+/// word through one, so the rules can come from vi-rs or from VKey itself.
+pub trait Transformer: fmt::Debug + Send + Sync {
+    /// Compose `keys`, typed in lowercase, and append the text to `output`
+    fn transform(&self, rules: RuleSet, keys: &str, output: &mut String) -> TransformOutcome;
+}
+
+/// The rules of the vi-rs crate
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ViRs;
+
+impl Transformer for ViRs {
+    fn transform(&self, rules: RuleSet, keys: &str, output: &mut String) -> TransformOutcome {
+        let definition = match rules {
+            RuleSet::Telex => &TELEX,
+            RuleSet::Vni => &VNI,
+        };
+        let result = vi::transform_buffer(definition, keys.chars(), output);
+        TransformOutcome {
+            tone_mark_removed: result.tone_mark_removed,
+            letter_modification_removed: result.letter_modification_removed,
+        }
+    }
+}
+
+/// The transformer new engines use: vi-rs, or VKey's own rules when built
+/// with the `vendored-rules` feature
+pub fn default_transformer() -> &'static dyn Transformer {
+    if cfg!(feature = "vendored-rules") {
+        &VendoredRules
+    } else {
+        &ViRs
+    }
+}
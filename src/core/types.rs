@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::core::corrections::DEFAULT_CORRECTION_RETENTION_DAYS;
+
 /// Represents the input method for Vietnamese text
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InputType {
@@ -10,6 +12,11 @@ pub enum InputType {
     VNI,
     /// VIQR input method (e.g., a^ -> â)
     VIQR,
+    /// Telex letters and VNI digits in the same word (e.g., aa or a6 -> â)
+    Hybrid,
+    /// Telex with only aa/ee/oo/dd and the s/f/r/x/j tones; w, z and the
+    /// bracket keys stay plain letters (e.g. aa -> â, but aw -> aw)
+    TelexSimple,
 }
 
 impl fmt::Display for InputType {
@@ -18,6 +25,8 @@ impl fmt::Display for InputType {
             InputType::Telex => write!(f, "Telex"),
             InputType::VNI => write!(f, "VNI"),
             InputType::VIQR => write!(f, "VIQR"),
+            InputType::Hybrid => write!(f, "Telex + VNI"),
+            InputType::TelexSimple => write!(f, "Telex đơn giản"),
         }
     }
 }
@@ -31,6 +40,10 @@ pub enum Encoding {
     TCVN3,
     /// VNI-Win encoding
     VNIWin,
+    /// VISCII encoding (RFC 1456)
+    VISCII,
+    /// Windows-1258 code page
+    CP1258,
 }
 
 impl fmt::Display for Encoding {
@@ -39,10 +52,105 @@ impl fmt::Display for Encoding {
             Encoding::Unicode => write!(f, "Unicode"),
             Encoding::TCVN3 => write!(f, "TCVN3"),
             Encoding::VNIWin => write!(f, "VNI-Win"),
+            Encoding::VISCII => write!(f, "VISCII"),
+            Encoding::CP1258 => write!(f, "CP1258"),
+        }
+    }
+}
+
+/// What happens when a word grows past the maximum word length
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WordOverflow {
+    /// Commit the composed word and start a new one with the next key
+    Commit,
+    /// Stop composing and pass keys through until the next word boundary
+    PassThrough,
+}
+
+/// What happens to digits typed before the first letter of a word ("3g", "4k")
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumericPrefix {
+    /// Leave the digits on screen and compose the word from the keys after them
+    Strip,
+    /// Compose the digits together with the rest of the word
+    Keep,
+    /// Finish the digits as a word of their own, so undo and the previous word
+    /// see them, and start a new word with the next key
+    Commit,
+}
+
+/// Unicode normalization form of the text typed into applications
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+    /// Precomposed letters (ế is one character), what almost every app expects
+    Nfc,
+    /// Base letters followed by combining marks (ế is e + ̂ + ́), for older
+    /// apps, file systems and editors that expect decomposed text
+    Nfd,
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Normalization::Nfc => write!(f, "NFC"),
+            Normalization::Nfd => write!(f, "NFD"),
         }
     }
 }
 
+/// Something worth a sound while typing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SoundEvent {
+    /// A key changed the word on screen
+    Transform,
+    /// A word was put back to the keys typed for it
+    Restore,
+    /// Vietnamese input was turned on or off
+    Toggle,
+}
+
+/// Set of sounds played for the typing events
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SoundTheme {
+    Classic,
+    Soft,
+    Typewriter,
+}
+
+impl SoundTheme {
+    pub const ALL: [SoundTheme; 3] = [SoundTheme::Classic, SoundTheme::Soft, SoundTheme::Typewriter];
+}
+
+impl fmt::Display for SoundTheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoundTheme::Classic => write!(f, "Cổ điển"),
+            SoundTheme::Soft => write!(f, "Nhẹ nhàng"),
+            SoundTheme::Typewriter => write!(f, "Máy đánh chữ"),
+        }
+    }
+}
+
+/// Default typing sound volume, in percent
+pub const DEFAULT_SOUND_VOLUME: u8 = 40;
+
+/// A key sequence that stops composing the word when typed, so the raw keys
+/// stay on screen (e.g. "ss" to undo a tone and keep the "s")
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestorePattern {
+    pub pattern: String,
+    pub enabled: bool,
+}
+
+/// An extra letter layered on top of the input method: once the composed
+/// word reads `from`, it shows `to` instead, e.g. "oq" for the ŏ of Tây
+/// Nguyên languages, or "eeq" for a letter typed after Telex makes "êq"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomDiacritic {
+    pub from: String,
+    pub to: String,
+}
+
 /// Represents the current input mode
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InputMode {
@@ -63,12 +171,18 @@ impl fmt::Display for InputMode {
 
 /// Configuration for keyboard modifiers
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KeyboardConfig {
     pub shift_enabled: bool,
     pub ctrl_enabled: bool,
     pub cmd_enabled: bool,
     pub home_enabled: bool,
     pub beep_enabled: bool,
+    /// Play a sound when a key transforms or restores a word, or switches input
+    pub typing_sounds: bool,
+    pub sound_theme: SoundTheme,
+    /// Volume of the typing sounds, 0 to 100
+    pub sound_volume: u8,
 }
 
 impl Default for KeyboardConfig {
@@ -79,20 +193,24 @@ impl Default for KeyboardConfig {
             cmd_enabled: true,
             home_enabled: true,
             beep_enabled: false,
+            typing_sounds: false,
+            sound_theme: SoundTheme::Classic,
+            sound_volume: DEFAULT_SOUND_VOLUME,
         }
     }
 }
 
 /// Additional configuration options for the VKey UI
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AdvancedSettings {
-    /// Replace oa, uy with oa, uy
+    /// Place tones the modern way (hoà, thuý) instead of the classic way (hòa, thúy)
     pub replace_oa_uy: bool,
     /// Spell checking
     pub spell_check: bool,
     /// Auto restart on typos
     pub auto_restart_typos: bool,
-    /// Write Vietnamese with capital letters
+    /// Capitalize the first letter of each sentence
     pub vietnamese_capital: bool,
     /// Smart input mode switching
     pub smart_switching: bool,
@@ -100,14 +218,76 @@ pub struct AdvancedSettings {
     pub remember_encoding: bool,
     /// Allow z w j f as silent consonants
     pub allow_silent_consonants: bool,
+    /// Accept the tone key anywhere in the syllable ("hofa" -> "hòa")
+    pub free_tone_placement: bool,
+    /// Telex: type [ ] for ơ ư and { } for Ơ Ư
+    pub telex_bracket_shortcuts: bool,
+    /// Telex: a w with no a, o or u before it types ư; when off it is a plain w
+    pub standalone_w: bool,
+    /// Show completions for the word being typed, picked with Tab or a digit
+    pub word_suggestions: bool,
+    /// Digits from the numeric keypad are typed as numbers and end the word,
+    /// instead of acting as VNI tone and mark keys
+    pub keypad_digits_literal: bool,
+    /// Digits typed before the first letter of a word
+    pub numeric_prefix: NumericPrefix,
     /// Auto-correct spelling mistakes
     pub auto_correct_spelling: bool,
     /// Temporarily disable spell check
     pub temp_disable_spell_check: bool,
     /// Temporarily disable VKey
     pub temp_disable_openkey: bool,
+    /// Expand abbreviations ("Gõ tắt") when a word is committed
+    pub macro_enabled: bool,
+    /// Expand `:name:` to its emoji when a space or punctuation follows
+    pub emoji_shortcodes: bool,
+    /// Escape that puts a word back to its keys also switches to English
+    /// until Vietnamese is turned on again
+    pub escape_to_english: bool,
+    /// Restore the typed keys when a word looks like English
+    pub detect_english: bool,
+    /// Show the word being composed in the menu bar. Off by default, since
+    /// anyone who can see the screen can read it there.
+    pub show_pending_word: bool,
+    /// Wait in milliseconds between erasing a word and typing its replacement,
+    /// for applications that reorder injected events under load
+    pub injection_delay_ms: u64,
+    /// Commit the word being composed after this many seconds without a key.
+    /// 0 disables it.
+    pub idle_commit_secs: u64,
+    /// Post a notification once a week with the words typed and restored
+    pub weekly_summary: bool,
+    /// After a click or cursor jump, take up the word before the caret so the
+    /// next key can still change it. Needs the app to expose its text.
+    pub reseed_from_caret: bool,
+    /// Apply the injection settings of the compatibility catalog to apps
+    /// with known quirks (Excel, JetBrains IDEs, VS Code, Discord)
+    pub strict_compatibility: bool,
+    /// VNI: the keys typed for the standard VNI keys 0-9, in that order, so
+    /// "0543216789" reverses the tones. Each digit appears once.
+    pub vni_key_order: String,
+    /// Punctuation that ends the word being composed. Removing `-` or `'`
+    /// keeps composing through them, for words like "ê-kíp" or "xì-tai".
+    pub word_boundaries: String,
+    /// Keep a local list of words restored automatically, for review
+    pub log_corrections: bool,
+    /// Log how VKey types in each application it switches to, and why
+    pub log_app_decisions: bool,
+    /// Days a logged correction is kept
+    pub correction_retention_days: u64,
 }
 
+/// Punctuation that ends the word being composed. Other punctuation typed
+/// within a word stays in it and splits it into syllables ("ê-kíp").
+pub const DEFAULT_WORD_BOUNDARIES: &str = "()[]{}<>/\\!@#$%^&*-_=+|~`,.;'\"?";
+
+/// Standard VNI keys: 0 clears the tone, 1-5 are the tones, 6 â ê ô,
+/// 7 ơ ư, 8 ă and 9 đ
+pub const VNI_STANDARD_KEYS: &str = "0123456789";
+
+/// Idle timeout used when idle commit is switched on from the settings window
+pub const DEFAULT_IDLE_COMMIT_SECS: u64 = 3;
+
 impl Default for AdvancedSettings {
     fn default() -> Self {
         Self {
@@ -118,9 +298,30 @@ impl Default for AdvancedSettings {
             smart_switching: true,
             remember_encoding: true,
             allow_silent_consonants: false,
+            free_tone_placement: false,
+            telex_bracket_shortcuts: false,
+            standalone_w: true,
+            word_suggestions: false,
+            keypad_digits_literal: false,
+            numeric_prefix: NumericPrefix::Strip,
             auto_correct_spelling: false,
             temp_disable_spell_check: false,
             temp_disable_openkey: false,
+            macro_enabled: true,
+            emoji_shortcodes: false,
+            escape_to_english: false,
+            show_pending_word: false,
+            detect_english: false,
+            injection_delay_ms: 0,
+            idle_commit_secs: 0,
+            weekly_summary: false,
+            reseed_from_caret: false,
+            strict_compatibility: false,
+            vni_key_order: VNI_STANDARD_KEYS.to_string(),
+            word_boundaries: DEFAULT_WORD_BOUNDARIES.to_string(),
+            log_corrections: false,
+            log_app_decisions: false,
+            correction_retention_days: DEFAULT_CORRECTION_RETENTION_DAYS,
         }
     }
 } 
\ No newline at end of file
@@ -1,43 +1,507 @@
-use vi::{VNI, TELEX, TransformResult};
-use crate::core::types::InputType;
+use crate::core::case;
+use crate::core::charset;
+use crate::core::edit::{apply_edits, EditOp};
+use crate::core::dictionary::{UserDictionary, MAX_WORDS};
+use crate::core::emoji::EmojiTable;
+use crate::core::encoding;
+use crate::core::engine::{EngineOptions, InputMethodEngine, ViEngine, VniKeyMap};
+use crate::core::english;
+use crate::core::habits::{LearnedRestore, RestoreHabits, MAX_LEARNED_WORDS};
+use crate::core::macros::{MacroTable, MAX_MACROS};
+use crate::core::memory::{string_bytes, MemoryReport};
+use crate::core::placeholders::{self, PlaceholderSource, UtcClock};
+use crate::core::reverse;
+use crate::core::scheme::{SchemeEngine, TransformScheme};
+use crate::core::stats::{QualityMetrics, TypingStats};
+use crate::core::suggest::{SuggestionEngine, MAX_HISTORY_WORDS, MAX_SUGGESTIONS};
+use crate::core::syllable::{self, ToneStyle};
+use crate::core::config::{DEFAULT_MAX_WORD_LENGTH, DEFAULT_RESTORE_PATTERNS};
+use crate::core::corrections::{Correction, CorrectionLog, CorrectionReason, MAX_LOGGED_CORRECTIONS};
+use crate::core::types::{AdvancedSettings, CustomDiacritic, Encoding, InputType, Normalization, NumericPrefix, WordOverflow, DEFAULT_WORD_BOUNDARIES};
+use std::cmp::Reverse;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+// The std clock panics on wasm32-unknown-unknown; the browser build reads the JavaScript one
+#[cfg(target_arch = "wasm32")]
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Typed before a word, turns off spell checking and restoring for that word
+pub const SPELL_CHECK_BYPASS_KEY: char = '^';
+
+/// A committed word whose conversion can still be undone
+#[derive(Debug, Clone)]
+struct Conversion {
+    /// Keys the user typed
+    raw: String,
+    /// What was typed on screen instead, in the output encoding
+    shown: String,
+    /// Key that committed the word, typed right after it
+    boundary: char,
+}
+
+/// Point-in-time view of a processor's word state
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BufferSnapshot {
+    /// Keys typed for the current word
+    pub typing_buffer: String,
+    /// The current word after transformation
+    pub display_buffer: String,
+    /// Whether keys are still being composed into the word
+    pub is_tracking: bool,
+    /// Completions offered for the word, in the order of their digit keys
+    pub suggestions: Vec<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct VietnameseInputProcessor {
-    /// Raw input buffer (what the user actually typed)
-    typing_buffer: String,
-    /// Display buffer (what's currently shown on screen after transformation)
+    /// Composes the keys of the current word
+    engine: Box<dyn InputMethodEngine>,
+    /// The engine's output with each letter in the case it was typed
     display_buffer: String,
-    input_type: InputType,
+    /// Input method and word options, shared with the engine
+    options: EngineOptions,
+    /// Output encoding applied to text handed back to the injection layer
+    encoding: Encoding,
+    /// Restore the raw keystrokes when a committed word is not valid Vietnamese
+    auto_restore_invalid: bool,
+    /// Restore the raw keystrokes when a committed word looks like English
+    detect_english: bool,
+    /// Type a word as English as soon as its keys can only be English
+    smart_switching: bool,
+    /// The word is being typed as English; Vietnamese resumes with the next word
+    english_word: bool,
+    /// `SPELL_CHECK_BYPASS_KEY` before a word turns off checks for it
+    spell_check_bypass: bool,
+    /// The bypass key was typed; the current or next word is not checked
+    word_unchecked: bool,
+    /// Telex: a w that has no vowel to modify types ư
+    standalone_w: bool,
+    /// Abbreviations expanded when a word is committed
+    macros: MacroTable,
+    macros_enabled: bool,
+    /// Clock and clipboard for the placeholders in macro expansions
+    placeholders: Arc<dyn PlaceholderSource>,
+    /// Emoji typed for `:shortcode:` when the word after the colon is committed
+    emoji: EmojiTable,
+    emoji_shortcodes: bool,
+    /// The key typed before the current one
+    previous_key: Option<char>,
+    /// The current word was begun right after a colon
+    word_after_colon: bool,
+    /// Words accepted as typed, never flagged or restored
+    dictionary: UserDictionary,
+    /// Completions for the word being typed, learning from committed words
+    suggester: SuggestionEngine,
+    word_suggestions: bool,
+    /// Keypad digits end the word instead of acting as tone and mark keys
+    keypad_digits_literal: bool,
+    /// Punctuation that ends the word; other punctuation joins its syllables
+    word_boundaries: String,
+    /// Digits typed before the first letter of a word
+    numeric_prefix: NumericPrefix,
+    /// What is offered for the current word, first entry picked by Tab
+    suggestions: Vec<String>,
+    /// Totals since statistics were first collected
+    stats: TypingStats,
+    /// Restores and corrections per input method
+    quality: QualityMetrics,
+    /// Words the user keeps transformed or puts back to their keys
+    habits: RestoreHabits,
+    /// Words restored automatically, recorded only while `log_corrections` is on
+    corrections: CorrectionLog,
+    log_corrections: bool,
+    /// Bundle path of the focused application, for the correction log
+    active_app: String,
+    /// A transformed letter of the current word was deleted; counted once per word
+    word_corrected: bool,
+    /// Swallow keystrokes and only type the finished word when it is committed
+    suppress_preview: bool,
+    /// Upper-case the first letter of a sentence
+    auto_capitalize: bool,
+    /// A sentence-ending mark was typed and may be followed by whitespace
+    sentence_end_pending: bool,
+    /// The next word starts a sentence
+    capitalize_next: bool,
+    /// The current word's first letter was capitalized automatically
+    auto_capitalized: bool,
     /// Track if we should continue processing characters
     should_track: bool,
+    /// Characters between the cursor and the end of the word on screen, after
+    /// arrow keys moved the cursor back into it
+    cursor_offset: usize,
+    /// The keys typed for the word are shown in its place until `end_peek`
+    peeking: bool,
     /// Previous word for restoration purposes
     previous_word: String,
+    /// The word committed last, while nothing has been typed after it
+    last_conversion: Option<Conversion>,
     /// Maximum word length to prevent infinite growth
     max_word_length: usize,
+    /// What to do with keys past the maximum word length
+    word_overflow: WordOverflow,
+    /// Lowercase key sequences that stop composing the word
+    restore_patterns: Vec<String>,
+    /// How long a word may sit untouched before it counts as finished
+    idle_timeout: Option<Duration>,
+    /// When the last key of the current word was processed
+    last_key_at: Option<Instant>,
+    /// Language pack composing words instead of the Vietnamese input method
+    scheme_name: Option<String>,
+    /// Extra letters substituted into the composed word, longest `from` first
+    custom_diacritics: Vec<CustomDiacritic>,
 }
 
 impl VietnameseInputProcessor {
     pub fn new(input_type: InputType) -> Self {
+        let options = EngineOptions::new(input_type);
         Self {
-            typing_buffer: String::new(),
+            engine: Box::new(ViEngine::new(options)),
             display_buffer: String::new(),
-            input_type,
+            options,
+            encoding: Encoding::Unicode,
+            auto_restore_invalid: false,
+            detect_english: false,
+            smart_switching: false,
+            english_word: false,
+            spell_check_bypass: false,
+            word_unchecked: false,
+            standalone_w: true,
+            macros: MacroTable::new(),
+            macros_enabled: true,
+            placeholders: Arc::new(UtcClock),
+            emoji: EmojiTable::builtin(),
+            emoji_shortcodes: false,
+            previous_key: None,
+            word_after_colon: false,
+            dictionary: UserDictionary::new(),
+            suggester: SuggestionEngine::new(),
+            word_suggestions: false,
+            keypad_digits_literal: false,
+            word_boundaries: DEFAULT_WORD_BOUNDARIES.to_string(),
+            numeric_prefix: NumericPrefix::Strip,
+            suggestions: Vec::new(),
+            stats: TypingStats::default(),
+            quality: QualityMetrics::default(),
+            habits: RestoreHabits::new(),
+            corrections: CorrectionLog::new(),
+            log_corrections: false,
+            active_app: String::new(),
+            word_corrected: false,
+            suppress_preview: false,
+            auto_capitalize: false,
+            sentence_end_pending: false,
+            capitalize_next: false,
+            auto_capitalized: false,
             should_track: true,
+            cursor_offset: 0,
+            peeking: false,
             previous_word: String::new(),
-            max_word_length: 10, // Maximum possible word length
+            last_conversion: None,
+            max_word_length: DEFAULT_MAX_WORD_LENGTH,
+            word_overflow: WordOverflow::Commit,
+            restore_patterns: DEFAULT_RESTORE_PATTERNS.iter().map(|pattern| pattern.to_string()).collect(),
+            idle_timeout: None,
+            last_key_at: None,
+            scheme_name: None,
+            custom_diacritics: Vec::new(),
         }
     }
 
     pub fn set_input_type(&mut self, input_type: InputType) {
-        self.input_type = input_type;
+        self.options.input_type = input_type;
+        self.engine.set_options(self.options);
         // Clear buffers when switching input types
         self.clear_buffer();
     }
 
+    pub fn get_input_type(&self) -> InputType {
+        self.options.input_type
+    }
+
+    /// Compose words with another engine from now on. The word being typed is dropped.
+    pub fn set_engine(&mut self, mut engine: Box<dyn InputMethodEngine>) {
+        engine.set_options(self.options);
+        self.engine = engine;
+        self.clear_buffer();
+    }
+
+    pub fn get_engine(&self) -> &dyn InputMethodEngine {
+        self.engine.as_ref()
+    }
+
+    /// Compose words with a language pack instead of the Vietnamese input
+    /// method, or with the input method again for None. Nothing changes if
+    /// the same scheme is already in use; otherwise the word being typed is dropped.
+    pub fn set_transform_scheme(&mut self, scheme: Option<Arc<dyn TransformScheme>>) {
+        let name = scheme.as_ref().map(|scheme| scheme.name().to_string());
+        if name == self.scheme_name {
+            return;
+        }
+        self.scheme_name = name;
+        match scheme {
+            Some(scheme) => self.set_engine(Box::new(SchemeEngine::new(scheme))),
+            None => self.set_engine(Box::new(ViEngine::new(self.options))),
+        }
+    }
+
+    /// Name of the language pack in use, None for Vietnamese
+    pub fn get_transform_scheme(&self) -> Option<&str> {
+        self.scheme_name.as_deref()
+    }
+
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+        // Text already on screen was written in the old encoding
+        self.clear_buffer();
+    }
+
+    pub fn get_encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn set_tone_style(&mut self, tone_style: ToneStyle) {
+        self.options.tone_style = tone_style;
+        self.engine.set_options(self.options);
+    }
+
+    /// Apply the advanced settings that affect how words are composed
+    pub fn apply_settings(&mut self, settings: &AdvancedSettings) {
+        self.set_tone_style(if settings.replace_oa_uy {
+            ToneStyle::Modern
+        } else {
+            ToneStyle::Classic
+        });
+        self.auto_restore_invalid = settings.spell_check && settings.auto_restart_typos;
+        self.detect_english = settings.detect_english;
+        self.smart_switching = settings.smart_switching;
+        self.spell_check_bypass = settings.temp_disable_spell_check;
+        self.options.allow_silent_consonants = settings.allow_silent_consonants;
+        self.options.free_tone_placement = settings.free_tone_placement;
+        self.options.bracket_shortcuts = settings.telex_bracket_shortcuts;
+        self.options.vni_keys = VniKeyMap::parse(&settings.vni_key_order).unwrap_or_default();
+        self.engine.set_options(self.options);
+        self.standalone_w = settings.standalone_w;
+        self.word_suggestions = settings.word_suggestions;
+        self.keypad_digits_literal = settings.keypad_digits_literal;
+        self.word_boundaries = settings.word_boundaries.clone();
+        self.numeric_prefix = settings.numeric_prefix;
+        self.log_corrections = settings.log_corrections;
+        if !self.log_corrections {
+            self.corrections.clear();
+        }
+        if !self.word_suggestions {
+            self.suggestions.clear();
+        }
+        self.macros_enabled = settings.macro_enabled;
+        self.emoji_shortcodes = settings.emoji_shortcodes;
+        self.auto_capitalize = settings.vietnamese_capital;
+        self.idle_timeout = match settings.idle_commit_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+    }
+
+    /// Whether the word being composed has been left alone longer than the idle
+    /// timeout, so a stray key should no longer modify it
+    pub fn is_idle(&self) -> bool {
+        match (self.idle_timeout, self.last_key_at) {
+            (Some(timeout), Some(last_key_at)) => !self.engine.keys().is_empty() && last_key_at.elapsed() >= timeout,
+            _ => false,
+        }
+    }
+
+    /// Set the longest word that is composed and what happens to keys beyond it
+    pub fn set_word_limit(&mut self, max_word_length: usize, word_overflow: WordOverflow) {
+        self.max_word_length = max_word_length;
+        self.word_overflow = word_overflow;
+    }
+
+    /// Set the key sequences that stop composing a word
+    pub fn set_restore_patterns(&mut self, patterns: Vec<String>) {
+        self.restore_patterns = patterns
+            .into_iter()
+            .map(|pattern| pattern.to_ascii_lowercase())
+            .filter(|pattern| !pattern.is_empty())
+            .collect();
+    }
+
+    /// Substitute extra letters into words once the input method composed
+    /// them. Takes effect from the next key.
+    pub fn set_custom_diacritics(&mut self, mut mappings: Vec<CustomDiacritic>) {
+        mappings.retain(|mapping| !mapping.from.is_empty());
+        mappings.sort_by_key(|mapping| Reverse(mapping.from.chars().count()));
+        self.custom_diacritics = mappings;
+    }
+
+    /// Hide words while they are composed. Text composed for the previous
+    /// application is dropped when the mode changes.
+    pub fn set_preview_suppressed(&mut self, suppress_preview: bool) {
+        if self.suppress_preview != suppress_preview {
+            self.suppress_preview = suppress_preview;
+            self.clear_buffer();
+        }
+    }
+
+    /// Whether the word being composed is hidden from the screen
+    fn is_preview_hidden(&self) -> bool {
+        self.suppress_preview && self.should_track
+    }
+
+    /// The composed word that has not been typed yet, in the output encoding.
+    /// Empty unless preview is suppressed.
+    pub fn get_preedit_text(&self) -> String {
+        if !self.is_preview_hidden() {
+            return String::new();
+        }
+        encoding::to_output(&self.display_buffer, self.encoding)
+    }
+
+    /// Take the composed word that has not been shown yet, committing it.
+    /// Used when the word ends without a key that commits it (Enter, arrows, shortcuts).
+    pub fn take_pending_output(&mut self) -> Option<String> {
+        let pending = self.get_pending_output()?;
+        self.new_word();
+        Some(pending)
+    }
+
+    /// The composed word that has not been shown yet, if any
+    fn get_pending_output(&self) -> Option<String> {
+        if !self.is_preview_hidden() || self.engine.keys().is_empty() {
+            return None;
+        }
+        Some(self.get_preedit_text())
+    }
+
+    pub fn set_macros(&mut self, macros: MacroTable) {
+        self.macros = macros;
+    }
+
+    pub fn get_macros(&self) -> &MacroTable {
+        &self.macros
+    }
+
+    /// Where `{date}`, `{time}` and `{clipboard}` in macro expansions are read from
+    pub fn set_placeholder_source(&mut self, source: Arc<dyn PlaceholderSource>) {
+        self.placeholders = source;
+    }
+
+    pub fn set_emoji(&mut self, emoji: EmojiTable) {
+        self.emoji = emoji;
+    }
+
+    pub fn get_emoji(&self) -> &EmojiTable {
+        &self.emoji
+    }
+
+    pub fn set_dictionary(&mut self, dictionary: UserDictionary) {
+        self.dictionary = dictionary;
+    }
+
+    pub fn get_dictionary(&self) -> &UserDictionary {
+        &self.dictionary
+    }
+
+    pub fn get_dictionary_mut(&mut self) -> &mut UserDictionary {
+        &mut self.dictionary
+    }
+
+    /// Recompute the display buffer from the engine, in the case the keys were typed
+    fn refresh_display(&mut self) {
+        let output = self.substitute_custom_diacritics(self.engine.output());
+        self.display_buffer = case::apply_case(self.engine.keys(), &output);
+    }
+
+    /// The composed word with the custom diacritics substituted, matched
+    /// left to right against the longest `from`
+    fn substitute_custom_diacritics(&self, output: &str) -> String {
+        if self.custom_diacritics.is_empty() {
+            return output.to_string();
+        }
+        let mut substituted = String::with_capacity(output.len());
+        let mut rest = output;
+        while let Some(ch) = rest.chars().next() {
+            match self.custom_diacritics.iter().find(|mapping| rest.starts_with(mapping.from.as_str())) {
+                Some(mapping) => {
+                    substituted.push_str(&mapping.to);
+                    rest = &rest[mapping.from.len()..];
+                }
+                None => {
+                    substituted.push(ch);
+                    rest = &rest[ch.len_utf8()..];
+                }
+            }
+        }
+        substituted
+    }
+
+    /// The display buffer as it appears on screen in the output encoding
+    pub(crate) fn get_display_output(&self) -> String {
+        if self.is_preview_hidden() {
+            return String::new();
+        }
+        encoding::to_output(&self.display_buffer, self.encoding)
+    }
+
+    /// Number of on-screen characters currently occupied by the display buffer
+    pub fn get_display_output_length(&self) -> usize {
+        if self.is_preview_hidden() {
+            return 0;
+        }
+        encoding::output_len(&self.display_buffer, self.encoding)
+    }
+
     pub fn process_key(&mut self, key: char) -> ProcessingResult {
+        self.stats.keystrokes += 1;
+        self.leave_word_if_cursor_moved();
+        if let Some(result) = self.handle_suggestion_key(key) {
+            return result;
+        }
+        let result = self.compose_key(key);
+        if matches!(result, ProcessingResult::RestoreText { .. }) {
+            self.count_restore(key);
+        }
+        self.refresh_suggestions();
+        result
+    }
+
+    /// Count a word put back to its keys when `key` was typed
+    fn count_restore(&mut self, key: char) {
+        self.stats.restores += 1;
+        let counts = self.quality.counts_mut(self.options.input_type);
+        if key == '\u{1B}' {
+            counts.escapes += 1;
+        } else {
+            counts.restores += 1;
+        }
+    }
+
+    fn compose_key(&mut self, key: char) -> ProcessingResult {
+        self.last_key_at = Some(Instant::now());
+        // Anything typed after a committed word moves the cursor away from it
+        self.last_conversion = None;
+        self.track_sentence_boundary(key);
+        let previous_key = self.previous_key.replace(key);
+
+        // The bypass key waits for a word; without one it is typed after all
+        if self.word_unchecked && self.engine.keys().is_empty() && !key.is_ascii_alphanumeric() && key != '\u{8}' {
+            self.word_unchecked = false;
+            if key == SPELL_CHECK_BYPASS_KEY {
+                return ProcessingResult::PassThrough(key);
+            }
+            return match self.compose_key(key) {
+                ProcessingResult::PassThrough(ch) => ProcessingResult::ProcessedText {
+                    text: format!("{}{}", SPELL_CHECK_BYPASS_KEY, ch),
+                    buffer_length: 0,
+                },
+                result => result,
+            };
+        }
+
         // Handle special keys
         match key {
-            '\u{8}' => return self.handle_backspace(), // Backspace
+            '\u{8}' => return self.delete_key(), // Backspace
             '\r' | '\n' => return self.handle_enter(),   // Enter
             ' ' => return self.handle_space(),           // Space
             '\t' => return self.handle_tab(),            // Tab
@@ -55,55 +519,68 @@ impl VietnameseInputProcessor {
             return ProcessingResult::PassThrough(key);
         }
 
-        // Handle special characters that should stop tracking
-        if "()[]{}<>/\\!@#$%^&*-_=+|~`,.;'\"?".contains(key) {
-            self.new_word();
-            return ProcessingResult::PassThrough(key);
+        if key == SPELL_CHECK_BYPASS_KEY && self.spell_check_bypass && self.engine.keys().is_empty() {
+            self.word_unchecked = true;
+            return ProcessingResult::consumed();
         }
 
-        // Remove numeric prefix if present
-        if let Some(first_char) = self.typing_buffer.chars().next() {
-            if first_char.is_numeric() {
-                self.typing_buffer.remove(0);
-                if !self.display_buffer.is_empty() {
-                    self.display_buffer.remove(0);
-                }
-            }
+        // Word boundaries end the word; other punctuation only joins syllables
+        // of a word already begun, so it never starts one
+        if key.is_ascii_punctuation()
+            && !self.is_bracket_shortcut(key)
+            && (self.word_boundaries.contains(key) || self.engine.keys().is_empty())
+        {
+            return self.end_word(key);
         }
 
-        // Check max word length
-        if self.typing_buffer.len() >= self.max_word_length {
-            self.new_word();
+        // A w with nothing to modify is an English letter, so the word is not composed
+        if self.is_literal_w(key) {
+            if let Some(pending) = self.take_pending_output() {
+                self.stop_tracking();
+                return ProcessingResult::ProcessedText {
+                    text: format!("{}{}", pending, key),
+                    buffer_length: 0,
+                };
+            }
+            self.stop_tracking();
             return ProcessingResult::PassThrough(key);
         }
 
-        // Store the current display buffer length for backspace counting
-        let previous_display_length = self.display_buffer.chars().count();
-        
-        // Add character to typing buffer
-        self.typing_buffer.push(key);
-
-        // Transform the buffer using vi-rs
-        let mut result = String::new();
-        let transform_result = match self.input_type {
-            InputType::Telex => {
-                vi::transform_buffer(&TELEX, self.typing_buffer.chars(), &mut result)
-            }
-            InputType::VNI => {
-                vi::transform_buffer(&VNI, self.typing_buffer.chars(), &mut result)
+        // Check max word length
+        if self.engine.keys().chars().count() >= self.max_word_length {
+            // A hidden word is shown as is and the rest of it passes through
+            if let Some(pending) = self.take_pending_output() {
+                self.stop_tracking();
+                return ProcessingResult::ProcessedText {
+                    text: format!("{}{}", pending, key),
+                    buffer_length: 0,
+                };
             }
-            InputType::VIQR => {
-                // VIQR is not supported by vi-rs, fallback to raw input
-                result = self.typing_buffer.clone();
-                TransformResult::default()
+            match self.word_overflow {
+                WordOverflow::Commit => self.new_word(),
+                WordOverflow::PassThrough => {
+                    self.stop_tracking();
+                    return ProcessingResult::PassThrough(key);
+                }
             }
-        };
+        }
+
+        let unshown_prefix = self.split_numeric_prefix(key);
 
-        // Update display buffer
-        self.display_buffer = result.clone();
+        // Store what is currently on screen to diff against
+        let previous_output = self.get_display_output();
+        
+        if self.engine.keys().is_empty() {
+            self.word_after_colon = previous_key == Some(':');
+        }
+
+        // Add character to typing buffer
+        let composed_key = self.capitalize_sentence_start(key);
+        let effect = self.engine.process_key(composed_key);
+        self.refresh_display();
 
-                    // Check if transformation removed letters or tone marks
-        if transform_result.letter_modification_removed || transform_result.tone_mark_removed {
+        // Check if transformation removed letters or tone marks
+        if effect.mark_removed {
             self.stop_tracking();
         }
 
@@ -112,49 +589,256 @@ impl VietnameseInputProcessor {
             self.stop_tracking();
         }
 
-        ProcessingResult::ProcessedText {
-            text: result,
-            buffer_length: previous_display_length,
+        // With smart switching, a word whose keys can only be English is put
+        // back to its keys and the rest of it passes through
+        if self.smart_switching && self.should_track && !self.word_unchecked && english::starts_english(self.engine.keys()) {
+            self.english_word = true;
+            self.display_buffer = self.engine.keys().to_string();
+            self.stop_tracking();
+        }
+        let result = self.display_buffer.clone();
+
+        // Nothing is shown until the word is committed, except digits split off it
+        if self.is_preview_hidden() {
+            return match unshown_prefix {
+                Some(digits) => ProcessingResult::ProcessedText {
+                    text: digits,
+                    buffer_length: 0,
+                },
+                None => ProcessingResult::consumed(),
+            };
+        }
+
+        let current = format!("{}{}", unshown_prefix.unwrap_or_default(), encoding::to_output(&result, self.encoding));
+        replacement(&previous_output, &current, key)
+    }
+
+    /// Take the digits typed so far out of the word when `key` is the first
+    /// key after them that is not a digit, as set by `numeric_prefix`. The
+    /// digits stay on screen; returns them when the word was hidden, since
+    /// they still have to be typed.
+    fn split_numeric_prefix(&mut self, key: char) -> Option<String> {
+        let keys = self.engine.keys();
+        if key.is_ascii_digit() || keys.is_empty() || !keys.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let unshown = self.get_pending_output();
+        match self.numeric_prefix {
+            NumericPrefix::Keep => return None,
+            NumericPrefix::Strip => self.clear_buffer(),
+            NumericPrefix::Commit => self.new_word(),
         }
+        unshown
+    }
+
+    /// A key from the numeric keypad. Keypad digits are typed as numbers and
+    /// end the word when they are set to be literal; otherwise every key is
+    /// handled like the same key on the main keyboard.
+    pub fn process_keypad_key(&mut self, key: char) -> ProcessingResult {
+        if !self.keypad_digits_literal || !key.is_ascii_digit() {
+            return self.process_key(key);
+        }
+        self.stats.keystrokes += 1;
+        self.leave_word_if_cursor_moved();
+        self.last_key_at = Some(Instant::now());
+        self.last_conversion = None;
+        self.track_sentence_boundary(key);
+        let result = if self.should_track && !self.engine.keys().is_empty() {
+            self.end_word(key)
+        } else {
+            self.new_word();
+            ProcessingResult::PassThrough(key)
+        };
+        if matches!(result, ProcessingResult::RestoreText { .. }) {
+            self.count_restore(key);
+        }
+        self.refresh_suggestions();
+        result
+    }
+
+    /// Commit the word because `key`, which is typed after it, cannot be part of it
+    fn end_word(&mut self, key: char) -> ProcessingResult {
+        if let Some(expanded) = self.expand_shortcode(key) {
+            return expanded;
+        }
+        if let Some(expanded) = self.expand_macro(key) {
+            return expanded;
+        }
+        if let Some(restore) = self.restore_invalid_word(key) {
+            return restore;
+        }
+        if let Some(pending) = self.get_pending_output() {
+            self.commit_converted(&pending, key);
+            return ProcessingResult::ProcessedText {
+                text: format!("{}{}", pending, key),
+                buffer_length: 0,
+            };
+        }
+        if self.should_track {
+            let shown = self.get_display_output();
+            self.commit_converted(&shown, key);
+        } else {
+            self.new_word();
+        }
+        ProcessingResult::PassThrough(key)
     }
 
     pub fn handle_backspace(&mut self) -> ProcessingResult {
-        if self.typing_buffer.is_empty() {
+        self.stats.keystrokes += 1;
+        self.leave_word_if_cursor_moved();
+        let result = self.delete_key();
+        self.refresh_suggestions();
+        result
+    }
+
+    fn delete_key(&mut self) -> ProcessingResult {
+        self.last_conversion = None;
+        self.previous_key = Some('\u{8}');
+        // The bypass key was never shown, so deleting it only takes it back
+        if self.word_unchecked && self.engine.keys().is_empty() {
+            self.word_unchecked = false;
+            return ProcessingResult::consumed();
+        }
+        if self.engine.keys().is_empty() {
+            // Deleting outside a word leaves the sentence position unknown
+            self.sentence_end_pending = false;
+            self.capitalize_next = false;
             return ProcessingResult::PassThrough('\u{8}');
         }
 
-        // Store the current displayed length before modifying buffer
-        let previous_display_length = self.display_buffer.chars().count();
+        // Deleting into a word the engine changed means it got the word wrong
+        if !self.word_corrected && !self.display_buffer.eq_ignore_ascii_case(self.engine.keys()) {
+            self.word_corrected = true;
+            self.quality.counts_mut(self.options.input_type).corrections += 1;
+        }
+
+        // Store what is currently on screen before modifying buffer
+        let previous_output = self.get_display_output();
         
         // Remove last character from typing buffer
-        self.typing_buffer.pop();
+        self.engine.handle_backspace();
+        self.refresh_display();
 
-        if self.typing_buffer.is_empty() {
+        if self.engine.keys().is_empty() {
+            // Deleting an automatic capital means the user wants it lowercase,
+            // so leave the retyped letter alone
+            if self.auto_capitalized {
+                self.auto_capitalized = false;
+                self.capitalize_next = false;
+            }
+            // A hidden word was never on screen, so the backspace must not reach it
+            let was_hidden = self.is_preview_hidden();
             self.clear_buffer();
+            if was_hidden {
+                return ProcessingResult::consumed();
+            }
             return ProcessingResult::ClearAndPassBackspace;
         }
 
-        // Re-transform the remaining buffer
-        let mut result = String::new();
-        match self.input_type {
-            InputType::Telex => {
-                vi::transform_buffer(&TELEX, self.typing_buffer.chars(), &mut result);
-            }
-            InputType::VNI => {
-                vi::transform_buffer(&VNI, self.typing_buffer.chars(), &mut result);
-            }
-            InputType::VIQR => {
-                result = self.typing_buffer.clone();
-            }
+        let result = self.display_buffer.clone();
+
+        if self.is_preview_hidden() {
+            return ProcessingResult::consumed();
         }
 
-        // Update display buffer
-        self.display_buffer = result.clone();
-        
-        ProcessingResult::ProcessedText {
-            text: result,
-            buffer_length: previous_display_length,
+        replacement(&previous_output, &encoding::to_output(&result, self.encoding), '\u{8}')
+    }
+
+    /// Take up `word`, the letters on screen before the caret, as if it had
+    /// just been typed, so the next key can still change it ("viêt" then `j`
+    /// gives "việt"). Returns false, leaving the buffer empty, when a word is
+    /// already being composed or the keys that would type `word` do not
+    /// compose back into it.
+    pub fn seed_word(&mut self, word: &str) -> bool {
+        // Legacy encodings would have to be decoded first, and a hidden word
+        // is typed whole, which would repeat the seeded letters
+        if !self.engine.keys().is_empty() || self.encoding != Encoding::Unicode || self.is_preview_hidden() {
+            return false;
         }
+        let word = charset::normalize(word, Normalization::Nfc);
+        if word.is_empty() || !word.chars().all(char::is_alphabetic) || word.chars().count() > self.max_word_length {
+            return false;
+        }
+
+        for key in self.reverse_transform(&word).chars() {
+            self.engine.process_key(key);
+        }
+        self.refresh_display();
+        if self.display_buffer != word {
+            self.clear_buffer();
+            return false;
+        }
+        self.last_conversion = None;
+        self.last_key_at = Some(Instant::now());
+        true
+    }
+
+    /// Left (`-1`) or right (`1`) arrow moved the cursor by one character.
+    /// While the cursor stays within the word on screen the word is kept, and
+    /// typing at its end goes on composing it. Returns false when the cursor
+    /// left the word or the word is not on screen, so it has to be committed.
+    pub fn move_cursor(&mut self, step: isize) -> bool {
+        if self.engine.keys().is_empty() || !self.should_track || self.is_preview_hidden() {
+            return false;
+        }
+        let offset = self.cursor_offset as isize - step;
+        if offset < 0 || offset > self.get_display_output_length() as isize {
+            return false;
+        }
+        self.cursor_offset = offset as usize;
+        self.suggestions.clear();
+        true
+    }
+
+    /// A key typed with the cursor inside the word would be composed against
+    /// text it does not follow on screen, so the word is left as it is there
+    /// and the key starts a new one
+    fn leave_word_if_cursor_moved(&mut self) {
+        if self.cursor_offset > 0 {
+            self.new_word();
+        }
+    }
+
+    /// Look up completions for the word as it now stands
+    fn refresh_suggestions(&mut self) {
+        self.suggestions = if self.word_suggestions && self.should_track {
+            self.suggester.suggest(&self.display_buffer, MAX_SUGGESTIONS)
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Tab or a digit picks a suggestion and Escape dismisses them, while any
+    /// are offered. Digits are tone and mark keys in VNI, so they only pick
+    /// suggestions in Telex and VIQR.
+    fn handle_suggestion_key(&mut self, key: char) -> Option<ProcessingResult> {
+        if self.suggestions.is_empty() {
+            return None;
+        }
+        let index = match key {
+            '\t' => 0,
+            '1'..='9' if matches!(self.options.input_type, InputType::Telex | InputType::TelexSimple | InputType::VIQR) => {
+                key as usize - '1' as usize
+            }
+            '\u{1B}' => {
+                self.suggestions.clear();
+                return Some(ProcessingResult::DismissSuggestions);
+            }
+            _ => return None,
+        };
+
+        let suggestion = self.suggestions.get(index)?.clone();
+        let buffer_length = self.get_display_output_length();
+        self.stats.words += 1;
+        self.quality.counts_mut(self.options.input_type).words += 1;
+        self.stats.add_saved(suggestion.chars().count(), self.engine.keys().chars().count() + 1);
+        self.suggester.learn(&suggestion);
+        self.new_word();
+        Some(ProcessingResult::AcceptSuggestion {
+            text: encoding::to_output(&suggestion, self.encoding),
+            buffer_length,
+        })
     }
 
     fn handle_enter(&mut self) -> ProcessingResult {
@@ -167,11 +851,20 @@ impl VietnameseInputProcessor {
         ProcessingResult::PassThrough('\t')
     }
 
+    /// Whether Escape would put the word back to its keys now, rather than
+    /// close the suggestions or pass through
+    pub fn escape_restores(&self) -> bool {
+        !self.engine.keys().is_empty() && self.suggestions.is_empty() && self.cursor_offset == 0
+    }
+
     fn handle_escape(&mut self) -> ProcessingResult {
         // Escape should restore the original typed text
-        if !self.typing_buffer.is_empty() {
-            let original_text = self.typing_buffer.clone();
-            let display_length = self.display_buffer.chars().count();
+        if !self.engine.keys().is_empty() {
+            let original_text = self.engine.keys().to_string();
+            let display_length = self.get_display_output_length();
+            if self.display_buffer != original_text {
+                self.habits.record_restored(&original_text, false);
+            }
             self.new_word();
             return ProcessingResult::RestoreText {
                 text: original_text,
@@ -182,42 +875,221 @@ impl VietnameseInputProcessor {
     }
 
     pub fn handle_space(&mut self) -> ProcessingResult {
-        if self.typing_buffer.is_empty() {
+        if self.engine.keys().is_empty() {
             return ProcessingResult::PassThrough(' ');
         }
 
-        // Get the final transformed text
-        let mut result = String::new();
-        match self.input_type {
-            InputType::Telex => {
-                vi::transform_buffer(&TELEX, self.typing_buffer.chars(), &mut result);
-            }
-            InputType::VNI => {
-                vi::transform_buffer(&VNI, self.typing_buffer.chars(), &mut result);
-            }
-            InputType::VIQR => {
-                result = self.typing_buffer.clone();
-            }
+        // Once tracking stopped, keys were passed through and the screen no
+        // longer matches the display buffer, so just commit it as is
+        if !self.should_track {
+            self.new_word();
+            return ProcessingResult::PassThrough(' ');
         }
-        
-        let display_length = self.display_buffer.chars().count();
+
+        if let Some(expanded) = self.expand_shortcode(' ') {
+            return expanded;
+        }
+
+        if let Some(expanded) = self.expand_macro(' ') {
+            return expanded;
+        }
+
+        if let Some(restore) = self.restore_invalid_word(' ') {
+            return restore;
+        }
+
+        let previous_output = self.get_display_output();
         
         // Commit the buffer and add space
-        self.new_word();
+        let shown = encoding::to_output(&self.display_buffer, self.encoding);
+        self.commit_converted(&shown, ' ');
         
-        ProcessingResult::ProcessedText {
-            text: format!("{} ", result),
-            buffer_length: display_length,
-        }
+        let committed = format!("{} ", shown);
+        replacement(&previous_output, &committed, ' ')
     }
 
     /// Start tracking a new word
     pub fn new_word(&mut self) {
-        if !self.typing_buffer.is_empty() {
-            self.previous_word = self.typing_buffer.clone();
+        if !self.engine.keys().is_empty() {
+            self.previous_word = self.engine.keys().to_string();
+            self.engine.commit();
         }
+        self.last_conversion = None;
         self.clear_buffer();
         self.should_track = true;
+        self.auto_capitalized = false;
+        self.word_corrected = false;
+        self.word_unchecked = false;
+        self.english_word = false;
+    }
+
+    /// Whether the current word is being typed as English by smart switching
+    pub fn is_english_word(&self) -> bool {
+        self.english_word
+    }
+
+    /// Commit the current word, which is shown as `shown` followed by `boundary`,
+    /// and remember it so the conversion can be undone
+    fn commit_converted(&mut self, shown: &str, boundary: char) {
+        // Only real words are worth suggesting later
+        if self.word_suggestions
+            && (self.is_valid_word(&self.display_buffer) || self.dictionary.contains(&self.display_buffer))
+        {
+            self.suggester.learn(&self.display_buffer);
+        }
+        self.stats.words += 1;
+        self.quality.counts_mut(self.options.input_type).words += 1;
+        let raw = self.engine.keys().to_string();
+        self.new_word();
+        if raw != shown {
+            self.habits.record_kept(&raw);
+            self.last_conversion = Some(Conversion {
+                raw,
+                shown: shown.to_string(),
+                boundary,
+            });
+        }
+    }
+
+    /// Replace the word being composed, or the word committed just before the
+    /// cursor, with the keys that were typed for it. Returns None when there is
+    /// nothing to undo.
+    pub fn undo_conversion(&mut self) -> Option<ProcessingResult> {
+        if !self.engine.keys().is_empty() {
+            // Once tracking stopped the typed keys are already on screen
+            if !self.should_track {
+                return None;
+            }
+            let original_text = self.engine.keys().to_string();
+            let display_length = self.get_display_output_length();
+            self.stats.restores += 1;
+            self.quality.counts_mut(self.options.input_type).corrections += 1;
+            if self.display_buffer != original_text {
+                self.habits.record_restored(&original_text, false);
+            }
+            self.new_word();
+            return Some(ProcessingResult::RestoreText {
+                text: original_text,
+                buffer_length: display_length,
+            });
+        }
+
+        let conversion = self.last_conversion.take()?;
+        self.stats.restores += 1;
+        self.quality.counts_mut(self.options.input_type).corrections += 1;
+        // Committing the word counted it as kept
+        self.habits.record_restored(&conversion.raw, true);
+        Some(ProcessingResult::RestoreText {
+            text: format!("{}{}", conversion.raw, conversion.boundary),
+            buffer_length: conversion.shown.chars().count() + 1,
+        })
+    }
+
+    /// The application deleted the word before the cursor (Option+Backspace).
+    /// That is the word being composed, or the previous word if none is.
+    /// Returns true when the word was hidden and the key should be blocked,
+    /// since the application would otherwise delete the word before it.
+    pub fn handle_word_deletion(&mut self) -> bool {
+        let hidden_word = self.is_preview_hidden() && !self.engine.keys().is_empty();
+        if self.engine.keys().is_empty() {
+            self.previous_word.clear();
+        }
+        self.discard_word();
+        hidden_word
+    }
+
+    /// The application deleted the character after the cursor (fn+Delete).
+    /// At the end of the word that is text after it, so the word goes on.
+    /// With the cursor inside the word one of its letters is gone and the
+    /// keys no longer type what is on screen, so the word is left as it is
+    /// there and the next key starts a new one.
+    pub fn handle_forward_delete(&mut self) {
+        if self.cursor_offset == 0 {
+            return;
+        }
+        self.new_word();
+        self.previous_word.clear();
+    }
+
+    /// The application performed undo or redo (Cmd+Z / Shift+Cmd+Z). The text
+    /// around the cursor no longer matches anything we composed, so forget
+    /// both the current and the previous word.
+    pub fn handle_undo(&mut self) {
+        self.previous_word.clear();
+        self.discard_word();
+    }
+
+    /// Drop the current word without committing it, e.g. after the application
+    /// changed the text itself. The sentence position becomes unknown too.
+    fn discard_word(&mut self) {
+        self.clear_buffer();
+        self.previous_key = None;
+        self.should_track = true;
+        self.english_word = false;
+        self.auto_capitalized = false;
+        self.sentence_end_pending = false;
+        self.capitalize_next = false;
+    }
+
+    /// Follow sentence boundaries across words: ".", "!" or "?" followed by
+    /// a space or Enter makes the next word start a sentence
+    fn track_sentence_boundary(&mut self, key: char) {
+        match key {
+            '.' | '!' | '?' => {
+                self.sentence_end_pending = true;
+                self.capitalize_next = false;
+            }
+            ' ' | '\r' | '\n' => {
+                if self.sentence_end_pending {
+                    self.capitalize_next = true;
+                }
+                self.sentence_end_pending = false;
+            }
+            // Handled in handle_backspace
+            '\u{8}' => {}
+            _ => self.sentence_end_pending = false,
+        }
+    }
+
+    /// Upper-case the first letter of a word that starts a sentence
+    fn capitalize_sentence_start(&mut self, key: char) -> char {
+        if !self.auto_capitalize || !self.capitalize_next || !self.engine.keys().is_empty() {
+            return key;
+        }
+        if key.is_ascii_digit() {
+            self.capitalize_next = false;
+            return key;
+        }
+        if !key.is_ascii_alphabetic() {
+            return key;
+        }
+
+        self.capitalize_next = false;
+        if key.is_ascii_lowercase() {
+            self.auto_capitalized = true;
+            return key.to_ascii_uppercase();
+        }
+        key
+    }
+
+    /// Whether the key is a Telex w that would type ư on its own while that is
+    /// switched off. A w after a, o or u still adds its mark.
+    fn is_literal_w(&self, key: char) -> bool {
+        !self.standalone_w
+            && matches!(self.options.input_type, InputType::Telex | InputType::Hybrid)
+            && key.eq_ignore_ascii_case(&'w')
+            && !self
+                .engine
+                .keys()
+                .chars()
+                .any(|c| matches!(c.to_ascii_lowercase(), 'a' | 'o' | 'u' | '[' | ']' | '{' | '}'))
+    }
+
+    /// Whether the key is a Telex bracket shortcut for ơ or ư
+    fn is_bracket_shortcut(&self, key: char) -> bool {
+        self.options.bracket_shortcuts
+            && matches!(self.options.input_type, InputType::Telex | InputType::Hybrid)
+            && matches!(key, '[' | ']' | '{' | '}')
     }
 
     /// Stop tracking the current word
@@ -227,22 +1099,20 @@ impl VietnameseInputProcessor {
 
     /// Check if we should stop tracking due to tone duplicate patterns
     fn should_stop_tracking_due_to_patterns(&self) -> bool {
+        if self.word_unchecked {
+            return false;
+        }
         // Detect attempts to restore a word by doubling tone marks like ss, rr, ff, jj, xx
-        const TONE_DUPLICATE_PATTERNS: [&str; 17] = [
-            "ss", "ff", "jj", "rr", "xx", "ww", "kk", "tt", "nn", "mm", "yy", "hh", "ii", "aaa", "eee",
-            "ooo", "ddd",
-        ];
-        
-        let buffer_lower = self.typing_buffer.to_ascii_lowercase();
-        TONE_DUPLICATE_PATTERNS
+        let buffer_lower = self.engine.keys().to_ascii_lowercase();
+        self.restore_patterns
             .iter()
-            .any(|pattern| buffer_lower.contains(pattern))
+            .any(|pattern| buffer_lower.contains(pattern.as_str()))
     }
 
     /// Get the backspace count needed to clear the current displayed text
     /// This includes special handling for text selection
     pub fn get_backspace_count(&self, is_delete: bool, has_text_selection: bool) -> usize {
-        let display_length = self.display_buffer.chars().count();
+        let display_length = self.get_display_output_length();
         let backspace_count = if is_delete && display_length >= 1 {
             display_length
         } else if display_length > 0 {
@@ -262,33 +1132,305 @@ impl VietnameseInputProcessor {
 
     /// Check if the current word should be restored based on validation
     pub fn should_restore_word(&self) -> bool {
-        if self.typing_buffer.is_empty() || self.display_buffer.is_empty() {
+        if self.engine.keys().is_empty() || self.display_buffer.is_empty() {
             return false;
         }
 
         // If the typing buffer and display buffer are the same, no transformation occurred
-        if self.typing_buffer == self.display_buffer {
+        if self.engine.keys() == self.display_buffer {
+            return false;
+        }
+
+        // The word was typed after the bypass key
+        if self.word_unchecked {
+            return false;
+        }
+
+        // The user said this word is fine as it is
+        if self.dictionary.contains(&self.display_buffer) {
             return false;
         }
 
         // Check if the transformed word is valid Vietnamese
-        // This would require the vi-rs validation functionality
-        // For now, we'll use a simple heuristic
-        false
+        !self.is_valid_word(&self.display_buffer)
+    }
+
+    /// Whether every syllable of `word` is a valid Vietnamese syllable. The
+    /// syllables of a word like "ê-kíp" are checked one by one. An engine
+    /// for another language checks its words itself.
+    fn is_valid_word(&self, word: &str) -> bool {
+        if let Some(valid) = self.engine.is_valid_word(word) {
+            return valid;
+        }
+        // A custom letter was typed on purpose, whatever the syllable rules say
+        let lowercase = word.to_lowercase();
+        if self.custom_diacritics.iter().any(|mapping| lowercase.contains(mapping.to.to_lowercase().as_str())) {
+            return true;
+        }
+        word.split(|c: char| c.is_ascii_punctuation())
+            .all(|part| part.is_empty() || syllable::is_valid_syllable(part, self.options.allow_silent_consonants))
+    }
+
+    /// On commit, restore the raw keystrokes followed by `boundary` if the
+    /// composed word is not a valid Vietnamese syllable and either invalid
+    /// words are restored or the keystrokes look like an English word. A word
+    /// the user keeps putting back is restored even if valid, and one they
+    /// keep as transformed is never restored.
+    fn restore_invalid_word(&mut self, boundary: char) -> Option<ProcessingResult> {
+        if !self.should_track || self.word_unchecked {
+            return None;
+        }
+        let reason = match self.habits.verdict(self.engine.keys()) {
+            Some(LearnedRestore::Keep) => return None,
+            Some(LearnedRestore::Restore) if self.engine.keys() != self.display_buffer => CorrectionReason::Learned,
+            _ => {
+                if !self.should_restore_word() {
+                    return None;
+                }
+                let is_english = self.detect_english && english::looks_english(self.engine.keys());
+                if !self.auto_restore_invalid && !is_english {
+                    return None;
+                }
+                if is_english {
+                    CorrectionReason::LooksEnglish
+                } else {
+                    CorrectionReason::NotVietnamese
+                }
+            }
+        };
+        self.log_correction(reason);
+
+        let original_text = self.engine.keys().to_string();
+        let display_length = self.get_display_output_length();
+        self.new_word();
+        Some(ProcessingResult::RestoreText {
+            text: format!("{}{}", original_text, boundary),
+            buffer_length: display_length,
+        })
+    }
+
+    /// Add the current word, about to be restored for `reason`, to the
+    /// correction log if logging is on
+    fn log_correction(&mut self, reason: CorrectionReason) {
+        if !self.log_corrections {
+            return;
+        }
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        self.corrections.record(Correction {
+            shown: self.display_buffer.clone(),
+            keys: self.engine.keys().to_string(),
+            reason,
+            app: self.active_app.clone(),
+            at,
+        });
+    }
+
+    /// On commit, replace the current word with its macro expansion followed by `boundary`.
+    /// The trigger is matched against the composed word first, then the raw keystrokes,
+    /// ignoring diacritics already applied to the word, among the macros scoped to the
+    /// active application. Placeholders in the expansion are filled in now.
+    fn expand_macro(&mut self, boundary: char) -> Option<ProcessingResult> {
+        if !self.macros_enabled || !self.should_track || self.engine.keys().is_empty() {
+            return None;
+        }
+
+        let expansion = self.macros.expand(&self.display_buffer, self.engine.keys(), &self.active_app)?;
+        let expansion = placeholders::expand(&expansion, self.placeholders.as_ref());
+
+        // The abbreviation on screen is exactly the display buffer
+        let display_length = self.get_display_output_length();
+        let shown = encoding::to_output(&expansion, self.encoding);
+        self.stats.add_saved(expansion.chars().count(), self.engine.keys().chars().count());
+        self.commit_converted(&shown, boundary);
+        Some(ProcessingResult::ProcessedText {
+            text: format!("{}{}", shown, boundary),
+            buffer_length: display_length,
+        })
+    }
+
+    /// On commit, replace `:name:` with its emoji followed by `boundary`. The
+    /// opening colon was typed before the word and the closing one is its
+    /// last key; a colon set as a word boundary never joins a word, which
+    /// leaves shortcodes off. The name is matched against the keys typed,
+    /// then the composed word.
+    fn expand_shortcode(&mut self, boundary: char) -> Option<ProcessingResult> {
+        if !self.emoji_shortcodes || !self.word_after_colon || !self.should_track || self.encoding != Encoding::Unicode {
+            return None;
+        }
+
+        let name = self.engine.keys().strip_suffix(':')?;
+        let emoji = self
+            .emoji
+            .lookup(name)
+            .or_else(|| self.emoji.lookup(self.display_buffer.strip_suffix(':')?))?
+            .to_string();
+
+        // Both colons are on screen, though a hidden word is not
+        let display_length = self.get_display_output_length() + 1;
+        self.stats.add_saved(emoji.chars().count(), name.chars().count() + 2);
+        self.commit_converted(&emoji, boundary);
+        Some(ProcessingResult::ProcessedText {
+            text: format!("{}{}", emoji, boundary),
+            buffer_length: display_length,
+        })
+    }
+
+    /// Show the keys typed for the word being composed in place of the word,
+    /// until `end_peek` puts it back. The word itself is not changed. None
+    /// when the word is not on screen or already shows its keys.
+    pub fn peek_keys(&mut self) -> Option<ProcessingResult> {
+        if self.peeking || !self.should_track || self.is_preview_hidden() || self.cursor_offset != 0 {
+            return None;
+        }
+        let keys = self.engine.keys().to_string();
+        if keys.is_empty() || self.get_display_output() == keys {
+            return None;
+        }
+        self.peeking = true;
+        Some(ProcessingResult::RestoreText {
+            text: keys,
+            buffer_length: self.get_display_output_length(),
+        })
+    }
+
+    /// Put the word back where `peek_keys` showed its keys. None when no
+    /// keys are shown, including after the word was dropped meanwhile.
+    pub fn end_peek(&mut self) -> Option<ProcessingResult> {
+        if !std::mem::take(&mut self.peeking) {
+            return None;
+        }
+        Some(ProcessingResult::ProcessedText {
+            text: self.get_display_output(),
+            buffer_length: self.engine.keys().chars().count(),
+        })
     }
 
     /// Get the original typed text for restoration
     pub fn get_restore_text(&self) -> String {
-        self.typing_buffer.clone()
+        self.engine.keys().to_string()
     }
 
     pub fn clear_buffer(&mut self) {
-        self.typing_buffer.clear();
+        self.engine.reset();
         self.display_buffer.clear();
+        self.suggestions.clear();
+        self.cursor_offset = 0;
+        self.word_after_colon = false;
+        self.peeking = false;
+    }
+
+    /// Completions offered for the current word, in the order of their digit keys
+    pub fn get_suggestions(&self) -> &[String] {
+        &self.suggestions
+    }
+
+    pub fn has_suggestions(&self) -> bool {
+        !self.suggestions.is_empty()
+    }
+
+    /// Totals of keys, words, restores and keys saved
+    pub fn get_stats(&self) -> TypingStats {
+        self.stats
+    }
+
+    /// Continue counting from previously saved totals
+    pub fn set_stats(&mut self, stats: TypingStats) {
+        self.stats = stats;
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = TypingStats::default();
+    }
+
+    pub fn get_quality(&self) -> QualityMetrics {
+        self.quality
+    }
+
+    /// Continue counting from previously saved quality metrics
+    pub fn set_quality(&mut self, quality: QualityMetrics) {
+        self.quality = quality;
+    }
+
+    pub fn reset_quality(&mut self) {
+        self.quality = QualityMetrics::default();
+    }
+
+    /// Words the user keeps transformed or puts back, learned so far
+    pub fn get_habits(&self) -> &RestoreHabits {
+        &self.habits
+    }
+
+    /// Continue learning from previously saved habits
+    pub fn set_habits(&mut self, habits: RestoreHabits) {
+        self.habits = habits;
+    }
+
+    pub fn reset_habits(&mut self) {
+        self.habits = RestoreHabits::new();
+    }
+
+    /// The application words are typed in from now on, for the correction log
+    /// and the macros scoped to applications
+    pub fn set_active_app(&mut self, app: &str) {
+        self.active_app = app.to_string();
+    }
+
+    pub fn get_corrections(&self) -> &CorrectionLog {
+        &self.corrections
+    }
+
+    /// Continue a previously saved correction log, if logging is on
+    pub fn set_corrections(&mut self, corrections: CorrectionLog) {
+        if self.log_corrections {
+            self.corrections = corrections;
+        }
+    }
+
+    /// Drop logged corrections older than `retention_days`
+    pub fn purge_corrections(&mut self, retention_days: u64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        self.corrections.purge(now, retention_days);
+    }
+
+    pub fn clear_corrections(&mut self) {
+        self.corrections.clear();
+    }
+
+    /// Stop restoring the word typed with `keys`, picked from the correction
+    /// log: the word it was restored from goes into the user dictionary, its
+    /// learned restore is forgotten, and it leaves the log
+    pub fn keep_corrected_word(&mut self, keys: &str) {
+        let shown = self
+            .corrections
+            .entries()
+            .iter()
+            .rev()
+            .find(|correction| correction.keys.eq_ignore_ascii_case(keys))
+            .map(|correction| correction.shown.clone());
+        if let Some(shown) = shown {
+            self.dictionary.add(&shown);
+        }
+        self.habits.forget(keys);
+        self.corrections.forget(keys);
+    }
+
+    pub fn set_suggestion_engine(&mut self, suggester: SuggestionEngine) {
+        self.suggester = suggester;
+        self.suggestions.clear();
+    }
+
+    pub fn get_suggestion_engine(&self) -> &SuggestionEngine {
+        &self.suggester
     }
 
     pub fn get_current_buffer(&self) -> &str {
-        &self.typing_buffer
+        self.engine.keys()
     }
 
     pub fn get_display_buffer(&self) -> &str {
@@ -303,15 +1445,126 @@ impl VietnameseInputProcessor {
         self.should_track
     }
 
+    /// Approximate memory held by the engine, for the diagnostics window
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        report.add(
+            "Word buffers",
+            3,
+            self.engine.approx_bytes() + string_bytes(&self.display_buffer) + string_bytes(&self.previous_word),
+            None,
+        );
+        report.add("Abbreviations", self.macros.len(), self.macros.approx_bytes(), Some(MAX_MACROS));
+        report.add("User dictionary", self.dictionary.len(), self.dictionary.approx_bytes(), Some(MAX_WORDS));
+        report.add(
+            "Suggestion history",
+            self.suggester.history_len(),
+            self.suggester.approx_bytes(),
+            Some(MAX_HISTORY_WORDS),
+        );
+        report.add("Learned restores", self.habits.len(), self.habits.approx_bytes(), Some(MAX_LEARNED_WORDS));
+        report.add("Correction log", self.corrections.len(), self.corrections.approx_bytes(), Some(MAX_LOGGED_CORRECTIONS));
+        report.add(
+            "Restore patterns",
+            self.restore_patterns.len(),
+            self.restore_patterns.iter().map(string_bytes).sum(),
+            None,
+        );
+        report
+    }
+
+    /// Copy of the buffers and tracking state, for showing the engine state elsewhere
+    pub fn snapshot(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            typing_buffer: self.engine.keys().to_string(),
+            display_buffer: self.display_buffer.clone(),
+            is_tracking: self.should_track,
+            suggestions: self.suggestions.clone(),
+        }
+    }
+
     pub fn is_buffer_empty(&self) -> bool {
-        self.typing_buffer.is_empty()
+        self.engine.keys().is_empty()
     }
 
     pub fn reset(&mut self) {
-        self.typing_buffer.clear();
-        self.display_buffer.clear();
+        self.clear_buffer();
         self.previous_word.clear();
+        self.previous_key = None;
         self.should_track = true;
+        self.english_word = false;
+        self.sentence_end_pending = false;
+        self.capitalize_next = false;
+        self.auto_capitalized = false;
+        self.last_conversion = None;
+    }
+
+    /// Transform a whole piece of text with the current input method, encoding
+    /// and word options, as if it had been typed. Words end at spaces,
+    /// punctuation, line breaks and non-ASCII characters, which are kept as is.
+    /// The processor's own typing state is left untouched.
+    pub fn transform_text(&self, text: &str) -> String {
+        let mut engine = self.clone();
+        engine.suppress_preview = false;
+        engine.idle_timeout = None;
+        engine.reset();
+
+        let mut output = String::new();
+        for ch in text.chars() {
+            if ch == ' ' || ch.is_ascii_graphic() {
+                engine.process_key(ch).apply_to(&mut output);
+            } else {
+                engine.finish_text_word(&mut output);
+                output.push(ch);
+            }
+        }
+        engine.finish_text_word(&mut output);
+        output
+    }
+
+    /// Keys that type `text` with the current input method, the reverse of
+    /// `transform_text`
+    pub fn reverse_transform(&self, text: &str) -> String {
+        reverse::reverse_transform(text, self.options.input_type, self.options.vni_keys)
+    }
+
+    /// Commit the word at the end of `output` the way a space would, without
+    /// keeping the space
+    fn finish_text_word(&mut self, output: &mut String) {
+        if self.engine.keys().is_empty() {
+            return;
+        }
+        self.process_key(' ').apply_to(output);
+        if output.ends_with(' ') {
+            output.pop();
+        }
+    }
+}
+
+/// Turn `previous` (on screen) into `current` by deleting and retyping only what
+/// follows their common prefix. When that amounts to the key itself, the key is
+/// passed through and nothing needs to be injected.
+fn replacement(previous: &str, current: &str, key: char) -> ProcessingResult {
+    let common = previous
+        .chars()
+        .zip(current.chars())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let backspaces = previous.chars().count() - common;
+    let suffix: String = current.chars().skip(common).collect();
+
+    let is_key_itself = if key == '\u{8}' {
+        backspaces == 1 && suffix.is_empty()
+    } else {
+        backspaces == 0 && suffix.chars().eq(std::iter::once(key))
+    };
+    if is_key_itself {
+        return ProcessingResult::PassThrough(key);
+    }
+
+    ProcessingResult::ProcessedText {
+        text: suffix,
+        buffer_length: backspaces,
     }
 }
 
@@ -319,7 +1572,8 @@ impl VietnameseInputProcessor {
 pub enum ProcessingResult {
     /// The character should be passed through without processing
     PassThrough(char),
-    /// Text has been processed and should replace the current buffer
+    /// Delete `buffer_length` characters before the cursor, then type `text`.
+    /// Only the part of the word that changed is retyped.
     ProcessedText {
         text: String,
         buffer_length: usize,
@@ -331,4 +1585,283 @@ pub enum ProcessingResult {
         text: String,
         buffer_length: usize,
     },
-}  
\ No newline at end of file
+    /// Replace the word being typed with the chosen suggestion.
+    /// The key that picked it is blocked.
+    AcceptSuggestion {
+        text: String,
+        buffer_length: usize,
+    },
+    /// Escape closed the suggestions; the key is blocked and the word is kept
+    DismissSuggestions,
+}
+
+impl ProcessingResult {
+    /// Block the key without changing the screen
+    pub fn consumed() -> Self {
+        ProcessingResult::ProcessedText {
+            text: String::new(),
+            buffer_length: 0,
+        }
+    }
+
+    /// The edits this result makes to the text before the cursor, including
+    /// those of a key passed through to the application
+    pub fn edit_ops(&self) -> Vec<EditOp> {
+        match self {
+            ProcessingResult::ProcessedText { text, buffer_length }
+            | ProcessingResult::RestoreText { text, buffer_length }
+            | ProcessingResult::AcceptSuggestion { text, buffer_length } => EditOp::replacement(*buffer_length, text),
+            ProcessingResult::PassThrough('\u{8}') | ProcessingResult::ClearAndPassBackspace => vec![EditOp::DeleteBack(1)],
+            ProcessingResult::PassThrough(ch) => vec![EditOp::Insert(ch.to_string())],
+            ProcessingResult::DismissSuggestions => Vec::new(),
+        }
+    }
+
+    /// Apply this result to a string that mirrors the text before the cursor
+    pub fn apply_to(&self, screen: &mut String) {
+        let mut cursor = screen.chars().count();
+        apply_edits(&self.edit_ops(), screen, &mut cursor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Type `keys` and return what ends up on screen
+    fn type_on_screen(processor: &mut VietnameseInputProcessor, keys: &str) -> String {
+        let mut screen = String::new();
+        for key in keys.chars() {
+            processor.process_key(key).apply_to(&mut screen);
+        }
+        screen
+    }
+
+    #[test]
+    fn numeric_prefix_stays_on_screen() {
+        for (mode, word) in [
+            (NumericPrefix::Strip, "gà"),
+            (NumericPrefix::Keep, "3gà"),
+            (NumericPrefix::Commit, "gà"),
+        ] {
+            let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+            processor.apply_settings(&AdvancedSettings {
+                numeric_prefix: mode,
+                ..AdvancedSettings::default()
+            });
+            assert_eq!(type_on_screen(&mut processor, "3gaf"), "3gà", "{:?}", mode);
+            assert_eq!(processor.get_display_buffer(), word, "{:?}", mode);
+
+            // A hidden word still gets its digits typed
+            processor.clear_buffer();
+            processor.set_preview_suppressed(true);
+            assert_eq!(type_on_screen(&mut processor, "4kaf "), "4kà ", "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn custom_diacritics_are_substituted_and_kept() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        processor.apply_settings(&AdvancedSettings {
+            spell_check: true,
+            auto_restart_typos: true,
+            ..AdvancedSettings::default()
+        });
+        processor.set_custom_diacritics(vec![
+            CustomDiacritic { from: "o".to_string(), to: "ó".to_string() },
+            CustomDiacritic { from: "oq".to_string(), to: "ŏ".to_string() },
+        ]);
+        assert_eq!(processor.transform_text("Boqk"), "Bŏk");
+        // Not a Vietnamese syllable, but not restored either
+        assert_eq!(type_on_screen(&mut processor, "boqk "), "bŏk ");
+        processor.set_custom_diacritics(Vec::new());
+        assert_eq!(processor.transform_text("boqk"), "boqk");
+    }
+
+    #[test]
+    fn bypass_key_skips_checks_for_one_word() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        processor.apply_settings(&AdvancedSettings {
+            spell_check: true,
+            auto_restart_typos: true,
+            temp_disable_spell_check: true,
+            ..AdvancedSettings::default()
+        });
+        assert_eq!(type_on_screen(&mut processor, "kaan "), "kaan ");
+        assert_eq!(type_on_screen(&mut processor, "^kaan kaan "), "kân kaan ");
+
+        // Without a word after it, the key is typed
+        assert_eq!(type_on_screen(&mut processor, "^ "), "^ ");
+        assert_eq!(type_on_screen(&mut processor, "^^"), "^");
+        processor.process_key(SPELL_CHECK_BYPASS_KEY);
+        assert_eq!(processor.handle_backspace(), ProcessingResult::consumed());
+        assert_eq!(type_on_screen(&mut processor, "kaan "), "kaan ");
+    }
+
+    #[test]
+    fn arrow_keys_within_the_word_keep_it() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        type_on_screen(&mut processor, "vieet");
+        assert!(processor.move_cursor(-1));
+        assert!(processor.move_cursor(1));
+        // Back at the end of the word, the tone still lands on it
+        processor.process_key('j');
+        assert_eq!(processor.get_display_buffer(), "việt");
+
+        // Typing inside the word starts a new one instead
+        assert!(processor.move_cursor(-1));
+        assert_eq!(processor.process_key('a'), ProcessingResult::PassThrough('a'));
+        assert_eq!(processor.get_current_buffer(), "a");
+        assert!(processor.move_cursor(-1));
+        assert_eq!(processor.handle_backspace(), ProcessingResult::PassThrough('\u{8}'));
+        assert!(processor.is_buffer_empty());
+
+        // Moving past either end of the word leaves it
+        type_on_screen(&mut processor, "ab");
+        assert!(!processor.move_cursor(1));
+        assert!(processor.move_cursor(-1) && processor.move_cursor(-1));
+        assert!(!processor.move_cursor(-1));
+    }
+
+    #[test]
+    fn macro_placeholders_are_filled_in_on_expansion() {
+        use crate::core::placeholders::LocalTime;
+
+        #[derive(Debug)]
+        struct Desk;
+
+        impl PlaceholderSource for Desk {
+            fn now(&self) -> LocalTime {
+                LocalTime::from_unix(1_725_260_709, 7 * 3600)
+            }
+
+            fn clipboard(&self) -> Option<String> {
+                Some("https://vkey.vn".to_string())
+            }
+        }
+
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        let mut macros = MacroTable::new();
+        macros.insert("nd", "ngày {date}");
+        macros.insert("lk", "link: {clipboard}");
+        processor.set_macros(macros);
+        processor.set_placeholder_source(Arc::new(Desk));
+        assert_eq!(type_on_screen(&mut processor, "nd "), "ngày 02/09/2024 ");
+        assert_eq!(type_on_screen(&mut processor, "lk."), "link: https://vkey.vn.");
+    }
+
+    #[test]
+    fn shortcodes_expand_to_emoji() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        assert_eq!(type_on_screen(&mut processor, ":cuoi: "), ":cuoi: ");
+        processor.apply_settings(&AdvancedSettings {
+            emoji_shortcodes: true,
+            ..AdvancedSettings::default()
+        });
+        assert_eq!(type_on_screen(&mut processor, "xin :cuoi: "), "xin 😀 ");
+        assert_eq!(type_on_screen(&mut processor, ":Sad:."), "😢.");
+        // Diacritics typed into the name do not matter
+        assert_eq!(type_on_screen(&mut processor, ":cuwowif: "), "😀 ");
+
+        // Without the opening colon, or with an unknown name, nothing changes
+        assert_eq!(type_on_screen(&mut processor, "cuoi: "), "cuoi: ");
+        assert_eq!(type_on_screen(&mut processor, ":meo: "), ":meo: ");
+
+        let mut emoji = EmojiTable::builtin();
+        emoji.merge([("cuoi".to_string(), String::new()), ("meo".to_string(), "🐱".to_string())].into());
+        processor.set_emoji(emoji);
+        assert_eq!(type_on_screen(&mut processor, ":cuoi: :meo: "), ":cuoi: 🐱 ");
+    }
+
+    #[test]
+    fn smart_switching_types_one_word_as_english() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        processor.apply_settings(&AdvancedSettings::default());
+        assert_eq!(type_on_screen(&mut processor, "flows"), "flows");
+        assert!(processor.is_english_word());
+
+        // The next word is Vietnamese again without a toggle
+        assert_eq!(type_on_screen(&mut processor, " vieetj "), " việt ");
+        assert!(!processor.is_english_word());
+
+        processor.apply_settings(&AdvancedSettings {
+            smart_switching: false,
+            ..AdvancedSettings::default()
+        });
+        assert_eq!(type_on_screen(&mut processor, "flows"), "flớ");
+    }
+
+    #[test]
+    fn peeking_shows_the_keys_and_puts_the_word_back() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        let mut screen = type_on_screen(&mut processor, "vieetj");
+        assert_eq!(screen, "việt");
+
+        processor.peek_keys().unwrap().apply_to(&mut screen);
+        assert_eq!(screen, "vieetj");
+        assert!(processor.peek_keys().is_none());
+        processor.end_peek().unwrap().apply_to(&mut screen);
+        assert_eq!(screen, "việt");
+        assert!(processor.end_peek().is_none());
+
+        // The word goes on as if nothing happened
+        screen.push_str(&type_on_screen(&mut processor, " "));
+        assert_eq!(screen, "việt ");
+        assert!(processor.peek_keys().is_none());
+    }
+
+    #[test]
+    fn reset_leaves_nothing_to_erase() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        type_on_screen(&mut processor, "vieetj nam");
+        processor.reset();
+        assert!(processor.undo_conversion().is_none());
+        assert!(matches!(processor.handle_backspace(), ProcessingResult::PassThrough('\u{8}')));
+        assert_eq!(type_on_screen(&mut processor, "j"), "j");
+    }
+
+    #[test]
+    fn escape_restores_only_a_word_being_composed() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        assert!(!processor.escape_restores());
+        type_on_screen(&mut processor, "vieet");
+        assert!(processor.escape_restores());
+        assert!(matches!(processor.process_key('\u{1B}'), ProcessingResult::RestoreText { .. }));
+        assert!(!processor.escape_restores());
+    }
+
+    #[test]
+    fn forward_delete_inside_the_word_leaves_it() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        type_on_screen(&mut processor, "vieet");
+        // At the end of the word only text after it goes
+        processor.handle_forward_delete();
+        processor.process_key('j');
+        assert_eq!(processor.get_display_buffer(), "việt");
+
+        assert!(processor.move_cursor(-1));
+        processor.handle_forward_delete();
+        assert!(processor.is_buffer_empty());
+        assert_eq!(processor.process_key('s'), ProcessingResult::PassThrough('s'));
+    }
+
+    #[test]
+    fn seeded_word_takes_the_next_key() {
+        let mut processor = VietnameseInputProcessor::new(InputType::Telex);
+        assert!(processor.seed_word("Viêt"));
+        assert_eq!(
+            processor.process_key('j'),
+            ProcessingResult::ProcessedText { text: "ệt".to_string(), buffer_length: 2 }
+        );
+        assert_eq!(processor.get_display_buffer(), "Việt");
+
+        // Keys that would not type the same word back are not taken up
+        processor.new_word();
+        assert!(!processor.seed_word("as"));
+        assert!(processor.is_buffer_empty());
+
+        let mut processor = VietnameseInputProcessor::new(InputType::VNI);
+        assert!(processor.seed_word("Nguyễn"));
+        assert_eq!(processor.get_current_buffer(), "Nguye64n");
+    }
+}
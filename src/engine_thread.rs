@@ -0,0 +1,265 @@
+// Engine thread
+// The input processor lives on a thread of its own and is only touched there.
+// The event tap, the UI and the background savers hand it jobs through two
+// bounded lock-free channels, and a job that needs an answer carries a
+// channel for it. Keys, and every change that must be in place before the
+// next key, go in the key lane; reads for the UI and the savers go in the
+// background lane, which is served only while no key waits. The key lane is
+// never waited on: when it is full the job is dropped and the key passes
+// through, so the tap callback waits at most for one job already running,
+// and jobs are short: slow work such as saving files is done by the sender
+// with what the job hands back. A job that panics is answered with nothing
+// and the processor is built again, so typing goes on. The UI follows the
+// word being composed through snapshots sent after every job that changed it.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError};
+use std::thread::{self, Thread};
+
+use crate::core::{BufferSnapshot, VietnameseInputProcessor};
+
+type Job = Box<dyn FnOnce(&mut VietnameseInputProcessor) + Send>;
+
+/// Jobs that can wait in each lane
+const QUEUE_CAPACITY: usize = 256;
+
+enum Message {
+    Run(Job),
+    Subscribe(Sender<BufferSnapshot>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Lane {
+    /// Keys and changes that must stay in order with them
+    Keys,
+    /// Reads that can wait for the keys sent after them
+    Background,
+}
+
+/// Handle to the thread that owns the processor
+pub struct EngineThread {
+    keys: SyncSender<Message>,
+    background: SyncSender<Message>,
+    /// The engine thread, woken after every message; None if it did not start
+    thread: Option<Thread>,
+}
+
+impl EngineThread {
+    /// Start the thread. The processor is built there by `build`, so loading
+    /// its files does not hold up the caller; jobs sent meanwhile wait for it.
+    /// It is built again after a job panics.
+    pub fn spawn<F>(build: F) -> Self
+    where
+        F: Fn() -> VietnameseInputProcessor + Send + 'static,
+    {
+        let (keys, key_jobs) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let (background, background_jobs) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let spawned = thread::Builder::new()
+            .name("vkey-engine".to_string())
+            .spawn(move || run(build, key_jobs, background_jobs));
+        let thread = match spawned {
+            Ok(handle) => Some(handle.thread().clone()),
+            Err(e) => {
+                eprintln!("Failed to start the engine thread: {}", e);
+                None
+            }
+        };
+        Self { keys, background, thread }
+    }
+
+    /// Run `f` on the processor in order with the keys and wait for its
+    /// result. None if the key lane is full, `f` panicked or the engine
+    /// thread is gone; the key then passes through. Must not be called from a job.
+    pub fn call<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut VietnameseInputProcessor) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.call_in(Lane::Keys, f)
+    }
+
+    /// Run `f` on the processor once no key is waiting, and wait for its
+    /// result. For the UI and the background savers, so their reads never
+    /// hold up typing; keys sent meanwhile run first.
+    pub fn query<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut VietnameseInputProcessor) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.call_in(Lane::Background, f)
+    }
+
+    /// Queue `f` to run on the processor without waiting for it. Jobs run in
+    /// the order they were sent, so later keys see its effect. Dropped when
+    /// the key lane is full.
+    pub fn post<F>(&self, f: F)
+    where
+        F: FnOnce(&mut VietnameseInputProcessor) + Send + 'static,
+    {
+        if !self.send(Lane::Keys, Message::Run(Box::new(f))) {
+            eprintln!("Engine job dropped");
+        }
+    }
+
+    /// Snapshots of the word being composed: the current one, then one after
+    /// every change. They stop once the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<BufferSnapshot> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Lane::Background, Message::Subscribe(sender));
+        receiver
+    }
+
+    fn call_in<R, F>(&self, lane: Lane, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut VietnameseInputProcessor) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (reply, answer) = mpsc::sync_channel(1);
+        let job = Message::Run(Box::new(move |processor| {
+            let _ = reply.send(f(processor));
+        }));
+        if !self.send(lane, job) {
+            return None;
+        }
+        answer.recv().ok()
+    }
+
+    /// Hand `message` to the engine thread. The key lane is only tried, the
+    /// background lane waits for room. False if the message was dropped.
+    fn send(&self, lane: Lane, message: Message) -> bool {
+        let sent = match lane {
+            Lane::Keys => match self.keys.try_send(message) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    eprintln!("Engine is {} jobs behind; letting the key through", QUEUE_CAPACITY);
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            },
+            Lane::Background => self.background.send(message).is_ok(),
+        };
+        if let (true, Some(thread)) = (sent, &self.thread) {
+            thread.unpark();
+        }
+        sent
+    }
+}
+
+fn run<F>(build: F, keys: Receiver<Message>, background: Receiver<Message>)
+where
+    F: Fn() -> VietnameseInputProcessor,
+{
+    let mut processor = build();
+    let mut subscribers: Vec<Sender<BufferSnapshot>> = Vec::new();
+    let mut published: Option<BufferSnapshot> = None;
+    loop {
+        let message = match keys.try_recv() {
+            Ok(message) => message,
+            Err(TryRecvError::Disconnected) => break,
+            Err(TryRecvError::Empty) => match background.try_recv() {
+                Ok(message) => message,
+                // Senders wake the thread after every message
+                Err(_) => {
+                    thread::park();
+                    continue;
+                }
+            },
+        };
+        match message {
+            Message::Run(job) => {
+                // The job's caller gets no answer; the processor may be half
+                // way through a change, so it starts over
+                if panic::catch_unwind(AssertUnwindSafe(|| job(&mut processor))).is_err() {
+                    eprintln!("An engine job panicked; building the processor again");
+                    processor = build();
+                    published = None;
+                }
+            }
+            Message::Subscribe(subscriber) => {
+                subscribers.push(subscriber);
+                published = None;
+            }
+        }
+        if subscribers.is_empty() {
+            continue;
+        }
+        let snapshot = processor.snapshot();
+        if published.as_ref() != Some(&snapshot) {
+            subscribers.retain(|subscriber| subscriber.send(snapshot.clone()).is_ok());
+            published = Some(snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::core::InputType;
+
+    fn engine() -> Arc<EngineThread> {
+        Arc::new(EngineThread::spawn(|| VietnameseInputProcessor::new(InputType::Telex)))
+    }
+
+    /// Keep the engine busy until the returned sender is used or dropped
+    fn hold(engine: &EngineThread) -> Sender<()> {
+        let (release, released) = mpsc::channel::<()>();
+        engine.post(move |_| {
+            let _ = released.recv();
+        });
+        thread::sleep(Duration::from_millis(50));
+        release
+    }
+
+    #[test]
+    fn keys_run_before_waiting_background_reads() {
+        let engine = engine();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        // Hold the engine until both lanes have a job waiting
+        let release = hold(&engine);
+        let read = {
+            let (engine, order) = (Arc::clone(&engine), Arc::clone(&order));
+            thread::spawn(move || engine.query(move |_| order.lock().unwrap().push("read")))
+        };
+        thread::sleep(Duration::from_millis(50));
+        let key = {
+            let (engine, order) = (Arc::clone(&engine), Arc::clone(&order));
+            thread::spawn(move || engine.call(move |processor| {
+                processor.process_key('a');
+                order.lock().unwrap().push("key");
+            }))
+        };
+        thread::sleep(Duration::from_millis(50));
+        release.send(()).unwrap();
+
+        assert!(read.join().unwrap().is_some());
+        assert!(key.join().unwrap().is_some());
+        assert_eq!(*order.lock().unwrap(), ["key", "read"]);
+    }
+
+    #[test]
+    fn a_full_key_lane_drops_keys_instead_of_waiting() {
+        let engine = engine();
+        let release = hold(&engine);
+        for _ in 0..QUEUE_CAPACITY {
+            engine.post(|_| ());
+        }
+        assert_eq!(engine.call(|_| 1), None);
+        release.send(()).unwrap();
+        // A background read runs once the keys before it are done
+        assert_eq!(engine.query(|_| ()), Some(()));
+        assert_eq!(engine.call(|_| 1), Some(1));
+    }
+
+    #[test]
+    fn a_panicking_job_leaves_a_fresh_processor() {
+        let engine = engine();
+        engine.call(|processor| processor.process_key('a'));
+        assert_eq!(engine.call(|_| -> bool { panic!("job failed") }), None);
+        assert_eq!(engine.call(|processor| processor.is_buffer_empty()), Some(true));
+        assert_eq!(engine.query(|processor| processor.is_buffer_empty()), Some(true));
+    }
+}
@@ -1,3 +1,5 @@
+pub mod reporter;
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two reports for the same application
+pub const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most applications tracked at once; the one with the oldest error is dropped first
+pub const MAX_TRACKED_APPS: usize = 64;
+
+/// A batch of errors ready to be shown to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    /// Bundle path of the application the errors happened in
+    pub app: String,
+    /// Number of errors since the previous report for this app
+    pub count: usize,
+    /// Context of the most recent error
+    pub last_context: String,
+}
+
+impl ErrorReport {
+    /// Short application name for display (e.g. "Safari" for /Applications/Safari.app)
+    pub fn app_display_name(&self) -> String {
+        Path::new(&self.app)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&self.app)
+            .to_string()
+    }
+}
+
+#[derive(Debug, Default)]
+struct AppErrors {
+    pending: usize,
+    total: usize,
+    last_reported: Option<Instant>,
+    last_error: Option<Instant>,
+}
+
+/// Collects errors per application and rate-limits how often they are surfaced
+#[derive(Debug)]
+pub struct ErrorReporter {
+    interval: Duration,
+    apps: HashMap<String, AppErrors>,
+}
+
+impl ErrorReporter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            apps: HashMap::new(),
+        }
+    }
+
+    /// Record an error. Returns a report when one is due for this app,
+    /// i.e. on the first error and then at most once per interval.
+    pub fn record(&mut self, app: &str, context: &str) -> Option<ErrorReport> {
+        let now = Instant::now();
+        if self.apps.len() >= MAX_TRACKED_APPS && !self.apps.contains_key(app) {
+            self.evict_oldest();
+        }
+        let errors = self.apps.entry(app.to_string()).or_default();
+        errors.pending += 1;
+        errors.total += 1;
+        errors.last_error = Some(now);
+
+        let due = match errors.last_reported {
+            Some(last) => now.duration_since(last) >= self.interval,
+            None => true,
+        };
+        if !due {
+            return None;
+        }
+
+        let report = ErrorReport {
+            app: app.to_string(),
+            count: errors.pending,
+            last_context: context.to_string(),
+        };
+        errors.pending = 0;
+        errors.last_reported = Some(now);
+        Some(report)
+    }
+
+    /// Forget the application whose last error is the oldest
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .apps
+            .iter()
+            .min_by_key(|(_, errors)| errors.last_error)
+            .map(|(app, _)| app.clone());
+        if let Some(app) = oldest {
+            self.apps.remove(&app);
+        }
+    }
+
+    /// Number of applications with recorded errors
+    pub fn tracked_apps(&self) -> usize {
+        self.apps.len()
+    }
+
+    /// Approximate bytes held by the per-application counters
+    pub fn approx_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .apps
+                .keys()
+                .map(|app| app.capacity() + std::mem::size_of::<(String, AppErrors)>())
+                .sum::<usize>()
+    }
+
+    /// Total number of errors recorded for an app since startup
+    pub fn total_for(&self, app: &str) -> usize {
+        self.apps.get(app).map(|errors| errors.total).unwrap_or(0)
+    }
+}
+
+impl Default for ErrorReporter {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPORT_INTERVAL)
+    }
+}
@@ -1,46 +1,163 @@
 use gpui::{
-    App, AppContext, Application, Bounds, WindowBounds, WindowOptions, px, size
+    App, AppContext, Application
 };
 
+mod cli;
+mod control;
 mod core;
+mod engine_thread;
 mod error;
 mod platform;
+mod shutdown;
 mod ui;
 use std::thread;
 
+use engine_thread::EngineThread;
 use ui::VKeyApp;
-use core::AppConfig;
+use core::{AppConfig, InputMode, Normalization, SoundEvent};
 
 #[cfg(target_os = "macos")]
 use platform::system_integration;
 use platform::{
-    run_event_listener, send_backspace, send_string, CallbackFn, EventTapType, Handle, KeyModifier, PressedKey, KEY_ENTER, KEY_ESCAPE,
+    run_event_listener, send_backspace, send_cursor_move, send_string, CallbackFn, EventTapType, Handle, InjectionError, InjectionMethod, InjectionQueue, KeyModifier, PressedKey, KEY_ENTER, KEY_ESCAPE, KEY_FORWARD_DELETE,
     KEY_TAB, initialize_keyboard_layout, should_dismiss_selection_if_needed, dismiss_text_selection_if_needed,
 };
 
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::sync::mpsc::{self, Sender};
 use once_cell::sync::Lazy;
-use crate::core::{VietnameseInputProcessor, ProcessingResult};
+use log::info;
+use crate::core::{apply_result, route_key, BufferSnapshot, CompatCatalog, CompatEntry, Correction, CorrectionLog, EditOp, EmojiTable, Injector, MemoryReport, SchemeCatalog, TransformScheme, VietnameseInputProcessor, ProcessingResult, MacroTable, QualityMetrics, RestoreHabits, SuggestionEngine, TypingKey, TypingStats, UserDictionary, WeekStart};
+use crate::error::reporter::MAX_TRACKED_APPS;
+use crate::error::reporter::ErrorReporter;
 
 // Global state for Vietnamese input processing
 static VIETNAMESE_ENABLED: AtomicBool = AtomicBool::new(true); // Start with Vietnamese enabled by default
-static INPUT_PROCESSOR: Lazy<Mutex<VietnameseInputProcessor>> = Lazy::new(|| {
+static DECOMPOSED_OUTPUT: AtomicBool = AtomicBool::new(false); // Type NFD instead of NFC
+static SECURE_INPUT: AtomicBool = AtomicBool::new(false); // Keys go to a password field
+static PEEKING: AtomicBool = AtomicBool::new(false); // The peek hotkey is held and the word shows its keys
+static APP_EXCLUDED: AtomicBool = AtomicBool::new(false); // The frontmost app is in `excluded_apps`, set on app switch
+// The input processor, owned by a thread of its own and reached through jobs
+static ENGINE: Lazy<EngineThread> = Lazy::new(|| EngineThread::spawn(build_processor));
+
+/// The processor as configured and with the data saved last time
+fn build_processor() -> VietnameseInputProcessor {
     // Load config to get initial input type
     let config = AppConfig::load_default().unwrap_or_default();
-    
-    // Always start with Vietnamese enabled by default
-    VIETNAMESE_ENABLED.store(true, Ordering::Relaxed);
-    
-    Mutex::new(VietnameseInputProcessor::new(config.input_type))
-});
+    VIETNAMESE_ENABLED.store(config.is_vietnamese_enabled(), Ordering::Relaxed);
+    DECOMPOSED_OUTPUT.store(config.effective_normalization() == Normalization::Nfd, Ordering::Relaxed);
+    let mut processor = VietnameseInputProcessor::new(config.input_type);
+    processor.set_encoding(config.encoding);
+    processor.apply_settings(&config.advanced);
+    processor.set_word_limit(config.max_word_length, config.word_overflow);
+    processor.set_restore_patterns(config.active_restore_patterns());
+    processor.set_custom_diacritics(config.custom_diacritics.clone());
+    processor.set_transform_scheme(transform_scheme(&config));
+    #[cfg(target_os = "macos")]
+    processor.set_placeholder_source(Arc::new(platform::SystemPlaceholders));
+    match MacroTable::load_default() {
+        Ok(macros) => processor.set_macros(macros),
+        Err(e) => eprintln!("Failed to load macros: {}", e),
+    }
+    match EmojiTable::load_default() {
+        Ok(emoji) => processor.set_emoji(emoji),
+        Err(e) => eprintln!("Failed to load emoji shortcodes: {}", e),
+    }
+    match UserDictionary::load_default() {
+        Ok(dictionary) => processor.set_dictionary(dictionary),
+        Err(e) => eprintln!("Failed to load user dictionary: {}", e),
+    }
+    match SuggestionEngine::load_default() {
+        Ok(suggester) => processor.set_suggestion_engine(suggester),
+        Err(e) => eprintln!("Failed to load typing history: {}", e),
+    }
+    match TypingStats::load_default() {
+        Ok(stats) => processor.set_stats(stats),
+        Err(e) => eprintln!("Failed to load typing statistics: {}", e),
+    }
+    match QualityMetrics::load_default() {
+        Ok(quality) => processor.set_quality(quality),
+        Err(e) => eprintln!("Failed to load quality metrics: {}", e),
+    }
+    match RestoreHabits::load_default() {
+        Ok(habits) => processor.set_habits(habits),
+        Err(e) => eprintln!("Failed to load learned restores: {}", e),
+    }
+    match CorrectionLog::load_default() {
+        Ok(corrections) => {
+            processor.set_corrections(corrections);
+            processor.purge_corrections(config.advanced.correction_retention_days);
+        }
+        Err(e) => eprintln!("Failed to load correction log: {}", e),
+    }
+    processor
+}
+
+// How often changed typing statistics are written to disk
+const STATS_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+// How often the daemon checks whether the config file changed
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+// How often the event tap is checked for having been switched off by macOS
+const TAP_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+// When the process started, for startup timing logs
+static LAUNCHED_AT: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Log how long after launch a startup step finished
+fn log_startup(step: &str) {
+    eprintln!("[startup] {} after {} ms", step, LAUNCHED_AT.elapsed().as_millis());
+}
 
 // Global configuration
 static GLOBAL_CONFIG: Lazy<Mutex<AppConfig>> = Lazy::new(|| {
     Mutex::new(AppConfig::load_default().unwrap_or_default())
 });
 
+// Injection failures, rate-limited before they are surfaced in the UI
+static INJECTION_ERRORS: Lazy<Mutex<ErrorReporter>> = Lazy::new(|| {
+    Mutex::new(ErrorReporter::default())
+});
+
+// What VKey does in the frontmost application, for the control socket
+static APP_POLICY: Lazy<Mutex<control::AppPolicy>> = Lazy::new(|| {
+    Mutex::new(control::AppPolicy::default())
+});
+
+// Whether the focused app accepts marked (pre-edit) text, and what is currently marked
+static MARKED_TEXT_SUPPORTED: AtomicBool = AtomicBool::new(false);
+static MARKED_TEXT: Mutex<String> = Mutex::new(String::new());
+
+// The cursor moved by a click or jump; the word before it may be taken up
+static RESEED_PENDING: AtomicBool = AtomicBool::new(false);
+
+// Serializes injected backspaces and text so erases always land before insertions
+static INJECTION_QUEUE: Lazy<Mutex<InjectionQueue>> = Lazy::new(|| {
+    let config = AppConfig::load_default().unwrap_or_default();
+    let mut queue = InjectionQueue::new();
+    queue.set_batch_delay(Duration::from_millis(config.advanced.injection_delay_ms));
+    Mutex::new(queue)
+});
+
+// Recommended injection settings for apps with known quirks
+static COMPAT_CATALOG: Lazy<CompatCatalog> = Lazy::new(|| {
+    CompatCatalog::load_default().unwrap_or_else(|e| {
+        eprintln!("Failed to load compatibility catalog: {}", e);
+        CompatCatalog::builtin()
+    })
+});
+
+// Language packs that can stand in for the Vietnamese input method
+static SCHEME_CATALOG: Lazy<SchemeCatalog> = Lazy::new(|| {
+    SchemeCatalog::load_default().unwrap_or_else(|e| {
+        eprintln!("Failed to load language packs: {}", e);
+        SchemeCatalog::builtin()
+    })
+});
+
 // Global hotkey state
 static mut HOTKEY_MODIFIERS: KeyModifier = KeyModifier::MODIFIER_NONE;
 static HOTKEY_MATCHING: AtomicBool = AtomicBool::new(false);
@@ -53,8 +170,18 @@ const RAW_KEY_GLOBE: u16 = 179; // Globe key on Mac keyboards
 pub enum SystemTrayEvent {
     ShowUI,
     ToggleVietnamese,
+    /// Quit through the shutdown coordinator
+    Quit,
+    /// Add the selected word to the user dictionary, or remove it
+    ToggleDictionaryWord,
     SetInputTypeTelex,
     SetInputTypeVNI,
+    /// Injection errors piled up in an application
+    InjectionErrors { app: String, count: usize },
+    /// The weekly summary notification was clicked
+    ShowStatistics,
+    /// The current keyboard layout stopped or started being readable
+    KeyboardLayout { available: bool },
 }
 
 // Global system tray event channel
@@ -74,16 +201,80 @@ pub fn send_system_tray_event(event: SystemTrayEvent) {
 }
 
 fn main() {
+    Lazy::force(&LAUNCHED_AT);
+    // Per-app decisions are logged at info level; RUST_LOG overrides it
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    // Files move into their subdirectories before anything reads them
+    match core::data_dir::migrate() {
+        Ok(moved) if !moved.is_empty() => eprintln!("Moved {} data files into the new layout", moved.len()),
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to migrate data directory: {}", e),
+    }
+
+    // Handle one-shot command line actions before starting the application
+    match cli::parse_args(std::env::args().skip(1)) {
+        Ok(cli::CliCommand::Run) => {}
+        Ok(cli::CliCommand::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(cli::CliCommand::Daemon) => {
+            shutdown::handle_termination_signals();
+            run_daemon();
+            return;
+        }
+        Ok(cli::CliCommand::Status) => {
+            if let Err(e) = cli::run_status_command() {
+                eprintln!("Failed to read VKey status: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(cli::CliCommand::Type { input_type, delay_ms, text }) => {
+            if let Err(e) = cli::run_type_command(input_type, delay_ms, &text) {
+                eprintln!("Failed to type text: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    shutdown::handle_termination_signals();
+
     eprintln!("Starting VKey application...");
+
+    // Hooks run last to first, so this runs once the event tap is off
+    shutdown::register("engine state", flush_engine_state);
     
-    // Initialize platform-specific components
+    // Keys use the US layout until the layout map is built
     #[cfg(target_os = "macos")]
-    platform::initialize_keyboard_layout();
+    thread::spawn(|| {
+        platform::initialize_keyboard_layout();
+        log_startup("keyboard layout map built");
+    });
     
     let result = std::panic::catch_unwind(|| {
         // Check and request permissions before starting the application
         #[cfg(target_os = "macos")]
         {
+            // A moved app keeps a login item for its old path, and macOS may
+            // no longer apply the permissions granted to the old copy
+            if let Some(relocation) = platform::relocation::detect() {
+                eprintln!("VKey moved from {} to {}", relocation.from, relocation.to);
+                platform::relocation::repair_launch_entries();
+                if !platform::Permissions::check().can_tap() || !system_integration::has_accessibility_permissions() {
+                    platform::notification::post_notification(
+                        "VKey was moved",
+                        "Remove the old VKey from Accessibility and Input Monitoring in System Settings, then allow this copy.",
+                    );
+                }
+            }
+
             eprintln!("Checking accessibility permissions...");
             if !system_integration::has_accessibility_permissions() {
                 eprintln!("Accessibility permissions not granted. Requesting permissions...");
@@ -108,12 +299,49 @@ fn main() {
                 eprintln!("Accessibility permissions already granted!");
             }
             
-            // Note: Keyboard hook will be installed by VKeyApp during initialization
+            // Typing only needs the tap; selection heuristics are turned off
+            // when the accessibility queries are not allowed
+            let permissions = platform::permissions::refresh();
+            eprintln!("Permissions: {}", permissions);
+
+            // Protect keystrokes before anything else; the window and tray come later
+            if permissions.can_tap() {
+                shutdown::register("event tap", || {
+                    if let Err(e) = system_integration::remove_keyboard_hook() {
+                        eprintln!("Failed to remove keyboard hook: {}", e);
+                    }
+                });
+                // An installed LaunchAgent runs the daemon, which already taps the keyboard
+                if platform::launchd::is_installed() {
+                    eprintln!("VKey LaunchAgent is installed; leaving typing to the daemon");
+                } else {
+                    start_event_tap();
+                    start_control_socket();
+                }
+                // Watches whichever tap this process installs, now or after
+                // the LaunchAgent is turned off
+                start_tap_watchdog();
+            }
         }
 
+        // Load and validate the config and build the engine off the event thread,
+        // so the first keystroke does not pay for it
+        thread::spawn(|| {
+            Lazy::force(&GLOBAL_CONFIG);
+            ENGINE.call(|_| ());
+            log_startup("engine ready");
+        });
+        start_stats_autosave();
+
         Application::new().run(|cx: &mut App| {
             gpui_component::init(cx);
 
+            // Quitting from the window or the tray ends up here
+            cx.on_app_quit(|_| async {
+                shutdown::run();
+            })
+            .detach();
+
             eprintln!("Creating window...");
             
             // Set up system tray event channel
@@ -121,68 +349,806 @@ fn main() {
             if let Ok(mut sender_guard) = SYSTEM_TRAY_SENDER.lock() {
                 *sender_guard = Some(sender);
             }
-            
-            let bounds = Bounds::centered(None, size(px(650.), px(560.)), cx);
-            match cx.open_window(
-                WindowOptions {
-                    window_bounds: Some(WindowBounds::Windowed(bounds)),
-                    ..Default::default()
-                },
-                |_, cx| {
-                    eprintln!("Initializing VKeyApp...");
-                    cx.new(|_| {
-                        let mut app = VKeyApp::new_with_system_tray_receiver(Some(receiver));
-                        
-                        // Mark permissions as checked since we did it in main
-                        app.set_permissions_checked(true);
-                        
-                        // Initialize the system tray
-                        match app.initialize_system_tray() {
-                            Ok(_) => eprintln!("VKeyApp system tray initialized successfully"),
-                            Err(e) => eprintln!("Failed to initialize VKeyApp system tray: {}", e),
-                        }
 
-                        // Initialize the keyboard system integration
-                        match app.initialize_keyboard_system() {
-                            Ok(_) => {
-                                thread::spawn(|| {
-                                    let handler = Box::new(event_handler) as CallbackFn;
-                                    run_event_listener(&handler);
-                                });
-                                eprintln!("VKeyApp keyboard system initialized successfully");
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to initialize VKeyApp keyboard system: {}", e);
-                            }
-                        }
-                        
-                        eprintln!("VKeyApp initialized successfully");
-                        app
-                    })
-                },
-            ) {
-                Ok(_) => eprintln!("Window created successfully"),
-                Err(e) => eprintln!("Failed to create window: {:?}", e),
+            // Re-apply per-application settings whenever another app comes to the front
+            #[cfg(target_os = "macos")]
+            {
+                platform::add_app_change_callback(on_active_app_changed);
+                on_active_app_changed();
             }
+
+            // Clicking the weekly summary opens the statistics
+            #[cfg(target_os = "macos")]
+            platform::notification::set_click_handler(|| {
+                send_system_tray_event(SystemTrayEvent::ShowStatistics);
+            });
+
+            // Warn when keys cannot be read through the keyboard layout
+            #[cfg(target_os = "macos")]
+            platform::keylayout::set_status_handler(|available| {
+                send_system_tray_event(SystemTrayEvent::KeyboardLayout { available });
+            });
+            
+            eprintln!("Initializing VKeyApp...");
+            let view = cx.new(|cx| {
+                let mut app = VKeyApp::new_with_system_tray_receiver(Some(receiver));
+                app.start_buffer_polling(cx);
+                app.start_tray_event_processing(cx);
+                
+                // Mark permissions as checked since we did it in main
+                app.set_permissions_checked(true);
+
+                // The event tap is already running; this only syncs the saved mode
+                match app.initialize_keyboard_system() {
+                    Ok(_) => eprintln!("VKeyApp keyboard system initialized successfully"),
+                    Err(e) => eprintln!("Failed to initialize VKeyApp keyboard system: {}", e),
+                }
+                
+                eprintln!("VKeyApp initialized successfully");
+                app
+            });
+
+            // The view outlives its window, so the tray keeps working when it is closed
+            VKeyApp::show_window(view.clone(), cx);
+            log_startup("window shown");
+
+            // The tray is not needed for typing, so it waits until the window is up
+            cx.defer(move |cx| {
+                view.update(cx, |app, _| match app.initialize_system_tray() {
+                    Ok(_) => eprintln!("VKeyApp system tray initialized successfully"),
+                    Err(e) => eprintln!("Failed to initialize VKeyApp system tray: {}", e),
+                });
+                log_startup("system tray ready");
+            });
         });
     });
     
     match result {
         Ok(_) => {
             eprintln!("Application finished normally");
-            
-            // Clean up the keyboard hook when the application exits
-            #[cfg(target_os = "macos")]
-            {
-                eprintln!("Cleaning up keyboard hook...");
-                if let Err(e) = system_integration::remove_keyboard_hook() {
-                    eprintln!("Failed to remove keyboard hook: {}", e);
-                } else {
-                    eprintln!("Keyboard hook removed successfully");
+            shutdown::run();
+        }
+        Err(e) => eprintln!("Application panicked: {:?}", e),
+    }
+}
+
+/// Run the input method without a window or menu bar icon, for launchd and
+/// other service managers. The event tap runs on the main thread until a
+/// termination signal stops it.
+#[cfg(target_os = "macos")]
+fn run_daemon() {
+    eprintln!("Starting VKey in daemon mode...");
+    shutdown::register("engine state", flush_engine_state);
+
+    // There is no window to ask from, so the permission must already be granted
+    let permissions = platform::permissions::refresh();
+    eprintln!("Permissions: {}", permissions);
+    if !permissions.can_tap() {
+        eprintln!("Input Monitoring and Accessibility permissions are required in daemon mode. Grant them by starting VKey normally once.");
+        std::process::exit(1);
+    }
+
+    thread::spawn(|| {
+        platform::initialize_keyboard_layout();
+        log_startup("keyboard layout map built");
+    });
+    Lazy::force(&GLOBAL_CONFIG);
+    ENGINE.call(|_| ());
+    log_startup("engine ready");
+    start_stats_autosave();
+    start_config_watch();
+    start_control_socket();
+    start_tap_watchdog();
+
+    platform::add_app_change_callback(on_active_app_changed);
+    on_active_app_changed();
+
+    shutdown::register("event tap", || {
+        if let Err(e) = system_integration::remove_keyboard_hook() {
+            eprintln!("Failed to remove keyboard hook: {}", e);
+        }
+    });
+    log_startup("installing event tap");
+    let handler = Box::new(event_handler) as CallbackFn;
+    run_event_listener(&handler);
+
+    // The run loop only returns once the tap has been stopped
+    shutdown::run();
+}
+
+/// Install the event tap on its own thread. Stopped through
+/// `system_integration::remove_keyboard_hook`.
+#[cfg(target_os = "macos")]
+pub fn start_event_tap() {
+    thread::spawn(|| {
+        log_startup("installing event tap");
+        let handler = Box::new(event_handler) as CallbackFn;
+        run_event_listener(&handler);
+    });
+}
+
+/// Check every `TAP_WATCH_INTERVAL` that macOS has not switched the event
+/// tap off. The tap re-enables itself when it is told, but the notice is
+/// not always delivered, as after waking from sleep, and VKey would then
+/// stop typing until restarted. Started once, from `main` or `run_daemon`;
+/// the tap itself may be installed again later, as when the LaunchAgent
+/// setting is toggled.
+#[cfg(target_os = "macos")]
+fn start_tap_watchdog() {
+    static STARTED: std::sync::Once = std::sync::Once::new();
+    STARTED.call_once(|| {
+        thread::spawn(|| loop {
+            thread::sleep(TAP_WATCH_INTERVAL);
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if platform::reenable_event_tap() {
+                eprintln!("Event tap found disabled; enabled it again");
+                // Keys typed meanwhile went straight to the application
+                ENGINE.post(|processor| processor.clear_buffer());
+            }
+        });
+    });
+}
+
+/// Answer status requests on the control socket while this process types.
+/// The socket is removed on shutdown.
+fn start_control_socket() {
+    match control::start(engine_status) {
+        Ok(()) => shutdown::register("control socket", || {
+            if let Ok(path) = control::socket_path() {
+                let _ = std::fs::remove_file(path);
+            }
+        }),
+        Err(e) => eprintln!("Failed to start control socket: {}", e),
+    }
+}
+
+/// The engine state reported by the `status` command
+fn engine_status() -> control::Status {
+    let (input_type, transform_scheme, buffer_empty) = ENGINE
+        .query(|processor| {
+            (
+                processor.get_input_type(),
+                processor.get_transform_scheme().map(str::to_string),
+                processor.is_buffer_empty(),
+            )
+        })
+        .unwrap_or((core::InputType::Telex, None, true));
+    #[cfg(target_os = "macos")]
+    let tap = match platform::event_tap_enabled() {
+        Some(true) => control::TapHealth::Running,
+        Some(false) => control::TapHealth::Disabled,
+        None => control::TapHealth::Stopped,
+    };
+    #[cfg(not(target_os = "macos"))]
+    let tap = control::TapHealth::Stopped;
+
+    control::Status {
+        enabled: VIETNAMESE_ENABLED.load(Ordering::Relaxed),
+        input_type,
+        transform_scheme,
+        app_policy: app_policy(),
+        buffer_empty,
+        tap,
+        version: env!("CARGO_PKG_VERSION"),
+    }
+}
+
+/// Pick up settings saved by the VKey window while running as a daemon.
+/// The config file is checked every `CONFIG_WATCH_INTERVAL`.
+#[cfg(target_os = "macos")]
+fn start_config_watch() {
+    thread::spawn(|| {
+        let modified = || {
+            AppConfig::get_config_path()
+                .ok()
+                .and_then(|path| std::fs::metadata(path).ok())
+                .and_then(|metadata| metadata.modified().ok())
+        };
+        let mut last_modified = modified();
+        let mut last_mode = GLOBAL_CONFIG.lock().map(|config| config.input_mode).ok();
+        loop {
+            thread::sleep(CONFIG_WATCH_INTERVAL);
+            let current = modified();
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            let config = match AppConfig::load_default() {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Failed to reload config: {}", e);
+                    continue;
+                }
+            };
+            apply_config_to_engine(&config);
+            // Only follow the saved mode when it changed, not when the file
+            // still holds a mode the hotkey has switched away from since
+            if last_mode != Some(config.input_mode) {
+                last_mode = Some(config.input_mode);
+                set_vietnamese_enabled(config.is_vietnamese_enabled());
+            }
+            eprintln!("Config reloaded");
+        }
+    });
+}
+
+/// Write typing statistics, quality metrics, learned restores and the
+/// correction log to disk every `STATS_SAVE_INTERVAL` if they changed, so a
+/// crash loses at most that much. Shutdown saves them as well.
+fn start_stats_autosave() {
+    thread::spawn(|| {
+        let mut saved = typing_stats();
+        let mut saved_quality = quality_metrics();
+        let mut saved_habits = restore_habits();
+        // Compared with the file, so a log switched off or purged at startup is written
+        let mut saved_corrections = CorrectionLog::load_default().unwrap_or_default();
+        loop {
+            thread::sleep(STATS_SAVE_INTERVAL);
+            let stats = typing_stats();
+            check_weekly_summary(stats);
+            if stats != saved {
+                match stats.save_default() {
+                    Ok(()) => saved = stats,
+                    Err(e) => eprintln!("Failed to save typing statistics: {}", e),
+                }
+            }
+            let quality = quality_metrics();
+            if quality != saved_quality {
+                match quality.save_default() {
+                    Ok(()) => saved_quality = quality,
+                    Err(e) => eprintln!("Failed to save quality metrics: {}", e),
+                }
+            }
+            let habits = restore_habits();
+            if habits != saved_habits {
+                match habits.save_default() {
+                    Ok(()) => saved_habits = habits,
+                    Err(e) => eprintln!("Failed to save learned restores: {}", e),
+                }
+            }
+            let corrections = purged_corrections();
+            if corrections != saved_corrections {
+                match corrections.save_default() {
+                    Ok(()) => saved_corrections = corrections,
+                    Err(e) => eprintln!("Failed to save correction log: {}", e),
                 }
             }
         }
-        Err(e) => eprintln!("Application panicked: {:?}", e),
+    });
+}
+
+/// Post the weekly summary once a week has passed since the last one, if it
+/// is switched on. The week starts over whether or not it is posted.
+fn check_weekly_summary(stats: TypingStats) {
+    let enabled = GLOBAL_CONFIG
+        .lock()
+        .map(|config| config.advanced.weekly_summary)
+        .unwrap_or(false);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+
+    let week = match WeekStart::load_default() {
+        Ok(Some(week)) => week,
+        Ok(None) => {
+            // First run: the first summary comes a week from now
+            if let Err(e) = WeekStart::new(now, stats).save_default() {
+                eprintln!("Failed to save weekly summary start: {}", e);
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to load weekly summary start: {}", e);
+            return;
+        }
+    };
+    if !week.is_over(now) {
+        return;
+    }
+    if let Err(e) = WeekStart::new(now, stats).save_default() {
+        eprintln!("Failed to save weekly summary start: {}", e);
+        return;
+    }
+
+    let summary = stats.since(&week.totals);
+    if enabled && summary.words > 0 {
+        #[cfg(target_os = "macos")]
+        platform::notification::post_notification("VKey", &summary.weekly_summary());
+    }
+}
+
+/// Current typing statistics, for the diagnostics window
+pub fn typing_stats() -> TypingStats {
+    ENGINE.query(|processor| processor.get_stats()).unwrap_or_default()
+}
+
+/// Current restores and corrections per input method, for the diagnostics window
+pub fn quality_metrics() -> QualityMetrics {
+    ENGINE.query(|processor| processor.get_quality()).unwrap_or_default()
+}
+
+/// Start the quality metrics over, on screen and on disk
+pub fn reset_quality_metrics() {
+    ENGINE.post(|processor| processor.reset_quality());
+    if let Err(e) = QualityMetrics::default().save_default() {
+        eprintln!("Failed to save quality metrics: {}", e);
+    }
+}
+
+fn restore_habits() -> RestoreHabits {
+    ENGINE.query(|processor| processor.get_habits().clone()).unwrap_or_default()
+}
+
+/// Number of words whose restore habit was learned, for the settings window
+pub fn learned_restore_count() -> usize {
+    ENGINE.query(|processor| processor.get_habits().len()).unwrap_or(0)
+}
+
+/// Forget the learned restore habits, in the engine and on disk
+pub fn reset_restore_habits() {
+    ENGINE.post(|processor| processor.reset_habits());
+    if let Err(e) = RestoreHabits::new().save_default() {
+        eprintln!("Failed to save learned restores: {}", e);
+    }
+}
+
+/// The correction log, without entries older than the retention period
+fn purged_corrections() -> CorrectionLog {
+    let retention_days = GLOBAL_CONFIG
+        .lock()
+        .map(|config| config.advanced.correction_retention_days)
+        .unwrap_or(crate::core::corrections::DEFAULT_CORRECTION_RETENTION_DAYS);
+    ENGINE
+        .query(move |processor| {
+            processor.purge_corrections(retention_days);
+            processor.get_corrections().clone()
+        })
+        .unwrap_or_default()
+}
+
+/// Words restored automatically, oldest first, for the diagnostics window
+pub fn correction_log() -> Vec<Correction> {
+    ENGINE
+        .query(|processor| processor.get_corrections().entries().to_vec())
+        .unwrap_or_default()
+}
+
+/// Stop restoring the word typed with `keys`: it joins the user dictionary
+/// and leaves the correction log, on screen and on disk
+pub fn keep_corrected_word(keys: &str) {
+    let keys = keys.to_string();
+    let kept = ENGINE.query(move |processor| {
+        processor.keep_corrected_word(&keys);
+        (
+            processor.get_dictionary().clone(),
+            processor.get_habits().clone(),
+            processor.get_corrections().clone(),
+        )
+    });
+    if let Some((dictionary, habits, corrections)) = kept {
+        if let Err(e) = dictionary.save_default() {
+            eprintln!("Failed to save user dictionary: {}", e);
+        }
+        if let Err(e) = habits.save_default() {
+            eprintln!("Failed to save learned restores: {}", e);
+        }
+        if let Err(e) = corrections.save_default() {
+            eprintln!("Failed to save correction log: {}", e);
+        }
+    }
+}
+
+/// Empty the correction log, in the engine and on disk
+pub fn clear_correction_log() {
+    ENGINE.post(|processor| processor.clear_corrections());
+    if let Err(e) = CorrectionLog::new().save_default() {
+        eprintln!("Failed to save correction log: {}", e);
+    }
+}
+
+/// Write the input mode, user dictionary, typing history and statistics to
+/// disk and drop any marked text. Everything else is saved as soon as it changes.
+/// Keys pass by the engine while shutting down, so it saves them itself.
+fn flush_engine_state() {
+    update_marked_text("");
+    if let Ok(config) = GLOBAL_CONFIG.lock() {
+        if let Ok(mut saved) = AppConfig::load_default() {
+            if saved.input_mode != config.input_mode {
+                saved.input_mode = config.input_mode;
+                if let Err(e) = saved.save_default() {
+                    eprintln!("Failed to save input mode: {}", e);
+                }
+            }
+        }
+    }
+    let state = ENGINE.call(|processor| {
+        let suggester = processor.get_suggestion_engine();
+        (
+            processor.get_dictionary().clone(),
+            (suggester.history_len() > 0).then(|| suggester.clone()),
+            processor.get_stats(),
+            processor.get_quality(),
+            processor.get_habits().clone(),
+            processor.get_corrections().clone(),
+        )
+    });
+    let Some((dictionary, history, stats, quality, habits, corrections)) = state else {
+        return;
+    };
+    if let Err(e) = dictionary.save_default() {
+        eprintln!("Failed to save user dictionary: {}", e);
+    }
+    if let Some(history) = history {
+        if let Err(e) = history.save_default() {
+            eprintln!("Failed to save typing history: {}", e);
+        }
+    }
+    if let Err(e) = stats.save_default() {
+        eprintln!("Failed to save typing statistics: {}", e);
+    }
+    if let Err(e) = quality.save_default() {
+        eprintln!("Failed to save quality metrics: {}", e);
+    }
+    if let Err(e) = habits.save_default() {
+        eprintln!("Failed to save learned restores: {}", e);
+    }
+    if let Err(e) = corrections.save_default() {
+        eprintln!("Failed to save correction log: {}", e);
+    }
+}
+
+/// Push configuration changes made in the UI to the engine
+pub fn apply_config_to_engine(config: &AppConfig) {
+    if let Ok(mut global_config) = GLOBAL_CONFIG.lock() {
+        global_config.input_type = config.input_type;
+        global_config.encoding = config.encoding;
+        global_config.output_normalization = config.output_normalization;
+        global_config.advanced = config.advanced.clone();
+        global_config.max_word_length = config.max_word_length;
+        global_config.word_overflow = config.word_overflow;
+        global_config.preview_suppressed_apps = config.preview_suppressed_apps.clone();
+        global_config.excluded_apps = config.excluded_apps.clone();
+        global_config.accessibility_replacement_apps = config.accessibility_replacement_apps.clone();
+        global_config.compat_overrides = config.compat_overrides.clone();
+        global_config.restore_patterns = config.restore_patterns.clone();
+        global_config.transform_scheme = config.transform_scheme.clone();
+        global_config.custom_diacritics = config.custom_diacritics.clone();
+        global_config.undo_conversion_hotkey = config.undo_conversion_hotkey.clone();
+        global_config.remove_diacritics_hotkey = config.remove_diacritics_hotkey.clone();
+        global_config.dictionary_hotkey = config.dictionary_hotkey.clone();
+        global_config.peek_hotkey = config.peek_hotkey.clone();
+        global_config.keyboard = config.keyboard.clone();
+    }
+
+    DECOMPOSED_OUTPUT.store(config.effective_normalization() == Normalization::Nfd, Ordering::Relaxed);
+
+    let (input_type, encoding) = (config.input_type, config.encoding);
+    let advanced = config.advanced.clone();
+    let (max_word_length, word_overflow) = (config.max_word_length, config.word_overflow);
+    let restore_patterns = config.active_restore_patterns();
+    let custom_diacritics = config.custom_diacritics.clone();
+    let scheme = transform_scheme(config);
+    ENGINE.post(move |processor| {
+        if processor.get_input_type() != input_type {
+            processor.set_input_type(input_type);
+        }
+        if processor.get_encoding() != encoding {
+            processor.set_encoding(encoding);
+        }
+        processor.apply_settings(&advanced);
+        processor.set_word_limit(max_word_length, word_overflow);
+        processor.set_restore_patterns(restore_patterns);
+        processor.set_custom_diacritics(custom_diacritics);
+        processor.set_transform_scheme(scheme);
+    });
+
+    let compat = COMPAT_CATALOG.entry_for(&platform::get_active_app_name(), config);
+    if let Ok(mut queue) = INJECTION_QUEUE.lock() {
+        queue.set_batch_delay(injection_delay(config, compat));
+    }
+}
+
+/// The language pack chosen in the settings, None for Vietnamese or a pack
+/// that does not exist
+fn transform_scheme(config: &AppConfig) -> Option<Arc<dyn TransformScheme>> {
+    let name = config.transform_scheme.as_deref()?;
+    let scheme = SCHEME_CATALOG.get(name);
+    if scheme.is_none() {
+        eprintln!("Unknown language pack '{}', typing Vietnamese", name);
+    }
+    scheme
+}
+
+/// The wait between erasing a word and typing it: the user's own setting,
+/// or the catalog's for the app if that is longer
+fn injection_delay(config: &AppConfig, compat: Option<&CompatEntry>) -> Duration {
+    let catalog_ms = compat.map_or(0, |entry| entry.injection_delay_ms);
+    Duration::from_millis(config.advanced.injection_delay_ms.max(catalog_ms))
+}
+
+/// How replacements reach `app`. The user's own list wins over the catalog.
+fn injection_method(config: &AppConfig, app: &str, compat: Option<&CompatEntry>) -> InjectionMethod {
+    if config.uses_accessibility_replacement(app) {
+        InjectionMethod::Accessibility
+    } else if compat.is_some_and(|entry| entry.paste) {
+        InjectionMethod::Paste
+    } else {
+        InjectionMethod::Keystrokes
+    }
+}
+
+/// Type a word that was held back until it was committed
+fn type_pending_word(handle: Handle, pending: &str) {
+    update_marked_text("");
+    report_injection_error(replace_text(handle, 0, pending));
+}
+
+/// Take up the word before the caret, if that is switched on, so a tone key
+/// typed after clicking into a word still changes it
+fn reseed_from_caret() {
+    let enabled = GLOBAL_CONFIG
+        .lock()
+        .map(|config| config.advanced.reseed_from_caret)
+        .unwrap_or(false);
+    if !enabled {
+        return;
+    }
+    #[cfg(target_os = "macos")]
+    if let Some(word) = platform::word_before_caret() {
+        ENGINE.post(move |processor| {
+            if processor.is_buffer_empty() && processor.seed_word(&word) {
+                eprintln!("Took up '{}' from before the caret", word);
+            }
+        });
+    }
+}
+
+/// Apply per-application settings when the frontmost application changes
+fn on_active_app_changed() {
+    let app = platform::get_active_app_name();
+    // Check the field focused in the new application before keys reach it
+    platform::refresh_secure_field();
+    let bundle_id = platform::get_active_app_bundle_id().unwrap_or_default();
+    let mut reasons = Vec::new();
+    let (excluded, suppress_preview, no_marked_text, method, delay, compat_name, log_decisions) = match GLOBAL_CONFIG.lock() {
+        Ok(config) => {
+            let excluded = config.is_excluded_app(&bundle_id);
+            if excluded {
+                reasons.push(format!("all keys pass through: {} is in the excluded apps", bundle_id));
+            }
+            let compat = COMPAT_CATALOG.entry_for(&app, &config);
+            if let Some(entry) = compat {
+                eprintln!("Applying compatibility settings for {}", entry.name);
+            }
+            if config.is_preview_suppressed_for(&app) {
+                reasons.push("commit only: listed in the apps without preview".to_string());
+            }
+            if let Some(entry) = compat {
+                if entry.commit_only {
+                    reasons.push(format!("commit only: compatibility entry {}", entry.name));
+                }
+                if entry.no_marked_text {
+                    reasons.push(format!("no marked text: compatibility entry {}", entry.name));
+                }
+            }
+            let delay = injection_delay(&config, compat);
+            if !delay.is_zero() {
+                let source = match compat {
+                    Some(entry) if entry.injection_delay_ms > config.advanced.injection_delay_ms => {
+                        format!("compatibility entry {}", entry.name)
+                    }
+                    _ => "injection delay setting".to_string(),
+                };
+                reasons.push(format!("{} ms between erasing and retyping: {}", delay.as_millis(), source));
+            }
+            let method = injection_method(&config, &app, compat);
+            match (method, compat) {
+                (InjectionMethod::Accessibility, _) => reasons.push(
+                    "words typed over through accessibility: listed in the accessibility replacement apps".to_string(),
+                ),
+                (InjectionMethod::Paste, Some(entry)) => {
+                    reasons.push(format!("text pasted from the clipboard: compatibility entry {}", entry.name))
+                }
+                _ => {}
+            }
+            (
+                excluded,
+                config.is_preview_suppressed_for(&app) || compat.is_some_and(|entry| entry.commit_only),
+                compat.is_some_and(|entry| entry.no_marked_text),
+                method,
+                delay,
+                compat.map(|entry| entry.name.clone()),
+                config.advanced.log_app_decisions,
+            )
+        }
+        Err(_) => (false, false, false, InjectionMethod::Keystrokes, Duration::ZERO, None, false),
+    };
+    APP_EXCLUDED.store(excluded, Ordering::Relaxed);
+    let terminal_safe = platform::is_terminal_app(&app);
+    if terminal_safe {
+        reasons.push("letters typed with their marks in one event: terminal emulator".to_string());
+    }
+    if let Ok(mut queue) = INJECTION_QUEUE.lock() {
+        queue.set_batch_delay(delay);
+        queue.set_terminal_safe(terminal_safe);
+        queue.set_method(method);
+    }
+
+    // Apps that accept marked text show the word as pre-edit, which needs the
+    // word to be held back until it is committed
+    let marked_text = !no_marked_text && platform::supports_marked_text();
+    if marked_text {
+        reasons.push("marked text: the focused field accepts it".to_string());
+    }
+    MARKED_TEXT_SUPPORTED.store(marked_text, Ordering::Relaxed);
+    if let Ok(mut shown) = MARKED_TEXT.lock() {
+        shown.clear();
+    }
+
+    // Keys meant for the word in the previous application must not erase
+    // text in this one, by backspace, Escape or undoing a conversion
+    PEEKING.store(false, Ordering::Relaxed);
+    RESEED_PENDING.store(false, Ordering::Relaxed);
+    let active_app = app.clone();
+    ENGINE.post(move |processor| {
+        processor.reset();
+        processor.set_preview_suppressed(suppress_preview || marked_text);
+        processor.set_active_app(&active_app);
+    });
+
+    if log_decisions {
+        if reasons.is_empty() {
+            info!("{}: typing each key as it comes, no per-app rule applies", app);
+        }
+        for reason in &reasons {
+            info!("{}: {}", app, reason);
+        }
+    }
+
+    if let Ok(mut policy) = APP_POLICY.lock() {
+        *policy = control::AppPolicy {
+            app,
+            excluded,
+            commit_only: suppress_preview,
+            marked_text,
+            terminal_safe,
+            accessibility_replacement: method == InjectionMethod::Accessibility,
+            pasted: method == InjectionMethod::Paste,
+            compat: compat_name,
+            reasons,
+        };
+    }
+}
+
+/// What VKey does in the frontmost application, and why
+pub fn app_policy() -> control::AppPolicy {
+    APP_POLICY.lock().map(|policy| policy.clone()).unwrap_or_default()
+}
+
+/// Show the word being composed as marked text in apps that support it.
+/// An empty preedit removes the marked text before the word is typed for real.
+fn update_marked_text(preedit: &str) {
+    if !MARKED_TEXT_SUPPORTED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut shown = match MARKED_TEXT.lock() {
+        Ok(shown) => shown,
+        Err(_) => return,
+    };
+    if *shown == preedit {
+        return;
+    }
+
+    match platform::set_marked_text(&shown, preedit) {
+        Ok(()) => *shown = preedit.to_string(),
+        Err(error) => {
+            // Fall back to committing the word without a preview until the next app switch
+            MARKED_TEXT_SUPPORTED.store(false, Ordering::Relaxed);
+            shown.clear();
+            report_injection_error(Err(error));
+        }
+    }
+}
+
+/// Replace the last `buffer_length` characters on screen with `text`.
+/// The text is not sent if deleting the old characters failed.
+fn replace_text(handle: Handle, buffer_length: usize, text: &str) -> Result<(), InjectionError> {
+    inject_edits(handle, &EditOp::replacement(buffer_length, text))
+}
+
+/// Make `edits` in the focused field, in order. Nothing after a failed step is sent.
+fn inject_edits(handle: Handle, edits: &[EditOp]) -> Result<(), InjectionError> {
+    // A backspace removes a letter with its combining marks, so the engine
+    // keeps counting in composed characters and only the typed text changes
+    let decomposed: Vec<EditOp>;
+    let edits = if DECOMPOSED_OUTPUT.load(Ordering::Relaxed) {
+        decomposed = edits
+            .iter()
+            .map(|edit| match edit {
+                EditOp::Insert(text) => EditOp::Insert(crate::core::normalize(text, Normalization::Nfd)),
+                other => other.clone(),
+            })
+            .collect();
+        &decomposed
+    } else {
+        edits
+    };
+
+    match INJECTION_QUEUE.lock() {
+        Ok(mut queue) => {
+            queue.push_edits(edits);
+            queue.flush(handle)
+        }
+        Err(_) => {
+            for edit in edits {
+                match edit {
+                    EditOp::DeleteBack(count) => send_backspace(handle, *count)?,
+                    EditOp::Insert(text) => send_string(handle, text)?,
+                    EditOp::MoveCursor(offset) => send_cursor_move(handle, *offset)?,
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The focused application, typed into through the event tap
+struct TapInjector {
+    handle: Handle,
+    sounds: bool,
+}
+
+impl TapInjector {
+    fn new(handle: Handle) -> Self {
+        Self { handle, sounds: true }
+    }
+
+    /// Without typing sounds, for backspaces and restores the user asked for
+    fn silent(handle: Handle) -> Self {
+        Self { handle, sounds: false }
+    }
+}
+
+impl Injector for TapInjector {
+    fn inject(&mut self, edits: &[EditOp]) {
+        report_injection_error(inject_edits(self.handle, edits));
+    }
+
+    fn dismiss_selection(&mut self) {
+        // Firefox and Chrome keep an autocomplete selection over typed text
+        let _ = dismiss_text_selection_if_needed(self.handle);
+    }
+
+    fn feedback(&mut self, event: SoundEvent) {
+        if self.sounds {
+            play_feedback(event);
+        }
+    }
+}
+
+/// Log a failed injection with its context and notify the UI when a report is due
+fn report_injection_error(result: Result<(), InjectionError>) {
+    let error = match result {
+        Ok(()) => return,
+        Err(error) => error,
+    };
+
+    let app = platform::get_active_app_name();
+    let context = error.to_string();
+    eprintln!("Injection failed in {}: {}", app, context);
+
+    let report = match INJECTION_ERRORS.lock() {
+        Ok(mut reporter) => reporter.record(&app, &context),
+        Err(_) => None,
+    };
+    if let Some(report) = report {
+        send_system_tray_event(SystemTrayEvent::InjectionErrors {
+            app: report.app_display_name(),
+            count: report.count,
+        });
     }
 }
 
@@ -196,11 +1162,63 @@ fn toggle_vietnamese() {
         let _ = config.set_vietnamese_mode(!current);
     }
     
-    if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-        processor.clear_buffer();
-    }
+    ENGINE.post(|processor| processor.clear_buffer());
     
     eprintln!("Vietnamese input: {}", if !current { "enabled" } else { "disabled" });
+    play_feedback(SoundEvent::Toggle);
+}
+
+/// Play the typing sound for `event` if typing sounds are on
+fn play_feedback(event: SoundEvent) {
+    #[cfg(target_os = "macos")]
+    if let Ok(config) = GLOBAL_CONFIG.lock() {
+        if config.keyboard.typing_sounds {
+            platform::play_sound(event, config.keyboard.sound_theme, config.keyboard.sound_volume);
+        }
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = event;
+}
+
+/// Run `f` on the processor fed by the event tap and wait for the result.
+/// It is the only engine, so the UI reads and controls typing state through
+/// it, behind any key waiting to be processed.
+pub fn with_engine<R, F>(f: F) -> Option<R>
+where
+    F: FnOnce(&mut VietnameseInputProcessor) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    ENGINE.query(f)
+}
+
+/// The state of the system-wide processor now and after every change
+pub fn buffer_snapshots() -> mpsc::Receiver<BufferSnapshot> {
+    ENGINE.subscribe()
+}
+
+/// Turn Vietnamese processing on or off from the UI. The caller saves the config.
+pub fn set_vietnamese_enabled(enabled: bool) {
+    VIETNAMESE_ENABLED.store(enabled, Ordering::Relaxed);
+    if let Ok(mut config) = GLOBAL_CONFIG.lock() {
+        config.input_mode = if enabled { InputMode::Vietnamese } else { InputMode::English };
+    }
+    ENGINE.post(|processor| processor.clear_buffer());
+}
+
+/// Approximate memory held by the engine and the error reporter
+pub fn memory_report() -> MemoryReport {
+    let mut report = ENGINE.query(|processor| processor.memory_report()).unwrap_or_default();
+    if let Ok(errors) = INJECTION_ERRORS.lock() {
+        report.add("Injection error counters", errors.tracked_apps(), errors.approx_bytes(), Some(MAX_TRACKED_APPS));
+    }
+    report
+}
+
+/// Pause or resume Vietnamese processing without touching the saved config,
+/// returning the previous state. Used while the sandbox posts its own keystrokes.
+pub fn set_vietnamese_paused(paused: bool) -> bool {
+    ENGINE.post(|processor| processor.clear_buffer());
+    !VIETNAMESE_ENABLED.swap(!paused, Ordering::Relaxed)
 }
 
 /// Check if the current key combination matches the configured hotkey
@@ -225,228 +1243,261 @@ fn is_hotkey_match(modifiers: KeyModifier, key: Option<PressedKey>) -> bool {
     })
 }
 
-/// Handle backspace using advanced approach
-/// This implements the "backspace technique" used by Vietnamese input methods
-fn handle_backspace_advanced(handle: Handle) -> bool {
-    eprintln!("Handling backspace with advanced approach");
-    
-    // First check if text is selected in the application
-    #[cfg(target_os = "macos")]
-    let has_text_selection = {
-        use platform::is_in_text_selection;
-        is_in_text_selection()
-    };
-    #[cfg(not(target_os = "macos"))]
-    let has_text_selection = false;
-    
-    if has_text_selection {
-        eprintln!("Text selection detected - clearing buffer and letting backspace pass through");
-        // Clear our internal buffer since the user is deleting selected text
-        if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-            processor.clear_buffer();
+/// Check a pressed key against a hotkey string such as "ctrl+shift+z".
+/// Modifiers must match exactly.
+fn hotkey_matches(hotkey: &str, modifiers: KeyModifier, key: PressedKey) -> bool {
+    let mut expected = KeyModifier::new();
+    let mut expected_key = None;
+    for part in hotkey.split('+').map(|part| part.trim().to_lowercase()) {
+        match part.as_str() {
+            "cmd" | "command" => expected.add_super(),
+            "ctrl" | "control" => expected.add_control(),
+            "alt" | "option" => expected.add_alt(),
+            "shift" => expected.add_shift(),
+            "space" => expected_key = Some(' '),
+            "enter" => expected_key = Some(KEY_ENTER),
+            "tab" => expected_key = Some(KEY_TAB),
+            "escape" => expected_key = Some(KEY_ESCAPE),
+            "backspace" => expected_key = Some('\u{8}'),
+            other => expected_key = other.chars().next(),
         }
-        // Let the system handle the deletion of selected text
-        return false;
     }
-    
-    // If Vietnamese input is not enabled, let backspace pass through normally
-    if !VIETNAMESE_ENABLED.load(Ordering::Relaxed) {
-        eprintln!("Vietnamese not enabled - letting backspace pass through");
+
+    // Caps Lock, the keypad and dead keys do not change which hotkey was pressed
+    let mut pressed = modifiers;
+    pressed.remove(KeyModifier::MODIFIER_CAPSLOCK);
+    pressed.remove(KeyModifier::MODIFIER_KEYPAD);
+    pressed.remove(KeyModifier::MODIFIER_DEAD_KEY);
+    match (key, expected_key) {
+        (PressedKey::Char(ch), Some(expected_key)) => {
+            pressed == expected && ch.to_ascii_lowercase() == expected_key
+        }
+        _ => false,
+    }
+}
+
+/// Check if the key is the configured undo conversion hotkey
+fn is_undo_conversion_hotkey(modifiers: KeyModifier, key: PressedKey) -> bool {
+    GLOBAL_CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.undo_conversion_hotkey.clone())
+        .map_or(false, |hotkey| hotkey_matches(&hotkey, modifiers, key))
+}
+
+/// Put back the keys typed for the word being composed or the word just committed.
+/// Returns false when there is nothing to undo, so the hotkey reaches the application.
+fn undo_conversion(handle: Handle) -> bool {
+    let undone = ENGINE.call(|processor| {
+        processor
+            .undo_conversion()
+            .map(|result| (result, processor.get_preedit_text()))
+    });
+    match undone.flatten() {
+        Some((result, preedit)) => {
+            eprintln!("Undoing conversion: {:?}", result);
+            update_marked_text(&preedit);
+            apply_result(&mut TapInjector::new(handle), &result)
+        }
+        None => false,
+    }
+}
+
+/// Check if the key is the configured remove diacritics hotkey
+fn is_remove_diacritics_hotkey(modifiers: KeyModifier, key: PressedKey) -> bool {
+    GLOBAL_CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.remove_diacritics_hotkey.clone())
+        .map_or(false, |hotkey| hotkey_matches(&hotkey, modifiers, key))
+}
+
+/// Replace the selected text with its diacritic-free form. Returns false when
+/// nothing is selected or there is nothing to remove, so the hotkey reaches the application.
+fn remove_diacritics_from_selection(handle: Handle) -> bool {
+    let selected = match platform::selected_text() {
+        Some(selected) => selected,
+        None => return false,
+    };
+    let folded = crate::core::remove_diacritics(&selected);
+    if folded == selected {
         return false;
     }
-    
-    // Handle Vietnamese input backspace
-    if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-        let buffer_before = processor.get_current_buffer().to_string();
-        eprintln!("Current buffer before backspace: '{}'", buffer_before);
-        
-        // Process backspace through Vietnamese processor
-        match processor.handle_backspace() {
-            ProcessingResult::ProcessedText { text, buffer_length } => {
-                eprintln!("Backspace processed - clearing {} chars, sending: '{}'", buffer_length, text);
-                
-                // Firefox/Chrome workaround: dismiss text selection if needed
-                let _ = dismiss_text_selection_if_needed(handle);
-                
-                // Send backspaces first with proper timing
-                if buffer_length > 0 {
-                    let _ = send_backspace(handle, buffer_length);
-                }
-                
-                // Then send the new transformed text
-                if !text.is_empty() {
-                    let _ = send_string(handle, &text);
-                }
-                return true; // Block the original backspace
-            }
-            ProcessingResult::ClearAndPassBackspace => {
-                eprintln!("Buffer cleared - letting backspace pass through");
-                // Buffer is now empty, let the backspace pass through to delete 
-                // the character before our Vietnamese input started
-                return false;
-            }
-            ProcessingResult::PassThrough(_) => {
-                eprintln!("Backspace passed through");
-                // Let backspace pass through
-                return false;
-            }
-            ProcessingResult::RestoreText { text, buffer_length } => {
-                eprintln!("Restoring text: '{}', clearing {} chars", text, buffer_length);
-                // Clear the current displayed text and send the original text
-                if buffer_length > 0 {
-                    let _ = send_backspace(handle, buffer_length);
-                }
-                if !text.is_empty() {
-                    let _ = send_string(handle, &text);
-                }
-                return true;
-            }
+
+    eprintln!("Removing diacritics from {} selected chars", selected.chars().count());
+    ENGINE.post(|processor| processor.clear_buffer());
+    // Typing over the selection also replaces it, for apps that ignore the attribute
+    if platform::replace_selected_text(&folded).is_err() {
+        report_injection_error(send_string(handle, &folded));
+    }
+    true
+}
+
+/// Check if the key is the configured dictionary hotkey
+fn is_dictionary_hotkey(modifiers: KeyModifier, key: PressedKey) -> bool {
+    GLOBAL_CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.dictionary_hotkey.clone())
+        .map_or(false, |hotkey| hotkey_matches(&hotkey, modifiers, key))
+}
+
+/// Check if the key is the configured peek hotkey
+fn is_peek_hotkey(modifiers: KeyModifier, key: PressedKey) -> bool {
+    GLOBAL_CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.peek_hotkey.clone())
+        .map_or(false, |hotkey| hotkey_matches(&hotkey, modifiers, key))
+}
+
+/// Show the keys typed for the word being composed in its place while the
+/// peek hotkey is held. Returns false when there is nothing to show, so the
+/// hotkey reaches the application.
+fn start_peek(handle: Handle) -> bool {
+    match ENGINE.call(|processor| processor.peek_keys()).flatten() {
+        Some(result) => {
+            PEEKING.store(true, Ordering::Relaxed);
+            apply_result(&mut TapInjector::silent(handle), &result)
         }
+        None => false,
     }
-    
-    // Fallback: let backspace pass through
-    eprintln!("Fallback - letting backspace pass through");
-    false
+}
+
+/// Put the word back once the peek hotkey is let go
+fn end_peek(handle: Handle) {
+    if !PEEKING.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    if let Some(result) = ENGINE.call(|processor| processor.end_peek()).flatten() {
+        apply_result(&mut TapInjector::silent(handle), &result);
+    }
+}
+
+/// Add the selected word, or the word being composed, to the user dictionary,
+/// or remove it if it is already there, and save the dictionary.
+/// Returns the word and whether it is now in the dictionary.
+pub fn toggle_dictionary_word() -> Option<(String, bool)> {
+    let selected = platform::selected_text();
+    let (word, added, dictionary) = ENGINE
+        .call(move |processor| {
+            let word = selected
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty() && !text.contains(char::is_whitespace))
+                .or_else(|| Some(processor.get_display_buffer().to_string()).filter(|word| !word.is_empty()))?;
+            let added = processor.get_dictionary_mut().toggle(&word);
+            Some((word, added, processor.get_dictionary().clone()))
+        })
+        .flatten()?;
+
+    if let Err(e) = dictionary.save_default() {
+        eprintln!("Failed to save user dictionary: {}", e);
+    }
+    eprintln!("{} '{}' {} the user dictionary", if added { "Added" } else { "Removed" }, word, if added { "to" } else { "from" });
+    Some((word, added))
 }
 
 /// Restore the original word by sending backspaces and the original text
 fn do_restore_word(handle: Handle) {
-    if let Ok(processor) = INPUT_PROCESSOR.lock() {
-        let original_text = processor.get_restore_text();
-        let display_length = processor.get_display_buffer().chars().count();
-        
+    let restore = ENGINE.call(|processor| (processor.get_restore_text(), processor.get_display_output_length()));
+    if let Some((original_text, display_length)) = restore {
         if !original_text.is_empty() {
             eprintln!("Restoring word: '{}', clearing {} chars", original_text, display_length);
-            
-            // Firefox/Chrome workaround: dismiss text selection if needed
-            let _ = dismiss_text_selection_if_needed(handle);
-            
-            // Send backspaces first with proper timing
-            if display_length > 0 {
-                let _ = send_backspace(handle, display_length);
-            }
-            
-            // Then send the original buffer back
-            let _ = send_string(handle, &original_text);
+            let restore = ProcessingResult::RestoreText {
+                text: original_text,
+                buffer_length: display_length,
+            };
+            apply_result(&mut TapInjector::silent(handle), &restore);
         }
     }
 }
 
-/// Transform keys based on Vietnamese input rules with improved handling
-fn transform_key(handle: Handle, key: PressedKey, modifiers: KeyModifier) -> bool {
-    eprintln!("Vietnamese enabled: {}", VIETNAMESE_ENABLED.load(Ordering::Relaxed));
-    
-    if let PressedKey::Char(character) = key {
-        // Handle backspace with advanced approach
-        if character == '\u{8}' { // Backspace
-            return handle_backspace_advanced(handle);
-        }
-        
-        // Handle special shifted character transformations (always apply, regardless of Vietnamese mode)
-        let mut transformed_character = character;
-        if modifiers.is_shift() {
-            transformed_character = match character {
-                // Handle Shift+. => >
-                '.' => '>',
-                // Handle other shifted characters
-                ',' => '<',
-                ';' => ':',
-                '\'' => '"',
-                '[' => '{',
-                ']' => '}',
-                '\\' => '|',
-                '/' => '?',
-                '1' => '!',
-                '2' => '@',
-                '3' => '#',
-                '4' => '$',
-                '5' => '%',
-                '6' => '^',
-                '7' => '&',
-                '8' => '*',
-                '9' => '(',
-                '0' => ')',
-                '-' => '_',
-                '=' => '+',
-                '`' => '~',
-                // Apply case conversion for letters
-                c if c.is_ascii_lowercase() => c.to_ascii_uppercase(),
-                // Keep other characters as is
-                c => c,
-            };
-        }
-        
-        // If the character was transformed and Vietnamese is not enabled, send the transformed character
-        if transformed_character != character && !VIETNAMESE_ENABLED.load(Ordering::Relaxed) {
-            let _ = send_string(handle, &transformed_character.to_string());
-            return true; // Block original key and send transformed character
-        }
-        
-        // If Vietnamese is not enabled, let the original character through
-        if !VIETNAMESE_ENABLED.load(Ordering::Relaxed) {
+/// Send a key to the processor and carry out what `route_key` decides
+fn transform_key(handle: Handle, key: TypingKey) -> bool {
+    let typed = matches!(key, TypingKey::Char(_) | TypingKey::Keypad(_));
+
+    // A key typed over a selection replaces it, and the word with it;
+    // a backspace deletes the selection itself
+    #[cfg(target_os = "macos")]
+    if (typed || key == TypingKey::Backspace) && platform::is_in_text_selection() {
+        eprintln!("Text selection detected - clearing buffer");
+        ENGINE.post(|processor| processor.clear_buffer());
+        if key == TypingKey::Backspace {
             return false;
         }
-        
-        // Before processing Vietnamese input, check if there's text selection
-        // If there is, we should clear our buffer and let the character replace the selection
-        #[cfg(target_os = "macos")]
-        {
-            use platform::is_in_text_selection;
-            if is_in_text_selection() {
-                eprintln!("Text selection detected for character input - clearing buffer");
-                if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-                    processor.clear_buffer();
-                }
-                // Continue with Vietnamese processing but with cleared buffer
-            }
-        }
-        
-        // Vietnamese input processing
-        if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-            match processor.process_key(transformed_character) {
-                ProcessingResult::ProcessedText { text, buffer_length } => {
-                    // Implement anti-flashing technique
-                    eprintln!("Sending Vietnamese text: '{}', clearing {} chars", text, buffer_length);
-                    
-                    // Firefox/Chrome workaround: dismiss text selection if needed
-                    let _ = dismiss_text_selection_if_needed(handle);
-                    
-                    // Send backspaces first with proper timing
-                    if buffer_length > 0 {
-                        let _ = send_backspace(handle, buffer_length);
-                    }
-                    
-                    // Then send the new text
-                    let _ = send_string(handle, &text);
-                    return true; // Block original key
-                }
-                ProcessingResult::PassThrough(_) => {
-                    // Let the original character through
-                    eprintln!("Vietnamese processor passed character through");
-                    return false;
-                }
-                ProcessingResult::ClearAndPassBackspace => {
-                    // Clear buffer and let backspace through
-                    eprintln!("Vietnamese processor cleared buffer for backspace");
-                    return false;
-                }
-                ProcessingResult::RestoreText { text, buffer_length } => {
-                    // Restore original text (typically for Escape key)
-                    eprintln!("Vietnamese processor restoring text: '{}', clearing {} chars", text, buffer_length);
-                    
-                    if buffer_length > 0 {
-                        let _ = send_backspace(handle, buffer_length);
-                    }
-                    if !text.is_empty() {
-                        let _ = send_string(handle, &text);
-                    }
-                    return true;
-                }
-            }
+    }
+
+    // After a click the key may continue the word already on screen
+    if typed && RESEED_PENDING.swap(false, Ordering::Relaxed) {
+        reseed_from_caret();
+    }
+
+    // The whole decision is made in a single trip to the engine
+    let outcome = match ENGINE.call(move |processor| route_key(processor, key)) {
+        Some(outcome) => outcome,
+        None => return false,
+    };
+    if let Some(pending) = &outcome.pending {
+        type_pending_word(handle, pending);
+    }
+    if outcome.cursor_moved {
+        RESEED_PENDING.store(true, Ordering::Relaxed);
+    }
+    match outcome.result {
+        Some(result) => {
+            update_marked_text(&outcome.preedit);
+            eprintln!("Vietnamese processor result: {:?}", result);
+            // Backspaces make no typing sound
+            let mut injector = match key {
+                TypingKey::Backspace => TapInjector::silent(handle),
+                _ => TapInjector::new(handle),
+            };
+            apply_result(&mut injector, &result)
         }
+        None => outcome.blocked,
+    }
+}
+
+/// Whether keys go to a password field or prompt. Vietnamese processing is
+/// suspended meanwhile, and the word being composed and the one before it
+/// are dropped when it starts, so nothing typed there is left in the engine.
+#[cfg(target_os = "macos")]
+fn secure_input_active() -> bool {
+    let secure = platform::is_secure_input();
+    if SECURE_INPUT.swap(secure, Ordering::Relaxed) != secure {
+        if secure {
+            eprintln!("Secure input is on; suspending Vietnamese processing");
+            ENGINE.post(|processor| processor.reset());
+            update_marked_text("");
+        } else {
+            eprintln!("Secure input is off; resuming Vietnamese processing");
+        }
+    }
+    secure
+}
+
+/// The key a raw cursor key is to the word being composed. Left and right
+/// by one character keep the word while the cursor stays within it; a
+/// selection or a jump by word, like any other cursor movement, lands
+/// somewhere the word cannot follow.
+fn cursor_key(raw_keycode: u16, modifiers: KeyModifier) -> Option<TypingKey> {
+    const RAW_ARROW_UP: u16 = 0x7e;
+    const RAW_ARROW_DOWN: u16 = 0x7d;
+    const RAW_ARROW_LEFT: u16 = 0x7b;
+    const RAW_ARROW_RIGHT: u16 = 0x7c;
+    const RAW_HOME: u16 = 0x73;
+    const RAW_END: u16 = 0x77;
+    const RAW_PAGE_UP: u16 = 0x74;
+    const RAW_PAGE_DOWN: u16 = 0x79;
+
+    let by_character = !modifiers.is_shift() && !modifiers.is_alt() && !modifiers.is_control();
+    match raw_keycode {
+        RAW_ARROW_LEFT if by_character => Some(TypingKey::Left),
+        RAW_ARROW_RIGHT if by_character => Some(TypingKey::Right),
+        RAW_ARROW_UP | RAW_ARROW_DOWN | RAW_ARROW_LEFT | RAW_ARROW_RIGHT | RAW_HOME | RAW_END | RAW_PAGE_UP
+        | RAW_PAGE_DOWN => Some(TypingKey::Jump),
+        _ => None,
     }
-    
-    false
 }
 
 /// Main event handler for keyboard events
@@ -456,12 +1507,51 @@ fn event_handler(
     pressed_key: Option<PressedKey>,
     modifiers: KeyModifier,
 ) -> bool {
+    // Nothing typed into a password field is composed, kept or logged
+    #[cfg(target_os = "macos")]
+    if event_type == EventTapType::KeyDown && secure_input_active() {
+        return false;
+    }
+
+    // Excluded applications get every key as typed, hotkeys included
+    if APP_EXCLUDED.load(Ordering::Relaxed) {
+        return false;
+    }
+
     eprintln!("Event received: type={:?}, key={:?}, modifiers={:?}", event_type, pressed_key, modifiers);
 
+    // Let keys through untouched while state is being flushed
+    if shutdown::is_shutting_down() {
+        return false;
+    }
+
     unsafe {
         HOTKEY_MODIFIERS = modifiers;
     }
 
+    // While the peek hotkey is held its repeats are swallowed, and letting go
+    // of its key or a modifier puts the word back. Anything else puts the
+    // word back before it is handled.
+    if PEEKING.load(Ordering::Relaxed) {
+        let peek_key = matches!(event_type, EventTapType::KeyDown | EventTapType::KeyUp)
+            && pressed_key.map_or(false, |key| is_peek_hotkey(modifiers, key));
+        if peek_key {
+            if event_type == EventTapType::KeyUp {
+                end_peek(handle);
+            }
+            return true;
+        }
+        end_peek(handle);
+    }
+    if event_type == EventTapType::KeyUp {
+        return false;
+    }
+
+    // A click moves the cursor away from the word being composed
+    if event_type == EventTapType::Other {
+        return transform_key(handle, TypingKey::Jump);
+    }
+
     // Handle hotkey combinations
     if event_type == EventTapType::FlagsChanged {
         let hotkey_active = unsafe { HOTKEY_MODIFIERS.is_super() };
@@ -476,68 +1566,81 @@ fn event_handler(
             return true; // Block the hotkey from reaching other applications
         }
 
-        // Handle Cmd key combinations - let them pass through
-        if modifiers.is_super() {
-            eprintln!("Cmd key combination detected, letting it pass through");
-            // Clear Vietnamese buffer when Cmd is used (user is probably switching apps or using shortcuts)
-            if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-                processor.new_word();
+        if VIETNAMESE_ENABLED.load(Ordering::Relaxed) && is_undo_conversion_hotkey(modifiers, key) {
+            if undo_conversion(handle) {
+                return true;
             }
-            return false; // Don't block Cmd key combinations
         }
 
-        // Handle special keys when Vietnamese is enabled
-        if VIETNAMESE_ENABLED.load(Ordering::Relaxed) {
-            if let PressedKey::Char(ch) = key {
-                match ch {
-                    KEY_ESCAPE => {
-                        // Escape key handling is now integrated into the Vietnamese processor
-                        return transform_key(handle, key, modifiers);
-                    }
-                    KEY_TAB | KEY_ENTER => {
-                        // Tab and Enter handling is now integrated into the Vietnamese processor
-                        return transform_key(handle, key, modifiers);
-                    }
-                    '\u{8}' => { // Backspace
-                        // Backspace handling is done in transform_key function
-                        return transform_key(handle, key, modifiers);
-                    }
-                    _ => {
-                        // Handle other modifier combinations that should reset the buffer
-                        if modifiers.is_alt() || modifiers.is_control() {
-                            if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-                                processor.new_word();
-                            }
-                            return false; // Let these combinations pass through
-                        }
-                    }
-                }
-            }
+        if is_remove_diacritics_hotkey(modifiers, key) && remove_diacritics_from_selection(handle) {
+            return true;
         }
 
-        // Handle raw key events (arrow keys, etc.)
-        if let PressedKey::Raw(raw_keycode) = key {
-            if raw_keycode == RAW_KEY_GLOBE {
+        if is_dictionary_hotkey(modifiers, key) && toggle_dictionary_word().is_some() {
+            return true;
+        }
+
+        if VIETNAMESE_ENABLED.load(Ordering::Relaxed) && is_peek_hotkey(modifiers, key) && start_peek(handle) {
+            return true;
+        }
+
+        // Name the key for `route_key`, which decides what it does to the word
+        let typing_key = match key {
+            // Undo/redo changes the text around the cursor behind our back
+            PressedKey::Char('z' | 'Z') if modifiers.is_super() => {
+                eprintln!("Undo/redo detected, dropping composition state");
+                TypingKey::Undo
+            }
+            // The application composes a dead key with the key after it (´ then e
+            // gives é), so the word ends before them and both go through untouched
+            _ if modifiers.is_dead_key() => {
+                eprintln!("Dead key sequence, letting it pass through");
+                TypingKey::Shortcut
+            }
+            // Cmd key combinations end the word (the user is probably switching
+            // apps or using shortcuts) and pass through
+            _ if modifiers.is_super() => {
+                eprintln!("Cmd key combination detected, letting it pass through");
+                TypingKey::Shortcut
+            }
+            PressedKey::Raw(RAW_KEY_GLOBE) => {
                 toggle_vietnamese();
                 return true;
             }
-            
-            // Arrow keys should reset the Vietnamese buffer
-            const RAW_ARROW_UP: u16 = 0x7e;
-            const RAW_ARROW_DOWN: u16 = 0x7d;
-            const RAW_ARROW_LEFT: u16 = 0x7b;
-            const RAW_ARROW_RIGHT: u16 = 0x7c;
-            
-            if [RAW_ARROW_UP, RAW_ARROW_DOWN, RAW_ARROW_LEFT, RAW_ARROW_RIGHT].contains(&raw_keycode) {
-                if let Ok(mut processor) = INPUT_PROCESSOR.lock() {
-                    processor.new_word();
+            PressedKey::Raw(raw_keycode) => match cursor_key(raw_keycode, modifiers) {
+                Some(typing_key) => typing_key,
+                None => return false,
+            },
+            PressedKey::Char(_) if !VIETNAMESE_ENABLED.load(Ordering::Relaxed) => return false,
+            PressedKey::Char(KEY_ESCAPE) => {
+                // Aborting a word can also leave Vietnamese off, for a command or
+                // password typed next
+                let to_english = GLOBAL_CONFIG.lock().map_or(false, |config| config.advanced.escape_to_english)
+                    && ENGINE.call(|processor| processor.escape_restores()).unwrap_or(false);
+                let blocked = transform_key(handle, TypingKey::Char(KEY_ESCAPE));
+                if to_english {
+                    toggle_vietnamese();
                 }
-                return false; // Let arrow keys pass through
+                return blocked;
             }
-        }
-
-        // Transform regular characters through Vietnamese input method
-        return transform_key(handle, key, modifiers);
+            PressedKey::Char(ch @ (KEY_TAB | KEY_ENTER)) => TypingKey::Char(ch),
+            PressedKey::Char('\u{8}') if modifiers.is_alt() => TypingKey::WordBackspace,
+            PressedKey::Char('\u{8}') => TypingKey::Backspace,
+            PressedKey::Char(KEY_FORWARD_DELETE) => TypingKey::ForwardDelete,
+            // Other modifier combinations end the word and pass through
+            PressedKey::Char(_) if modifiers.is_alt() || modifiers.is_control() => TypingKey::Shortcut,
+            PressedKey::Char(ch) => {
+                // Caps Lock capitalizes letters like Shift; the processor keeps the
+                // case of every key through transforms and backspaces
+                let ch = if modifiers.is_capslock() { ch.to_ascii_uppercase() } else { ch };
+                if modifiers.is_keypad() {
+                    TypingKey::Keypad(ch)
+                } else {
+                    TypingKey::Char(ch)
+                }
+            }
+        };
+        return transform_key(handle, typing_key);
     }
 
     false
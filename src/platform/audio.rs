@@ -0,0 +1,43 @@
+// Typing sound feedback
+// The themes are made of the system sounds every Mac ships with, played
+// through NSSound so nothing has to be bundled with the application.
+
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use objc::{class, msg_send, sel, sel_impl};
+
+use crate::core::{SoundEvent, SoundTheme};
+
+/// Name of the system sound played for `event` in `theme`
+fn sound_name(theme: SoundTheme, event: SoundEvent) -> &'static str {
+    match (theme, event) {
+        (SoundTheme::Classic, SoundEvent::Transform) => "Tink",
+        (SoundTheme::Classic, SoundEvent::Restore) => "Pop",
+        (SoundTheme::Classic, SoundEvent::Toggle) => "Glass",
+        (SoundTheme::Soft, SoundEvent::Transform) => "Bottle",
+        (SoundTheme::Soft, SoundEvent::Restore) => "Purr",
+        (SoundTheme::Soft, SoundEvent::Toggle) => "Blow",
+        (SoundTheme::Typewriter, SoundEvent::Transform) => "Morse",
+        (SoundTheme::Typewriter, SoundEvent::Restore) => "Frog",
+        (SoundTheme::Typewriter, SoundEvent::Toggle) => "Submarine",
+    }
+}
+
+/// Play the sound for `event` at `volume` percent without waiting for it.
+/// A sound that is still playing starts over, so fast typing does not queue.
+pub fn play_sound(event: SoundEvent, theme: SoundTheme, volume: u8) {
+    if volume == 0 {
+        return;
+    }
+    unsafe {
+        let name = NSString::alloc(nil).init_str(sound_name(theme, event));
+        // soundNamed: caches the sound, so it is not released here
+        let sound: id = msg_send![class!(NSSound), soundNamed: name];
+        if sound == nil {
+            return;
+        }
+        let _: () = msg_send![sound, setVolume: f32::from(volume.min(100)) / 100.];
+        let _: () = msg_send![sound, stop];
+        let _: () = msg_send![sound, play];
+    }
+}
@@ -0,0 +1,354 @@
+// Clipboard and clock for macro placeholders, and pasting replacements
+// `{clipboard}` reads the general pasteboard as plain text. Items a password
+// manager marked as concealed or transient are never typed, and long text is
+// cut short so a stray expansion cannot type a whole document.
+// Applications that get their text pasted see it on the pasteboard only
+// briefly; `core::paste` decides when each paste is offered and when the
+// clipboard is put back. The text is offered through a data provider, so
+// VKey sees the application read it.
+
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use cocoa::base::{id, nil, BOOL};
+use cocoa::foundation::{NSInteger, NSString, NSUInteger};
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+use once_cell::sync::Lazy;
+
+use crate::core::{LocalTime, Pasteboard, PasteboardItems, Pastes, PlaceholderSource};
+
+/// Most characters `{clipboard}` types
+const MAX_CLIPBOARD_CHARS: usize = 2000;
+
+const PLAIN_TEXT_TYPE: &str = "public.utf8-plain-text";
+
+/// Pasteboard types that mark an item as not meant to be kept or shown,
+/// from nspasteboard.org
+const PRIVATE_TYPES: [&str; 2] = ["org.nspasteboard.ConcealedType", "org.nspasteboard.TransientType"];
+
+/// Marks pasted replacements so clipboard managers do not keep them
+const TRANSIENT_TYPE: &str = PRIVATE_TYPES[1];
+
+/// How long after the last paste the clipboard gets its contents back, so
+/// pastes in quick succession keep the contents saved by the first
+const PASTE_RESTORE_DELAY: Duration = Duration::from_millis(300);
+
+/// How long text written as it is gets to be read, where reads are not seen
+const PASTE_SETTLE: Duration = Duration::from_millis(50);
+
+const PROVIDER_CLASS: &str = "VKeyPasteProvider";
+
+/// Text offered for the current paste: its number, the text, and whether
+/// it goes through the data provider
+static OFFERED: Mutex<Option<(u64, String, bool)>> = Mutex::new(None);
+
+/// The last paste an application read, signalled by `READ`
+static LAST_READ: Mutex<u64> = Mutex::new(0);
+static READ: Condvar = Condvar::new();
+
+/// Pastes over the general pasteboard, one at a time
+static PASTES: Mutex<Pastes<GeneralPasteboard>> = Mutex::new(Pastes::new(GeneralPasteboard));
+
+/// Answers the pasteboard when an application reads offered text. Never released.
+static PROVIDER: Lazy<usize> = Lazy::new(|| unsafe {
+    let provider: id = msg_send![provider_class(), new];
+    provider as usize
+});
+
+/// Plain text on the general pasteboard, unless it is marked private
+pub fn clipboard_text() -> Option<String> {
+    unsafe {
+        let pool: id = msg_send![class!(NSAutoreleasePool), new];
+        let text = read_pasteboard();
+        let _: () = msg_send![pool, drain];
+        text
+    }
+}
+
+unsafe fn read_pasteboard() -> Option<String> {
+    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+    if pasteboard == nil {
+        return None;
+    }
+    for private in PRIVATE_TYPES {
+        let private_type = NSString::alloc(nil).init_str(private);
+        let data: id = msg_send![pasteboard, dataForType: private_type];
+        let _: () = msg_send![private_type, release];
+        if data != nil {
+            return None;
+        }
+    }
+
+    let text_type = NSString::alloc(nil).init_str(PLAIN_TEXT_TYPE);
+    let text: id = msg_send![pasteboard, stringForType: text_type];
+    let _: () = msg_send![text_type, release];
+    if text == nil {
+        return None;
+    }
+    let text = crate::nsstring_to_string!(text)?;
+    Some(text.chars().take(MAX_CLIPBOARD_CHARS).collect())
+}
+
+/// Put `text` on the clipboard to be pasted, once the application has read
+/// the paste before it, saving what was there to be put back shortly after.
+/// False if the pasteboard could not be written.
+pub fn stage_paste(text: &str) -> bool {
+    let Ok(mut pastes) = PASTES.lock() else {
+        return false;
+    };
+    let Some(paste) = pastes.stage(text) else {
+        return false;
+    };
+    std::thread::spawn(move || {
+        std::thread::sleep(PASTE_RESTORE_DELAY);
+        if let Ok(mut pastes) = PASTES.lock() {
+            pastes.restore(paste);
+        }
+    });
+    true
+}
+
+/// The general pasteboard. Offered text is provided when an application
+/// reads it, except on the main thread: its run loop is what answers the
+/// provider, so text offered from there is written as it is and counts as
+/// read after `PASTE_SETTLE`.
+#[derive(Debug)]
+struct GeneralPasteboard;
+
+impl Pasteboard for GeneralPasteboard {
+    fn change_count(&self) -> i64 {
+        with_pasteboard(|pasteboard| unsafe {
+            let change_count: NSInteger = msg_send![pasteboard, changeCount];
+            change_count as i64
+        })
+        .unwrap_or(-1)
+    }
+
+    fn read_items(&self) -> PasteboardItems {
+        with_pasteboard(|pasteboard| unsafe { read_items(pasteboard) }).unwrap_or_default()
+    }
+
+    fn write_items(&self, items: &PasteboardItems) {
+        with_pasteboard(|pasteboard| unsafe { write_items(pasteboard, items) });
+    }
+
+    fn offer(&self, text: &str, paste: u64) -> Option<i64> {
+        let lazy = unsafe {
+            let main: BOOL = msg_send![class!(NSThread), isMainThread];
+            main == 0
+        };
+        if let Ok(mut offered) = OFFERED.lock() {
+            *offered = Some((paste, text.to_string(), lazy));
+        }
+        with_pasteboard(|pasteboard| unsafe {
+            let written = if lazy { offer_lazily(pasteboard) } else { offer_now(pasteboard, text) };
+            if !written {
+                return None;
+            }
+            let change_count: NSInteger = msg_send![pasteboard, changeCount];
+            Some(change_count as i64)
+        })
+        .flatten()
+    }
+
+    fn wait_until_read(&self, paste: u64, deadline: Duration) -> bool {
+        let written_now = OFFERED
+            .lock()
+            .map_or(false, |offered| matches!(offered.as_ref(), Some((offered, _, false)) if *offered == paste));
+        if written_now {
+            std::thread::sleep(PASTE_SETTLE.min(deadline));
+            return true;
+        }
+        let until = Instant::now() + deadline;
+        let Ok(mut last_read) = LAST_READ.lock() else {
+            return false;
+        };
+        while *last_read < paste {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return false;
+            }
+            last_read = match READ.wait_timeout(last_read, left) {
+                Ok((last_read, _)) => last_read,
+                Err(_) => return false,
+            };
+        }
+        true
+    }
+}
+
+/// Run `f` on the general pasteboard in an autorelease pool. None if there
+/// is no pasteboard.
+fn with_pasteboard<T>(f: impl FnOnce(id) -> T) -> Option<T> {
+    unsafe {
+        let pool: id = msg_send![class!(NSAutoreleasePool), new];
+        let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+        let result = if pasteboard == nil { None } else { Some(f(pasteboard)) };
+        let _: () = msg_send![pool, drain];
+        result
+    }
+}
+
+/// Offer the current text through the data provider, marked transient
+unsafe fn offer_lazily(pasteboard: id) -> bool {
+    let item: id = msg_send![class!(NSPasteboardItem), new];
+    let text_type = NSString::alloc(nil).init_str(PLAIN_TEXT_TYPE);
+    let types: id = msg_send![class!(NSArray), arrayWithObject: text_type];
+    let provider = *PROVIDER as id;
+    let provided: BOOL = msg_send![item, setDataProvider: provider forTypes: types];
+    let _: () = msg_send![text_type, release];
+    mark_transient(item);
+
+    let _: NSInteger = msg_send![pasteboard, clearContents];
+    let objects: id = msg_send![class!(NSArray), arrayWithObject: item];
+    let written: BOOL = msg_send![pasteboard, writeObjects: objects];
+    let _: () = msg_send![item, release];
+    provided != 0 && written != 0
+}
+
+/// Write `text` to the pasteboard as it is, marked transient
+unsafe fn offer_now(pasteboard: id, text: &str) -> bool {
+    let _: NSInteger = msg_send![pasteboard, clearContents];
+    let text_type = NSString::alloc(nil).init_str(PLAIN_TEXT_TYPE);
+    let string = NSString::alloc(nil).init_str(text);
+    let written: BOOL = msg_send![pasteboard, setString: string forType: text_type];
+    let _: () = msg_send![string, release];
+    let _: () = msg_send![text_type, release];
+    if written == 0 {
+        return false;
+    }
+    mark_transient(pasteboard);
+    true
+}
+
+/// Mark a pasteboard or one of its items so clipboard managers do not keep it
+unsafe fn mark_transient(target: id) {
+    let transient_type = NSString::alloc(nil).init_str(TRANSIENT_TYPE);
+    let empty: id = msg_send![class!(NSData), data];
+    let _: BOOL = msg_send![target, setData: empty forType: transient_type];
+    let _: () = msg_send![transient_type, release];
+}
+
+fn provider_class() -> &'static Class {
+    if let Some(class) = Class::get(PROVIDER_CLASS) {
+        return class;
+    }
+
+    let mut decl = ClassDecl::new(PROVIDER_CLASS, class!(NSObject)).unwrap();
+
+    // An application reads the offered text: hand it over, and let the next paste through
+    extern "C" fn provide_data(_this: &Object, _cmd: Sel, _pasteboard: id, item: id, data_type: id) {
+        let offered = OFFERED
+            .lock()
+            .ok()
+            .and_then(|offered| offered.as_ref().map(|(paste, text, _)| (*paste, text.clone())));
+        let Some((paste, text)) = offered else {
+            return;
+        };
+        unsafe {
+            let string = NSString::alloc(nil).init_str(&text);
+            let _: BOOL = msg_send![item, setString: string forType: data_type];
+            let _: () = msg_send![string, release];
+        }
+        if let Ok(mut last_read) = LAST_READ.lock() {
+            *last_read = (*last_read).max(paste);
+            READ.notify_all();
+        }
+    }
+
+    extern "C" fn finished(_this: &Object, _cmd: Sel, _pasteboard: id) {}
+
+    unsafe {
+        decl.add_method(
+            sel!(pasteboard:item:provideDataForType:),
+            provide_data as extern "C" fn(&Object, Sel, id, id, id),
+        );
+        decl.add_method(
+            sel!(pasteboardFinishedWithDataProvider:),
+            finished as extern "C" fn(&Object, Sel, id),
+        );
+    }
+
+    decl.register()
+}
+
+unsafe fn read_items(pasteboard: id) -> PasteboardItems {
+    let items: id = msg_send![pasteboard, pasteboardItems];
+    if items == nil {
+        return Vec::new();
+    }
+    let count: NSUInteger = msg_send![items, count];
+    (0..count)
+        .map(|index| {
+            let item: id = msg_send![items, objectAtIndex: index];
+            let types: id = msg_send![item, types];
+            let type_count: NSUInteger = msg_send![types, count];
+            (0..type_count)
+                .filter_map(|type_index| {
+                    let data_type: id = msg_send![types, objectAtIndex: type_index];
+                    let data: id = msg_send![item, dataForType: data_type];
+                    if data == nil {
+                        return None;
+                    }
+                    let length: NSUInteger = msg_send![data, length];
+                    let bytes: *const u8 = msg_send![data, bytes];
+                    let bytes = if bytes.is_null() {
+                        Vec::new()
+                    } else {
+                        std::slice::from_raw_parts(bytes, length as usize).to_vec()
+                    };
+                    Some((crate::nsstring_to_string!(data_type)?, bytes))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+unsafe fn write_items(pasteboard: id, items: &PasteboardItems) {
+    let _: NSInteger = msg_send![pasteboard, clearContents];
+    if items.is_empty() {
+        return;
+    }
+    let objects: id = msg_send![class!(NSMutableArray), array];
+    for types in items {
+        let item: id = msg_send![class!(NSPasteboardItem), new];
+        for (data_type, bytes) in types {
+            let data: id = msg_send![class!(NSData), dataWithBytes: bytes.as_ptr() as *const std::ffi::c_void length: bytes.len() as NSUInteger];
+            let type_name = NSString::alloc(nil).init_str(data_type);
+            let _: BOOL = msg_send![item, setData: data forType: type_name];
+            let _: () = msg_send![type_name, release];
+        }
+        let _: () = msg_send![objects, addObject: item];
+        let _: () = msg_send![item, release];
+    }
+    let _: BOOL = msg_send![pasteboard, writeObjects: objects];
+}
+
+/// The local clock and the general pasteboard
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemPlaceholders;
+
+impl PlaceholderSource for SystemPlaceholders {
+    fn now(&self) -> LocalTime {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        // The offset is looked up each time, so it follows daylight saving
+        // and time zone changes
+        let time = secs as libc::time_t;
+        let mut local: libc::tm = unsafe { std::mem::zeroed() };
+        let offset = if unsafe { libc::localtime_r(&time, &mut local) }.is_null() {
+            0
+        } else {
+            local.tm_gmtoff as i64
+        };
+        LocalTime::from_unix(secs, offset)
+    }
+
+    fn clipboard(&self) -> Option<String> {
+        clipboard_text()
+    }
+}
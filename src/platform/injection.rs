@@ -0,0 +1,214 @@
+// Ordered keystroke injection
+// Backspaces and replacement text are posted as separate events, and some
+// applications reorder them under load. The queue serializes every post so
+// that a batch of erases always lands before the text that follows it.
+// Terminals get their text one grapheme cluster per event, so a letter and
+// its combining marks never arrive in separate writes. Apps listed for it get
+// a replacement typed over the old text through accessibility instead, with
+// key events only when that fails, or their text pasted from the clipboard.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::core::EditOp;
+
+use super::{get_active_app_name, paste_string, replace_before_caret, send_backspace, send_cursor_move, send_string, send_string_by_cluster, Handle, InjectionError};
+
+/// Terminal emulators, matched against the bundle path of the application
+const TERMINAL_APPS: [&str; 10] = [
+    "Terminal.app", "iTerm.app", "Alacritty.app", "kitty.app", "WezTerm.app", "Warp.app",
+    "Hyper.app", "Ghostty.app", "Tabby.app", "Rio.app",
+];
+
+/// Whether the application at `app` is a terminal emulator
+pub fn is_terminal_app(app: &str) -> bool {
+    TERMINAL_APPS.iter().any(|terminal| app.contains(terminal))
+}
+
+/// A single queued injection step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InjectionOp {
+    /// Delete this many characters before the cursor
+    Backspace(usize),
+    /// Type this text at the cursor
+    Text(String),
+    /// Move the cursor this many characters, left when negative
+    MoveCursor(isize),
+}
+
+/// How a replacement reaches the focused application
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InjectionMethod {
+    /// Backspaces followed by the text, as key events
+    #[default]
+    Keystrokes,
+    /// The old text is selected and typed over through accessibility
+    Accessibility,
+    /// Backspaces as key events, then the text pasted from the clipboard
+    Paste,
+}
+
+/// Serializes injected events for the focused application
+#[derive(Debug, Default)]
+pub struct InjectionQueue {
+    ops: VecDeque<InjectionOp>,
+    /// Application the pending operations were queued for
+    target: Option<String>,
+    /// Extra wait between an erase batch and the text that follows it
+    batch_delay: Duration,
+    /// Type text one grapheme cluster per event, for terminals
+    terminal_safe: bool,
+    method: InjectionMethod,
+}
+
+impl InjectionQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the wait between batches. Zero disables it.
+    pub fn set_batch_delay(&mut self, delay: Duration) {
+        self.batch_delay = delay;
+    }
+
+    /// Type text one grapheme cluster per event instead of all at once
+    pub fn set_terminal_safe(&mut self, terminal_safe: bool) {
+        self.terminal_safe = terminal_safe;
+    }
+
+    /// Choose how replacements are made in the focused application
+    pub fn set_method(&mut self, method: InjectionMethod) {
+        self.method = method;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Queue backspaces, merging with a preceding backspace batch
+    pub fn push_backspace(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.remember_target();
+        if let Some(InjectionOp::Backspace(pending)) = self.ops.back_mut() {
+            *pending += count;
+            return;
+        }
+        self.ops.push_back(InjectionOp::Backspace(count));
+    }
+
+    /// Queue text, merging with preceding text
+    pub fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.remember_target();
+        if let Some(InjectionOp::Text(pending)) = self.ops.back_mut() {
+            pending.push_str(text);
+            return;
+        }
+        self.ops.push_back(InjectionOp::Text(text.to_string()));
+    }
+
+    /// Queue cursor movement, merging with a preceding move
+    pub fn push_cursor_move(&mut self, offset: isize) {
+        if offset == 0 {
+            return;
+        }
+        self.remember_target();
+        if let Some(InjectionOp::MoveCursor(pending)) = self.ops.back_mut() {
+            *pending += offset;
+            return;
+        }
+        self.ops.push_back(InjectionOp::MoveCursor(offset));
+    }
+
+    /// Queue the edits of a processing result, in order
+    pub fn push_edits(&mut self, edits: &[EditOp]) {
+        for edit in edits {
+            match edit {
+                EditOp::DeleteBack(count) => self.push_backspace(*count),
+                EditOp::Insert(text) => self.push_text(text),
+                EditOp::MoveCursor(offset) => self.push_cursor_move(*offset),
+            }
+        }
+    }
+
+    /// Post all queued operations in order. Operations queued for an application
+    /// that is no longer focused are dropped rather than typed into the wrong place.
+    pub fn flush(&mut self, handle: Handle) -> Result<(), InjectionError> {
+        let target = self.target.take();
+        if target.is_some() && target != Some(get_active_app_name()) {
+            eprintln!("Focus changed before injection, dropping {} queued operations", self.ops.len());
+            self.ops.clear();
+            return Ok(());
+        }
+
+        if self.method == InjectionMethod::Accessibility {
+            if let Some((length, text)) = self.pending_replacement() {
+                match replace_before_caret(length, &text) {
+                    Ok(()) => {
+                        self.ops.clear();
+                        return Ok(());
+                    }
+                    Err(error) => eprintln!("Accessibility replacement failed ({}), typing it instead", error),
+                }
+            }
+        }
+
+        let mut previous_was_erase = false;
+        while let Some(op) = self.ops.pop_front() {
+            let result = match op {
+                InjectionOp::Backspace(count) => {
+                    previous_was_erase = true;
+                    send_backspace(handle, count)
+                }
+                InjectionOp::Text(ref text) => {
+                    if previous_was_erase && !self.batch_delay.is_zero() {
+                        std::thread::sleep(self.batch_delay);
+                    }
+                    previous_was_erase = false;
+                    if self.method == InjectionMethod::Paste {
+                        paste_string(handle, text).or_else(|error| {
+                            eprintln!("Pasting failed ({}), typing it instead", error);
+                            send_string(handle, text)
+                        })
+                    } else if self.terminal_safe {
+                        send_string_by_cluster(handle, text)
+                    } else {
+                        send_string(handle, text)
+                    }
+                }
+                InjectionOp::MoveCursor(offset) => {
+                    previous_was_erase = false;
+                    send_cursor_move(handle, offset)
+                }
+            };
+
+            // Anything after a failed step would land in the wrong place
+            if let Err(error) = result {
+                self.ops.clear();
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// The queued operations as one erase followed by text, if that is all
+    /// they are; cursor moves are left to key events
+    fn pending_replacement(&self) -> Option<(usize, String)> {
+        match self.ops.iter().collect::<Vec<_>>().as_slice() {
+            [InjectionOp::Backspace(count)] => Some((*count, String::new())),
+            [InjectionOp::Text(text)] => Some((0, text.clone())),
+            [InjectionOp::Backspace(count), InjectionOp::Text(text)] => Some((*count, text.clone())),
+            _ => None,
+        }
+    }
+
+    fn remember_target(&mut self) {
+        if self.target.is_none() {
+            self.target = Some(get_active_app_name());
+        }
+    }
+}
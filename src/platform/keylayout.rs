@@ -0,0 +1,274 @@
+// Keyboard layout translation
+// Asks the current keyboard layout what a key types through UCKeyTranslate,
+// the call applications themselves rely on, so every layout, shifted symbols
+// and dead keys (´ then e gives é on US International and French layouts)
+// are read the way the focused application sees them.
+
+use std::ffi::c_void;
+use std::sync::Mutex;
+
+use core_foundation::base::{CFType, CFTypeRef, TCFType};
+use core_foundation::data::{CFData, CFDataRef};
+use core_foundation::string::CFStringRef;
+use core_graphics::event::{CGEventFlags, CGKeyCode};
+
+type TISInputSourceRef = *mut c_void;
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    fn TISCopyCurrentKeyboardLayoutInputSource() -> TISInputSourceRef;
+    fn TISGetInputSourceProperty(source: TISInputSourceRef, key: CFStringRef) -> *const c_void;
+    static kTISPropertyUnicodeKeyLayoutData: CFStringRef;
+    fn LMGetKbdType() -> u8;
+    fn UCKeyTranslate(
+        key_layout: *const u8,
+        virtual_key_code: u16,
+        key_action: u16,
+        modifier_key_state: u32,
+        keyboard_type: u32,
+        key_translate_options: u32,
+        dead_key_state: *mut u32,
+        max_string_length: usize,
+        actual_string_length: *mut usize,
+        unicode_string: *mut u16,
+    ) -> i32;
+}
+
+/// kUCKeyActionDown
+const KEY_ACTION_DOWN: u16 = 0;
+
+/// kUCKeyTranslateNoDeadKeysMask: a dead key types its accent on its own
+const NO_DEAD_KEYS: u32 = 1;
+
+/// Longest text a single key is expected to type
+const MAX_KEY_CHARS: usize = 8;
+
+/// What a key typed on the current layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyOutput {
+    /// Text typed by the key alone
+    Text(String),
+    /// The key is a dead key, so nothing is typed until the next key
+    DeadKey,
+    /// Text typed by the key together with the dead key before it
+    Composed(String),
+}
+
+impl KeyOutput {
+    /// The application composes this key with another one itself
+    pub fn is_dead_key_sequence(&self) -> bool {
+        matches!(self, KeyOutput::DeadKey | KeyOutput::Composed(_))
+    }
+}
+
+/// Key layout data of the current input source, and the dead-key state left
+/// by the keys typed so far
+struct LayoutState {
+    /// Whether the current input source was read yet
+    loaded: bool,
+    /// The layout's `uchr` data; missing for input sources that have none
+    data: Option<CFData>,
+    dead_key_state: u32,
+}
+
+impl LayoutState {
+    /// Take the layout data of the current input source. Returns whether a
+    /// layout can be read when that changed; before the first load it is
+    /// assumed it can.
+    fn load(&mut self, data: Option<CFData>) -> Option<bool> {
+        let same = match (&self.data, &data) {
+            (Some(old), Some(new)) => old.as_concrete_TypeRef() == new.as_concrete_TypeRef(),
+            (None, None) => true,
+            _ => false,
+        };
+        if self.loaded && same {
+            return None;
+        }
+        let was_available = !self.loaded || self.data.is_some();
+        self.loaded = true;
+        self.data = data;
+        self.dead_key_state = 0;
+        let available = self.data.is_some();
+        (available != was_available).then_some(available)
+    }
+}
+
+// The layout data is immutable once created, so any thread may read it
+unsafe impl Send for LayoutState {}
+
+static LAYOUT: Mutex<LayoutState> = Mutex::new(LayoutState {
+    loaded: false,
+    data: None,
+    dead_key_state: 0,
+});
+
+static STATUS_HANDLER: Mutex<Option<Box<dyn Fn(bool) + Send>>> = Mutex::new(None);
+
+/// Run `handler` whenever the current layout stops or starts being readable,
+/// with whether it can be read now. Runs at once if it already cannot.
+pub fn set_status_handler<F>(handler: F)
+where
+    F: Fn(bool) + Send + 'static,
+{
+    let unavailable = LAYOUT
+        .lock()
+        .map(|layout| layout.loaded && layout.data.is_none())
+        .unwrap_or(false);
+    if unavailable {
+        handler(false);
+    }
+    if let Ok(mut guard) = STATUS_HANDLER.lock() {
+        *guard = Some(Box::new(handler));
+    }
+}
+
+/// Log a change in whether the layout can be read and pass it to the handler
+fn report_status(available: bool) {
+    if available {
+        eprintln!("Keyboard layout can be read again");
+    } else {
+        eprintln!("Keyboard layout cannot be read; keys pass through unchanged until the layout changes");
+    }
+    if let Ok(guard) = STATUS_HANDLER.lock() {
+        if let Some(handler) = guard.as_ref() {
+            handler(available);
+        }
+    }
+}
+
+/// Load the layout of the current input source. Called at startup and
+/// whenever the keyboard layout changes.
+pub fn refresh_layout() {
+    let data = current_layout_data();
+    let changed = LAYOUT.lock().ok().and_then(|mut layout| layout.load(data));
+    if let Some(available) = changed {
+        report_status(available);
+    }
+}
+
+fn current_layout_data() -> Option<CFData> {
+    unsafe {
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            None
+        } else {
+            let source = CFType::wrap_under_create_rule(source as CFTypeRef);
+            let data = TISGetInputSourceProperty(
+                source.as_CFTypeRef() as TISInputSourceRef,
+                kTISPropertyUnicodeKeyLayoutData,
+            );
+            // Retained, so the data outlives the input source
+            (!data.is_null()).then(|| CFData::wrap_under_get_rule(data as CFDataRef))
+        }
+    }
+}
+
+/// Run `f` with the layout of the current input source. The layout is
+/// looked up on every key, so switching input sources takes effect at once
+/// and one that could not be read is tried again; a new layout starts with
+/// no pending dead key.
+fn with_layout<R>(f: impl FnOnce(&mut LayoutState) -> Option<R>) -> Option<R> {
+    let data = current_layout_data();
+    let (result, changed) = {
+        let mut layout = LAYOUT.lock().ok()?;
+        let changed = layout.load(data);
+        (f(&mut layout), changed)
+    };
+    // The handler may take other locks, so it runs once the layout is released
+    if let Some(available) = changed {
+        report_status(available);
+    }
+    result
+}
+
+/// Call UCKeyTranslate for a key press. Returns the typed UTF-16 units, or
+/// None when the layout could not translate the key.
+fn key_translate(data: &CFData, keycode: CGKeyCode, modifiers: u32, options: u32, dead_key_state: &mut u32) -> Option<String> {
+    let mut chars = [0u16; MAX_KEY_CHARS];
+    let mut length = 0usize;
+    let status = unsafe {
+        UCKeyTranslate(
+            data.bytes().as_ptr(),
+            keycode,
+            KEY_ACTION_DOWN,
+            modifiers,
+            u32::from(LMGetKbdType()),
+            options,
+            dead_key_state,
+            MAX_KEY_CHARS,
+            &mut length,
+            chars.as_mut_ptr(),
+        )
+    };
+    (status == 0).then(|| String::from_utf16_lossy(&chars[..length]))
+}
+
+/// The character a key types on the current layout with Shift and Caps Lock.
+/// With Command, Control or Option held it is the key's plain character, so
+/// shortcuts read the same on every layout. Returns None for keys that type
+/// no character, such as arrows and function keys.
+pub fn key_char(keycode: CGKeyCode, flags: CGEventFlags) -> Option<char> {
+    let shortcut = flags.intersects(
+        CGEventFlags::CGEventFlagCommand | CGEventFlags::CGEventFlagControl | CGEventFlags::CGEventFlagAlternate,
+    );
+    let modifiers = if shortcut { 0 } else { modifier_state(flags) };
+
+    let text = with_layout(|layout| {
+        let data = layout.data.as_ref()?;
+        key_translate(data, keycode, modifiers, NO_DEAD_KEYS, &mut 0)
+    })?;
+    let mut chars = text.chars();
+    let ch = chars.next()?;
+    // Arrows and function keys type control or private use characters
+    let printable = !ch.is_control() && !('\u{E000}'..='\u{F8FF}').contains(&ch);
+    (printable && chars.next().is_none()).then_some(ch)
+}
+
+/// Translate a key press with the current layout, carrying the dead-key
+/// state over to the next key. Returns None when the layout cannot be read,
+/// the key types nothing, or Command is held, since shortcuts never take part
+/// in dead-key sequences.
+pub fn translate_key(keycode: CGKeyCode, flags: CGEventFlags) -> Option<KeyOutput> {
+    if flags.contains(CGEventFlags::CGEventFlagCommand) {
+        return None;
+    }
+
+    with_layout(|layout| {
+        let data = layout.data.as_ref()?;
+        let pending = layout.dead_key_state != 0;
+        let text = match key_translate(data, keycode, modifier_state(flags), 0, &mut layout.dead_key_state) {
+            Some(text) => text,
+            None => {
+                layout.dead_key_state = 0;
+                return None;
+            }
+        };
+
+        if text.is_empty() {
+            return (layout.dead_key_state != 0).then_some(KeyOutput::DeadKey);
+        }
+        Some(if pending {
+            KeyOutput::Composed(text)
+        } else {
+            KeyOutput::Text(text)
+        })
+    })
+}
+
+/// Carbon modifier state, shifted right by 8 as UCKeyTranslate expects
+fn modifier_state(flags: CGEventFlags) -> u32 {
+    let mut state = 0;
+    if flags.contains(CGEventFlags::CGEventFlagShift) {
+        state |= 0x02; // shiftKey
+    }
+    if flags.contains(CGEventFlags::CGEventFlagAlphaShift) {
+        state |= 0x04; // alphaLock
+    }
+    if flags.contains(CGEventFlags::CGEventFlagAlternate) {
+        state |= 0x08; // optionKey
+    }
+    if flags.contains(CGEventFlags::CGEventFlagControl) {
+        state |= 0x10; // controlKey
+    }
+    state
+}
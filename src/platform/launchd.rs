@@ -0,0 +1,125 @@
+// LaunchAgent management
+// As an alternative to a login item, VKey's daemon mode can run as a per-user
+// LaunchAgent: launchd starts it at login and starts it again if it crashes.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::macos::get_home_dir;
+use crate::core::data_dir::{self, DataKind};
+
+/// launchd label of the agent, also the plist file name
+pub const LABEL: &str = "com.vkey.inputmethod";
+
+const LAUNCHCTL: &str = "/bin/launchctl";
+
+/// Where the agent's plist is installed
+pub fn plist_path() -> Option<PathBuf> {
+    let mut path = get_home_dir()?;
+    path.push("Library");
+    path.push("LaunchAgents");
+    path.push(format!("{}.plist", LABEL));
+    Some(path)
+}
+
+/// Where the daemon's output goes
+pub fn log_path() -> Option<PathBuf> {
+    data_dir::data_file(DataKind::Logs, "daemon.log").ok()
+}
+
+pub fn is_installed() -> bool {
+    plist_path().map_or(false, |path| path.exists())
+}
+
+/// Property list running `program daemon` at login. launchd restarts it when
+/// it crashes, but not after a clean exit such as `launchctl bootout`.
+pub fn generate_plist(program: &Path, log_path: &Path) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{program}</string>
+        <string>daemon</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>ProcessType</key>
+    <string>Interactive</string>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = LABEL,
+        program = escape_xml(&program.to_string_lossy()),
+        log = escape_xml(&log_path.to_string_lossy()),
+    )
+}
+
+/// Write the plist for the running executable and start the agent
+pub fn install() -> io::Result<()> {
+    let program = std::env::current_exe()?;
+    let plist = plist_path().ok_or_else(|| not_found("home directory"))?;
+    let log = log_path().ok_or_else(|| not_found("log directory"))?;
+
+    for dir in [plist.parent(), log.parent()].into_iter().flatten() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&plist, generate_plist(&program, &log))?;
+
+    // A previous version of the agent may still be loaded
+    let _ = launchctl(&["bootout", &service_target()]);
+    launchctl(&["bootstrap", &domain_target(), &plist.to_string_lossy()])
+}
+
+/// Stop the agent and remove its plist
+pub fn uninstall() -> io::Result<()> {
+    // Not loaded is fine, the plist still has to go
+    let _ = launchctl(&["bootout", &service_target()]);
+    match plist_path() {
+        Some(plist) if plist.exists() => std::fs::remove_file(plist),
+        _ => Ok(()),
+    }
+}
+
+/// The current user's GUI session, where agents that handle input run
+fn domain_target() -> String {
+    format!("gui/{}", unsafe { libc::getuid() })
+}
+
+fn service_target() -> String {
+    format!("{}/{}", domain_target(), LABEL)
+}
+
+fn launchctl(args: &[&str]) -> io::Result<()> {
+    let status = Command::new(LAUNCHCTL).args(args).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("launchctl {} failed ({})", args.join(" "), status),
+        ))
+    }
+}
+
+fn not_found(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("Cannot find the {}", what))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
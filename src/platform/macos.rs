@@ -1,15 +1,25 @@
+use std::collections::HashMap;
 use std::env::current_exe;
 use std::path::Path;
-use std::{env, path::PathBuf, ptr};
+use std::{env, ffi::c_void, path::PathBuf, ptr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::core::grapheme_clusters;
+use crate::platform::{clipboard, keylayout, permissions};
 use crate::platform::macos_ext;
 use auto_launch::{AutoLaunch, AutoLaunchBuilder};
+use cocoa::appkit::{NSApp, NSApplication};
 use cocoa::base::id;
 use cocoa::{
     base::{nil, YES},
-    foundation::NSDictionary,
+    foundation::{NSDictionary, NSUInteger},
 };
 use core_graphics::{
+    display::CGDisplay,
+    geometry::{CGPoint, CGRect, CGSize},
     event::{
         CGEventFlags, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement, CGEventType,
         CGKeyCode, EventField, KeyCode,
@@ -23,10 +33,18 @@ use objc::{class, msg_send, sel, sel_impl};
 // pub use macos_ext::SystemTrayMenuItemKey;
 use once_cell::sync::Lazy;
 
-use crate::platform::KEYBOARD_LAYOUT_CHARACTER_MAP;
 use accessibility::{AXAttribute, AXUIElement};
-use accessibility_sys::{kAXFocusedUIElementAttribute, kAXSelectedTextAttribute};
+use accessibility_sys::{
+    kAXBoundsForRangeParameterizedAttribute, kAXErrorCannotComplete, kAXErrorFailure, kAXErrorSuccess,
+    kAXSelectedTextRangeAttribute, kAXValueTypeCFRange, kAXValueTypeCGRect, AXUIElementCopyAttributeValue,
+    AXUIElementCopyParameterizedAttributeValue, AXUIElementIsAttributeSettable, AXUIElementSetAttributeValue,
+    AXUIElementSetMessagingTimeout, AXValueCreate, AXValueGetValue, AXValueRef,
+    kAXFocusedUIElementAttribute, kAXRoleAttribute, kAXSelectedTextAttribute, kAXSubroleAttribute,
+    kAXValueAttribute,
+};
 use core_foundation::{
+    base::{CFIndex, CFRange, CFType, CFTypeRef, TCFType},
+    mach_port::CFMachPort,
     runloop::{kCFRunLoopCommonModes, CFRunLoop},
     string::CFString,
 };
@@ -34,11 +52,11 @@ use core_foundation::{
 pub use self::macos_ext::Handle;
 use self::macos_ext::{
     kAXTrustedCheckOptionPrompt, new_tap, AXIsProcessTrustedWithOptions,
-    CGEventCreateKeyboardEvent, CGEventKeyboardSetUnicodeString, CGEventTapPostEvent,
+    CGEventCreateKeyboardEvent, CGEventKeyboardSetUnicodeString, CGEventPost, CGEventSetFlags, CGEventTapPostEvent,
 };
 
 use super::{
-    CallbackFn, EventTapType, KeyModifier, PressedKey, KEY_DELETE, KEY_ENTER, KEY_ESCAPE,
+    CallbackFn, EventTapType, FocusedElementInfo, InjectionError, InjectionStage, KeyModifier, PressedKey, KEY_DELETE, KEY_ENTER, KEY_ESCAPE, KEY_FORWARD_DELETE,
     KEY_SPACE, KEY_TAB,
 };
 
@@ -51,6 +69,7 @@ impl From<CGEventType> for EventTapType {
     fn from(value: CGEventType) -> Self {
         match value {
             CGEventType::KeyDown => EventTapType::KeyDown,
+            CGEventType::KeyUp => EventTapType::KeyUp,
             CGEventType::FlagsChanged => EventTapType::FlagsChanged,
             _ => EventTapType::Other,
         }
@@ -72,7 +91,7 @@ static AUTO_LAUNCH: Lazy<AutoLaunch> = Lazy::new(|| {
 
 /// On macOS, current_exe gives path to /Applications/Example.app/MacOS/Example but this results in seeing a Unix Executable in macOS login items. It must be: /Applications/Example.app
 /// If it didn't find exactly a single occurrence of .app, it will default to exe path to not break it.
-fn get_current_app_path() -> String {
+pub(crate) fn get_current_app_path() -> String {
     let current_exe = current_exe().unwrap();
     let exe_path = current_exe.canonicalize().unwrap().display().to_string();
     let parts: Vec<&str> = exe_path.split(".app/").collect();
@@ -106,124 +125,561 @@ pub fn get_home_dir() -> Option<PathBuf> {
 }
 
 // List of keycode: https://eastmanreference.com/complete-list-of-applescript-key-codes
-fn get_char(keycode: CGKeyCode) -> Option<PressedKey> {
-    if let Some(key_map_mutex) = KEYBOARD_LAYOUT_CHARACTER_MAP.get() {
-        if let Ok(key_map) = key_map_mutex.lock() {
-            return match keycode {
-                0 => Some(PressedKey::Char(key_map.get(&'a').copied().unwrap_or('a'))),
-                1 => Some(PressedKey::Char(key_map.get(&'s').copied().unwrap_or('s'))),
-                2 => Some(PressedKey::Char(key_map.get(&'d').copied().unwrap_or('d'))),
-                3 => Some(PressedKey::Char(key_map.get(&'f').copied().unwrap_or('f'))),
-                4 => Some(PressedKey::Char(key_map.get(&'h').copied().unwrap_or('h'))),
-                5 => Some(PressedKey::Char(key_map.get(&'g').copied().unwrap_or('g'))),
-                6 => Some(PressedKey::Char(key_map.get(&'z').copied().unwrap_or('z'))),
-                7 => Some(PressedKey::Char(key_map.get(&'x').copied().unwrap_or('x'))),
-                8 => Some(PressedKey::Char(key_map.get(&'c').copied().unwrap_or('c'))),
-                9 => Some(PressedKey::Char(key_map.get(&'v').copied().unwrap_or('v'))),
-                11 => Some(PressedKey::Char(key_map.get(&'b').copied().unwrap_or('b'))),
-                12 => Some(PressedKey::Char(key_map.get(&'q').copied().unwrap_or('q'))),
-                13 => Some(PressedKey::Char(key_map.get(&'w').copied().unwrap_or('w'))),
-                14 => Some(PressedKey::Char(key_map.get(&'e').copied().unwrap_or('e'))),
-                15 => Some(PressedKey::Char(key_map.get(&'r').copied().unwrap_or('r'))),
-                16 => Some(PressedKey::Char(key_map.get(&'y').copied().unwrap_or('y'))),
-                17 => Some(PressedKey::Char(key_map.get(&'t').copied().unwrap_or('t'))),
-                31 => Some(PressedKey::Char(key_map.get(&'o').copied().unwrap_or('o'))),
-                32 => Some(PressedKey::Char(key_map.get(&'u').copied().unwrap_or('u'))),
-                34 => Some(PressedKey::Char(key_map.get(&'i').copied().unwrap_or('i'))),
-                35 => Some(PressedKey::Char(key_map.get(&'p').copied().unwrap_or('p'))),
-                37 => Some(PressedKey::Char(key_map.get(&'l').copied().unwrap_or('l'))),
-                38 => Some(PressedKey::Char(key_map.get(&'j').copied().unwrap_or('j'))),
-                40 => Some(PressedKey::Char(key_map.get(&'k').copied().unwrap_or('k'))),
-                45 => Some(PressedKey::Char(key_map.get(&'n').copied().unwrap_or('n'))),
-                46 => Some(PressedKey::Char(key_map.get(&'m').copied().unwrap_or('m'))),
-                18 => Some(PressedKey::Char(key_map.get(&'1').copied().unwrap_or('1'))),
-                19 => Some(PressedKey::Char(key_map.get(&'2').copied().unwrap_or('2'))),
-                20 => Some(PressedKey::Char(key_map.get(&'3').copied().unwrap_or('3'))),
-                21 => Some(PressedKey::Char(key_map.get(&'4').copied().unwrap_or('4'))),
-                22 => Some(PressedKey::Char(key_map.get(&'6').copied().unwrap_or('6'))),
-                23 => Some(PressedKey::Char(key_map.get(&'5').copied().unwrap_or('5'))),
-                25 => Some(PressedKey::Char(key_map.get(&'9').copied().unwrap_or('9'))),
-                26 => Some(PressedKey::Char(key_map.get(&'7').copied().unwrap_or('7'))),
-                28 => Some(PressedKey::Char(key_map.get(&'8').copied().unwrap_or('8'))),
-                29 => Some(PressedKey::Char(key_map.get(&'0').copied().unwrap_or('0'))),
-                27 => Some(PressedKey::Char(key_map.get(&'-').copied().unwrap_or('-'))),
-                33 => Some(PressedKey::Char(key_map.get(&'[').copied().unwrap_or('['))),
-                30 => Some(PressedKey::Char(key_map.get(&']').copied().unwrap_or(']'))),
-                41 => Some(PressedKey::Char(key_map.get(&';').copied().unwrap_or(';'))),
-                43 => Some(PressedKey::Char(key_map.get(&',').copied().unwrap_or(','))),
-                24 => Some(PressedKey::Char(key_map.get(&'=').copied().unwrap_or('='))),
-                42 => Some(PressedKey::Char(key_map.get(&'\\').copied().unwrap_or('\\'))),
-                44 => Some(PressedKey::Char(key_map.get(&'/').copied().unwrap_or('/'))),
-                39 => Some(PressedKey::Char(key_map.get(&'\'').copied().unwrap_or('\''))),
-                47 => Some(PressedKey::Char(key_map.get(&'.').copied().unwrap_or('.'))),
-                50 => Some(PressedKey::Char(key_map.get(&'`').copied().unwrap_or('`'))),  // backtick/grave accent
-                36 | 52 => Some(PressedKey::Char(KEY_ENTER)), // ENTER
-                49 => Some(PressedKey::Char(KEY_SPACE)),      // SPACE
-                48 => Some(PressedKey::Char(KEY_TAB)),        // TAB
-                51 => Some(PressedKey::Char(KEY_DELETE)),     // DELETE
-                53 => Some(PressedKey::Char(KEY_ESCAPE)),     // ESC
-                _ => Some(PressedKey::Raw(keycode)),
-            };
-        }
+/// The key pressed, as the current keyboard layout types it with `flags`
+fn get_char(keycode: CGKeyCode, flags: CGEventFlags) -> Option<PressedKey> {
+    match keycode {
+        36 | 52 => Some(PressedKey::Char(KEY_ENTER)), // ENTER
+        49 => Some(PressedKey::Char(KEY_SPACE)),      // SPACE
+        48 => Some(PressedKey::Char(KEY_TAB)),        // TAB
+        51 => Some(PressedKey::Char(KEY_DELETE)),     // DELETE
+        53 => Some(PressedKey::Char(KEY_ESCAPE)),     // ESC
+        117 => Some(PressedKey::Char(KEY_FORWARD_DELETE)), // FORWARD DELETE
+        // Keypad digits type the same digit whatever the layout
+        _ => keypad_digit(keycode)
+            .or_else(|| keylayout::key_char(keycode, flags))
+            .map(PressedKey::Char)
+            .or(Some(PressedKey::Raw(keycode))),
     }
-    None
 }
 
-/// Check if text is currently selected in the active application
-/// This is used to handle backspace properly when text is selected
-pub fn is_in_text_selection() -> bool {
-    // Use the higher-level accessibility crate for better safety
+/// The digit typed by a numeric keypad key
+fn keypad_digit(keycode: CGKeyCode) -> Option<char> {
+    match keycode {
+        82 => Some('0'),
+        83 => Some('1'),
+        84 => Some('2'),
+        85 => Some('3'),
+        86 => Some('4'),
+        87 => Some('5'),
+        88 => Some('6'),
+        89 => Some('7'),
+        91 => Some('8'),
+        92 => Some('9'),
+        _ => None,
+    }
+}
+
+/// Get the currently focused UI element of the frontmost application
+/// How long one accessibility message may wait on an application. Set on
+/// the system-wide element, it applies to every query VKey makes.
+const AX_MESSAGING_TIMEOUT_SECS: f32 = 0.1;
+
+/// Longest the key path waits for a selection query, in case an application
+/// hangs in a way the messaging timeout does not cover
+const SELECTION_QUERY_DEADLINE: Duration = Duration::from_millis(150);
+
+static AX_TIMEOUT: Once = Once::new();
+
+/// A selection query is still waiting on an application past its deadline
+static SELECTION_QUERY_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Selection queries that timed out, per application
+static AX_TIMEOUTS: Lazy<Mutex<HashMap<String, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn focused_element() -> Option<AXUIElement> {
+    // Without the accessibility permission every query would fail anyway
+    if !permissions::selection_queries_enabled() {
+        return None;
+    }
     let system_element = AXUIElement::system_wide();
-    
-    let focused_element = match system_element
+    AX_TIMEOUT.call_once(|| unsafe {
+        AXUIElementSetMessagingTimeout(system_element.as_concrete_TypeRef(), AX_MESSAGING_TIMEOUT_SECS);
+    });
+    system_element
         .attribute(&AXAttribute::new(&CFString::from_static_string(
             kAXFocusedUIElementAttribute,
-        ))) {
-        Ok(element) => {
-            match element.downcast_into::<AXUIElement>() {
-                Some(element) => element,
-                None => return false,
-            }
+        )))
+        .ok()?
+        .downcast_into::<AXUIElement>()
+}
+
+/// Read a string attribute from an element, if present and actually a string
+fn string_attribute(element: &AXUIElement, name: &'static str) -> Option<String> {
+    element
+        .attribute(&AXAttribute::new(&CFString::from_static_string(name)))
+        .ok()?
+        .downcast_into::<CFString>()
+        .map(|value| value.to_string())
+}
+
+/// Check if text is currently selected in the active application
+/// This is used to handle backspace properly when text is selected.
+/// The query runs off the key path with a deadline; an application that does
+/// not answer in time counts as having no selection.
+pub fn is_in_text_selection() -> bool {
+    // An application still hanging on the last query is not asked again
+    if SELECTION_QUERY_PENDING.swap(true, Ordering::AcqRel) {
+        return false;
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let selected = query_text_selection();
+        SELECTION_QUERY_PENDING.store(false, Ordering::Release);
+        let _ = sender.send(selected);
+    });
+
+    match receiver.recv_timeout(SELECTION_QUERY_DEADLINE) {
+        Ok(Some(selected)) => selected,
+        // The application did not answer within the messaging timeout or the deadline
+        Ok(None) | Err(_) => {
+            record_ax_timeout();
+            false
         }
-        _ => return false,
+    }
+}
+
+/// Whether the focused element has selected text. None when the application
+/// did not answer within the messaging timeout.
+fn query_text_selection() -> Option<bool> {
+    let element = match focused_element() {
+        Some(element) => element,
+        None => return Some(false),
     };
-    
-    let selected_text = match focused_element
-        .attribute(&AXAttribute::new(&CFString::from_static_string(
-            kAXSelectedTextAttribute,
-        ))) {
-        Ok(text) => {
-            match text.downcast_into::<CFString>() {
-                Some(text) => text,
-                None => return false,
-            }
+
+    let mut value: CFTypeRef = ptr::null();
+    let error = unsafe {
+        AXUIElementCopyAttributeValue(
+            element.as_concrete_TypeRef(),
+            CFString::from_static_string(kAXSelectedTextAttribute).as_concrete_TypeRef(),
+            &mut value,
+        )
+    };
+    if error == kAXErrorCannotComplete {
+        return None;
+    }
+    if error != kAXErrorSuccess || value.is_null() {
+        return Some(false);
+    }
+    // Released when dropped
+    let value = unsafe { CFType::wrap_under_create_rule(value) };
+    Some(
+        value
+            .downcast_into::<CFString>()
+            .map_or(false, |text| !text.to_string().is_empty()),
+    )
+}
+
+/// Count a selection query that timed out against the frontmost application
+fn record_ax_timeout() {
+    let app = get_active_app_name();
+    let name = Path::new(&app)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&app)
+        .to_string();
+    eprintln!("Selection query timed out in {}", name);
+    if let Ok(mut timeouts) = AX_TIMEOUTS.lock() {
+        *timeouts.entry(name).or_insert(0) += 1;
+    }
+}
+
+/// Selection queries that timed out since launch, per application, most first
+pub fn ax_timeouts() -> Vec<(String, u32)> {
+    let mut timeouts: Vec<(String, u32)> = AX_TIMEOUTS
+        .lock()
+        .map(|timeouts| timeouts.iter().map(|(app, count)| (app.clone(), *count)).collect())
+        .unwrap_or_default();
+    timeouts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    timeouts
+}
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    fn IsSecureEventInputEnabled() -> u8;
+}
+
+/// Role and subrole of password fields
+const AX_SECURE_TEXT_FIELD: &str = "AXSecureTextField";
+
+/// How long a check of the focused element for a password field is reused
+/// before keys ask for another
+const SECURE_FIELD_RECHECK: Duration = Duration::from_millis(500);
+
+/// The focused element was a secure text field at the last check
+static SECURE_FIELD: AtomicBool = AtomicBool::new(false);
+
+/// When the last check for a password field was started
+static SECURE_FIELD_CHECKED: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// A check for a password field is still waiting on the application
+static SECURE_FIELD_QUERY_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Whether keys are going to a password field or prompt: some process
+/// turned on secure event input, as login windows, most password fields and
+/// terminals at a sudo prompt do, or the focused element was a secure text
+/// field that did not at the last check. Only secure event input is asked on
+/// the key path; the focused element is checked off it, and the answer
+/// serves the keys after it.
+pub fn is_secure_input() -> bool {
+    if unsafe { IsSecureEventInputEnabled() } != 0 {
+        return true;
+    }
+    let stale = SECURE_FIELD_CHECKED
+        .lock()
+        .map_or(false, |checked| checked.map_or(true, |at| at.elapsed() >= SECURE_FIELD_RECHECK));
+    if stale {
+        refresh_secure_field();
+    }
+    SECURE_FIELD.load(Ordering::Acquire)
+}
+
+/// Check on another thread whether the focused element is a secure text
+/// field, as when focus moves to another application. An application still
+/// hanging on the last check is not asked again.
+pub fn refresh_secure_field() {
+    if SECURE_FIELD_QUERY_PENDING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    if let Ok(mut checked) = SECURE_FIELD_CHECKED.lock() {
+        *checked = Some(Instant::now());
+    }
+    thread::spawn(|| {
+        let secure = focused_element().map_or(false, |element| {
+            string_attribute(&element, kAXRoleAttribute).as_deref() == Some(AX_SECURE_TEXT_FIELD)
+                || string_attribute(&element, kAXSubroleAttribute).as_deref() == Some(AX_SECURE_TEXT_FIELD)
+        });
+        SECURE_FIELD.store(secure, Ordering::Release);
+        SECURE_FIELD_QUERY_PENDING.store(false, Ordering::Release);
+    });
+}
+
+/// Attribute some Cocoa text views expose for the input method's marked (pre-edit) range
+const AX_MARKED_TEXT_RANGE_ATTRIBUTE: &str = "AXMarkedTextRange";
+
+/// Check if an attribute of an element can be written
+fn is_attribute_settable(element: &AXUIElement, name: &'static str) -> bool {
+    let mut settable = 0;
+    let error = unsafe {
+        AXUIElementIsAttributeSettable(
+            element.as_concrete_TypeRef(),
+            CFString::from_static_string(name).as_concrete_TypeRef(),
+            &mut settable,
+        )
+    };
+    error == kAXErrorSuccess && settable != 0
+}
+
+/// Read a range attribute from an element, if present and actually a range
+fn range_attribute(element: &AXUIElement, name: &'static str) -> Option<CFRange> {
+    let value = element
+        .attribute(&AXAttribute::new(&CFString::from_static_string(name)))
+        .ok()?;
+    let mut range = CFRange { location: 0, length: 0 };
+    let found = unsafe {
+        AXValueGetValue(
+            value.as_CFTypeRef() as AXValueRef,
+            kAXValueTypeCFRange,
+            &mut range as *mut CFRange as *mut c_void,
+        )
+    };
+    if found {
+        Some(range)
+    } else {
+        None
+    }
+}
+
+/// Write an attribute of an element, returning the accessibility error on failure
+fn set_attribute_value(element: &AXUIElement, name: &'static str, value: CFTypeRef) -> Result<(), i32> {
+    let error = unsafe {
+        AXUIElementSetAttributeValue(
+            element.as_concrete_TypeRef(),
+            CFString::from_static_string(name).as_concrete_TypeRef(),
+            value,
+        )
+    };
+    if error == kAXErrorSuccess {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
+
+fn set_range_attribute(element: &AXUIElement, name: &'static str, range: CFRange) -> Result<(), i32> {
+    let value = unsafe { AXValueCreate(kAXValueTypeCFRange, &range as *const CFRange as *const c_void) };
+    if value.is_null() {
+        return Err(kAXErrorFailure);
+    }
+    // Released when dropped
+    let value = unsafe { CFType::wrap_under_create_rule(value as CFTypeRef) };
+    set_attribute_value(element, name, value.as_CFTypeRef())
+}
+
+/// Where the text cursor of the focused element is on screen: the bottom-left
+/// corner of the insertion point, in Cocoa coordinates (origin at the bottom
+/// left of the main display). None if the app does not expose its text bounds.
+pub fn caret_anchor() -> Option<(f64, f64)> {
+    let element = focused_element()?;
+    let range = range_attribute(&element, kAXSelectedTextRangeAttribute)?;
+
+    let range_value = unsafe { AXValueCreate(kAXValueTypeCFRange, &range as *const CFRange as *const c_void) };
+    if range_value.is_null() {
+        return None;
+    }
+    // Released when dropped
+    let range_value = unsafe { CFType::wrap_under_create_rule(range_value as CFTypeRef) };
+
+    let mut bounds_value: CFTypeRef = ptr::null();
+    let error = unsafe {
+        AXUIElementCopyParameterizedAttributeValue(
+            element.as_concrete_TypeRef(),
+            CFString::from_static_string(kAXBoundsForRangeParameterizedAttribute).as_concrete_TypeRef(),
+            range_value.as_CFTypeRef(),
+            &mut bounds_value,
+        )
+    };
+    if error != kAXErrorSuccess || bounds_value.is_null() {
+        return None;
+    }
+    let bounds_value = unsafe { CFType::wrap_under_create_rule(bounds_value) };
+
+    let mut bounds = CGRect::new(&CGPoint::new(0., 0.), &CGSize::new(0., 0.));
+    let found = unsafe {
+        AXValueGetValue(
+            bounds_value.as_CFTypeRef() as AXValueRef,
+            kAXValueTypeCGRect,
+            &mut bounds as *mut CGRect as *mut c_void,
+        )
+    };
+    if !found {
+        return None;
+    }
+
+    // Accessibility measures from the top left of the main display
+    let main_height = CGDisplay::main().bounds().size.height;
+    Some((bounds.origin.x, main_height - (bounds.origin.y + bounds.size.height)))
+}
+
+/// Check if the focused element lets VKey show the word being composed as marked text
+pub fn supports_marked_text() -> bool {
+    focused_element()
+        .map(|element| is_attribute_settable(&element, AX_MARKED_TEXT_RANGE_ATTRIBUTE))
+        .unwrap_or(false)
+}
+
+/// Replace the marked text `previous` before the cursor with `text` and mark it,
+/// so the app underlines it like an input method's pre-edit. An empty `text`
+/// removes the marked text.
+pub fn set_marked_text(previous: &str, text: &str) -> Result<(), InjectionError> {
+    let fail = |status| InjectionError {
+        stage: InjectionStage::MarkedText,
+        length: text.chars().count(),
+        status,
+    };
+
+    let element = focused_element().ok_or_else(|| fail(kAXErrorFailure))?;
+    let cursor = range_attribute(&element, kAXSelectedTextRangeAttribute).ok_or_else(|| fail(kAXErrorFailure))?;
+    let previous_len = previous.encode_utf16().count() as CFIndex;
+    let start = cursor.location - previous_len;
+
+    // Only touch text we marked ourselves; focus may have moved to another field
+    if previous_len > 0 {
+        match range_attribute(&element, AX_MARKED_TEXT_RANGE_ATTRIBUTE) {
+            Some(marked) if marked.location == start && marked.length == previous_len => {}
+            _ => return Err(fail(kAXErrorFailure)),
         }
-        _ => return false,
+    }
+    if start < 0 {
+        return Err(fail(kAXErrorFailure));
+    }
+
+    // Select the previous marked text and type over it
+    set_range_attribute(&element, kAXSelectedTextRangeAttribute, CFRange { location: start, length: previous_len })
+        .map_err(fail)?;
+    set_attribute_value(&element, kAXSelectedTextAttribute, CFString::new(text).as_CFTypeRef())
+        .map_err(fail)?;
+
+    let text_len = text.encode_utf16().count() as CFIndex;
+    set_range_attribute(&element, AX_MARKED_TEXT_RANGE_ATTRIBUTE, CFRange { location: start, length: text_len })
+        .map_err(fail)?;
+    set_range_attribute(&element, kAXSelectedTextRangeAttribute, CFRange { location: start + text_len, length: 0 })
+        .map_err(fail)
+}
+
+/// Snapshot the accessibility data VKey's heuristics see for the focused element
+pub fn inspect_focused_element() -> FocusedElementInfo {
+    let mut info = FocusedElementInfo {
+        app_name: get_active_app_name(),
+        selection_workaround: should_dismiss_selection_if_needed(),
+        ..Default::default()
     };
-    
-    !selected_text.to_string().is_empty()
+
+    if let Some(element) = focused_element() {
+        info.has_focused_element = true;
+        info.marked_text = is_attribute_settable(&element, AX_MARKED_TEXT_RANGE_ATTRIBUTE);
+        info.role = string_attribute(&element, kAXRoleAttribute);
+        info.subrole = string_attribute(&element, kAXSubroleAttribute);
+        info.selected_text = string_attribute(&element, kAXSelectedTextAttribute);
+        info.value_length = string_attribute(&element, kAXValueAttribute)
+            .map(|value| value.chars().count());
+    }
+
+    info
+}
+
+/// The letters right before the caret in the focused element, when nothing
+/// is selected. None if the application does not expose its text and caret.
+pub fn word_before_caret() -> Option<String> {
+    let element = focused_element()?;
+    let range = range_attribute(&element, kAXSelectedTextRangeAttribute)?;
+    if range.length != 0 || range.location <= 0 {
+        return None;
+    }
+    let value = string_attribute(&element, kAXValueAttribute)?;
+
+    // Accessibility counts UTF-16 units
+    let units: Vec<u16> = value.encode_utf16().take(range.location as usize).collect();
+    let before = String::from_utf16(&units).ok()?;
+    // Combining marks belong to the word when the text is decomposed
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| ch.is_alphabetic() || ('\u{300}'..='\u{36F}').contains(ch))
+        .last()
+        .map(|(index, _)| index)?;
+    Some(before[start..].to_string())
+}
+
+/// Replace the `length` characters before the caret in the focused element
+/// with `text` by selecting them and typing over the selection, without any
+/// key events. Fails before touching the text when the element does not
+/// expose its text and caret, or has a selection of its own.
+pub fn replace_before_caret(length: usize, text: &str) -> Result<(), InjectionError> {
+    let fail = |status| InjectionError {
+        stage: InjectionStage::Accessibility,
+        length: text.chars().count(),
+        status,
+    };
+
+    let element = focused_element().ok_or_else(|| fail(kAXErrorFailure))?;
+    if !is_attribute_settable(&element, kAXSelectedTextRangeAttribute)
+        || !is_attribute_settable(&element, kAXSelectedTextAttribute)
+    {
+        return Err(fail(kAXErrorFailure));
+    }
+    let caret = range_attribute(&element, kAXSelectedTextRangeAttribute).ok_or_else(|| fail(kAXErrorFailure))?;
+    if caret.length != 0 || caret.location < 0 {
+        return Err(fail(kAXErrorFailure));
+    }
+
+    // Accessibility counts UTF-16 units, the engine counts characters with
+    // their combining marks
+    let mut erased = 0;
+    if length > 0 {
+        let value = string_attribute(&element, kAXValueAttribute).ok_or_else(|| fail(kAXErrorFailure))?;
+        let units: Vec<u16> = value.encode_utf16().take(caret.location as usize).collect();
+        let before = String::from_utf16(&units).map_err(|_| fail(kAXErrorFailure))?;
+        let clusters = grapheme_clusters(&before);
+        if clusters.len() < length {
+            return Err(fail(kAXErrorFailure));
+        }
+        erased = clusters[clusters.len() - length..]
+            .iter()
+            .map(|cluster| cluster.encode_utf16().count())
+            .sum::<usize>() as CFIndex;
+    }
+    let start = caret.location - erased;
+
+    set_range_attribute(&element, kAXSelectedTextRangeAttribute, CFRange { location: start, length: erased })
+        .map_err(fail)?;
+    if let Err(status) = set_attribute_value(&element, kAXSelectedTextAttribute, CFString::new(text).as_CFTypeRef()) {
+        // Put the caret back so backspaces sent instead erase the right text
+        let _ = set_range_attribute(&element, kAXSelectedTextRangeAttribute, caret);
+        return Err(fail(status));
+    }
+
+    // The text is in; most apps already leave the caret after it
+    let text_len = text.encode_utf16().count() as CFIndex;
+    let _ = set_range_attribute(&element, kAXSelectedTextRangeAttribute, CFRange { location: start + text_len, length: 0 });
+    Ok(())
+}
+
+/// Read the text selected in the focused element, if any
+pub fn selected_text() -> Option<String> {
+    focused_element()
+        .and_then(|element| string_attribute(&element, kAXSelectedTextAttribute))
+        .filter(|text| !text.is_empty())
+}
+
+/// Replace the selected text of the focused element through accessibility
+pub fn replace_selected_text(text: &str) -> Result<(), InjectionError> {
+    let fail = |status| InjectionError {
+        stage: InjectionStage::Text,
+        length: text.chars().count(),
+        status,
+    };
+    let element = focused_element().ok_or_else(|| fail(kAXErrorFailure))?;
+    set_attribute_value(&element, kAXSelectedTextAttribute, CFString::new(text).as_CFTypeRef())
+        .map_err(fail)
+}
+
+/// Read the full text of the focused element, if it exposes one
+pub fn focused_value() -> Option<String> {
+    focused_element().and_then(|element| string_attribute(&element, kAXValueAttribute))
+}
+
+/// CGError returned when an event could not be created or posted
+const CG_ERROR_CANNOT_COMPLETE: i32 = 1004;
+
+/// Create a keyboard event, reporting a null event as an injection failure
+fn create_keyboard_event(
+    keycode: CGKeyCode,
+    keydown: bool,
+    stage: InjectionStage,
+    length: usize,
+) -> Result<sys::CGEventRef, InjectionError> {
+    let null_event_source = ptr::null_mut() as *mut sys::CGEventSource;
+    let event = unsafe { CGEventCreateKeyboardEvent(null_event_source, keycode, keydown) };
+    if event.is_null() {
+        return Err(InjectionError {
+            stage,
+            length,
+            status: CG_ERROR_CANNOT_COMPLETE,
+        });
+    }
+    Ok(event)
 }
 
-pub fn send_backspace(handle: Handle, count: usize) -> Result<(), ()> {
+/// Create a key down event carrying the given text
+fn create_string_event(string: &str) -> Result<sys::CGEventRef, InjectionError> {
+    let utf_16_str: Vec<u16> = string.encode_utf16().collect();
+    let event_str = create_keyboard_event(0, true, InjectionStage::Text, string.chars().count())?;
+    unsafe {
+        let buflen = utf_16_str.len() as libc::c_ulong;
+        let bufptr = utf_16_str.as_ptr();
+        CGEventKeyboardSetUnicodeString(event_str, buflen, bufptr);
+    }
+    Ok(event_str)
+}
+
+/// Handle for posting without an event tap, as the command line does.
+/// Events then go to the system event stream.
+pub const NO_TAP: Handle = ptr::null();
+
+/// Post `event` through the tap `handle`, or to the system event stream for `NO_TAP`
+unsafe fn post_event(handle: Handle, event: sys::CGEventRef) {
+    if handle.is_null() {
+        CGEventPost(CGEventTapLocation::HID, event);
+    } else {
+        CGEventTapPostEvent(handle, event);
+    }
+}
+
+pub fn send_backspace(handle: Handle, count: usize) -> Result<(), InjectionError> {
     if count == 0 {
         return Ok(());
     }
     
-    let null_event_source = ptr::null_mut() as *mut sys::CGEventSource;
-    
     // Create backspace events once and reuse them
-    let (event_bs_down, event_bs_up) = unsafe {
-        (
-            CGEventCreateKeyboardEvent(null_event_source, KeyCode::DELETE, true),
-            CGEventCreateKeyboardEvent(null_event_source, KeyCode::DELETE, false),
-        )
-    };
+    let event_bs_down = create_keyboard_event(KeyCode::DELETE, true, InjectionStage::Backspace, count)?;
+    let event_bs_up = create_keyboard_event(KeyCode::DELETE, false, InjectionStage::Backspace, count)?;
     
     // Send backspaces with proper timing to prevent flashing
     for _ in 0..count {
         unsafe {
-            CGEventTapPostEvent(handle, event_bs_down);
-            CGEventTapPostEvent(handle, event_bs_up);
+            post_event(handle, event_bs_down);
+            post_event(handle, event_bs_up);
         }
     }
     
@@ -234,25 +690,95 @@ pub fn send_backspace(handle: Handle, count: usize) -> Result<(), ()> {
     Ok(())
 }
 
-pub fn send_string(handle: Handle, string: &str) -> Result<(), ()> {
+/// Move the cursor `offset` characters with the arrow keys, left when negative
+pub fn send_cursor_move(handle: Handle, offset: isize) -> Result<(), InjectionError> {
+    if offset == 0 {
+        return Ok(());
+    }
+
+    let keycode = if offset < 0 { KeyCode::LEFT_ARROW } else { KeyCode::RIGHT_ARROW };
+    let count = offset.unsigned_abs();
+    let event_down = create_keyboard_event(keycode, true, InjectionStage::Cursor, count)?;
+    let event_up = create_keyboard_event(keycode, false, InjectionStage::Cursor, count)?;
+    for _ in 0..count {
+        unsafe {
+            post_event(handle, event_down);
+            post_event(handle, event_up);
+        }
+    }
+    Ok(())
+}
+
+pub fn send_string(handle: Handle, string: &str) -> Result<(), InjectionError> {
     if string.is_empty() {
         return Ok(());
     }
     
-    let utf_16_str: Vec<u16> = string.encode_utf16().collect();
-    let null_event_source = ptr::null_mut() as *mut sys::CGEventSource;
+    // Single text event with all characters
+    let event_str = create_string_event(string)?;
+    unsafe {
+        post_event(handle, event_str);
+    }
+    Ok(())
+}
+
+/// Key code of V on an ANSI keyboard, which Command+V goes by on most layouts
+const KEY_CODE_V: CGKeyCode = 0x09;
 
+/// Type a string by putting it on the clipboard and pressing Command+V, for
+/// applications that mishandle typed text. A paste waits for the application
+/// to read the one before it, and the clipboard gets its contents back once
+/// the application has pasted.
+pub fn paste_string(handle: Handle, string: &str) -> Result<(), InjectionError> {
+    if string.is_empty() {
+        return Ok(());
+    }
+
+    let length = string.chars().count();
+    if !clipboard::stage_paste(string) {
+        return Err(InjectionError {
+            stage: InjectionStage::Paste,
+            length,
+            status: CG_ERROR_CANNOT_COMPLETE,
+        });
+    }
+    let event_down = create_keyboard_event(KEY_CODE_V, true, InjectionStage::Paste, length)?;
+    let event_up = create_keyboard_event(KEY_CODE_V, false, InjectionStage::Paste, length)?;
     unsafe {
-        // Create single text event with all characters
-        let event_str = CGEventCreateKeyboardEvent(null_event_source, 0, true);
-        let buflen = utf_16_str.len() as libc::c_ulong;
-        let bufptr = utf_16_str.as_ptr();
-        CGEventKeyboardSetUnicodeString(event_str, buflen, bufptr);
-        CGEventTapPostEvent(handle, event_str);
+        CGEventSetFlags(event_down, CGEventFlags::CGEventFlagCommand);
+        CGEventSetFlags(event_up, CGEventFlags::CGEventFlagCommand);
+        post_event(handle, event_down);
+        post_event(handle, event_up);
+    }
+    Ok(())
+}
+
+/// Type a string one grapheme cluster per event. Terminals write the text
+/// of each event on its own, and some draw a combining mark that arrives
+/// apart from its letter in a cell of its own.
+pub fn send_string_by_cluster(handle: Handle, string: &str) -> Result<(), InjectionError> {
+    for cluster in grapheme_clusters(string) {
+        send_string(handle, cluster)?;
     }
     Ok(())
 }
 
+/// Post a string one grapheme cluster per event without going through an
+/// event tap, like `send_string_by_cluster`
+pub fn post_string_by_cluster(string: &str) -> Result<(), InjectionError> {
+    send_string_by_cluster(NO_TAP, string)
+}
+
+/// Post a string to the focused application without going through an event tap
+pub fn post_string(string: &str) -> Result<(), InjectionError> {
+    send_string(NO_TAP, string)
+}
+
+/// Post backspaces to the focused application without going through an event tap
+pub fn post_backspace(count: usize) -> Result<(), InjectionError> {
+    send_backspace(NO_TAP, count)
+}
+
 /// Check if we should dismiss text selection
 pub fn should_dismiss_selection_if_needed() -> bool {
     let app_name = get_active_app_name();
@@ -276,6 +802,17 @@ where
     macos_ext::add_app_change_callback(cb);
 }
 
+/// The running event tap, kept so it can be stopped from another thread
+struct RunningTap {
+    run_loop: CFRunLoop,
+    mach_port: CFMachPort,
+}
+
+// CFRunLoopStop and CGEventTapEnable may be called from any thread
+unsafe impl Send for RunningTap {}
+
+static RUNNING_TAP: Mutex<Option<RunningTap>> = Mutex::new(None);
+
 pub fn run_event_listener(callback: &CallbackFn) {
     let current = CFRunLoop::get_current();
     if let Ok(event_tap) = new_tap::CGEventTap::new(
@@ -284,15 +821,16 @@ pub fn run_event_listener(callback: &CallbackFn) {
         CGEventTapOptions::Default,
         vec![
             CGEventType::KeyDown,
+            CGEventType::KeyUp,
             CGEventType::RightMouseDown,
             CGEventType::LeftMouseDown,
             CGEventType::OtherMouseDown,
             CGEventType::FlagsChanged,
         ],
         |proxy, _, event| {
-            if !is_process_trusted() {
-                eprintln!("Accessibility access removed!");
-                std::process::exit(1);
+            if !permissions::can_post_events() {
+                eprintln!("Permission to post keystrokes removed!");
+                crate::shutdown::exit(1);
             }
 
             let mut modifiers = KeyModifier::new();
@@ -318,6 +856,15 @@ pub fn run_event_listener(callback: &CallbackFn) {
                 modifiers = KeyModifier::MODIFIER_NONE;
             }
 
+            // macOS switches off a tap that answered too slowly, or around
+            // sleep; it is switched back on and the key handler hears of it
+            // as a cursor move, since keys were missed meanwhile
+            if let CGEventType::TapDisabledByTimeout | CGEventType::TapDisabledByUserInput = event.get_type() {
+                if reenable_event_tap() {
+                    eprintln!("Event tap was disabled by macOS; enabled it again");
+                }
+            }
+
             let event_tap_type: EventTapType = EventTapType::from(event.get_type());
             match event_tap_type {
                 EventTapType::KeyDown => {
@@ -327,13 +874,36 @@ pub fn run_event_listener(callback: &CallbackFn) {
                         let key_code = event
                             .get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE)
                             as CGKeyCode;
+                        if keypad_digit(key_code).is_some() {
+                            modifiers.add_keypad();
+                        }
+                        // Every key goes through the layout so dead keys are followed
+                        if keylayout::translate_key(key_code, flags)
+                            .map_or(false, |output| output.is_dead_key_sequence())
+                        {
+                            modifiers.add_dead_key();
+                        }
 
-                        if callback(proxy, event_tap_type, get_char(key_code), modifiers) {
+                        if callback(proxy, event_tap_type, get_char(key_code, flags), modifiers) {
                             // block the key if already processed
                             return None;
                         }
                     }
                 }
+                // Only a key held for a hotkey needs its release, so the
+                // layout is not consulted again
+                EventTapType::KeyUp => {
+                    let source_state_id =
+                        event.get_integer_value_field(EventField::EVENT_SOURCE_STATE_ID);
+                    if source_state_id == 1 {
+                        let key_code = event
+                            .get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE)
+                            as CGKeyCode;
+                        if callback(proxy, event_tap_type, get_char(key_code, flags), modifiers) {
+                            return None;
+                        }
+                    }
+                }
                 EventTapType::FlagsChanged => {
                     callback(proxy, event_tap_type, None, modifiers);
                 }
@@ -348,11 +918,74 @@ pub fn run_event_listener(callback: &CallbackFn) {
             let loop_source = event_tap.mach_port.create_runloop_source(0).expect("Cannot start event tap. Make sure you have granted Accessibility Access for the application.");
             current.add_source(&loop_source, kCFRunLoopCommonModes);
             event_tap.enable();
+            if let Ok(mut running) = RUNNING_TAP.lock() {
+                *running = Some(RunningTap {
+                    run_loop: current.clone(),
+                    mach_port: event_tap.mach_port.clone(),
+                });
+            }
             CFRunLoop::run_current();
         }
     }
 }
 
+/// Whether the event tap is enabled, or None when it is not installed
+pub fn event_tap_enabled() -> Option<bool> {
+    let running = RUNNING_TAP.lock().ok()?;
+    let tap = running.as_ref()?;
+    Some(unsafe { macos_ext::new_tap::CGEventTapIsEnabled(tap.mach_port.as_concrete_TypeRef()) })
+}
+
+/// Enable the event tap again if macOS switched it off. Returns whether it
+/// had to; a tap that was stopped or never installed is left alone.
+pub fn reenable_event_tap() -> bool {
+    let Ok(running) = RUNNING_TAP.lock() else {
+        return false;
+    };
+    let Some(tap) = running.as_ref() else {
+        return false;
+    };
+    let port = tap.mach_port.as_concrete_TypeRef();
+    unsafe {
+        if macos_ext::new_tap::CGEventTapIsEnabled(port) {
+            return false;
+        }
+        macos_ext::new_tap::CGEventTapEnable(port, true);
+    }
+    true
+}
+
+/// Disable the event tap so keys reach applications untouched, and end the
+/// listener thread's run loop
+pub fn stop_event_listener() {
+    let running = RUNNING_TAP.lock().ok().and_then(|mut running| running.take());
+    if let Some(tap) = running {
+        unsafe {
+            macos_ext::new_tap::CGEventTapEnable(tap.mach_port.as_concrete_TypeRef(), false);
+        }
+        tap.run_loop.stop();
+    }
+}
+
+/// Make VKey the active application and pull its windows onto the current
+/// Space, so a window shown from the menu bar is not left on another desktop
+/// or behind a full-screen app
+pub fn bring_app_to_front() {
+    unsafe {
+        let app = NSApp();
+        let windows: id = msg_send![app, windows];
+        let count: NSUInteger = msg_send![windows, count];
+        for i in 0..count {
+            let window: id = msg_send![windows, objectAtIndex: i];
+            let behavior: NSUInteger = msg_send![window, collectionBehavior];
+            let _: () = msg_send![window, setCollectionBehavior: behavior
+                | macos_ext::COLLECTION_BEHAVIOR_MOVE_TO_ACTIVE_SPACE
+                | macos_ext::COLLECTION_BEHAVIOR_FULL_SCREEN_AUXILIARY];
+        }
+        app.activateIgnoringOtherApps_(YES);
+    }
+}
+
 pub fn is_process_trusted() -> bool {
     unsafe { accessibility_sys::AXIsProcessTrusted() }
 }
@@ -378,6 +1011,22 @@ pub fn get_active_app_name() -> String {
     }
 }
 
+/// Bundle identifier of the frontmost application, such as `com.apple.Terminal`
+pub fn get_active_app_bundle_id() -> Option<String> {
+    unsafe {
+        let shared_workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let front_most_app: id = msg_send![shared_workspace, frontmostApplication];
+        if front_most_app == nil {
+            return None;
+        }
+        let bundle_id: id = msg_send![front_most_app, bundleIdentifier];
+        if bundle_id == nil {
+            return None;
+        }
+        nsstring_to_string!(bundle_id)
+    }
+}
+
 pub fn update_launch_on_login(is_enable: bool) -> Result<(), auto_launch::Error> {
     match is_enable {
         true => AUTO_LAUNCH.enable(),
@@ -1,12 +1,12 @@
 use cocoa::appkit::{
     NSApp, NSApplication, NSButton, NSMenu, NSMenuItem, NSStatusBar, NSStatusItem,
 };
-use cocoa::base::{id, nil, YES};
-use cocoa::foundation::{NSAutoreleasePool, NSString};
+use cocoa::base::{id, nil, NO, YES};
+use cocoa::foundation::{NSAutoreleasePool, NSInteger, NSPoint, NSRect, NSSize, NSString, NSUInteger};
 use core_foundation::dictionary::CFDictionaryRef;
 use core_foundation::string::CFStringRef;
 use core_graphics::{
-    event::{CGEventTapProxy, CGKeyCode},
+    event::{CGEventFlags, CGEventTapLocation, CGEventTapProxy, CGKeyCode},
     sys,
 };
 use druid::{Data, Lens};
@@ -35,6 +35,7 @@ pub enum SystemTrayMenuItemKey {
     Enable,
     TypingMethodTelex,
     TypingMethodVNI,
+    ToggleDictionaryWord,
     Exit,
 }
 
@@ -114,6 +115,8 @@ impl SystemTray {
         self.add_menu_item("Telex ✓", || ());
         self.add_menu_item("VNI", || ());
         self.add_menu_separator();
+        self.add_menu_item("Thêm/xoá từ trong từ điển", || ());
+        self.add_menu_separator();
         self.add_menu_item("Thoát ứng dụng", || ());
     }
 
@@ -147,7 +150,8 @@ impl SystemTray {
             SystemTrayMenuItemKey::Enable => 2,
             SystemTrayMenuItemKey::TypingMethodTelex => 4,
             SystemTrayMenuItemKey::TypingMethodVNI => 5,
-            SystemTrayMenuItemKey::Exit => 7,
+            SystemTrayMenuItemKey::ToggleDictionaryWord => 7,
+            SystemTrayMenuItemKey::Exit => 9,
         }
     }
 
@@ -183,6 +187,7 @@ pub type Handle = CGEventTapProxy;
 #[link(name = "CoreGraphics", kind = "framework")]
 extern "C" {
     pub(crate) fn CGEventTapPostEvent(proxy: CGEventTapProxy, event: sys::CGEventRef);
+    pub(crate) fn CGEventPost(tap: CGEventTapLocation, event: sys::CGEventRef);
     pub(crate) fn CGEventCreateKeyboardEvent(
         source: sys::CGEventSourceRef,
         keycode: CGKeyCode,
@@ -193,6 +198,7 @@ extern "C" {
         length: libc::c_ulong,
         string: *const u16,
     );
+    pub(crate) fn CGEventSetFlags(event: sys::CGEventRef, flags: CGEventFlags);
 }
 
 pub mod new_tap {
@@ -232,7 +238,8 @@ pub mod new_tap {
             callback: CGEventTapCallBackInternal,
             userInfo: *const c_void,
         ) -> CFMachPortRef;
-        fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+        pub(crate) fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+        pub(crate) fn CGEventTapIsEnabled(tap: CFMachPortRef) -> bool;
     }
 
     #[no_mangle]
@@ -403,3 +410,106 @@ where
         ];
     }
 }
+
+/// NSWindowCollectionBehavior flags
+pub const COLLECTION_BEHAVIOR_CAN_JOIN_ALL_SPACES: NSUInteger = 1 << 0;
+pub const COLLECTION_BEHAVIOR_MOVE_TO_ACTIVE_SPACE: NSUInteger = 1 << 1;
+pub const COLLECTION_BEHAVIOR_STATIONARY: NSUInteger = 1 << 4;
+pub const COLLECTION_BEHAVIOR_IGNORES_CYCLE: NSUInteger = 1 << 6;
+pub const COLLECTION_BEHAVIOR_FULL_SCREEN_AUXILIARY: NSUInteger = 1 << 8;
+
+/// NSWindowStyleMaskNonactivatingPanel: clicking the panel does not take
+/// focus away from the application being typed into
+const STYLE_MASK_NONACTIVATING_PANEL: NSUInteger = 1 << 7;
+
+/// How high a floating window sits above other windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatingLevel {
+    /// Mode indicators such as the VN/EN HUD (NSStatusWindowLevel)
+    Hud,
+    /// Popups tied to the text being typed, such as suggestions (NSPopUpMenuWindowLevel)
+    Popup,
+}
+
+impl FloatingLevel {
+    fn window_level(self) -> NSInteger {
+        match self {
+            FloatingLevel::Hud => 25,
+            FloatingLevel::Popup => 101,
+        }
+    }
+}
+
+/// Set up a window for floating UI: above full-screen apps, present on every
+/// Space, left out of window cycling and Exposé, and kept visible while
+/// another application is active. Every floating window goes through here.
+pub fn configure_floating_window(window: id, level: FloatingLevel) {
+    unsafe {
+        let _: () = msg_send![window, setLevel: level.window_level()];
+        let _: () = msg_send![window, setCollectionBehavior: COLLECTION_BEHAVIOR_CAN_JOIN_ALL_SPACES
+            | COLLECTION_BEHAVIOR_STATIONARY
+            | COLLECTION_BEHAVIOR_IGNORES_CYCLE
+            | COLLECTION_BEHAVIOR_FULL_SCREEN_AUXILIARY];
+        let _: () = msg_send![window, setHidesOnDeactivate: NO];
+        let _: () = msg_send![window, setReleasedWhenClosed: NO];
+    }
+}
+
+/// Create a borderless panel for floating UI that never becomes the key
+/// window, so typing keeps going to the focused application
+pub fn new_floating_panel(width: f64, height: f64, level: FloatingLevel) -> id {
+    unsafe {
+        let frame = NSRect::new(NSPoint::new(0., 0.), NSSize::new(width, height));
+        let panel: id = msg_send![class!(NSPanel), alloc];
+        let panel: id = msg_send![panel,
+            initWithContentRect: frame
+            styleMask: STYLE_MASK_NONACTIVATING_PANEL
+            backing: 2 as NSUInteger
+            defer: YES
+        ];
+        let _: () = msg_send![panel, setFloatingPanel: YES];
+        let _: () = msg_send![panel, setBecomesKeyOnlyIfNeeded: YES];
+        let _: () = msg_send![panel, setOpaque: NO];
+        let _: () = msg_send![panel, setHasShadow: YES];
+        configure_floating_window(panel, level);
+        panel
+    }
+}
+
+/// Show a floating window with its top-left corner at `anchor` (screen
+/// coordinates, origin at the bottom left), on the screen that contains the
+/// anchor and inside its visible area. Does not activate VKey.
+pub fn show_floating_window_at(window: id, anchor: NSPoint) {
+    unsafe {
+        let frame: NSRect = msg_send![window, frame];
+        let visible = screen_containing(anchor)
+            .map(|screen| -> NSRect { msg_send![screen, visibleFrame] })
+            .unwrap_or(NSRect::new(anchor, frame.size));
+
+        let max_x = visible.origin.x + visible.size.width - frame.size.width;
+        let min_y = visible.origin.y + frame.size.height;
+        let top_left = NSPoint::new(
+            anchor.x.min(max_x).max(visible.origin.x),
+            anchor.y.max(min_y).min(visible.origin.y + visible.size.height),
+        );
+        let _: () = msg_send![window, setFrameTopLeftPoint: top_left];
+        let _: () = msg_send![window, orderFrontRegardless];
+    }
+}
+
+/// The screen whose frame contains a point, if any
+fn screen_containing(point: NSPoint) -> Option<id> {
+    unsafe {
+        let screens: id = msg_send![class!(NSScreen), screens];
+        let count: NSUInteger = msg_send![screens, count];
+        (0..count)
+            .map(|i| -> id { msg_send![screens, objectAtIndex: i] })
+            .find(|&screen| {
+                let frame: NSRect = msg_send![screen, frame];
+                point.x >= frame.origin.x
+                    && point.x < frame.origin.x + frame.size.width
+                    && point.y >= frame.origin.y
+                    && point.y < frame.origin.y + frame.size.height
+            })
+    }
+}
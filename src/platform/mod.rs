@@ -3,12 +3,10 @@
 // for Vietnamese input method integration
 
 use core_graphics::event::{CGEventTapProxy};
-use std::collections::HashMap;
-use once_cell::sync::OnceCell;
 use bitflags::bitflags;
-use rdev::{Keyboard, KeyboardState};
 use log::debug;
-use std::sync::Mutex;
+use std::fmt;
+use thiserror::Error;
 
 // Platform type definitions
 pub type CallbackFn = Box<dyn Fn(CGEventTapProxy, EventTapType, Option<PressedKey>, KeyModifier) -> bool>;
@@ -16,6 +14,7 @@ pub type CallbackFn = Box<dyn Fn(CGEventTapProxy, EventTapType, Option<PressedKe
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EventTapType {
     KeyDown,
+    KeyUp,
     FlagsChanged,
     Other,
 }
@@ -26,6 +25,68 @@ pub enum PressedKey {
     Raw(u16),
 }
 
+/// Accessibility data for the focused UI element, as seen by the input heuristics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FocusedElementInfo {
+    /// Bundle path of the frontmost application
+    pub app_name: String,
+    /// Whether a focused element could be read at all
+    pub has_focused_element: bool,
+    /// AXRole of the focused element (e.g. AXTextField)
+    pub role: Option<String>,
+    /// AXSubrole of the focused element (e.g. AXSecureTextField)
+    pub subrole: Option<String>,
+    /// Currently selected text, if the element exposes it
+    pub selected_text: Option<String>,
+    /// Length in characters of the element's string value
+    pub value_length: Option<usize>,
+    /// Whether the Firefox/Chrome selection workaround applies to this app
+    pub selection_workaround: bool,
+    /// Whether the element accepts marked (pre-edit) text
+    pub marked_text: bool,
+}
+
+/// Which part of a text replacement failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectionStage {
+    /// Deleting the previously composed text
+    Backspace,
+    /// Typing the replacement text
+    Text,
+    /// Showing the word being composed as marked text through accessibility
+    MarkedText,
+    /// Moving the cursor with arrow keys
+    Cursor,
+    /// Typing over the previously composed text through accessibility
+    Accessibility,
+    /// Pasting the replacement text from the clipboard
+    Paste,
+}
+
+impl fmt::Display for InjectionStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InjectionStage::Backspace => write!(f, "backspace"),
+            InjectionStage::Text => write!(f, "text"),
+            InjectionStage::MarkedText => write!(f, "marked text"),
+            InjectionStage::Cursor => write!(f, "cursor move"),
+            InjectionStage::Accessibility => write!(f, "accessibility replacement"),
+            InjectionStage::Paste => write!(f, "paste"),
+        }
+    }
+}
+
+/// Synthesized key events could not be delivered to the focused application
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{stage} of {length} chars failed (error {status})")]
+pub struct InjectionError {
+    pub stage: InjectionStage,
+    /// Number of characters being deleted or typed
+    pub length: usize,
+    /// CoreGraphics or accessibility error code describing the failure
+    pub status: i32,
+}
+
 bitflags! {
     pub struct KeyModifier: u32 {
         const MODIFIER_NONE     = 0b00000000;
@@ -34,6 +95,11 @@ bitflags! {
         const MODIFIER_CONTROL  = 0b00000100;
         const MODIFIER_ALT      = 0b00001000;
         const MODIFIER_CAPSLOCK = 0b00010000;
+        /// Not a modifier key: the key comes from the numeric keypad
+        const MODIFIER_KEYPAD   = 0b00100000;
+        /// Not a modifier key: the key starts or finishes a dead-key sequence
+        /// that the application composes itself
+        const MODIFIER_DEAD_KEY = 0b01000000;
     }
 }
 
@@ -62,6 +128,14 @@ impl KeyModifier {
         self.insert(Self::MODIFIER_CAPSLOCK);
     }
 
+    pub fn add_keypad(&mut self) {
+        self.insert(Self::MODIFIER_KEYPAD);
+    }
+
+    pub fn add_dead_key(&mut self) {
+        self.insert(Self::MODIFIER_DEAD_KEY);
+    }
+
     pub fn is_shift(&self) -> bool {
         self.contains(Self::MODIFIER_SHIFT)
     }
@@ -81,6 +155,14 @@ impl KeyModifier {
     pub fn is_capslock(&self) -> bool {
         self.contains(Self::MODIFIER_CAPSLOCK)
     }
+
+    pub fn is_keypad(&self) -> bool {
+        self.contains(Self::MODIFIER_KEYPAD)
+    }
+
+    pub fn is_dead_key(&self) -> bool {
+        self.contains(Self::MODIFIER_DEAD_KEY)
+    }
 }
 
 // Key constants
@@ -88,165 +170,19 @@ pub const KEY_ENTER: char = '\r';
 pub const KEY_SPACE: char = ' ';
 pub const KEY_TAB: char = '\t';
 pub const KEY_DELETE: char = '\u{0008}'; // Backspace
+pub const KEY_FORWARD_DELETE: char = '\u{007F}'; // fn+Delete
 pub const KEY_ESCAPE: char = '\u{001B}';
 
-// Predefined character set for keyboard layout detection
-pub const PREDEFINED_CHARS: [char; 47] = [
-    'a', '`', '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', '-', '=', 'q', 'w', 'e', 'r', 't',
-    'y', 'u', 'i', 'o', 'p', '[', ']', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l', ';', '\'', '\\',
-    'z', 'x', 'c', 'v', 'b', 'n', 'm', ',', '.', '/',
-];
-
-// Keyboard layout character mapping
-pub static KEYBOARD_LAYOUT_CHARACTER_MAP: OnceCell<Mutex<HashMap<char, char>>> = OnceCell::new();
-
-/// Convert character to rdev::Key
-pub fn get_key_from_char(c: char) -> rdev::Key {
-    use rdev::Key::*;
-    match &c {
-        'a' => KeyA,
-        '`' => BackQuote,
-        '1' => Num1,
-        '2' => Num2,
-        '3' => Num3,
-        '4' => Num4,
-        '5' => Num5,
-        '6' => Num6,
-        '7' => Num7,
-        '8' => Num8,
-        '9' => Num9,
-        '0' => Num0,
-        '-' => Minus,
-        '=' => Equal,
-        'q' => KeyQ,
-        'w' => KeyW,
-        'e' => KeyE,
-        'r' => KeyR,
-        't' => KeyT,
-        'y' => KeyY,
-        'u' => KeyU,
-        'i' => KeyI,
-        'o' => KeyO,
-        'p' => KeyP,
-        '[' => LeftBracket,
-        ']' => RightBracket,
-        's' => KeyS,
-        'd' => KeyD,
-        'f' => KeyF,
-        'g' => KeyG,
-        'h' => KeyH,
-        'j' => KeyJ,
-        'k' => KeyK,
-        'l' => KeyL,
-        ';' => SemiColon,
-        '\'' => Quote,
-        '\\' => BackSlash,
-        'z' => KeyZ,
-        'x' => KeyX,
-        'c' => KeyC,
-        'v' => KeyV,
-        'b' => KeyB,
-        'n' => KeyN,
-        'm' => KeyM,
-        ',' => Comma,
-        '.' => Dot,
-        '/' => Slash,
-        _ => Unknown(0),
-    }
-}
-
-/// Build keyboard layout map using rdev
-fn build_keyboard_layout_map(map: &mut HashMap<char, char>) {
-    map.clear();
-    if let Some(mut kb) = Keyboard::new() {
-        for c in PREDEFINED_CHARS {
-            let key = rdev::EventType::KeyPress(get_key_from_char(c));
-            if let Some(s) = kb.add(&key) {
-                if let Some(ch) = s.chars().last() {
-                    map.insert(c, ch);
-                }
-            }
-        }
-        debug!("Built keyboard layout map with {} entries", map.len());
-    } else {
-        debug!("Failed to create rdev::Keyboard, falling back to static mapping");
-        // Fallback to static mapping if rdev fails
-        for c in PREDEFINED_CHARS {
-            map.insert(c, c);
-        }
-    }
-}
-
-/// Initialize keyboard layout using rdev
+/// Load the current keyboard layout so keys are read the way it types them
 pub fn initialize_keyboard_layout() {
-    let mut map = HashMap::new();
-    build_keyboard_layout_map(&mut map);
-    if let Err(_) = KEYBOARD_LAYOUT_CHARACTER_MAP.set(Mutex::new(map)) {
-        debug!("Keyboard layout map already initialized");
-    } else {
-        debug!("Keyboard layout map initialized successfully");
-    }
+    #[cfg(target_os = "macos")]
+    keylayout::refresh_layout();
+    debug!("Keyboard layout loaded");
 }
 
-/// Rebuild keyboard layout map when layout changes
+/// Reload the keyboard layout when it changes
 pub fn rebuild_keyboard_layout_map() {
-    // Get mutable reference to existing map if it exists
-    if let Some(mutex) = KEYBOARD_LAYOUT_CHARACTER_MAP.get() {
-        if let Ok(mut map) = mutex.lock() {
-            debug!("Rebuilding keyboard layout map...");
-            build_keyboard_layout_map(&mut map);
-            debug!("Keyboard layout map rebuilt");
-        } else {
-            debug!("Failed to lock keyboard layout map mutex");
-        }
-    } else {
-        debug!("Creating new keyboard layout map...");
-        initialize_keyboard_layout();
-        debug!("New keyboard layout map created");
-    }
-}
-
-// MacOS keyboard handler
-#[cfg(target_os = "macos")]
-pub struct MacOSKeyboardHandler {
-    input_type: crate::core::InputType,
-    enabled: bool,
-    current_buffer: String,
-    system_integrated: bool,
-}
-
-#[cfg(target_os = "macos")]
-impl MacOSKeyboardHandler {
-    pub fn new(input_type: crate::core::InputType) -> Self {
-        Self {
-            input_type,
-            enabled: false,
-            current_buffer: String::new(),
-            system_integrated: false,
-        }
-    }
-    
-    pub fn new_with_system_integration(input_type: crate::core::InputType) -> Result<Self, String> {
-        let mut handler = Self::new(input_type);
-        handler.system_integrated = true;
-        Ok(handler)
-    }
-    
-    pub fn set_input_type(&mut self, input_type: crate::core::InputType) {
-        self.input_type = input_type;
-    }
-    
-    pub fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
-    }
-    
-    pub fn get_current_buffer(&self) -> String {
-        self.current_buffer.clone()
-    }
-    
-    pub fn clear_buffer(&mut self) {
-        self.current_buffer.clear();
-    }
+    initialize_keyboard_layout();
 }
 
 // System integration module
@@ -257,6 +193,12 @@ pub mod system_integration {
     pub fn has_accessibility_permissions() -> bool {
         macos::is_process_trusted()
     }
+
+    /// Whether the event tap can read keys and type replacements, which
+    /// does not need the accessibility queries to work
+    pub fn can_start_event_tap() -> bool {
+        super::permissions::refresh().can_tap()
+    }
     
     pub fn request_accessibility_permissions() -> Result<(), String> {
         if macos::ensure_accessibility_permission() {
@@ -267,8 +209,7 @@ pub mod system_integration {
     }
     
     pub fn remove_keyboard_hook() -> Result<(), String> {
-        // This is a placeholder implementation
-        // In a real implementation, you would clean up event taps and other resources
+        macos::stop_event_listener();
         Ok(())
     }
 }
@@ -282,11 +223,62 @@ pub mod macos_ext;
 
 #[cfg(target_os = "macos")]
 pub use macos::{
-    add_app_change_callback, ensure_accessibility_permission, get_active_app_name, get_home_dir,
-    is_in_text_selection, is_launch_on_login, run_event_listener, send_backspace, send_string,
+    add_app_change_callback, ax_timeouts, bring_app_to_front, caret_anchor, ensure_accessibility_permission, event_tap_enabled, focused_value, get_active_app_bundle_id, get_active_app_name, get_home_dir,
+    inspect_focused_element, is_in_text_selection, is_secure_input, refresh_secure_field, paste_string, word_before_caret, replace_before_caret, replace_selected_text, selected_text, set_marked_text, supports_marked_text, is_launch_on_login, post_backspace, NO_TAP, post_string, post_string_by_cluster, reenable_event_tap, run_event_listener, send_backspace, send_cursor_move, send_string, send_string_by_cluster,
     update_launch_on_login, Handle, SYMBOL_ALT, SYMBOL_CTRL, SYMBOL_SHIFT, SYMBOL_SUPER,
     should_dismiss_selection_if_needed, dismiss_text_selection_if_needed,
 };
 
 #[cfg(target_os = "macos")]
-pub use macos_ext::{SystemTray, SystemTrayMenuItemKey}; 
\ No newline at end of file
+pub mod injection;
+
+#[cfg(target_os = "macos")]
+pub use injection::{is_terminal_app, InjectionMethod, InjectionQueue};
+
+#[cfg(target_os = "macos")]
+pub mod sandbox;
+
+#[cfg(target_os = "macos")]
+pub use sandbox::{SandboxField, SandboxWindow};
+
+#[cfg(target_os = "macos")]
+pub mod suggestions;
+
+#[cfg(target_os = "macos")]
+pub use suggestions::SuggestionPopup;
+
+#[cfg(target_os = "macos")]
+pub mod launchd;
+
+#[cfg(target_os = "macos")]
+pub mod keylayout;
+
+#[cfg(target_os = "macos")]
+pub mod relocation;
+
+#[cfg(target_os = "macos")]
+pub mod permissions;
+
+#[cfg(target_os = "macos")]
+pub use permissions::Permissions;
+
+#[cfg(target_os = "macos")]
+pub mod audio;
+
+#[cfg(target_os = "macos")]
+pub use audio::play_sound;
+
+#[cfg(target_os = "macos")]
+pub mod notification;
+
+#[cfg(target_os = "macos")]
+pub mod clipboard;
+
+#[cfg(target_os = "macos")]
+pub use clipboard::SystemPlaceholders;
+
+#[cfg(target_os = "macos")]
+pub use macos_ext::{
+    configure_floating_window, new_floating_panel, show_floating_window_at, FloatingLevel, SystemTray,
+    SystemTrayMenuItemKey,
+}; 
\ No newline at end of file
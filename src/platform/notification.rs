@@ -0,0 +1,82 @@
+// Local notifications
+// Posted through NSUserNotificationCenter, which needs no entitlement or
+// permission prompt. Clicking a notification runs the handler set with
+// `set_click_handler`.
+
+use std::sync::{Mutex, Once};
+
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel, BOOL, YES};
+use objc::{class, msg_send, sel, sel_impl};
+
+const DELEGATE_CLASS: &str = "VKeyNotificationDelegate";
+
+static CLICK_HANDLER: Mutex<Option<Box<dyn Fn() + Send>>> = Mutex::new(None);
+static DELEGATE: Once = Once::new();
+
+/// Run `handler` when the user clicks a notification posted by VKey
+pub fn set_click_handler<F>(handler: F)
+where
+    F: Fn() + Send + 'static,
+{
+    if let Ok(mut guard) = CLICK_HANDLER.lock() {
+        *guard = Some(Box::new(handler));
+    }
+}
+
+/// Show a notification in Notification Center
+pub fn post_notification(title: &str, text: &str) {
+    unsafe {
+        let center: id = msg_send![class!(NSUserNotificationCenter), defaultUserNotificationCenter];
+        if center == nil {
+            return;
+        }
+        DELEGATE.call_once(|| {
+            // The center does not retain its delegate, so this one is never released
+            let delegate: id = msg_send![delegate_class(), new];
+            let _: () = msg_send![center, setDelegate: delegate];
+        });
+
+        let notification: id = msg_send![class!(NSUserNotification), new];
+        let _: () = msg_send![notification, setTitle: NSString::alloc(nil).init_str(title)];
+        let _: () = msg_send![notification, setInformativeText: NSString::alloc(nil).init_str(text)];
+        let _: () = msg_send![center, deliverNotification: notification];
+        let _: () = msg_send![notification, release];
+    }
+}
+
+fn delegate_class() -> &'static Class {
+    if let Some(class) = Class::get(DELEGATE_CLASS) {
+        return class;
+    }
+
+    let mut decl = ClassDecl::new(DELEGATE_CLASS, class!(NSObject)).unwrap();
+
+    extern "C" fn did_activate(_this: &Object, _cmd: Sel, _center: id, _notification: id) {
+        if let Ok(guard) = CLICK_HANDLER.lock() {
+            if let Some(handler) = guard.as_ref() {
+                handler();
+            }
+        }
+    }
+
+    // Show the notification even while VKey is the frontmost application
+    extern "C" fn should_present(_this: &Object, _cmd: Sel, _center: id, _notification: id) -> BOOL {
+        YES
+    }
+
+    unsafe {
+        decl.add_method(
+            sel!(userNotificationCenter:didActivateNotification:),
+            did_activate as extern "C" fn(&Object, Sel, id, id),
+        );
+        decl.add_method(
+            sel!(userNotificationCenter:shouldPresentNotification:),
+            should_present as extern "C" fn(&Object, Sel, id, id) -> BOOL,
+        );
+    }
+
+    decl.register()
+}
@@ -0,0 +1,82 @@
+// Permission pre-flight
+// The event tap and the selection queries rest on different permissions: the
+// tap reads keys (Input Monitoring) and types replacements (posting events),
+// while selection, marked text and caret queries go through the accessibility
+// API. Each is checked on its own, so VKey keeps typing when only the tap is
+// allowed and just stops asking applications about their text.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGPreflightListenEventAccess() -> bool;
+    fn CGPreflightPostEventAccess() -> bool;
+}
+
+/// Whether the accessibility API may be asked about the focused element
+static SELECTION_QUERIES: AtomicBool = AtomicBool::new(true);
+
+/// What VKey is allowed to do, each permission checked separately
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Permissions {
+    /// Keys can be read from the event tap (Input Monitoring)
+    pub listen_events: bool,
+    /// Keystrokes can be posted to applications
+    pub post_events: bool,
+    /// The accessibility API answers queries about the focused element
+    pub accessibility: bool,
+}
+
+impl Permissions {
+    /// Check every permission without prompting
+    pub fn check() -> Self {
+        unsafe {
+            Self {
+                listen_events: CGPreflightListenEventAccess(),
+                post_events: CGPreflightPostEventAccess(),
+                accessibility: accessibility_sys::AXIsProcessTrusted(),
+            }
+        }
+    }
+
+    /// The event tap can read keys and type the words they compose
+    pub fn can_tap(&self) -> bool {
+        self.listen_events && self.post_events
+    }
+
+    /// Selected text, marked text and the caret can be read from applications
+    pub fn can_query_selection(&self) -> bool {
+        self.accessibility
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let summary = match (self.can_tap(), self.can_query_selection()) {
+            (true, true) => "full: typing and selection queries",
+            (true, false) => "partial: typing, selection heuristics off",
+            (false, true) => "partial: selection queries only, no typing",
+            (false, false) => "none",
+        };
+        f.write_str(summary)
+    }
+}
+
+/// Check the permissions and turn selection queries on or off to match.
+/// Called at startup and whenever the permissions are shown.
+pub fn refresh() -> Permissions {
+    let permissions = Permissions::check();
+    SELECTION_QUERIES.store(permissions.can_query_selection(), Ordering::Relaxed);
+    permissions
+}
+
+/// Whether the accessibility API may be asked about the focused element
+pub fn selection_queries_enabled() -> bool {
+    SELECTION_QUERIES.load(Ordering::Relaxed)
+}
+
+/// Whether keystrokes can still be posted; checked while the tap runs
+pub fn can_post_events() -> bool {
+    unsafe { CGPreflightPostEventAccess() }
+}
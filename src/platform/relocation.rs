@@ -0,0 +1,87 @@
+// App relocation check
+// macOS ties login items and, for apps without a stable signature, privacy
+// permissions to where the app is. Once VKey is moved, for example from
+// Downloads to Applications, the login item starts a path that no longer
+// exists and the Accessibility and Input Monitoring entries may name the old
+// copy. The path of every launch is kept, so a move is noticed at the next
+// one: the login item and the LaunchAgent are pointed at the new path and,
+// if the permissions did not follow, the user is asked to grant them again.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::launchd;
+use super::macos::{get_current_app_path, is_launch_on_login, update_launch_on_login};
+use crate::core::data_dir::{self, DataKind};
+use crate::error::{Result, VKeyError};
+
+/// Where VKey was started from last time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct LaunchLocation {
+    app_path: String,
+}
+
+/// VKey was started from a different place than the previous time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub from: String,
+    pub to: String,
+}
+
+fn location_path() -> Result<PathBuf> {
+    data_dir::data_file(DataKind::Config, "location.json")
+}
+
+fn load_location() -> Option<LaunchLocation> {
+    let path = location_path().ok()?;
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn save_location(location: &LaunchLocation) -> Result<()> {
+    data_dir::ensure_data_dir(DataKind::Config)?;
+    let path = location_path()?;
+    let text = serde_json::to_string_pretty(location)
+        .map_err(|e| VKeyError::ConfigError(format!("Failed to serialize launch location: {}", e)))?;
+    std::fs::write(&path, text)
+        .map_err(|e| VKeyError::ConfigError(format!("Failed to write '{}': {}", path.display(), e)))
+}
+
+/// Compare where this copy of VKey runs from with the previous launch, and
+/// remember it for the next one. The first launch is never a move.
+pub fn detect() -> Option<Relocation> {
+    let current = get_current_app_path();
+    let previous = load_location();
+    if previous.as_ref().map(|location| location.app_path.as_str()) != Some(current.as_str()) {
+        if let Err(e) = save_location(&LaunchLocation { app_path: current.clone() }) {
+            eprintln!("Failed to remember the launch location: {}", e);
+        }
+    }
+    match previous {
+        Some(location) if location.app_path != current => Some(Relocation {
+            from: location.app_path,
+            to: current,
+        }),
+        _ => None,
+    }
+}
+
+/// Point the login item and the LaunchAgent, whichever is set up, at the
+/// running copy
+pub fn repair_launch_entries() {
+    if is_launch_on_login() {
+        // The login item is found by name, so it is added again with the new path
+        let result = update_launch_on_login(false).and_then(|_| update_launch_on_login(true));
+        match result {
+            Ok(()) => eprintln!("Login item now opens {}", get_current_app_path()),
+            Err(e) => eprintln!("Failed to update the login item: {}", e),
+        }
+    }
+    if launchd::is_installed() {
+        match launchd::install() {
+            Ok(()) => eprintln!("LaunchAgent now runs the moved executable"),
+            Err(e) => eprintln!("Failed to update the LaunchAgent: {}", e),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+// Native text fields for the injection sandbox
+// gpui draws its own text input, so to see how injected events land in real
+// AppKit and WebKit fields the sandbox opens a plain Cocoa window with one
+// field of each kind.
+
+use std::fmt;
+
+use cocoa::appkit::{NSApp, NSApplication, NSBackingStoreType, NSView, NSWindow, NSWindowStyleMask};
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::{NSPoint, NSRect, NSSize, NSString};
+use objc::runtime::Class;
+use objc::{class, msg_send, sel, sel_impl};
+
+const FIELD_WIDTH: f64 = 420.;
+const FIELD_HEIGHT: f64 = 80.;
+const MARGIN: f64 = 20.;
+
+const WEBKIT_PATH: &str = "/System/Library/Frameworks/WebKit.framework";
+const WEB_VIEW_HTML: &str = "<html><body style=\"margin:0\">\
+    <textarea id=\"field\" style=\"width:100%;height:100%;box-sizing:border-box\"></textarea>\
+    </body></html>";
+
+/// Kind of text field an injection strategy can be tried against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxField {
+    /// Single line NSTextField
+    PlainText,
+    /// NSTextView accepting rich text
+    RichText,
+    /// Textarea inside a WKWebView
+    WebView,
+}
+
+impl SandboxField {
+    pub const ALL: [SandboxField; 3] = [SandboxField::PlainText, SandboxField::RichText, SandboxField::WebView];
+}
+
+impl fmt::Display for SandboxField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxField::PlainText => write!(f, "Plain text"),
+            SandboxField::RichText => write!(f, "Rich text"),
+            SandboxField::WebView => write!(f, "Web view"),
+        }
+    }
+}
+
+/// Cocoa window holding one field of each kind. Must be used from the main thread.
+pub struct SandboxWindow {
+    window: id,
+    plain_text: id,
+    rich_text: id,
+    /// Missing when WebKit could not be loaded
+    web_view: Option<id>,
+}
+
+impl SandboxWindow {
+    pub fn new() -> Self {
+        unsafe {
+            let height = MARGIN + SandboxField::ALL.len() as f64 * (FIELD_HEIGHT + MARGIN);
+            let frame = NSRect::new(NSPoint::new(0., 0.), NSSize::new(FIELD_WIDTH + 2. * MARGIN, height));
+            let window = NSWindow::alloc(nil).initWithContentRect_styleMask_backing_defer_(
+                frame,
+                NSWindowStyleMask::NSTitledWindowMask | NSWindowStyleMask::NSClosableWindowMask,
+                NSBackingStoreType::NSBackingStoreBuffered,
+                NO,
+            );
+            // Closing only hides the window; it is released on drop
+            let _: () = msg_send![window, setReleasedWhenClosed: NO];
+            window.setTitle_(ns_string("VKey Injection Sandbox"));
+            window.center();
+            let content = window.contentView();
+
+            // Cocoa places rows from the bottom, so the plain field ends up on top
+            let row_frame = |row: usize| {
+                NSRect::new(
+                    NSPoint::new(MARGIN, MARGIN + row as f64 * (FIELD_HEIGHT + MARGIN)),
+                    NSSize::new(FIELD_WIDTH, FIELD_HEIGHT),
+                )
+            };
+
+            let plain_text: id = msg_send![class!(NSTextField), alloc];
+            let plain_text: id = msg_send![plain_text, initWithFrame: row_frame(2)];
+            content.addSubview_(plain_text);
+
+            let rich_text: id = msg_send![class!(NSTextView), alloc];
+            let rich_text: id = msg_send![rich_text, initWithFrame: row_frame(1)];
+            let _: () = msg_send![rich_text, setRichText: YES];
+            content.addSubview_(rich_text);
+
+            let web_view = create_web_view(row_frame(0));
+            if let Some(web_view) = web_view {
+                content.addSubview_(web_view);
+            }
+
+            Self {
+                window,
+                plain_text,
+                rich_text,
+                web_view,
+            }
+        }
+    }
+
+    pub fn is_available(&self, field: SandboxField) -> bool {
+        field != SandboxField::WebView || self.web_view.is_some()
+    }
+
+    /// Bring the window to the front and make it key so posted events reach it
+    pub fn show(&self) {
+        unsafe {
+            NSApp().activateIgnoringOtherApps_(YES);
+            self.window.makeKeyAndOrderFront_(nil);
+        }
+    }
+
+    /// Empty a field and give it keyboard focus. Returns false if the field is unavailable.
+    pub fn focus_empty(&self, field: SandboxField) -> bool {
+        unsafe {
+            let view = match field {
+                SandboxField::PlainText => {
+                    let _: () = msg_send![self.plain_text, setStringValue: ns_string("")];
+                    self.plain_text
+                }
+                SandboxField::RichText => {
+                    let _: () = msg_send![self.rich_text, setString: ns_string("")];
+                    self.rich_text
+                }
+                SandboxField::WebView => match self.web_view {
+                    Some(web_view) => {
+                        evaluate_script(web_view, "var f = document.getElementById('field'); f.value = ''; f.focus();");
+                        web_view
+                    }
+                    None => return false,
+                },
+            };
+            self.show();
+            let focused: BOOL = msg_send![self.window, makeFirstResponder: view];
+            focused == YES
+        }
+    }
+}
+
+impl Drop for SandboxWindow {
+    fn drop(&mut self) {
+        unsafe {
+            let _: () = msg_send![self.window, close];
+            let _: () = msg_send![self.window, release];
+        }
+    }
+}
+
+/// Autoreleased NSString
+unsafe fn ns_string(string: &str) -> id {
+    let string = NSString::alloc(nil).init_str(string);
+    msg_send![string, autorelease]
+}
+
+/// Create a WKWebView with a single textarea, loading WebKit on demand
+unsafe fn create_web_view(frame: NSRect) -> Option<id> {
+    let bundle: id = msg_send![class!(NSBundle), bundleWithPath: ns_string(WEBKIT_PATH)];
+    if bundle == nil {
+        return None;
+    }
+    let loaded: BOOL = msg_send![bundle, load];
+    if loaded != YES {
+        return None;
+    }
+
+    let configuration_class = Class::get("WKWebViewConfiguration")?;
+    let web_view_class = Class::get("WKWebView")?;
+    let configuration: id = msg_send![configuration_class, new];
+    let web_view: id = msg_send![web_view_class, alloc];
+    let web_view: id = msg_send![web_view, initWithFrame: frame configuration: configuration];
+    let _: () = msg_send![configuration, release];
+    if web_view == nil {
+        return None;
+    }
+
+    let _: id = msg_send![web_view, loadHTMLString: ns_string(WEB_VIEW_HTML) baseURL: nil];
+    Some(web_view)
+}
+
+/// Run a script in the web view without waiting for its result
+unsafe fn evaluate_script(web_view: id, script: &str) {
+    let _: () = msg_send![web_view, evaluateJavaScript: ns_string(script) completionHandler: nil];
+}
@@ -0,0 +1,73 @@
+// Suggestion popup
+// A small native panel under the text cursor listing completions for the
+// word being typed. It never takes focus, so keys keep going to the
+// application and reach VKey through the event tap.
+
+use cocoa::appkit::NSView;
+use cocoa::base::{id, nil, NO};
+use cocoa::foundation::{NSPoint, NSRect, NSSize, NSString};
+use objc::{class, msg_send, sel, sel_impl};
+
+use super::macos_ext::{new_floating_panel, show_floating_window_at, FloatingLevel};
+
+const PADDING: f64 = 6.;
+const FONT_SIZE: f64 = 13.;
+
+/// Floating list of suggestions, numbered by the digit key that picks each one
+pub struct SuggestionPopup {
+    panel: id,
+    label: id,
+}
+
+impl SuggestionPopup {
+    /// Create the panel, hidden until suggestions are shown.
+    /// Must be called on the main thread, like every other method here.
+    pub fn new() -> Self {
+        unsafe {
+            let panel = new_floating_panel(200., 24., FloatingLevel::Popup);
+            let label: id = msg_send![class!(NSTextField), labelWithString: NSString::alloc(nil).init_str("")];
+            let font: id = msg_send![class!(NSFont), systemFontOfSize: FONT_SIZE];
+            let _: () = msg_send![label, setFont: font];
+            let _: () = msg_send![label, setSelectable: NO];
+            let content: id = msg_send![panel, contentView];
+            content.addSubview_(label);
+            Self { panel, label }
+        }
+    }
+
+    /// Show `suggestions` with the popup's top-left corner at `anchor`
+    /// (screen coordinates, origin at the bottom left)
+    pub fn show(&self, suggestions: &[String], anchor: (f64, f64)) {
+        let text = suggestions
+            .iter()
+            .enumerate()
+            .map(|(index, word)| format!("{} {}", index + 1, word))
+            .collect::<Vec<_>>()
+            .join("   ");
+
+        unsafe {
+            let _: () = msg_send![self.label, setStringValue: NSString::alloc(nil).init_str(&text)];
+            let _: () = msg_send![self.label, sizeToFit];
+            let frame: NSRect = msg_send![self.label, frame];
+            let size = frame.size;
+            let _: () = msg_send![self.label, setFrameOrigin: NSPoint::new(PADDING, PADDING)];
+            let _: () = msg_send![self.panel, setContentSize: NSSize::new(size.width + 2. * PADDING, size.height + 2. * PADDING)];
+        }
+        show_floating_window_at(self.panel, NSPoint::new(anchor.0, anchor.1));
+    }
+
+    pub fn hide(&self) {
+        unsafe {
+            let _: () = msg_send![self.panel, orderOut: nil];
+        }
+    }
+}
+
+impl Drop for SuggestionPopup {
+    fn drop(&mut self) {
+        unsafe {
+            let _: () = msg_send![self.panel, close];
+            let _: () = msg_send![self.panel, release];
+        }
+    }
+}
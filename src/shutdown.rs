@@ -0,0 +1,78 @@
+// Orderly shutdown
+// Quitting goes through here instead of exiting the process directly, so the
+// event tap stops intercepting keys and pending state reaches disk before the
+// process ends.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::{ptr, thread};
+
+type ShutdownHook = Box<dyn FnOnce() + Send>;
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static HOOKS: Mutex<Vec<(&'static str, ShutdownHook)>> = Mutex::new(Vec::new());
+
+/// Run `hook` on shutdown. Hooks run in reverse order of registration, so
+/// whatever was started last is stopped first.
+pub fn register<F>(name: &'static str, hook: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    if let Ok(mut hooks) = HOOKS.lock() {
+        hooks.push((name, Box::new(hook)));
+    }
+}
+
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::Relaxed)
+}
+
+/// Run every registered hook once. Later calls do nothing, but only return
+/// after the first call has finished, so no thread ends the process midway.
+pub fn run() {
+    let mut hooks = match HOOKS.lock() {
+        Ok(hooks) => hooks,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if SHUTTING_DOWN.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    for (name, hook) in std::mem::take(&mut *hooks).into_iter().rev() {
+        eprintln!("Shutting down: {}", name);
+        hook();
+    }
+}
+
+/// Shut down and end the process
+pub fn exit(code: i32) -> ! {
+    run();
+    std::process::exit(code)
+}
+
+/// Shut down on SIGTERM or SIGINT, so a service manager stopping VKey does not
+/// leave the event tap installed. Call this before spawning any thread: the
+/// signals are blocked and every later thread inherits that, leaving them to
+/// a single thread that waits for them.
+#[cfg(unix)]
+pub fn handle_termination_signals() {
+    let signals = unsafe {
+        let mut signals: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGTERM);
+        libc::sigaddset(&mut signals, libc::SIGINT);
+        if libc::pthread_sigmask(libc::SIG_BLOCK, &signals, ptr::null_mut()) != 0 {
+            eprintln!("Failed to block termination signals");
+            return;
+        }
+        signals
+    };
+
+    thread::spawn(move || {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&signals, &mut signal) } == 0 {
+            eprintln!("Received signal {}, shutting down", signal);
+            exit(0);
+        }
+    });
+}
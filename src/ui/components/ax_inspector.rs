@@ -0,0 +1,353 @@
+use gpui::{
+    div, prelude::*, px, rgb, size, App, Bounds, Context, IntoElement, MouseButton, Render, Styled,
+    Window, WindowBounds, WindowOptions,
+};
+use std::time::Duration;
+
+use crate::control::AppPolicy;
+use crate::core::memory::format_bytes;
+use crate::core::{Correction, MemoryReport, QualityMetrics, TypingStats};
+use crate::platform::FocusedElementInfo;
+
+/// How often the inspector re-reads the focused element
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Maximum number of selected-text characters shown in the window
+const MAX_SELECTED_TEXT_CHARS: usize = 80;
+
+/// Most recent corrections listed in the window
+const MAX_SHOWN_CORRECTIONS: usize = 8;
+
+/// Hidden debug window showing the permissions VKey holds, the accessibility data it sees
+/// for the focused element, how VKey types in the frontmost application and why, how much memory VKey holds, typing statistics, transform quality and the words restored
+/// automatically. Opened with Option+click on the
+/// main window title, or by clicking the weekly summary notification.
+pub struct AxInspector {
+    info: FocusedElementInfo,
+    policy: AppPolicy,
+    memory: MemoryReport,
+    stats: TypingStats,
+    quality: QualityMetrics,
+    /// Most recent corrections first
+    corrections: Vec<Correction>,
+    #[cfg(target_os = "macos")]
+    permissions: crate::platform::Permissions,
+    /// Selection queries that timed out, per application
+    timeouts: Vec<(String, u32)>,
+}
+
+impl AxInspector {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        // Poll while the window is alive; the loop ends once the entity is dropped
+        cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(REFRESH_INTERVAL).await;
+            let updated = this.update(cx, |this, cx| {
+                this.refresh();
+                cx.notify();
+            });
+            if updated.is_err() {
+                break;
+            }
+        })
+        .detach();
+
+        let mut inspector = Self {
+            info: FocusedElementInfo::default(),
+            policy: AppPolicy::default(),
+            memory: MemoryReport::default(),
+            stats: TypingStats::default(),
+            quality: QualityMetrics::default(),
+            corrections: Vec::new(),
+            #[cfg(target_os = "macos")]
+            permissions: crate::platform::Permissions::default(),
+            timeouts: Vec::new(),
+        };
+        inspector.refresh();
+        inspector
+    }
+
+    /// Open the inspector in its own window
+    pub fn open(cx: &mut App) {
+        let bounds = Bounds::centered(None, size(px(460.), px(1080.)), cx);
+        if let Err(e) = cx.open_window(
+            WindowOptions {
+                window_bounds: Some(WindowBounds::Windowed(bounds)),
+                ..Default::default()
+            },
+            |_, cx| cx.new(AxInspector::new),
+        ) {
+            eprintln!("Failed to open accessibility inspector: {:?}", e);
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.policy = crate::app_policy();
+        self.memory = crate::memory_report();
+        self.stats = crate::typing_stats();
+        self.quality = crate::quality_metrics();
+        self.corrections = crate::correction_log().into_iter().rev().take(MAX_SHOWN_CORRECTIONS).collect();
+        #[cfg(target_os = "macos")]
+        {
+            // Picks up permissions granted while VKey is running
+            self.permissions = crate::platform::permissions::refresh();
+            self.info = crate::platform::inspect_focused_element();
+            self.timeouts = crate::platform::ax_timeouts();
+        }
+    }
+
+    /// Each permission and what VKey does with the ones it has
+    fn permission_rows(&self) -> Vec<(&'static str, String)> {
+        #[cfg(target_os = "macos")]
+        {
+            let granted = |allowed: bool| if allowed { "granted" } else { "missing" }.to_string();
+            vec![
+                ("Input Monitoring:", granted(self.permissions.listen_events)),
+                ("Post keystrokes:", granted(self.permissions.post_events)),
+                ("Accessibility:", granted(self.permissions.accessibility)),
+                ("Capability:", self.permissions.to_string()),
+            ]
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            Vec::new()
+        }
+    }
+
+    fn render_row(&self, label: &str, value: String) -> impl IntoElement {
+        div()
+            .flex()
+            .gap_3()
+            .child(
+                div()
+                    .text_color(rgb(0xa0aec0))
+                    .text_sm()
+                    .min_w_32()
+                    .child(label.to_string())
+            )
+            .child(
+                div()
+                    .text_color(rgb(0xe2e8f0))
+                    .text_sm()
+                    .child(value)
+            )
+    }
+}
+
+fn describe(value: &Option<String>) -> String {
+    value.clone().unwrap_or_else(|| "—".to_string())
+}
+
+/// The application name from its bundle path, "/Applications/Notes.app" gives "Notes"
+fn app_name(path: &str) -> String {
+    let name = path.rsplit('/').find(|part| !part.is_empty()).unwrap_or("—");
+    name.trim_end_matches(".app").to_string()
+}
+
+impl Render for AxInspector {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let selected_text = self.info.selected_text.as_ref().map(|text| {
+            let shown: String = text.chars().take(MAX_SELECTED_TEXT_CHARS).collect();
+            format!("\"{}\" ({} chars)", shown, text.chars().count())
+        });
+
+        div()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .bg(rgb(0x2d3748))
+            .w_full()
+            .h_full()
+            .p_4()
+            .child(
+                div()
+                    .text_color(rgb(0xe2e8f0))
+                    .text_base()
+                    .mb_2()
+                    .child("Accessibility Inspector")
+            )
+            .children(self.permission_rows().into_iter().map(|(label, value)| self.render_row(label, value)))
+            .child(self.render_row("Application:", self.info.app_name.clone()))
+            .child(self.render_row(
+                "Focused element:",
+                if self.info.has_focused_element { "yes" } else { "no" }.to_string(),
+            ))
+            .child(self.render_row("Role:", describe(&self.info.role)))
+            .child(self.render_row("Subrole:", describe(&self.info.subrole)))
+            .child(self.render_row("Selected text:", describe(&selected_text)))
+            .child(self.render_row(
+                "Value length:",
+                self.info
+                    .value_length
+                    .map(|len| len.to_string())
+                    .unwrap_or_else(|| "—".to_string()),
+            ))
+            .child(self.render_row(
+                "Marked text:",
+                if self.info.marked_text { "supported" } else { "unsupported" }.to_string(),
+            ))
+            .child(self.render_row(
+                "Selection fix:",
+                if self.info.selection_workaround { "active" } else { "inactive" }.to_string(),
+            ))
+            .child(self.render_row(
+                "Timeouts:",
+                if self.timeouts.is_empty() {
+                    "none".to_string()
+                } else {
+                    self.timeouts
+                        .iter()
+                        .map(|(app, count)| format!("{} ×{}", app, count))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                },
+            ))
+            .child(
+                div()
+                    .text_color(rgb(0xe2e8f0))
+                    .text_base()
+                    .mt_2()
+                    .child(format!("Typing in {}", app_name(&self.policy.app)))
+            )
+            .when(self.policy.reasons.is_empty(), |this| {
+                this.child(self.render_row("Default:", "each key typed as it comes, no per-app rule".to_string()))
+            })
+            .children(self.policy.reasons.iter().map(|reason| {
+                let (decision, cause) = reason.split_once(": ").unwrap_or((reason.as_str(), ""));
+                self.render_row(&format!("{}:", decision), cause.to_string())
+            }))
+            .child(
+                div()
+                    .text_color(rgb(0xe2e8f0))
+                    .text_base()
+                    .mt_2()
+                    .child("Memory")
+            )
+            .children(self.memory.entries.iter().map(|entry| {
+                let items = match entry.cap {
+                    Some(cap) => format!("{} / {}", entry.items, cap),
+                    None => entry.items.to_string(),
+                };
+                self.render_row(entry.name, format!("{} items, ~{}", items, format_bytes(entry.bytes)))
+            }))
+            .child(self.render_row("Total:", format!("~{}", format_bytes(self.memory.total_bytes()))))
+            .child(
+                div()
+                    .text_color(rgb(0xe2e8f0))
+                    .text_base()
+                    .mt_2()
+                    .child("Typing")
+            )
+            .child(self.render_row("Keystrokes:", self.stats.keystrokes.to_string()))
+            .child(self.render_row("Words:", self.stats.words.to_string()))
+            .child(self.render_row("Restores:", self.stats.restores.to_string()))
+            .child(self.render_row("Keys saved:", self.stats.keystrokes_saved.to_string()))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .items_center()
+                    .mt_2()
+                    .child(
+                        div()
+                            .text_color(rgb(0xe2e8f0))
+                            .text_base()
+                            .child("Quality (local only)")
+                    )
+                    .child(
+                        div()
+                            .px_2()
+                            .rounded_sm()
+                            .text_sm()
+                            .cursor_pointer()
+                            .bg(rgb(0x4a5568))
+                            .text_color(rgb(0xe2e8f0))
+                            .hover(|this| this.bg(rgb(0x5a6c7d)))
+                            .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                                crate::reset_quality_metrics();
+                                this.refresh();
+                                cx.notify();
+                            }))
+                            .child("Reset")
+                    )
+            )
+            .when(self.quality.used().is_empty(), |this| {
+                this.child(self.render_row("No data:", "nothing typed since the last reset".to_string()))
+            })
+            .children(self.quality.used().into_iter().map(|(input_type, counts)| {
+                self.render_row(
+                    &format!("{}:", input_type),
+                    format!(
+                        "{} words, {} restores, {} escapes, {} corrections ({:.1}%)",
+                        counts.words,
+                        counts.restores,
+                        counts.escapes,
+                        counts.corrections,
+                        counts.failure_rate(),
+                    ),
+                )
+            }))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .items_center()
+                    .mt_2()
+                    .child(
+                        div()
+                            .text_color(rgb(0xe2e8f0))
+                            .text_base()
+                            .child("Corrections (local only)")
+                    )
+                    .child(
+                        div()
+                            .px_2()
+                            .rounded_sm()
+                            .text_sm()
+                            .cursor_pointer()
+                            .bg(rgb(0x4a5568))
+                            .text_color(rgb(0xe2e8f0))
+                            .hover(|this| this.bg(rgb(0x5a6c7d)))
+                            .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                                crate::clear_correction_log();
+                                this.refresh();
+                                cx.notify();
+                            }))
+                            .child("Clear")
+                    )
+            )
+            .when(self.corrections.is_empty(), |this| {
+                this.child(self.render_row("No data:", "logging is off or nothing was restored".to_string()))
+            })
+            .children(self.corrections.iter().map(|correction| {
+                let keys = correction.keys.clone();
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .child(
+                        div()
+                            .flex_1()
+                            .child(self.render_row(
+                                &format!("{} → {}", correction.shown, correction.keys),
+                                format!("{} · {}", correction.reason, app_name(&correction.app)),
+                            ))
+                    )
+                    .child(
+                        div()
+                            .px_2()
+                            .rounded_sm()
+                            .text_sm()
+                            .cursor_pointer()
+                            .bg(rgb(0x4a5568))
+                            .text_color(rgb(0xe2e8f0))
+                            .hover(|this| this.bg(rgb(0x5a6c7d)))
+                            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                                crate::keep_corrected_word(&keys);
+                                this.refresh();
+                                cx.notify();
+                            }))
+                            .child("Keep")
+                    )
+            }))
+    }
+}
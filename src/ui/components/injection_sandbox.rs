@@ -0,0 +1,614 @@
+use gpui::{
+    div, prelude::*, px, rgb, size, App, Bounds, Context, IntoElement, MouseButton, Render, Styled,
+    Window, WindowBounds, WindowOptions,
+};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::core::{AppConfig, InputType, ProcessingResult, VietnameseInputProcessor, VniKeyMap};
+use crate::platform::{self, InjectionError, SandboxField, SandboxWindow};
+
+/// Keystrokes typed into every field, per input method
+const TELEX_SAMPLE: &str = "Tieengs Vieetj rats ddepj ";
+const VNI_SAMPLE: &str = "Tie6ng1 Vie6t5 ra6t1 d9ep5 ";
+
+/// Wait for focus changes and posted events to be handled before reading a field
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+/// Times the sample is typed per field in A/B compare mode
+const COMPARE_ROUNDS: usize = 3;
+
+/// A way of getting composed text into the focused field, matching the per-app modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectionStrategy {
+    /// Retype the changed part of the word after every keystroke
+    Immediate,
+    /// Same, waiting the configured injection delay between erasing and typing
+    Delayed,
+    /// Type each word only once it is committed
+    CommitOnly,
+    /// Show the word as marked text and type it once it is committed
+    MarkedText,
+    /// Retype like Immediate, one grapheme cluster per event, as for terminals
+    TerminalSafe,
+}
+
+impl InjectionStrategy {
+    pub const ALL: [InjectionStrategy; 5] = [
+        InjectionStrategy::Immediate,
+        InjectionStrategy::Delayed,
+        InjectionStrategy::CommitOnly,
+        InjectionStrategy::MarkedText,
+        InjectionStrategy::TerminalSafe,
+    ];
+
+    /// The strategy after this one, skipping `other`
+    fn next_except(self, other: InjectionStrategy) -> InjectionStrategy {
+        let index = Self::ALL.iter().position(|&s| s == self).unwrap_or(0);
+        (1..Self::ALL.len())
+            .map(|step| Self::ALL[(index + step) % Self::ALL.len()])
+            .find(|&s| s != other)
+            .unwrap_or(self)
+    }
+}
+
+impl fmt::Display for InjectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InjectionStrategy::Immediate => write!(f, "Immediate"),
+            InjectionStrategy::Delayed => write!(f, "Delayed"),
+            InjectionStrategy::CommitOnly => write!(f, "Commit only"),
+            InjectionStrategy::MarkedText => write!(f, "Marked text"),
+            InjectionStrategy::TerminalSafe => write!(f, "Terminal safe"),
+        }
+    }
+}
+
+/// Result of one strategy against one field
+#[derive(Debug, Clone)]
+enum Outcome {
+    /// The field ended up with the expected text
+    Matched(Duration),
+    /// Injection went through but the field holds something else
+    Mismatch { expected: String, actual: Option<String> },
+    Failed(InjectionError),
+    Unavailable,
+}
+
+/// Words typed with one strategy into one field in A/B compare mode
+#[derive(Debug, Clone, Copy, Default)]
+struct CompareTally {
+    words: u32,
+    /// Words that did not arrive as expected, or could not be injected
+    errors: u32,
+    /// Time spent injecting the words that arrived
+    latency: Duration,
+}
+
+impl CompareTally {
+    fn record(&mut self, outcome: &Outcome) {
+        self.words += 1;
+        match outcome {
+            Outcome::Matched(elapsed) => self.latency += *elapsed,
+            _ => self.errors += 1,
+        }
+    }
+
+    fn error_rate(&self) -> f64 {
+        if self.words == 0 {
+            return 0.0;
+        }
+        self.errors as f64 * 100.0 / self.words as f64
+    }
+
+    /// Mean time to inject a word that arrived
+    fn mean_latency(&self) -> Option<Duration> {
+        let matched = self.words - self.errors;
+        (matched > 0).then(|| self.latency / matched)
+    }
+}
+
+/// Diagnostic window that types a sample through the real processor and injection
+/// calls into native fields, timing each strategy and checking what arrived.
+/// A/B compare mode alternates two strategies word by word to compare their
+/// error rates and latency on equal terms.
+/// Opened with Shift+Option+click on the main window title.
+pub struct InjectionSandbox {
+    fields: SandboxWindow,
+    results: Vec<(InjectionStrategy, SandboxField, Outcome)>,
+    /// Strategies compared in A/B mode
+    compare: [InjectionStrategy; 2],
+    compare_results: Vec<(InjectionStrategy, SandboxField, CompareTally)>,
+    running: bool,
+}
+
+impl InjectionSandbox {
+    pub fn new(_cx: &mut Context<Self>) -> Self {
+        let fields = SandboxWindow::new();
+        fields.show();
+        Self {
+            fields,
+            results: Vec::new(),
+            compare: [InjectionStrategy::Immediate, InjectionStrategy::Delayed],
+            compare_results: Vec::new(),
+            running: false,
+        }
+    }
+
+    /// Open the sandbox controls in their own window, next to the native fields
+    pub fn open(cx: &mut App) {
+        let bounds = Bounds::centered(None, size(px(560.), px(500.)), cx);
+        if let Err(e) = cx.open_window(
+            WindowOptions {
+                window_bounds: Some(WindowBounds::Windowed(bounds)),
+                ..Default::default()
+            },
+            |_, cx| cx.new(InjectionSandbox::new),
+        ) {
+            eprintln!("Failed to open injection sandbox: {:?}", e);
+        }
+    }
+
+    /// Try the strategies against every field, one at a time
+    fn run(&mut self, strategies: Vec<InjectionStrategy>, cx: &mut Context<Self>) {
+        if self.running {
+            return;
+        }
+        self.running = true;
+        self.results.retain(|(strategy, _, _)| !strategies.contains(strategy));
+        cx.notify();
+
+        let config = AppConfig::load_default().unwrap_or_default();
+
+        cx.spawn(async move |this, cx| {
+            // The event tap must not transform the sandbox's own keystrokes
+            let was_paused = crate::set_vietnamese_paused(true);
+
+            'runs: for strategy in strategies {
+                for field in SandboxField::ALL {
+                    let focused = match this.update(cx, |this, _| this.fields.focus_empty(field)) {
+                        Ok(focused) => focused,
+                        Err(_) => break 'runs,
+                    };
+
+                    let outcome = if focused {
+                        cx.background_executor().timer(SETTLE_DELAY).await;
+                        let run_config = config.clone();
+                        let typed = cx
+                            .background_executor()
+                            .spawn(async move { type_sample(strategy, &run_config) })
+                            .await;
+                        cx.background_executor().timer(SETTLE_DELAY).await;
+
+                        match typed {
+                            Ok((expected, elapsed)) => {
+                                let actual = cx
+                                    .background_executor()
+                                    .spawn(async move { platform::focused_value() })
+                                    .await;
+                                if actual.as_deref() == Some(expected.as_str()) {
+                                    Outcome::Matched(elapsed)
+                                } else {
+                                    Outcome::Mismatch { expected, actual }
+                                }
+                            }
+                            Err(error) => Outcome::Failed(error),
+                        }
+                    } else {
+                        Outcome::Unavailable
+                    };
+
+                    let recorded = this.update(cx, |this, cx| {
+                        this.results.push((strategy, field, outcome));
+                        cx.notify();
+                    });
+                    if recorded.is_err() {
+                        break 'runs;
+                    }
+                }
+            }
+
+            crate::set_vietnamese_paused(was_paused);
+            let _ = this.update(cx, |this, cx| {
+                this.running = false;
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Type the sample word by word into every field, alternating the two
+    /// compared strategies. Each word goes into an emptied field and is
+    /// checked on its own; every word is typed with both strategies over the
+    /// rounds, so neither gets the easier words.
+    fn run_compare(&mut self, cx: &mut Context<Self>) {
+        if self.running {
+            return;
+        }
+        self.running = true;
+        self.compare_results.clear();
+        cx.notify();
+
+        let config = AppConfig::load_default().unwrap_or_default();
+        let strategies = self.compare;
+        let words: Vec<String> = sample_keys(&config)
+            .split_inclusive(' ')
+            .map(str::to_string)
+            .collect();
+
+        cx.spawn(async move |this, cx| {
+            let was_paused = crate::set_vietnamese_paused(true);
+
+            'fields: for field in SandboxField::ALL {
+                for round in 0..COMPARE_ROUNDS {
+                    for (index, word) in words.iter().enumerate() {
+                        let strategy = strategies[(index + round) % 2];
+                        let focused = match this.update(cx, |this, _| this.fields.focus_empty(field)) {
+                            Ok(focused) => focused,
+                            Err(_) => break 'fields,
+                        };
+                        if !focused {
+                            continue 'fields;
+                        }
+
+                        cx.background_executor().timer(SETTLE_DELAY).await;
+                        let run_config = config.clone();
+                        let keys = word.clone();
+                        let typed = cx
+                            .background_executor()
+                            .spawn(async move { type_keys(strategy, &run_config, &keys) })
+                            .await;
+                        cx.background_executor().timer(SETTLE_DELAY).await;
+
+                        let outcome = match typed {
+                            Ok((expected, elapsed)) => {
+                                let actual = cx
+                                    .background_executor()
+                                    .spawn(async move { platform::focused_value() })
+                                    .await;
+                                if actual.as_deref() == Some(expected.as_str()) {
+                                    Outcome::Matched(elapsed)
+                                } else {
+                                    Outcome::Mismatch { expected, actual }
+                                }
+                            }
+                            Err(error) => Outcome::Failed(error),
+                        };
+
+                        let recorded = this.update(cx, |this, cx| {
+                            this.record_compare(strategy, field, &outcome);
+                            cx.notify();
+                        });
+                        if recorded.is_err() {
+                            break 'fields;
+                        }
+                    }
+                }
+            }
+
+            crate::set_vietnamese_paused(was_paused);
+            let _ = this.update(cx, |this, cx| {
+                this.running = false;
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn record_compare(&mut self, strategy: InjectionStrategy, field: SandboxField, outcome: &Outcome) {
+        match self
+            .compare_results
+            .iter_mut()
+            .find(|(s, f, _)| *s == strategy && *f == field)
+        {
+            Some((_, _, tally)) => tally.record(outcome),
+            None => {
+                let mut tally = CompareTally::default();
+                tally.record(outcome);
+                self.compare_results.push((strategy, field, tally));
+            }
+        }
+    }
+
+    fn compare_tally(&self, strategy: InjectionStrategy, field: SandboxField) -> Option<&CompareTally> {
+        self.compare_results
+            .iter()
+            .find(|(s, f, _)| *s == strategy && *f == field)
+            .map(|(_, _, tally)| tally)
+    }
+
+    fn outcome(&self, strategy: InjectionStrategy, field: SandboxField) -> Option<&Outcome> {
+        self.results
+            .iter()
+            .find(|(s, f, _)| *s == strategy && *f == field)
+            .map(|(_, _, outcome)| outcome)
+    }
+
+    fn render_run_button(&self, label: &str, strategies: Vec<InjectionStrategy>, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .px_3()
+            .py_1()
+            .rounded_md()
+            .text_sm()
+            .cursor_pointer()
+            .bg(rgb(0x4a5568))
+            .text_color(rgb(0xe2e8f0))
+            .hover(|this| this.bg(rgb(0x5a6c7d)))
+            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                this.run(strategies.clone(), cx);
+            }))
+            .child(label.to_string())
+    }
+
+    /// Label picking strategy `slot` of the comparison; a click moves to the next strategy
+    fn render_compare_choice(&self, slot: usize, cx: &mut Context<Self>) -> impl IntoElement {
+        let label = format!("{}: {}", if slot == 0 { "A" } else { "B" }, self.compare[slot]);
+        div()
+            .min_w_32()
+            .px_2()
+            .rounded_sm()
+            .text_sm()
+            .cursor_pointer()
+            .bg(rgb(0x3182ce))
+            .text_color(rgb(0xffffff))
+            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                if !this.running {
+                    this.compare[slot] = this.compare[slot].next_except(this.compare[1 - slot]);
+                    this.compare_results.clear();
+                    cx.notify();
+                }
+            }))
+            .child(label)
+    }
+
+    fn render_compare_cell(&self, tally: Option<&CompareTally>) -> impl IntoElement {
+        let (text, color) = match tally {
+            None => ("—".to_string(), 0xa0aec0),
+            Some(tally) => {
+                let latency = tally
+                    .mean_latency()
+                    .map(|latency| format!("{} ms", latency.as_millis()))
+                    .unwrap_or_else(|| "—".to_string());
+                let color = if tally.errors == 0 { 0x68d391 } else { 0xfc8181 };
+                (
+                    format!("{}/{} errors ({:.0}%), {}", tally.errors, tally.words, tally.error_rate(), latency),
+                    color,
+                )
+            }
+        };
+        div()
+            .flex_1()
+            .text_sm()
+            .text_color(rgb(color))
+            .child(text)
+    }
+
+    fn render_cell(&self, outcome: Option<&Outcome>) -> impl IntoElement {
+        let (text, color) = match outcome {
+            None => ("—".to_string(), 0xa0aec0),
+            Some(Outcome::Matched(elapsed)) => (format!("✓ {} ms", elapsed.as_millis()), 0x68d391),
+            Some(Outcome::Mismatch { expected, actual }) => (
+                format!("✗ \"{}\" ≠ \"{}\"", actual.as_deref().unwrap_or("?"), expected),
+                0xfc8181,
+            ),
+            Some(Outcome::Failed(error)) => (format!("✗ {}", error), 0xfc8181),
+            Some(Outcome::Unavailable) => ("unavailable".to_string(), 0xa0aec0),
+        };
+        div()
+            .flex_1()
+            .text_sm()
+            .text_color(rgb(color))
+            .child(text)
+    }
+}
+
+/// The sample for the configured input method, with VNI marks on the keys
+/// the user picked for them
+fn sample_keys(config: &AppConfig) -> String {
+    match config.input_type {
+        InputType::VNI => {
+            let vni_keys = VniKeyMap::parse(&config.advanced.vni_key_order).unwrap_or_default();
+            VNI_SAMPLE.chars().map(|key| vni_keys.from_standard(key)).collect()
+        }
+        _ => TELEX_SAMPLE.to_string(),
+    }
+}
+
+/// Compose the sample with a processor set up like the real one and inject it
+/// with the given strategy. Returns the text that should be in the field.
+fn type_sample(strategy: InjectionStrategy, config: &AppConfig) -> Result<(String, Duration), InjectionError> {
+    type_keys(strategy, config, &sample_keys(config))
+}
+
+/// Compose `keys` with a processor set up like the real one and inject them
+/// with the given strategy. Returns the text that should be in the field.
+fn type_keys(strategy: InjectionStrategy, config: &AppConfig, keys: &str) -> Result<(String, Duration), InjectionError> {
+    let input_type = match config.input_type {
+        InputType::VNI | InputType::Hybrid | InputType::TelexSimple => config.input_type,
+        _ => InputType::Telex,
+    };
+    let mut processor = VietnameseInputProcessor::new(input_type);
+    processor.set_encoding(config.encoding);
+    processor.apply_settings(&config.advanced);
+    processor.set_word_limit(config.max_word_length, config.word_overflow);
+    processor.set_restore_patterns(config.active_restore_patterns());
+    processor.set_custom_diacritics(config.custom_diacritics.clone());
+    processor.set_preview_suppressed(matches!(
+        strategy,
+        InjectionStrategy::CommitOnly | InjectionStrategy::MarkedText
+    ));
+
+    let post_text = |text: &str| match strategy {
+        InjectionStrategy::TerminalSafe => platform::post_string_by_cluster(text),
+        _ => platform::post_string(text),
+    };
+    let batch_delay = match strategy {
+        InjectionStrategy::Delayed => Duration::from_millis(config.advanced.injection_delay_ms),
+        _ => Duration::ZERO,
+    };
+
+    let mut expected = String::new();
+    let mut marked = String::new();
+    let started = Instant::now();
+
+    for key in keys.chars() {
+        let result = processor.process_key(key);
+        result.apply_to(&mut expected);
+
+        // Like the event tap, update the preview before typing anything
+        if strategy == InjectionStrategy::MarkedText {
+            let preedit = processor.get_preedit_text();
+            if preedit != marked {
+                platform::set_marked_text(&marked, &preedit)?;
+                marked = preedit;
+            }
+        }
+
+        match result {
+            ProcessingResult::ProcessedText { text, buffer_length }
+            | ProcessingResult::RestoreText { text, buffer_length }
+            | ProcessingResult::AcceptSuggestion { text, buffer_length } => {
+                platform::post_backspace(buffer_length)?;
+                if buffer_length > 0 && !batch_delay.is_zero() {
+                    std::thread::sleep(batch_delay);
+                }
+                post_text(&text)?;
+            }
+            // Passed-through keys were never really pressed, so type them here
+            ProcessingResult::PassThrough('\u{8}') | ProcessingResult::ClearAndPassBackspace => {
+                platform::post_backspace(1)?;
+            }
+            ProcessingResult::PassThrough(ch) => post_text(&ch.to_string())?,
+            ProcessingResult::DismissSuggestions => {}
+        }
+    }
+
+    if let Some(pending) = processor.take_pending_output() {
+        if !marked.is_empty() {
+            platform::set_marked_text(&marked, "")?;
+        }
+        post_text(&pending)?;
+        expected.push_str(&pending);
+    }
+
+    Ok((expected, started.elapsed()))
+}
+
+impl Render for InjectionSandbox {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let header = SandboxField::ALL.iter().fold(
+            div().flex().gap_3().child(div().min_w_32()),
+            |row, field| {
+                row.child(
+                    div()
+                        .flex_1()
+                        .text_sm()
+                        .text_color(rgb(0xa0aec0))
+                        .child(field.to_string())
+                )
+            },
+        );
+
+        let rows: Vec<_> = InjectionStrategy::ALL.iter().map(|&strategy| {
+            SandboxField::ALL.iter().fold(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_3()
+                    .child(
+                        div()
+                            .min_w_32()
+                            .child(self.render_run_button(&strategy.to_string(), vec![strategy], cx))
+                    ),
+                |row, &field| row.child(self.render_cell(self.outcome(strategy, field))),
+            )
+        }).collect();
+
+        let compare_header = SandboxField::ALL.iter().fold(
+            div()
+                .flex()
+                .items_center()
+                .gap_3()
+                .mt_4()
+                .child(
+                    div()
+                        .min_w_32()
+                        .text_sm()
+                        .text_color(rgb(0xe2e8f0))
+                        .child("A/B compare")
+                ),
+            |row, field| {
+                row.child(
+                    div()
+                        .flex_1()
+                        .text_sm()
+                        .text_color(rgb(0xa0aec0))
+                        .child(field.to_string())
+                )
+            },
+        );
+
+        let compare_rows: Vec<_> = (0..2).map(|slot| {
+            let strategy = self.compare[slot];
+            SandboxField::ALL.iter().fold(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_3()
+                    .child(self.render_compare_choice(slot, cx)),
+                |row, &field| row.child(self.render_compare_cell(self.compare_tally(strategy, field))),
+            )
+        }).collect();
+
+        let compare_button = div()
+            .px_3()
+            .py_1()
+            .rounded_md()
+            .text_sm()
+            .cursor_pointer()
+            .bg(rgb(0x4a5568))
+            .text_color(rgb(0xe2e8f0))
+            .hover(|this| this.bg(rgb(0x5a6c7d)))
+            .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                this.run_compare(cx);
+            }))
+            .child(format!("Compare ({} rounds per field)", COMPARE_ROUNDS));
+
+        div()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .bg(rgb(0x2d3748))
+            .w_full()
+            .h_full()
+            .p_4()
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .mb_2()
+                    .child(
+                        div()
+                            .text_color(rgb(0xe2e8f0))
+                            .text_base()
+                            .child("Injection Sandbox")
+                    )
+                    .when(!self.running, |this| {
+                        this.child(self.render_run_button("Run all", InjectionStrategy::ALL.to_vec(), cx))
+                    })
+                    .when(self.running, |this| {
+                        this.child(
+                            div()
+                                .text_sm()
+                                .text_color(rgb(0xa0aec0))
+                                .child("Running…")
+                        )
+                    })
+            )
+            .child(header)
+            .children(rows)
+            .child(compare_header)
+            .children(compare_rows)
+            .when(!self.running, |this| this.child(div().flex().child(compare_button)))
+    }
+}
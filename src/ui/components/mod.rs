@@ -1,4 +1,7 @@
 pub mod vkey_app;
 pub mod dropdown;
+pub mod ax_inspector;
+#[cfg(target_os = "macos")]
+pub mod injection_sandbox;
 
 pub use vkey_app::VKeyApp; 
\ No newline at end of file
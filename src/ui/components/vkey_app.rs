@@ -1,11 +1,33 @@
 use gpui::{
-    div, prelude::*, rgb, Context, IntoElement, Render, Styled, Window, MouseButton, Entity
+    div, prelude::*, px, rgb, size, App, Bounds, Context, IntoElement, Render, Styled, Window, MouseButton, Entity,
+    FocusHandle, Global, KeyDownEvent, WindowBounds, WindowHandle, WindowOptions
 };
-use crate::core::{AppConfig, InputType, Encoding, InputMode, VietnameseInputProcessor};
+use crate::core::{AppConfig, BufferSnapshot, InputType, Encoding, InputMode};
+use crate::core::config::DEFAULT_RESTORE_PATTERNS;
+use crate::core::types::{AdvancedSettings, KeyboardConfig, SoundEvent, SoundTheme, DEFAULT_IDLE_COMMIT_SECS};
 use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+/// How often the debug strip re-reads the system-wide processor
+const BUFFER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often pending system tray events are picked up while the window is idle
+const TRAY_EVENT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Most characters of the word being composed shown in the menu bar
+const PENDING_WORD_TITLE_CHARS: usize = 12;
+
+const WINDOW_WIDTH: f32 = 650.;
+const WINDOW_HEIGHT: f32 = 900.;
+
+/// Keeps the settings view alive while its window is closed, so the tray
+/// keeps working and "Show UI" can reopen it
+struct MainView(Entity<VKeyApp>);
+
+impl Global for MainView {}
 
 #[cfg(target_os = "macos")]
-use crate::platform::{MacOSKeyboardHandler, system_integration, SystemTray};
+use crate::platform::{launchd, system_integration, SuggestionPopup, SystemTray};
 
 // Add gpui-component imports using correct module paths
 use gpui_component::{
@@ -14,17 +36,36 @@ use gpui_component::{
 
 pub struct VKeyApp {
     config: AppConfig,
-    vietnamese_processor: VietnameseInputProcessor,
-    input_text: String,
-    #[cfg(target_os = "macos")]
-    keyboard_handler: Option<MacOSKeyboardHandler>,
     #[cfg(target_os = "macos")]
     system_tray: Option<SystemTray>,
     system_tray_receiver: Option<Receiver<crate::SystemTrayEvent>>,
     permissions_checked: bool,
+    /// Latest rate-limited injection error report (app name, error count)
+    injection_error_notice: Option<(String, usize)>,
+    /// The current keyboard layout cannot be read, so keys pass through unchanged
+    layout_unavailable: bool,
+    /// Title last set on the menu bar item
+    #[cfg(target_os = "macos")]
+    tray_title: String,
     // Dropdown states for proper selection tracking
     input_type_dropdown: Option<Entity<DropdownState<Vec<String>>>>,
     encoding_dropdown: Option<Entity<DropdownState<Vec<String>>>>,
+    /// Restore pattern being typed into the pattern editor
+    restore_pattern_draft: String,
+    restore_pattern_focus: Option<FocusHandle>,
+    /// Latest state of the processor used by the event tap
+    buffer_snapshot: BufferSnapshot,
+    /// Window currently showing this view, if it was opened
+    main_window: Option<WindowHandle<VKeyApp>>,
+    /// Completions for the word being typed, created when first needed
+    #[cfg(target_os = "macos")]
+    suggestion_popup: Option<SuggestionPopup>,
+    /// Whether VKey is registered as a login item
+    #[cfg(target_os = "macos")]
+    launch_at_login: bool,
+    /// Whether the daemon is installed as a LaunchAgent, which then does the typing
+    #[cfg(target_os = "macos")]
+    launch_agent_installed: bool,
 }
 
 impl VKeyApp {
@@ -38,26 +79,70 @@ impl VKeyApp {
             eprintln!("Failed to load config: {}. Using default.", e);
             AppConfig::default()
         });
-        let vietnamese_processor = VietnameseInputProcessor::new(config.input_type);
-        
-        #[cfg(target_os = "macos")]
-        let keyboard_handler = Some(MacOSKeyboardHandler::new(config.input_type));
         
         Self {
             config,
-            vietnamese_processor,
-            input_text: String::new(),
-            #[cfg(target_os = "macos")]
-            keyboard_handler,
             #[cfg(target_os = "macos")]
             system_tray: None,
             system_tray_receiver: receiver,
             permissions_checked: false,
+            injection_error_notice: None,
+            layout_unavailable: false,
+            #[cfg(target_os = "macos")]
+            tray_title: String::new(),
             input_type_dropdown: None,
             encoding_dropdown: None,
+            restore_pattern_draft: String::new(),
+            restore_pattern_focus: None,
+            buffer_snapshot: BufferSnapshot::default(),
+            main_window: None,
+            #[cfg(target_os = "macos")]
+            suggestion_popup: None,
+            #[cfg(target_os = "macos")]
+            launch_at_login: crate::platform::is_launch_on_login(),
+            #[cfg(target_os = "macos")]
+            launch_agent_installed: launchd::is_installed(),
         }
     }
 
+    /// Bring the settings window to the front, reopening it if it was closed
+    pub fn show_window(view: Entity<VKeyApp>, cx: &mut App) {
+        cx.set_global(MainView(view.clone()));
+
+        let existing = view.read(cx).main_window;
+        let activated = existing
+            .map(|handle| handle.update(cx, |_, window, _| window.activate_window()).is_ok())
+            .unwrap_or(false);
+
+        if !activated {
+            // Dropdowns subscribed through the old window have to be rebuilt
+            view.update(cx, |app, _| {
+                app.input_type_dropdown = None;
+                app.encoding_dropdown = None;
+                app.main_window = None;
+            });
+
+            let bounds = Bounds::centered(None, size(px(WINDOW_WIDTH), px(WINDOW_HEIGHT)), cx);
+            let root = view.clone();
+            match cx.open_window(
+                WindowOptions {
+                    window_bounds: Some(WindowBounds::Windowed(bounds)),
+                    ..Default::default()
+                },
+                move |_, _| root,
+            ) {
+                Ok(handle) => view.update(cx, |app, _| app.main_window = Some(handle)),
+                Err(e) => {
+                    eprintln!("Failed to open window: {:?}", e);
+                    return;
+                }
+            }
+        }
+
+        #[cfg(target_os = "macos")]
+        crate::platform::bring_app_to_front();
+    }
+
     /// Initialize the system tray
     pub fn initialize_system_tray(&mut self) -> Result<(), String> {
         #[cfg(target_os = "macos")]
@@ -80,37 +165,13 @@ impl VKeyApp {
     pub fn initialize_keyboard_system(&mut self) -> Result<(), String> {
         #[cfg(target_os = "macos")]
         {
-            // Check accessibility permissions
-            if !system_integration::has_accessibility_permissions() {
-                return Err("Accessibility permissions are required but not granted".to_string());
+            // Typing needs the event tap; the accessibility queries are optional
+            if !system_integration::can_start_event_tap() {
+                return Err("Input Monitoring and Accessibility permissions are required but not granted".to_string());
             }
             
-            // Initialize the keyboard handler with system integration
-            match crate::platform::MacOSKeyboardHandler::new_with_system_integration(self.config.input_type) {
-                Ok(handler) => {
-                    self.keyboard_handler = Some(handler);
-                    println!("Vietnamese input system initialized with full system integration");
-                }
-                Err(e) => {
-                    // Fall back to basic handler without system integration
-                    println!("System integration failed ({}), using basic handler", e);
-                    self.keyboard_handler = Some(crate::platform::MacOSKeyboardHandler::new(self.config.input_type));
-                }
-            }
-            
-            // Set the initial input mode state
-            match self.config.input_mode {
-                crate::core::InputMode::Vietnamese => {
-                    if let Some(ref mut handler) = self.keyboard_handler {
-                        handler.set_enabled(true);
-                    }
-                }
-                crate::core::InputMode::English => {
-                    if let Some(ref mut handler) = self.keyboard_handler {
-                        handler.set_enabled(false);
-                    }
-                }
-            }
+            // Start in the saved input mode
+            crate::set_vietnamese_enabled(self.config.is_vietnamese_enabled());
             
             println!("Vietnamese input system ready for macOS");
         }
@@ -124,39 +185,13 @@ impl VKeyApp {
     }
     
 
-    /// Process a keyboard character through Vietnamese input
-    pub fn process_vietnamese_input(&mut self, ch: char) -> String {
-        use crate::core::ProcessingResult;
-        
-        match self.vietnamese_processor.process_key(ch) {
-            ProcessingResult::ProcessedText { text, .. } => {
-                self.input_text = text.clone();
-                text
-            }
-            ProcessingResult::PassThrough(ch) => {
-                self.input_text.push(ch);
-                ch.to_string()
-            }
-            ProcessingResult::ClearAndPassBackspace => {
-                self.input_text.clear();
-                "\u{8}".to_string()
-            }
-            ProcessingResult::RestoreText { text, .. } => {
-                self.input_text = text.clone();
-                text
-            }
-        }
-    }
-
     /// Toggle Vietnamese input on/off
     pub fn toggle_vietnamese_input(&mut self) {
         match self.config.toggle_vietnamese_mode() {
             Ok(_) => {
+                crate::set_vietnamese_enabled(self.config.is_vietnamese_enabled());
                 #[cfg(target_os = "macos")]
                 {
-                    if let Some(ref mut handler) = self.keyboard_handler {
-                        handler.set_enabled(self.config.is_vietnamese_enabled());
-                    }
                     self.update_system_tray_state();
                     self.update_system_tray_title();
                 }
@@ -173,11 +208,9 @@ impl VKeyApp {
     pub fn set_vietnamese_input(&mut self, enabled: bool) {
         match self.config.set_vietnamese_mode(enabled) {
             Ok(_) => {
+                crate::set_vietnamese_enabled(enabled);
                 #[cfg(target_os = "macos")]
                 {
-                    if let Some(ref mut handler) = self.keyboard_handler {
-                        handler.set_enabled(enabled);
-                    }
                     self.update_system_tray_state();
                     self.update_system_tray_title();
                 }
@@ -193,7 +226,7 @@ impl VKeyApp {
     /// Handle input type change
     pub fn set_input_type(&mut self, input_type: InputType) {
         self.config.input_type = input_type;
-        self.vietnamese_processor.set_input_type(input_type);
+        crate::apply_config_to_engine(&self.config);
         
         // Rebuild keyboard layout when input type changes
         crate::platform::rebuild_keyboard_layout_map();
@@ -205,9 +238,6 @@ impl VKeyApp {
         
         #[cfg(target_os = "macos")]
         {
-            if let Some(ref mut handler) = self.keyboard_handler {
-                handler.set_input_type(input_type);
-            }
             self.update_system_tray_state();
             self.update_system_tray_title();
         }
@@ -216,6 +246,7 @@ impl VKeyApp {
     /// Handle encoding change
     pub fn set_encoding(&mut self, encoding: Encoding) {
         self.config.encoding = encoding;
+        crate::apply_config_to_engine(&self.config);
         
         // Save configuration
         if let Err(e) = self.config.update_and_save() {
@@ -223,22 +254,125 @@ impl VKeyApp {
         }
     }
     
+    /// Toggle one of the advanced settings and apply it to typing immediately
+    pub fn toggle_advanced_setting(&mut self, toggle: fn(&mut AdvancedSettings)) {
+        toggle(&mut self.config.advanced);
+        crate::apply_config_to_engine(&self.config);
+        // The title may show or stop showing the word being composed
+        self.update_system_tray_title();
+
+        if let Err(e) = self.config.update_and_save() {
+            eprintln!("Failed to save config after advanced setting change: {}", e);
+        }
+    }
+    
+    /// Start VKey at login as a login item. Replaces the LaunchAgent, since
+    /// both would start a process that taps the keyboard.
+    #[cfg(target_os = "macos")]
+    fn set_launch_at_login(&mut self, enabled: bool) {
+        if enabled && self.launch_agent_installed {
+            self.set_launch_agent_installed(false);
+        }
+        match crate::platform::update_launch_on_login(enabled) {
+            Ok(()) => self.launch_at_login = enabled,
+            Err(e) => eprintln!("Failed to update login item: {}", e),
+        }
+    }
+
+    /// Install or remove the daemon LaunchAgent. While it is installed the
+    /// daemon handles typing and this process stops its own event tap.
+    #[cfg(target_os = "macos")]
+    fn set_launch_agent_installed(&mut self, install: bool) {
+        if install {
+            if self.launch_at_login {
+                self.set_launch_at_login(false);
+            }
+            // Stop our tap first so keys are never converted twice
+            if let Err(e) = system_integration::remove_keyboard_hook() {
+                eprintln!("Failed to remove keyboard hook: {}", e);
+            }
+            match launchd::install() {
+                Ok(()) => self.launch_agent_installed = true,
+                Err(e) => {
+                    eprintln!("Failed to install LaunchAgent: {}", e);
+                    // The plist may have been written even though loading it failed
+                    let _ = launchd::uninstall();
+                    crate::start_event_tap();
+                }
+            }
+        } else {
+            if let Err(e) = launchd::uninstall() {
+                eprintln!("Failed to remove LaunchAgent: {}", e);
+                return;
+            }
+            self.launch_agent_installed = false;
+            if system_integration::can_start_event_tap() {
+                crate::start_event_tap();
+            }
+        }
+    }
+
+    /// Change the typing sound settings, previewing the sound when they are on
+    fn update_sound_settings(&mut self, update: impl FnOnce(&mut KeyboardConfig)) {
+        update(&mut self.config.keyboard);
+        crate::apply_config_to_engine(&self.config);
+
+        #[cfg(target_os = "macos")]
+        if self.config.keyboard.typing_sounds {
+            crate::platform::play_sound(SoundEvent::Transform, self.config.keyboard.sound_theme, self.config.keyboard.sound_volume);
+        }
+
+        if let Err(e) = self.config.update_and_save() {
+            eprintln!("Failed to save config after sound setting change: {}", e);
+        }
+    }
+
+    /// Toggle, add or remove restore patterns, then apply and save them
+    fn update_restore_patterns(&mut self, update: impl FnOnce(&mut AppConfig)) {
+        update(&mut self.config);
+        crate::apply_config_to_engine(&self.config);
+
+        if let Err(e) = self.config.update_and_save() {
+            eprintln!("Failed to save config after restore pattern change: {}", e);
+        }
+    }
+
+    /// Edit the restore pattern draft with a key typed into the pattern editor
+    fn on_restore_pattern_key(&mut self, event: &KeyDownEvent, cx: &mut Context<Self>) {
+        match event.keystroke.key.as_str() {
+            "enter" => {
+                let draft = std::mem::take(&mut self.restore_pattern_draft);
+                self.update_restore_patterns(|config| {
+                    if !config.add_restore_pattern(&draft) {
+                        eprintln!("Restore pattern '{}' is invalid or already exists", draft);
+                    }
+                });
+            }
+            "backspace" => {
+                self.restore_pattern_draft.pop();
+            }
+            "escape" => self.restore_pattern_draft.clear(),
+            key if key.chars().count() == 1 && key.chars().all(|c| c.is_ascii_graphic()) => {
+                self.restore_pattern_draft.push_str(&key.to_ascii_lowercase());
+            }
+            _ => return,
+        }
+        cx.notify();
+    }
+
     /// Reset configuration to defaults
     pub fn reset_to_defaults(&mut self) {
         match self.config.reset_to_default() {
             Ok(_) => {
-                // Update processor and handler with new settings
-                self.vietnamese_processor.set_input_type(self.config.input_type);
+                // Update the engine and handler with new settings
+                crate::apply_config_to_engine(&self.config);
                 
                 // Rebuild keyboard layout when configuration is reset
                 crate::platform::rebuild_keyboard_layout_map();
                 
                 #[cfg(target_os = "macos")]
                 {
-                    if let Some(ref mut handler) = self.keyboard_handler {
-                        handler.set_input_type(self.config.input_type);
-                        handler.set_enabled(self.config.is_vietnamese_enabled());
-                    }
+                    crate::set_vietnamese_enabled(self.config.is_vietnamese_enabled());
                     self.update_system_tray_state();
                     self.update_system_tray_title();
                 }
@@ -251,57 +385,107 @@ impl VKeyApp {
         }
     }
 
+    /// Keep `buffer_snapshot` in sync with the processor used by the event tap.
+    /// The window only re-renders when the snapshot changes, and the
+    /// suggestion popup follows the processor's suggestions.
+    pub fn start_buffer_polling(&mut self, cx: &mut Context<Self>) {
+        let snapshots = crate::buffer_snapshots();
+        cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(BUFFER_POLL_INTERVAL).await;
+            // Only the latest state matters after a burst of keys
+            let Some(snapshot) = snapshots.try_iter().last() else {
+                continue;
+            };
+            let updated = this.update(cx, |this, cx| {
+                if this.buffer_snapshot != snapshot {
+                    let suggestions_changed = this.buffer_snapshot.suggestions != snapshot.suggestions;
+                    let word_changed = this.buffer_snapshot.display_buffer != snapshot.display_buffer;
+                    this.buffer_snapshot = snapshot;
+                    #[cfg(target_os = "macos")]
+                    if suggestions_changed {
+                        this.update_suggestion_popup();
+                    }
+                    #[cfg(target_os = "macos")]
+                    if word_changed {
+                        this.update_system_tray_title();
+                    }
+                    cx.notify();
+                }
+            });
+            if updated.is_err() {
+                break;
+            }
+        })
+        .detach();
+    }
+
+    /// Show the processor's suggestions under the text cursor, or hide the popup
+    #[cfg(target_os = "macos")]
+    fn update_suggestion_popup(&mut self) {
+        let suggestions = &self.buffer_snapshot.suggestions;
+        // Without the cursor position there is nowhere sensible to show them
+        let anchor = if suggestions.is_empty() {
+            None
+        } else {
+            crate::platform::caret_anchor()
+        };
+        match anchor {
+            Some(anchor) => self
+                .suggestion_popup
+                .get_or_insert_with(SuggestionPopup::new)
+                .show(suggestions, anchor),
+            None => {
+                if let Some(popup) = &self.suggestion_popup {
+                    popup.hide();
+                }
+            }
+        }
+    }
+
+    /// Handle system tray events as they arrive instead of on the next repaint
+    pub fn start_tray_event_processing(&mut self, cx: &mut Context<Self>) {
+        cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(TRAY_EVENT_INTERVAL).await;
+            let updated = this.update(cx, |this, cx| {
+                if this.process_system_tray_events(cx) {
+                    cx.notify();
+                }
+            });
+            if updated.is_err() {
+                break;
+            }
+        })
+        .detach();
+    }
+
     /// Get current input buffer for display
     pub fn get_current_input_buffer(&self) -> String {
-        #[cfg(target_os = "macos")]
-        if let Some(ref handler) = self.keyboard_handler {
-            return handler.get_current_buffer();
-        }
-        
-        self.vietnamese_processor.get_current_buffer().to_string()
+        self.buffer_snapshot.typing_buffer.clone()
     }
 
     /// Get current display buffer for showing transformed text
     pub fn get_current_display_buffer(&self) -> String {
-        #[cfg(target_os = "macos")]
-        if let Some(ref handler) = self.keyboard_handler {
-            // If handler has display buffer method, use it
-            return handler.get_current_buffer();
-        }
-        
-        self.vietnamese_processor.get_display_buffer().to_string()
+        self.buffer_snapshot.display_buffer.clone()
     }
 
     /// Clear the input buffer
     pub fn clear_input_buffer(&mut self) {
-        self.vietnamese_processor.clear_buffer();
-        self.input_text.clear();
-        
-        #[cfg(target_os = "macos")]
-        if let Some(ref mut handler) = self.keyboard_handler {
-            handler.clear_buffer();
-        }
+        crate::with_engine(|engine| engine.clear_buffer());
     }
 
     /// Get the previous word for restoration
     pub fn get_previous_word(&self) -> String {
-        self.vietnamese_processor.get_previous_word().to_string()
+        crate::with_engine(|engine| engine.get_previous_word().to_string()).unwrap_or_default()
     }
 
     /// Check if the processor is currently tracking input
     pub fn is_tracking_input(&self) -> bool {
-        self.vietnamese_processor.is_tracking()
+        self.buffer_snapshot.is_tracking
     }
 
     /// Start a new word (reset buffers and enable tracking)
     pub fn start_new_word(&mut self) {
-        self.vietnamese_processor.new_word();
-        
-        #[cfg(target_os = "macos")]
-        if let Some(ref mut handler) = self.keyboard_handler {
-            // If handler has new_word method, call it
-            handler.clear_buffer();
-        }
+        crate::with_engine(|engine| engine.new_word());
     }
 
     /// Check if accessibility permissions are granted
@@ -368,10 +552,16 @@ impl VKeyApp {
                 crate::send_system_tray_event(crate::SystemTrayEvent::SetInputTypeVNI);
             });
 
+            // Add the selected word to the user dictionary, or remove it
+            system_tray.set_menu_item_callback(SystemTrayMenuItemKey::ToggleDictionaryWord, || {
+                println!("System tray: Toggle dictionary word");
+                crate::send_system_tray_event(crate::SystemTrayEvent::ToggleDictionaryWord);
+            });
+
             // Exit application callback
             system_tray.set_menu_item_callback(SystemTrayMenuItemKey::Exit, || {
                 println!("System tray: Exit application");
-                std::process::exit(0);
+                crate::send_system_tray_event(crate::SystemTrayEvent::Quit);
             });
 
             // Update the initial state of menu items
@@ -423,12 +613,15 @@ impl VKeyApp {
         Ok(())
     }
 
-    /// Update system tray title based on current state
+    /// Update system tray title based on current state, followed by the
+    /// word being composed when that is switched on
     #[cfg(target_os = "macos")]
     pub fn update_system_tray_title(&mut self) {
         if let Some(ref mut system_tray) = self.system_tray {
             let vietnamese_enabled = self.config.is_vietnamese_enabled();
-            let title = if vietnamese_enabled {
+            let mode = if self.layout_unavailable {
+                "VN ⚠"
+            } else if vietnamese_enabled {
                 match self.config.input_type {
                     crate::core::InputType::Telex => "VN",
                     crate::core::InputType::VNI => "VN",
@@ -437,7 +630,21 @@ impl VKeyApp {
             } else {
                 "EN"
             };
-            system_tray.set_title(title);
+            let word = &self.buffer_snapshot.display_buffer;
+            let title = if self.config.advanced.show_pending_word && !word.is_empty() {
+                let mut shown: String = word.chars().take(PENDING_WORD_TITLE_CHARS).collect();
+                if word.chars().count() > PENDING_WORD_TITLE_CHARS {
+                    shown.push('…');
+                }
+                format!("{} {}", mode, shown)
+            } else {
+                mode.to_string()
+            };
+            // The word changes with every key, so unchanged titles are not set again
+            if self.tray_title != title {
+                system_tray.set_title(&title);
+                self.tray_title = title;
+            }
         }
     }
 
@@ -447,7 +654,8 @@ impl VKeyApp {
     }
 
     /// Process pending system tray events
-    pub fn process_system_tray_events(&mut self) {
+    /// Handle pending system tray events. Returns whether there were any.
+    pub fn process_system_tray_events(&mut self, cx: &mut Context<Self>) -> bool {
         let mut events = Vec::new();
         
         // Collect all pending events first
@@ -457,17 +665,31 @@ impl VKeyApp {
             }
         }
         
+        let received = !events.is_empty();
+
         // Process the events
         for event in events {
             match event {
                 crate::SystemTrayEvent::ShowUI => {
                     println!("Processing system tray event: Show UI");
-                    // TODO: Implement showing the main window
+                    // Windows can't be opened while this view is being updated
+                    let view = cx.entity();
+                    cx.defer(move |cx| VKeyApp::show_window(view, cx));
                 }
                 crate::SystemTrayEvent::ToggleVietnamese => {
                     println!("Processing system tray event: Toggle Vietnamese");
                     self.toggle_vietnamese_input();
                 }
+                crate::SystemTrayEvent::Quit => {
+                    println!("Processing system tray event: Quit");
+                    cx.quit();
+                }
+                crate::SystemTrayEvent::ToggleDictionaryWord => {
+                    println!("Processing system tray event: Toggle dictionary word");
+                    if crate::toggle_dictionary_word().is_none() {
+                        eprintln!("No word selected to add to the user dictionary");
+                    }
+                }
                 crate::SystemTrayEvent::SetInputTypeTelex => {
                     println!("Processing system tray event: Set input type Telex");
                     self.set_input_type(InputType::Telex);
@@ -476,8 +698,26 @@ impl VKeyApp {
                     println!("Processing system tray event: Set input type VNI");
                     self.set_input_type(InputType::VNI);
                 }
+                crate::SystemTrayEvent::InjectionErrors { app, count } => {
+                    println!("Processing system tray event: {} injection errors in {}", count, app);
+                    self.injection_error_notice = Some((app, count));
+                }
+                crate::SystemTrayEvent::KeyboardLayout { available } => {
+                    println!("Processing system tray event: Keyboard layout available: {}", available);
+                    self.layout_unavailable = !available;
+                    self.update_system_tray_title();
+                }
+                crate::SystemTrayEvent::ShowStatistics => {
+                    println!("Processing system tray event: Show statistics");
+                    cx.defer(|cx| {
+                        cx.activate(true);
+                        super::ax_inspector::AxInspector::open(cx);
+                    });
+                }
             }
         }
+
+        received
     }
 
     fn render_dropdown(&mut self, label: &str, options: &[&str], selected_index: usize, dropdown_type: &str, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
@@ -548,6 +788,8 @@ impl VKeyApp {
                         "Telex" => InputType::Telex,
                         "VNI" => InputType::VNI,
                         "VIQR" => InputType::VIQR,
+                        "Telex + VNI" => InputType::Hybrid,
+                        "Telex đơn giản" => InputType::TelexSimple,
                         _ => InputType::Telex, // Default fallback
                     };
                     self.set_input_type(input_type);
@@ -573,6 +815,8 @@ impl VKeyApp {
                         "Unicode" => Encoding::Unicode,
                         "TCVN3" => Encoding::TCVN3,
                         "VNI-Win" => Encoding::VNIWin,
+                        "VISCII" => Encoding::VISCII,
+                        "CP1258" => Encoding::CP1258,
                         _ => Encoding::Unicode, // Default fallback
                     };
                     self.set_encoding(encoding);
@@ -620,6 +864,21 @@ impl VKeyApp {
             )
     }
 
+    fn render_setting_checkbox(
+        &self,
+        label: &str,
+        checked: bool,
+        toggle: fn(&mut AdvancedSettings),
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        div()
+            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                this.toggle_advanced_setting(toggle);
+                cx.notify();
+            }))
+            .child(self.render_checkbox(label, checked))
+    }
+
     fn render_vietnamese_toggle(&self) -> impl IntoElement {
         div()
             .flex()
@@ -827,10 +1086,12 @@ impl VKeyApp {
                             InputType::Telex => 0,
                             InputType::VNI => 1,
                             InputType::VIQR => 2,
+                            InputType::Hybrid => 3,
+                            InputType::TelexSimple => 4,
                         };
                         self.render_dropdown(
                             "Kiểu gõ:",
-                            &["Telex", "VNI", "VIQR"],
+                            &["Telex", "VNI", "VIQR", "Telex + VNI", "Telex đơn giản"],
                             input_type_index,
                             "input_type",
                             window,
@@ -842,10 +1103,12 @@ impl VKeyApp {
                             Encoding::Unicode => 0,
                             Encoding::TCVN3 => 1,
                             Encoding::VNIWin => 2,
+                            Encoding::VISCII => 3,
+                            Encoding::CP1258 => 4,
                         };
                         self.render_dropdown(
                             "Bảng mã:",
-                            &["Unicode", "TCVN3", "VNI-Win"],
+                            &["Unicode", "TCVN3", "VNI-Win", "VISCII", "CP1258"],
                             encoding_index,
                             "encoding",
                             window,
@@ -905,6 +1168,62 @@ impl VKeyApp {
             )
     }
 
+    fn render_injection_error_notice(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        div().when_some(self.injection_error_notice.clone(), |this, (app, count)| {
+            this.child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .gap_3()
+                    .px_3()
+                    .py_2()
+                    .mb_3()
+                    .rounded_md()
+                    .bg(rgb(0x744210))
+                    .child(
+                        div()
+                            .text_color(rgb(0xfefcbf))
+                            .text_sm()
+                            .child(format!(
+                                "{} lỗi gửi phím trong {} — thử đổi chế độ gửi phím?",
+                                count, app
+                            ))
+                    )
+                    .child(
+                        div()
+                            .px_2()
+                            .rounded_sm()
+                            .cursor_pointer()
+                            .text_color(rgb(0xfefcbf))
+                            .text_sm()
+                            .hover(|this| this.bg(rgb(0x975a16)))
+                            .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                                this.injection_error_notice = None;
+                                cx.notify();
+                            }))
+                            .child("✕")
+                    )
+            )
+        })
+    }
+
+    fn render_layout_warning(&self) -> impl IntoElement {
+        div().when(self.layout_unavailable, |this| {
+            this.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .mb_3()
+                    .rounded_md()
+                    .bg(rgb(0x742a2a))
+                    .text_color(rgb(0xfed7d7))
+                    .text_sm()
+                    .child("Không đọc được bố cục bàn phím hiện tại — VKey để phím đi qua nguyên vẹn cho đến khi đổi bố cục")
+            )
+        })
+    }
+
     fn render_tabs(&self) -> impl IntoElement {
         div()
             .flex()
@@ -916,7 +1235,7 @@ impl VKeyApp {
             .child(self.render_button("Thông tin", false))
     }
 
-    fn render_advanced_settings(&self) -> impl IntoElement {
+    fn render_advanced_settings(&self, cx: &mut Context<Self>) -> impl IntoElement {
         div()
             .bg(rgb(0x4a5568))
             .rounded_lg()
@@ -933,12 +1252,12 @@ impl VKeyApp {
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Đặt dấu òa, úy (thay vì òa, úy)", self.config.advanced.replace_oa_uy))
+                                    .child(self.render_setting_checkbox("Đặt dấu oà, uý (thay vì òa, úy)", self.config.advanced.replace_oa_uy, |s| s.replace_oa_uy = !s.replace_oa_uy, cx))
                             )
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Kiểm tra chính tả", self.config.advanced.spell_check))
+                                    .child(self.render_setting_checkbox("Kiểm tra chính tả", self.config.advanced.spell_check, |s| s.spell_check = !s.spell_check, cx))
                             )
                     )
                     .child(
@@ -953,7 +1272,109 @@ impl VKeyApp {
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Tự khởi phục phím với tự sai", self.config.advanced.auto_restart_typos))
+                                    .child(self.render_setting_checkbox("Tự khôi phục phím với từ sai", self.config.advanced.auto_restart_typos, |s| s.auto_restart_typos = !s.auto_restart_typos, cx))
+                            )
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_8()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Viết Hoa chữ cái đầu câu", self.config.advanced.vietnamese_capital, |s| s.vietnamese_capital = !s.vietnamese_capital, cx))
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Cho phép \"z w j f\" làm phụ âm", self.config.advanced.allow_silent_consonants, |s| s.allow_silent_consonants = !s.allow_silent_consonants, cx))
+                            )
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_8()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Chuyển chế độ thông minh", self.config.advanced.smart_switching, |s| s.smart_switching = !s.smart_switching, cx))
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Tạm tắt chính tả bằng phím ^", self.config.advanced.temp_disable_spell_check, |s| s.temp_disable_spell_check = !s.temp_disable_spell_check, cx))
+                            )
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_8()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Tự ghi nhớ bảng mã theo ứng dụng", self.config.advanced.remember_encoding, |s| s.remember_encoding = !s.remember_encoding, cx))
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Tạm tắt VKey bằng phím ⌘", self.config.advanced.temp_disable_openkey, |s| s.temp_disable_openkey = !s.temp_disable_openkey, cx))
+                            )
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_8()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Cho phép gõ tắt", self.config.advanced.macro_enabled, |s| s.macro_enabled = !s.macro_enabled, cx))
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Tự nhận diện từ tiếng Anh", self.config.advanced.detect_english, |s| s.detect_english = !s.detect_english, cx))
+                            )
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_8()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Gõ emoji bằng :tên:", self.config.advanced.emoji_shortcodes, |s| s.emoji_shortcodes = !s.emoji_shortcodes, cx))
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Esc khôi phục từ và chuyển sang tiếng Anh", self.config.advanced.escape_to_english, |s| s.escape_to_english = !s.escape_to_english, cx))
+                            )
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_8()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Hiện từ đang gõ trên thanh menu", self.config.advanced.show_pending_word, |s| s.show_pending_word = !s.show_pending_word, cx))
+                            )
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_8()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Tự kết thúc từ khi ngừng gõ", self.config.advanced.idle_commit_secs > 0, |s| {
+                                        s.idle_commit_secs = if s.idle_commit_secs > 0 { 0 } else { DEFAULT_IDLE_COMMIT_SECS };
+                                    }, cx))
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .child(self.render_setting_checkbox("Đặt dấu tự do", self.config.advanced.free_tone_placement, |s| s.free_tone_placement = !s.free_tone_placement, cx))
                             )
                     )
                     .child(
@@ -963,12 +1384,12 @@ impl VKeyApp {
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Viết Hoa chữ cái đầu câu", self.config.advanced.vietnamese_capital))
+                                    .child(self.render_setting_checkbox("Gõ [ ] thành ơ ư (Telex)", self.config.advanced.telex_bracket_shortcuts, |s| s.telex_bracket_shortcuts = !s.telex_bracket_shortcuts, cx))
                             )
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Cho phép \"z w j f\" làm phụ âm", self.config.advanced.allow_silent_consonants))
+                                    .child(self.render_setting_checkbox("Gõ w thành ư", self.config.advanced.standalone_w, |s| s.standalone_w = !s.standalone_w, cx))
                             )
                     )
                     .child(
@@ -978,12 +1399,12 @@ impl VKeyApp {
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Chuyển chế độ thông minh", self.config.advanced.smart_switching))
+                                    .child(self.render_setting_checkbox("Gợi ý từ (Tab hoặc số để chọn)", self.config.advanced.word_suggestions, |s| s.word_suggestions = !s.word_suggestions, cx))
                             )
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Tạm tắt chính tả bằng phím ^", self.config.advanced.temp_disable_spell_check))
+                                    .child(self.render_setting_checkbox("Bàn phím số gõ số, không bỏ dấu", self.config.advanced.keypad_digits_literal, |s| s.keypad_digits_literal = !s.keypad_digits_literal, cx))
                             )
                     )
                     .child(
@@ -993,17 +1414,273 @@ impl VKeyApp {
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Tự ghi nhớ bảng mã theo ứng dụng", self.config.advanced.remember_encoding))
+                                    .child(self.render_setting_checkbox("Bỏ dấu cho từ trước con trỏ sau khi nhấp chuột", self.config.advanced.reseed_from_caret, |s| s.reseed_from_caret = !s.reseed_from_caret, cx))
                             )
                             .child(
                                 div()
                                     .flex_1()
-                                    .child(self.render_checkbox("Tạm tắt VKey bằng phím ⌘", self.config.advanced.temp_disable_openkey))
+                                    .child(self.render_setting_checkbox("Tương thích nghiêm ngặt (Excel, JetBrains, VS Code, Discord)", self.config.advanced.strict_compatibility, |s| s.strict_compatibility = !s.strict_compatibility, cx))
+                                    .child(self.render_setting_checkbox("Ghi lại cách gõ theo ứng dụng (xem trong cửa sổ chẩn đoán)", self.config.advanced.log_app_decisions, |s| s.log_app_decisions = !s.log_app_decisions, cx))
                             )
                     )
             )
     }
 
+    fn render_restore_patterns(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let chips = self.config.restore_patterns.iter().map(|restore| {
+            let pattern = restore.pattern.clone();
+            let is_custom = !DEFAULT_RESTORE_PATTERNS.contains(&pattern.as_str());
+            div()
+                .flex()
+                .items_center()
+                .gap_1()
+                .px_2()
+                .rounded_sm()
+                .text_sm()
+                .cursor_pointer()
+                .when(restore.enabled, |this| {
+                    this.bg(rgb(0x3182ce)).text_color(rgb(0xffffff))
+                })
+                .when(!restore.enabled, |this| {
+                    this.bg(rgb(0x2d3748)).text_color(rgb(0x718096))
+                })
+                .on_mouse_down(MouseButton::Left, cx.listener({
+                    let pattern = pattern.clone();
+                    move |this, _, _, cx| {
+                        this.update_restore_patterns(|config| config.toggle_restore_pattern(&pattern));
+                        cx.notify();
+                    }
+                }))
+                .child(pattern.clone())
+                .when(is_custom, |this| {
+                    this.child(
+                        div()
+                            .text_xs()
+                            .hover(|this| this.text_color(rgb(0xfc8181)))
+                            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                                this.update_restore_patterns(|config| config.remove_restore_pattern(&pattern));
+                                cx.stop_propagation();
+                                cx.notify();
+                            }))
+                            .child("✕")
+                    )
+                })
+        }).collect::<Vec<_>>();
+
+        let editor = div()
+            .px_2()
+            .min_w_20()
+            .rounded_sm()
+            .text_sm()
+            .bg(rgb(0x2d3748))
+            .text_color(rgb(0xe2e8f0))
+            .border_1()
+            .border_color(rgb(0x718096))
+            .cursor_text()
+            .child(if self.restore_pattern_draft.is_empty() {
+                "+ thêm".to_string()
+            } else {
+                self.restore_pattern_draft.clone()
+            });
+        let editor = match self.restore_pattern_focus {
+            Some(ref focus) => editor
+                .track_focus(focus)
+                .on_mouse_down(MouseButton::Left, {
+                    let focus = focus.clone();
+                    move |_, window, _| window.focus(&focus)
+                })
+                .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                    this.on_restore_pattern_key(event, cx);
+                })),
+            None => editor,
+        };
+
+        div()
+            .bg(rgb(0x4a5568))
+            .rounded_lg()
+            .p_3()
+            .mt_3()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .child(
+                div()
+                    .text_color(rgb(0xe2e8f0))
+                    .text_sm()
+                    .child("Mẫu phím khôi phục (bấm để bật/tắt, Enter để thêm):")
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_wrap()
+                    .gap_2()
+                    .children(chips)
+                    .child(editor)
+            )
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_color(rgb(0xa0aec0))
+                            .text_sm()
+                            .child(format!("Đã học {} từ hay khôi phục hoặc giữ nguyên", crate::learned_restore_count()))
+                    )
+                    .child(
+                        div()
+                            .px_2()
+                            .rounded_sm()
+                            .text_sm()
+                            .cursor_pointer()
+                            .bg(rgb(0x2d3748))
+                            .text_color(rgb(0xe2e8f0))
+                            .hover(|this| this.bg(rgb(0x5a6c7d)))
+                            .on_mouse_down(MouseButton::Left, cx.listener(|_, _, _, cx| {
+                                crate::reset_restore_habits();
+                                cx.notify();
+                            }))
+                            .child("Quên từ đã học")
+                    )
+            )
+            .child(self.render_setting_checkbox("Ghi lại từ bị tự động khôi phục (xem trong cửa sổ chẩn đoán)", self.config.advanced.log_corrections, |s| s.log_corrections = !s.log_corrections, cx))
+    }
+
+    /// How VKey starts with macOS: as a login item, or as a background
+    /// LaunchAgent that launchd keeps running
+    fn render_system_settings(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let section = div()
+            .bg(rgb(0x4a5568))
+            .rounded_lg()
+            .p_3()
+            .mt_3()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .child(
+                div()
+                    .text_color(rgb(0xe2e8f0))
+                    .text_sm()
+                    .child("Hệ thống:")
+            );
+
+        #[cfg(target_os = "macos")]
+        let section = section.child(
+            div()
+                .flex()
+                .gap_8()
+                .child(
+                    div()
+                        .flex_1()
+                        .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                            this.set_launch_at_login(!this.launch_at_login);
+                            cx.notify();
+                        }))
+                        .child(self.render_checkbox("Khởi động cùng macOS", self.launch_at_login))
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                            this.set_launch_agent_installed(!this.launch_agent_installed);
+                            cx.notify();
+                        }))
+                        .child(self.render_checkbox("Chạy nền bằng LaunchAgent", self.launch_agent_installed))
+                )
+        );
+
+        let theme_chips = SoundTheme::ALL.iter().map(|&theme| {
+            let selected = self.config.keyboard.sound_theme == theme;
+            div()
+                .px_2()
+                .rounded_sm()
+                .text_sm()
+                .cursor_pointer()
+                .when(selected, |this| this.bg(rgb(0x3182ce)).text_color(rgb(0xffffff)))
+                .when(!selected, |this| this.bg(rgb(0x2d3748)).text_color(rgb(0xe2e8f0)))
+                .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                    this.update_sound_settings(|keyboard| keyboard.sound_theme = theme);
+                    cx.notify();
+                }))
+                .child(theme.to_string())
+        }).collect::<Vec<_>>();
+
+        let volume_button = |label: &'static str, step: i16| {
+            div()
+                .px_2()
+                .rounded_sm()
+                .text_sm()
+                .cursor_pointer()
+                .bg(rgb(0x2d3748))
+                .text_color(rgb(0xe2e8f0))
+                .hover(|this| this.bg(rgb(0x5a6c7d)))
+                .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                    this.update_sound_settings(|keyboard| {
+                        keyboard.sound_volume = (keyboard.sound_volume as i16 + step).clamp(0, 100) as u8;
+                    });
+                    cx.notify();
+                }))
+                .child(label)
+        };
+
+        section.child(
+            div()
+                .flex()
+                .items_center()
+                .gap_2()
+                .child(
+                    div()
+                        .flex_1()
+                        .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                            this.update_sound_settings(|keyboard| keyboard.typing_sounds = !keyboard.typing_sounds);
+                            cx.notify();
+                        }))
+                        .child(self.render_checkbox("Âm thanh khi gõ", self.config.keyboard.typing_sounds))
+                )
+                .children(theme_chips)
+                .child(volume_button("−", -10))
+                .child(
+                    div()
+                        .min_w_10()
+                        .text_center()
+                        .text_color(rgb(0xe2e8f0))
+                        .text_sm()
+                        .child(format!("{}%", self.config.keyboard.sound_volume))
+                )
+                .child(volume_button("+", 10))
+        )
+        .child(self.render_setting_checkbox(
+            "Thông báo thống kê hằng tuần",
+            self.config.advanced.weekly_summary,
+            |s| s.weekly_summary = !s.weekly_summary,
+            cx,
+        ))
+    }
+
+    /// One-line view of the live engine state: typed keys, composed word, tracking
+    fn render_debug_strip(&self) -> impl IntoElement {
+        let snapshot = &self.buffer_snapshot;
+        let describe = |text: &str| {
+            if text.is_empty() { "—".to_string() } else { format!("\"{}\"", text) }
+        };
+
+        div()
+            .flex()
+            .gap_4()
+            .mt_3()
+            .px_3()
+            .py_1()
+            .rounded_md()
+            .bg(rgb(0x1a202c))
+            .text_xs()
+            .text_color(rgb(0xa0aec0))
+            .child(format!("Phím: {}", describe(&snapshot.typing_buffer)))
+            .child(format!("Hiển thị: {}", describe(&snapshot.display_buffer)))
+            .child(if snapshot.is_tracking { "Đang ghép từ" } else { "Ngừng ghép" })
+    }
+
     fn render_bottom_buttons(&self) -> impl IntoElement {
         div()
             .flex()
@@ -1020,8 +1697,9 @@ impl VKeyApp {
 
 impl Render for VKeyApp {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        // Process any pending system tray events
-        self.process_system_tray_events();
+        if self.restore_pattern_focus.is_none() {
+            self.restore_pattern_focus = Some(cx.focus_handle());
+        }
         div()
             .flex()
             .flex_col()
@@ -1036,11 +1714,26 @@ impl Render for VKeyApp {
                     .text_lg()
                     .text_center()
                     .mb_4()
+                    .on_mouse_down(MouseButton::Left, |event, _, cx| {
+                        // Option+click opens the hidden accessibility inspector,
+                        // Shift+Option+click the injection sandbox
+                        if event.modifiers.alt && event.modifiers.shift {
+                            #[cfg(target_os = "macos")]
+                            super::injection_sandbox::InjectionSandbox::open(cx);
+                        } else if event.modifiers.alt {
+                            super::ax_inspector::AxInspector::open(cx);
+                        }
+                    })
                     .child("VKey - Bộ gõ Tiếng Việt")
             )
+            .child(self.render_layout_warning())
+            .child(self.render_injection_error_notice(cx))
             .child(self.render_control_section(window, cx))
             .child(self.render_tabs())
-            .child(self.render_advanced_settings())
+            .child(self.render_advanced_settings(cx))
+            .child(self.render_restore_patterns(cx))
+            .child(self.render_system_settings(cx))
+            .child(self.render_debug_strip())
             .child(self.render_bottom_buttons())
     }
 } 
\ No newline at end of file
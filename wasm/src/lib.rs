@@ -0,0 +1,147 @@
+// WebAssembly build of the input processor
+// The application's `core` and `error` modules are compiled here unchanged,
+// without the platform and UI code, so the browser playground composes words
+// exactly the way VKey does. Build with `wasm-pack build --target web`.
+
+#[path = "../../src/core/mod.rs"]
+pub mod core;
+#[path = "../../src/error/mod.rs"]
+pub mod error;
+
+use wasm_bindgen::prelude::*;
+
+use crate::core::types::AdvancedSettings;
+use crate::core::{InputType, ProcessingResult, VietnameseInputProcessor};
+
+/// Read an input type by its name in the config file ("Telex", "VNI", ...)
+fn parse_input_type(name: &str) -> Result<InputType, JsError> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|_| JsError::new(&format!("Unknown input type '{}'", name)))
+}
+
+/// What a key did to the text before the cursor
+#[wasm_bindgen]
+pub struct Edit {
+    backspaces: usize,
+    text: String,
+    blocked: bool,
+}
+
+#[wasm_bindgen]
+impl Edit {
+    /// Characters deleted before the cursor
+    #[wasm_bindgen(getter)]
+    pub fn backspaces(&self) -> usize {
+        self.backspaces
+    }
+
+    /// Text typed after deleting them
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    /// The key itself is not typed
+    #[wasm_bindgen(getter)]
+    pub fn blocked(&self) -> bool {
+        self.blocked
+    }
+}
+
+impl From<&ProcessingResult> for Edit {
+    fn from(result: &ProcessingResult) -> Self {
+        match result {
+            ProcessingResult::ProcessedText { text, buffer_length }
+            | ProcessingResult::RestoreText { text, buffer_length }
+            | ProcessingResult::AcceptSuggestion { text, buffer_length } => Edit {
+                backspaces: *buffer_length,
+                text: text.clone(),
+                blocked: true,
+            },
+            ProcessingResult::PassThrough('\u{8}') | ProcessingResult::ClearAndPassBackspace => Edit {
+                backspaces: 1,
+                text: String::new(),
+                blocked: false,
+            },
+            ProcessingResult::PassThrough(key) => Edit {
+                backspaces: 0,
+                text: key.to_string(),
+                blocked: false,
+            },
+            ProcessingResult::DismissSuggestions => Edit {
+                backspaces: 0,
+                text: String::new(),
+                blocked: true,
+            },
+        }
+    }
+}
+
+/// The input processor, keys typed one at a time, with the text they left
+/// on a mirrored screen
+#[wasm_bindgen]
+pub struct Processor {
+    processor: VietnameseInputProcessor,
+    screen: String,
+}
+
+#[wasm_bindgen]
+impl Processor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(input_type: &str) -> Result<Processor, JsError> {
+        let mut processor = VietnameseInputProcessor::new(parse_input_type(input_type)?);
+        processor.apply_settings(&AdvancedSettings::default());
+        Ok(Processor {
+            processor,
+            screen: String::new(),
+        })
+    }
+
+    /// Use the `advanced` settings of a config file, given as JSON, so a
+    /// bug report can be replayed with the reporter's settings
+    #[wasm_bindgen(js_name = applySettings)]
+    pub fn apply_settings(&mut self, settings_json: &str) -> Result<(), JsError> {
+        let settings: AdvancedSettings = serde_json::from_str(settings_json)
+            .map_err(|e| JsError::new(&format!("Invalid settings: {}", e)))?;
+        self.processor.apply_settings(&settings);
+        Ok(())
+    }
+
+    /// Type a key: "\b" is Backspace and "\u001b" Escape
+    #[wasm_bindgen(js_name = processKey)]
+    pub fn process_key(&mut self, key: char) -> Edit {
+        let result = match key {
+            '\u{8}' => self.processor.handle_backspace(),
+            ' ' => self.processor.handle_space(),
+            _ => self.processor.process_key(key),
+        };
+        result.apply_to(&mut self.screen);
+        Edit::from(&result)
+    }
+
+    /// Text typed so far, as an application would show it
+    #[wasm_bindgen(getter)]
+    pub fn screen(&self) -> String {
+        self.screen.clone()
+    }
+
+    /// Keys of the word being composed
+    #[wasm_bindgen(getter)]
+    pub fn keys(&self) -> String {
+        self.processor.get_current_buffer().to_string()
+    }
+
+    /// Start over with an empty screen
+    pub fn reset(&mut self) {
+        self.processor.reset();
+        self.screen.clear();
+    }
+}
+
+/// Transform a whole text with an input method and the default settings
+#[wasm_bindgen(js_name = transformText)]
+pub fn transform_text(input_type: &str, text: &str) -> Result<String, JsError> {
+    let mut processor = VietnameseInputProcessor::new(parse_input_type(input_type)?);
+    processor.apply_settings(&AdvancedSettings::default());
+    Ok(processor.transform_text(text))
+}